@@ -0,0 +1,137 @@
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use cpp_parser::args::{LangStandard, Language, PreprocOptions};
+use cpp_parser::defaults;
+use cpp_parser::lexer::preprocessor::cache::IfCache;
+use cpp_parser::lexer::preprocessor::context::{DefaultContext, PreprocContext};
+use cpp_parser::lexer::source;
+use cpp_parser::lexer::{Lexer, TLexer, Token};
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use std::sync::Arc;
+use tempdir::TempDir;
+
+const ASCII: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/benches/basic/ascii.cpp");
+
+/// Most of the headers of the standard library
+const LARGE: &str = "#include <algorithm>
+#include <chrono>
+#include <functional>
+#include <iostream>
+#include <map>
+#include <memory>
+#include <regex>
+#include <sstream>
+#include <string>
+#include <thread>
+#include <unordered_map>
+#include <vector>
+";
+
+fn get_options() -> PreprocOptions {
+    let mut opt = PreprocOptions {
+        lang: Language::CPP,
+        std: LangStandard::default_for(Language::CPP),
+        def: defaults::get_defined(),
+        ..Default::default()
+    };
+    opt.set_default_paths(&defaults::get_sys_paths());
+    opt
+}
+
+fn count_tokens<PC: PreprocContext>(lexer: &mut Lexer<PC>) -> usize {
+    let mut n = 0;
+    while lexer.next_useful() != Token::Eof {
+        n += 1;
+    }
+    n
+}
+
+fn lex_file(file: &str) -> usize {
+    let mut lexer = Lexer::<DefaultContext>::new_from_file(
+        file,
+        source::get_source_mutex(),
+        Arc::new(IfCache::default()),
+        get_options(),
+    )
+    .unwrap();
+    count_tokens(&mut lexer)
+}
+
+fn lex_ascii(c: &mut Criterion) {
+    let mut group = c.benchmark_group("lexer");
+    group.sample_size(20);
+    group.bench_function("ascii.cpp", |b| b.iter(|| lex_file(ASCII)));
+    group.finish();
+}
+
+fn lex_large(c: &mut Criterion) {
+    let tmp = TempDir::new("bench").unwrap();
+    let file = tmp.path().join("large.cpp");
+    std::fs::write(&file, LARGE).unwrap();
+    let file = file.to_str().unwrap();
+
+    let mut group = c.benchmark_group("lexer");
+    group.sample_size(10);
+    group.bench_function("large TU", |b| b.iter(|| lex_file(file)));
+    group.finish();
+}
+
+/// Some header-like code: the same lines repeated n times (between a prefix and a suffix)
+fn get_synthetic(prefix: &str, line: &str, suffix: &str, n: usize) -> Vec<u8> {
+    let mut code = String::from(prefix);
+    for _ in 0..n {
+        code.push_str(line);
+    }
+    code.push_str(suffix);
+    code.into_bytes()
+}
+
+fn lex_synthetic(c: &mut Criterion) {
+    let inputs = [
+        (
+            "comments",
+            get_synthetic(
+                "/*\n",
+                " * A long comment with some text in it, as the licenses at the top of the files\n",
+                " */\n// and a single line comment with a lot of text and a \\ somewhere\n",
+                10000,
+            ),
+        ),
+        (
+            "skipped block",
+            get_synthetic(
+                "#if 0\n",
+                "    int skipped = call(\"a string\", 'c') /* comment */ + other_call(a, b);\n",
+                "#endif\n",
+                10000,
+            ),
+        ),
+        (
+            "code",
+            get_synthetic(
+                "",
+                "static inline unsigned long long function_with_a_long_name(int first_argument);\n",
+                "",
+                10000,
+            ),
+        ),
+    ];
+
+    let mut group = c.benchmark_group("lexer");
+    for (name, data) in inputs.iter() {
+        group.throughput(Throughput::Bytes(data.len() as u64));
+        group.bench_function(*name, |b| {
+            b.iter(|| {
+                let mut lexer = Lexer::<DefaultContext>::new(data);
+                count_tokens(&mut lexer)
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, lex_ascii, lex_large, lex_synthetic);
+criterion_main!(benches);
@@ -16,10 +16,11 @@ use cpp_parser::defaults;
 use cpp_parser::lexer::buffer::{BufferData, FileInfo, Position};
 use cpp_parser::lexer::preprocessor::cache::IfCache;
 use cpp_parser::lexer::preprocessor::context::{DefaultContext, IfState, PreprocContext};
-use cpp_parser::lexer::preprocessor::include::{IncludeLocator, PathIndex};
+use cpp_parser::lexer::preprocessor::include::{IncludeLocator, IncludePaths, PathIndex};
 use cpp_parser::lexer::preprocessor::macros::{Macro, MacroFunction, MacroObject, MacroType};
 use cpp_parser::lexer::source::{self, FileId, SourceMutex};
 use cpp_parser::lexer::{Lexer, TLexer, Token};
+use cpp_parser::toolchain::Profiles;
 use crossbeam::channel::{Receiver, Sender};
 use crossbeam::crossbeam_channel::unbounded;
 use globset::{Glob, GlobSet, GlobSetBuilder};
@@ -182,7 +183,7 @@ impl IncludeLocator for StatsContext {
         next: bool,
         current: FileId,
         path_index: PathIndex,
-    ) -> Option<BufferData> {
+    ) -> Option<BufferData<'static>> {
         self.default.find(angle, path, next, current, path_index)
     }
 
@@ -201,6 +202,10 @@ impl IncludeLocator for StatsContext {
     fn set_sys_paths<P: AsRef<Path>>(&mut self, paths: &[P]) {
         self.default.set_sys_paths(paths);
     }
+
+    fn set_include_paths(&mut self, paths: &IncludePaths) {
+        self.default.set_include_paths(paths);
+    }
 }
 
 #[derive(Clone, Debug, Hash, PartialEq)]
@@ -232,19 +237,21 @@ fn consumer(receiver: JobReceiver) {
             source,
             stats,
         } = job.unwrap();
-        let file = cmd.file.to_str().unwrap();
+        let file = cmd.file.to_string_lossy();
         //eprintln!("File {}", file);
 
         if !file.contains("ecp_25519.c") {
             //continue;
         }
 
-        let mut lexer = Lexer::<StatsContext>::new_from_file(
-            cmd.file.to_str().unwrap(),
-            source,
-            if_cache,
-            cmd.opt,
-        );
+        let mut lexer = match Lexer::<StatsContext>::new_from_file(&file, source, if_cache, cmd.opt)
+        {
+            Ok(lexer) => lexer,
+            Err(e) => {
+                eprintln!("Skip {}: {}", file, e);
+                continue;
+            }
+        };
 
         loop {
             let tok = lexer.next_useful();
@@ -346,6 +353,15 @@ fn main() {
                 .default_value("")
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("profiles")
+                .help(
+                    "Directory containing toolchain profiles (foo.macros, foo.search and foo.caps)",
+                )
+                .short("p")
+                .long("profiles")
+                .takes_value(true),
+        )
         .arg(
             Arg::with_name("output")
                 .help("Output file/directory")
@@ -369,6 +385,14 @@ fn main() {
 
     let output = matches.value_of("output").unwrap().to_string();
 
+    let mut profiles = Profiles::bundled();
+    if let Some(dir) = matches.value_of("profiles") {
+        if let Err(e) = profiles.load_dir(dir) {
+            eprintln!("Cannot load the profiles from {}: {}", dir, e);
+            process::exit(1);
+        }
+    }
+
     let all_stats = Arc::new(Mutex::new(HashMap::default()));
     let source = source::get_source_mutex();
     let if_cache = Arc::new(IfCache::default());
@@ -389,20 +413,35 @@ fn main() {
         receivers.push(t);
     }
 
-    let mut sent: HashSet<PathBuf> = HashSet::default();
-    let sys_paths = defaults::get_sys_paths();
-    for mut cmd in CompilationDB::from_json(&database) {
+    // The same file can appear several times with different outputs
+    // (e.g. one object per target) so only skip exact duplicates
+    let mut sent: HashSet<(PathBuf, Option<PathBuf>)> = HashSet::default();
+    let db = match CompilationDB::from_json(&database) {
+        Ok(db) => db,
+        Err(e) => {
+            eprintln!("Cannot load the compilation database: {}", e);
+            process::exit(1);
+        }
+    };
+    for e in db.get_errors() {
+        eprintln!("Skip {}", e);
+    }
+
+    for mut cmd in db {
         let file = if cmd.opt.file.is_absolute() {
             cmd.opt.file.clone()
         } else {
             cmd.opt.current_dir.join(&cmd.opt.file)
         };
-        if file.exists() && !sent.contains(&file) {
-            cmd.file = file.clone();
-            sent.insert(file.clone());
-
-            cmd.opt.sys_paths.extend_from_slice(&sys_paths);
-            let mut def = defaults::get_defined();
+        let key = (file.clone(), cmd.output.clone());
+        if file.exists() && !sent.contains(&key) {
+            cmd.file = file;
+            sent.insert(key);
+
+            let profile = profiles.get_for(&cmd.opt).unwrap();
+            cmd.opt.set_default_paths(&profile.sys_paths);
+            cmd.opt.capabilities = profile.capabilities.clone();
+            let mut def = defaults::get_defined_for(profile, cmd.opt.lang);
             def.extend_from_slice(&cmd.opt.def);
             cmd.opt.def = def;
 
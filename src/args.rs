@@ -3,10 +3,15 @@
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
-use serde::Deserialize;
-use std::fs::File;
-use std::io::Read;
-use std::path::PathBuf;
+use serde::{Deserialize, Serialize};
+use std::ffi::OsStr;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use crate::errors::FileError;
+use crate::lexer::encoding::Encoding;
+use crate::lexer::preprocessor::include::IncludePaths;
+use crate::toolchain::Capabilities;
 
 macro_rules! skip_whites {
     ( $lexer: expr) => {{
@@ -30,7 +35,7 @@ pub enum Macro {
     Undef(String),
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Language {
     C,
     CPP,
@@ -42,16 +47,389 @@ impl Default for Language {
     }
 }
 
+impl Language {
+    fn from_x(s: &str) -> Option<Self> {
+        match s {
+            "c" | "c-header" | "cpp-output" => Some(Language::C),
+            "c++" | "c++-header" | "c++-cpp-output" => Some(Language::CPP),
+            _ => None,
+        }
+    }
+
+    fn from_extension(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "c" | "i" => Some(Language::C),
+            "C" | "cc" | "cp" | "cpp" | "cxx" | "c++" | "CPP" | "ii" | "hh" | "hpp" | "hxx"
+            | "h++" | "H" | "ipp" | "tcc" | "inl" => Some(Language::CPP),
+            _ => None,
+        }
+    }
+}
+
+/// The compiler driver which is used to interpret a command line
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Driver {
+    /// gcc, clang, ...
+    #[default]
+    GNU,
+    /// cl.exe
+    CL,
+    /// clang-cl or clang --driver-mode=cl
+    ClangCL,
+}
+
+impl Driver {
+    fn from_parts(parts: &[String]) -> Self {
+        for part in parts.iter().skip(1) {
+            if let Some(mode) = part.strip_prefix("--driver-mode=") {
+                if mode == "cl" {
+                    return Driver::ClangCL;
+                }
+                return Driver::GNU;
+            }
+        }
+
+        let name = parts.first().map_or("", |p| {
+            // The executable can be a Windows path on a Unix machine
+            p.rsplit(['/', '\\']).next().unwrap()
+        });
+        let name = name.to_lowercase();
+        let name = name.strip_suffix(".exe").unwrap_or(&name);
+        if name == "cl" {
+            Driver::CL
+        } else if name.starts_with("clang-cl") {
+            Driver::ClangCL
+        } else {
+            Driver::GNU
+        }
+    }
+
+    pub fn is_msvc(self) -> bool {
+        self != Driver::GNU
+    }
+}
+
+/// The version of the C or C++ standard
+/// The order matters: it allows to compare two versions of the same language
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+pub enum Standard {
+    C89,
+    C99,
+    C11,
+    C17,
+    C23,
+    CPP98,
+    CPP11,
+    CPP14,
+    CPP17,
+    CPP20,
+    CPP23,
+}
+
+impl Standard {
+    pub fn get_language(self) -> Language {
+        use Standard::*;
+        match self {
+            C89 | C99 | C11 | C17 | C23 => Language::C,
+            _ => Language::CPP,
+        }
+    }
+
+    /// Value of __cplusplus
+    pub fn cplusplus(self) -> Option<&'static str> {
+        use Standard::*;
+        match self {
+            CPP98 => Some("199711L"),
+            CPP11 => Some("201103L"),
+            CPP14 => Some("201402L"),
+            CPP17 => Some("201703L"),
+            CPP20 => Some("202002L"),
+            CPP23 => Some("202302L"),
+            _ => None,
+        }
+    }
+
+    /// Value of __STDC_VERSION__ (not defined in C89)
+    pub fn stdc_version(self) -> Option<&'static str> {
+        use Standard::*;
+        match self {
+            C99 => Some("199901L"),
+            C11 => Some("201112L"),
+            C17 => Some("201710L"),
+            C23 => Some("202311L"),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LangStandard {
+    pub std: Standard,
+    /// true for -std=gnu* (GNU extensions enabled)
+    pub gnu: bool,
+}
+
+impl Default for LangStandard {
+    fn default() -> Self {
+        Self::default_for(Language::default())
+    }
+}
+
+impl LangStandard {
+    pub fn new(std: Standard, gnu: bool) -> Self {
+        Self { std, gnu }
+    }
+
+    /// Default standard used by clang when there is no -std
+    pub fn default_for(lang: Language) -> Self {
+        match lang {
+            Language::C => Self::new(Standard::C11, true),
+            Language::CPP => Self::new(Standard::CPP17, true),
+        }
+    }
+
+    /// Default standard used by cl when there is no /std
+    pub fn default_for_msvc(lang: Language) -> Self {
+        match lang {
+            Language::C => Self::new(Standard::C89, false),
+            Language::CPP => Self::new(Standard::CPP14, false),
+        }
+    }
+
+    /// Parse the value of /std:...
+    pub fn from_msvc_std(s: &str) -> Option<Self> {
+        use Standard::*;
+        let std = match s {
+            "c11" => C11,
+            "c17" => C17,
+            "clatest" => C23,
+            "c++14" => CPP14,
+            "c++17" => CPP17,
+            "c++20" => CPP20,
+            "c++latest" | "c++23preview" => CPP23,
+            _ => return None,
+        };
+
+        Some(Self::new(std, false))
+    }
+
+    /// Parse the value of -std=...
+    pub fn from_std(s: &str) -> Option<Self> {
+        use Standard::*;
+        let (gnu, s) = if let Some(s) = s.strip_prefix("gnu") {
+            (true, s)
+        } else if let Some(s) = s.strip_prefix('c') {
+            (false, s)
+        } else if let Some(s) = s.strip_prefix("iso9899:") {
+            (false, s)
+        } else {
+            return None;
+        };
+
+        let std = match s {
+            "89" | "90" | "1990" | "199409" => C89,
+            "99" | "9x" | "1999" => C99,
+            "11" | "1x" | "2011" => C11,
+            "17" | "18" | "2017" | "2018" => C17,
+            "2x" | "23" | "2024" => C23,
+            "++98" | "++03" => CPP98,
+            "++11" | "++0x" => CPP11,
+            "++14" | "++1y" => CPP14,
+            "++17" | "++1z" => CPP17,
+            "++20" | "++2a" => CPP20,
+            "++23" | "++2b" => CPP23,
+            _ => return None,
+        };
+
+        Some(Self::new(std, gnu))
+    }
+
+    pub fn get_language(&self) -> Language {
+        self.std.get_language()
+    }
+
+    pub fn is_cpp(&self) -> bool {
+        self.get_language() == Language::CPP
+    }
+
+    /// Check if we're at least in the given standard (for the same language)
+    pub fn at_least(&self, std: Standard) -> bool {
+        self.get_language() == std.get_language() && self.std >= std
+    }
+
+    /// Get the macros depending on the language standard
+    pub fn get_defined(&self) -> Vec<Macro> {
+        let mut defs = Vec::new();
+        let mut def = |name: &str, value: &str| {
+            defs.push(Macro::Defined((name.to_string(), value.to_string())));
+        };
+
+        if let Some(v) = self.std.cplusplus() {
+            def("__cplusplus", v);
+        }
+        if let Some(v) = self.std.stdc_version() {
+            def("__STDC_VERSION__", v);
+        }
+        if !self.gnu {
+            def("__STRICT_ANSI__", "1");
+        }
+        if self.std == Standard::C89 {
+            def("__GNUC_GNU_INLINE__", "1");
+        } else {
+            def("__GNUC_STDC_INLINE__", "1");
+        }
+        if self.is_cpp() {
+            def("__GNUG__", "4");
+            if self.gnu {
+                def("_GNU_SOURCE", "1");
+            }
+        }
+
+        defs
+    }
+
+    /// Get the macros depending on the language standard for cl or clang-cl:
+    /// cl keeps __cplusplus at 199711L (without /Zc:__cplusplus) and uses _MSVC_LANG
+    pub fn get_msvc_defined(&self, driver: Driver) -> Vec<Macro> {
+        let mut defs = Vec::new();
+        let mut def = |name: &str, value: &str| {
+            defs.push(Macro::Defined((name.to_string(), value.to_string())));
+        };
+
+        if let Some(v) = self.std.cplusplus() {
+            if driver == Driver::CL {
+                def("__cplusplus", "199711L");
+            } else {
+                def("__cplusplus", v);
+            }
+            def("_MSVC_LANG", v);
+        }
+        if let Some(v) = self.std.stdc_version() {
+            def("__STDC_VERSION__", v);
+        }
+
+        defs
+    }
+}
+
 #[derive(Debug, Default)]
 pub struct PreprocOptions {
     pub def: Vec<Macro>,
+    /// -I
     pub sys_paths: Vec<String>,
+    /// -iquote
+    pub quote_paths: Vec<String>,
+    /// -isystem
+    pub system_paths: Vec<String>,
+    /// -idirafter
+    pub after_paths: Vec<String>,
+    /// The compiler default system paths, see set_default_paths
+    pub default_paths: Vec<String>,
     pub includes: Vec<String>,
+    pub imacros: Vec<String>,
+    pub nostdinc: bool,
+    pub nostdincpp: bool,
+    pub sysroot: Option<PathBuf>,
+    pub isysroot: Option<PathBuf>,
     pub current_dir: PathBuf,
     pub file: PathBuf,
     pub lang: Language,
+    pub std: LangStandard,
+    pub driver: Driver,
+    /// --target=...
+    pub target: Option<String>,
+    /// -march=...
+    pub march: Option<String>,
+    /// -m32 or -m64
+    pub machine_bits: Option<u32>,
+    /// -trigraphs or -fno-trigraphs (None means that the standard decides)
+    pub trigraphs: Option<bool>,
+    /// -finput-charset=... or /source-charset:... (the encoding of the files without a BOM)
+    pub charset: Encoding,
+    /// The answers to __has_attribute(x), __has_builtin(x), ... (see toolchain::Profile):
+    /// None means toolchain::default_capabilities()
+    pub capabilities: Option<Capabilities>,
+}
+
+impl PreprocOptions {
+    /// Get the macros depending on the language standard and on the driver
+    pub fn get_std_defined(&self) -> Vec<Macro> {
+        if self.driver.is_msvc() {
+            self.std.get_msvc_defined(self.driver)
+        } else {
+            self.std.get_defined()
+        }
+    }
+
+    /// -isysroot takes precedence over --sysroot for the headers
+    pub fn get_sysroot(&self) -> Option<&PathBuf> {
+        self.isysroot.as_ref().or(self.sysroot.as_ref())
+    }
+
+    fn rebase(root: &Path, path: &str) -> PathBuf {
+        root.join(path.trim_start_matches('/'))
+    }
+
+    /// A leading '=' in an include path is replaced by the sysroot
+    fn get_path(&self, path: &str) -> PathBuf {
+        if let Some(path) = path.strip_prefix('=') {
+            if let Some(root) = self.get_sysroot() {
+                return Self::rebase(root, path);
+            }
+            PathBuf::from(path)
+        } else {
+            PathBuf::from(path)
+        }
+    }
+
+    fn get_paths(&self, paths: &[String]) -> Vec<PathBuf> {
+        paths.iter().map(|p| self.get_path(p)).collect()
+    }
+
+    /// Set the compiler default system paths (e.g. defaults::get_sys_paths()):
+    /// they're all dropped with -nostdinc, the C++ ones are dropped with -nostdinc++
+    /// or when the language is C and the remaining ones are rebased onto the sysroot.
+    pub fn set_default_paths<S: AsRef<str>>(&mut self, paths: &[S]) {
+        self.default_paths.clear();
+        if self.nostdinc {
+            return;
+        }
+
+        let no_cpp = self.nostdincpp || self.lang == Language::C;
+        for path in paths.iter().map(|p| p.as_ref()) {
+            if no_cpp
+                && Path::new(path)
+                    .components()
+                    .any(|c| c == Component::Normal("c++".as_ref()))
+            {
+                continue;
+            }
+            let path = if let Some(root) = self.get_sysroot() {
+                Self::rebase(root, path).to_str().unwrap().to_string()
+            } else {
+                path.to_string()
+            };
+            self.default_paths.push(path);
+        }
+    }
+
+    /// Get the include search chain: -iquote, -I, -isystem and defaults, -idirafter
+    pub fn get_include_paths(&self) -> IncludePaths {
+        let mut system = self.get_paths(&self.system_paths);
+        system.extend(self.default_paths.iter().map(PathBuf::from));
+
+        IncludePaths {
+            quote: self.get_paths(&self.quote_paths),
+            angled: self.get_paths(&self.sys_paths),
+            system,
+            after: self.get_paths(&self.after_paths),
+        }
+    }
 }
 
+/// Max nesting level for response files
+const MAX_RESPONSE_FILE_DEPTH: usize = 16;
+
 struct Args<'a> {
     buf: &'a [u8],
     pos: usize,
@@ -131,6 +509,9 @@ impl<'a> Args<'a> {
                         break;
                     }
                     b'\\' => {
+                        if !self.has_char() {
+                            break;
+                        }
                         let c = self.next_char();
                         self.inc();
                         match c {
@@ -149,6 +530,9 @@ impl<'a> Args<'a> {
                                 let mut n = (c - b'0') as u8;
                                 for _ in 0..1 {
                                     self.inc();
+                                    if !self.has_char() {
+                                        break;
+                                    }
                                     let c = self.next_char();
                                     if b'0' <= c && c <= b'7' {
                                         n = 8 * n + ((c - b'0') as u8);
@@ -160,6 +544,9 @@ impl<'a> Args<'a> {
                             }
                             b'x' => {
                                 self.inc();
+                                if self.pos + 1 >= self.len {
+                                    break;
+                                }
                                 let c = self.next_char();
                                 let n = Self::get_hex_digit(c);
                                 self.inc();
@@ -172,7 +559,9 @@ impl<'a> Args<'a> {
                                 }
                             }
                             _ => {
-                                unreachable!();
+                                // Like in bash, the backslash is kept
+                                buf.push('\\');
+                                buf.push(c as char);
                             }
                         }
                     }
@@ -180,6 +569,9 @@ impl<'a> Args<'a> {
                         buf.push(c as char);
                     }
                 }
+            } else {
+                // Unterminated string
+                break;
             }
         }
     }
@@ -205,6 +597,9 @@ impl<'a> Args<'a> {
                         buf.push(c as char);
                     }
                 }
+            } else {
+                // Unterminated string
+                break;
             }
         }
     }
@@ -267,16 +662,221 @@ impl<'a> Args<'a> {
     }
 
     fn parse(&mut self) {
-        let parts = self.get_parts();
-        self.opt.lang = Self::get_language(&parts[0]);
+        // cl command lines use the Windows quoting rules
+        let cl = String::from_utf8_lossy(self.buf);
+        let parts = Self::split_windows(&cl);
+        let parts = if Driver::from_parts(&parts).is_msvc() {
+            parts
+        } else {
+            self.get_parts()
+        };
+        self.parse_parts(&parts);
+    }
+
+    /// Split a command line according to the rules used by gcc (libiberty)
+    fn split_gnu(s: &str) -> Vec<String> {
+        let mut parts = Vec::new();
+        let mut buf = String::new();
+        let mut in_part = false;
+        let mut quote = None;
+        let mut chars = s.chars();
+
+        while let Some(c) = chars.next() {
+            if c == '\\' {
+                if let Some(c) = chars.next() {
+                    buf.push(c);
+                }
+                in_part = true;
+            } else if let Some(q) = quote {
+                if c == q {
+                    quote = None;
+                } else {
+                    buf.push(c);
+                }
+            } else if c == '\'' || c == '"' {
+                quote = Some(c);
+                in_part = true;
+            } else if c.is_whitespace() {
+                if in_part {
+                    parts.push(std::mem::take(&mut buf));
+                    in_part = false;
+                }
+            } else {
+                buf.push(c);
+                in_part = true;
+            }
+        }
+
+        if in_part {
+            parts.push(buf);
+        }
+
+        parts
+    }
+
+    /// Split a command line according to the rules used by the MSVC runtime:
+    /// 2n backslashes followed by a quote give n backslashes and the quote toggles the quoted mode,
+    /// 2n+1 backslashes followed by a quote give n backslashes and a literal quote,
+    /// other backslashes are literals and "" in quoted mode is a literal quote.
+    fn split_windows(s: &str) -> Vec<String> {
+        let mut parts = Vec::new();
+        let mut buf = String::new();
+        let mut in_part = false;
+        let mut quoted = false;
+        let mut chars = s.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => {
+                    let mut n = 1;
+                    while chars.peek() == Some(&'\\') {
+                        chars.next();
+                        n += 1;
+                    }
+                    if chars.peek() == Some(&'"') {
+                        buf.extend(std::iter::repeat_n('\\', n / 2));
+                        if n % 2 == 1 {
+                            chars.next();
+                            buf.push('"');
+                        }
+                    } else {
+                        buf.extend(std::iter::repeat_n('\\', n));
+                    }
+                    in_part = true;
+                }
+                '"' => {
+                    if quoted && chars.peek() == Some(&'"') {
+                        chars.next();
+                        buf.push('"');
+                    } else {
+                        quoted = !quoted;
+                    }
+                    in_part = true;
+                }
+                c if c.is_whitespace() && !quoted => {
+                    if in_part {
+                        parts.push(std::mem::take(&mut buf));
+                        in_part = false;
+                    }
+                }
+                c => {
+                    buf.push(c);
+                    in_part = true;
+                }
+            }
+        }
+
+        if in_part {
+            parts.push(buf);
+        }
+
+        parts
+    }
+
+    /// Replace the @file arguments by the arguments contained in the response file
+    /// A file which cannot be read is kept as is (like gcc does)
+    fn expand_response_files(&self, parts: &[String], depth: usize) -> Vec<String> {
+        let mut expanded = Vec::with_capacity(parts.len());
+        for part in parts {
+            if let Some(file) = part.strip_prefix('@') {
+                // Avoid infinite recursion with response files including themselves
+                if depth < MAX_RESPONSE_FILE_DEPTH {
+                    if let Ok(data) = std::fs::read(self.opt.current_dir.join(file)) {
+                        let data = String::from_utf8_lossy(&data);
+                        let inner = if self.opt.driver.is_msvc() {
+                            Self::split_windows(&data)
+                        } else {
+                            Self::split_gnu(&data)
+                        };
+                        expanded.extend(self.expand_response_files(&inner, depth + 1));
+                        continue;
+                    }
+                }
+            }
+            expanded.push(part.clone());
+        }
+
+        expanded
+    }
+
+    fn add_define(&mut self, defined: &str) {
+        // cl accepts /DFOO#1 for FOO=1
+        let sep: &[char] = if self.opt.driver.is_msvc() {
+            &['=', '#']
+        } else {
+            &['=']
+        };
+        let toks: Vec<_> = defined.splitn(2, sep).collect();
+        if toks.len() == 1 {
+            self.opt
+                .def
+                .push(Macro::Defined((toks[0].to_string(), "1".to_string())));
+        } else {
+            self.opt
+                .def
+                .push(Macro::Defined((toks[0].to_string(), toks[1].to_string())));
+        }
+    }
+
+    /// --target=foo or --target foo (also accepted by clang-cl)
+    fn parse_target(&mut self, parts: &[String], i: &mut usize) -> bool {
+        let part = &parts[*i];
+        if let Some(target) = part.strip_prefix("--target=") {
+            self.opt.target = Some(target.to_string());
+        } else if part == "--target" && *i + 1 < parts.len() {
+            *i += 1;
+            self.opt.target = Some(parts[*i].clone());
+        } else {
+            return false;
+        }
+        true
+    }
+
+    /// -m32, -m64 and -march=...
+    fn parse_machine(&mut self, part: &str) -> bool {
+        match part {
+            "-m32" => self.opt.machine_bits = Some(32),
+            "-m64" => self.opt.machine_bits = Some(64),
+            _ => {
+                if let Some(arch) = part.strip_prefix("-march=") {
+                    self.opt.march = Some(arch.to_string());
+                } else {
+                    return false;
+                }
+            }
+        }
+        true
+    }
+
+    fn parse_parts(&mut self, parts: &[String]) {
+        if parts.is_empty() {
+            return;
+        }
+
+        self.opt.driver = Driver::from_parts(parts);
+        let parts = &self.expand_response_files(parts, 0);
+
+        if self.opt.driver.is_msvc() {
+            self.parse_msvc_parts(parts);
+            return;
+        }
+
         let file = parts.last().unwrap();
         let path = PathBuf::from(file);
+        self.opt.lang = Self::get_language(&parts[0]);
+        if self.opt.lang == Language::C {
+            // clang++/g++ compile everything as C++ whatever the extension is
+            if let Some(lang) = Language::from_extension(&path) {
+                self.opt.lang = lang;
+            }
+        }
         self.opt.file = path;
 
+        let mut std = None;
         let mut i = 1;
         while i < parts.len() {
             let part = &parts[i];
-            if !part.starts_with('-') {
+            if !part.starts_with('-') || part.len() < 2 {
                 i += 1;
                 continue;
             }
@@ -285,58 +885,190 @@ impl<'a> Args<'a> {
 
             match bytes[1] {
                 b'D' => {
-                    let defined = if part.len() > 2 {
-                        &part[2..]
-                    } else {
-                        i += 1;
-                        &parts[i]
-                    };
-                    let toks: Vec<_> = defined.splitn(2, '=').collect();
-                    if toks.len() == 1 {
-                        self.opt
-                            .def
-                            .push(Macro::Defined((toks[0].to_string(), "1".to_string())));
-                    } else {
-                        self.opt
-                            .def
-                            .push(Macro::Defined((toks[0].to_string(), toks[1].to_string())));
+                    if let Some(defined) = Self::get_short_value(parts, &mut i) {
+                        self.add_define(defined);
                     }
                 }
                 b'I' => {
-                    let path = if part.len() > 2 {
-                        &part[2..]
-                    } else {
-                        i += 1;
-                        &parts[i]
-                    };
-                    self.opt.sys_paths.push(path.to_string());
+                    if let Some(path) = Self::get_short_value(parts, &mut i) {
+                        self.opt.sys_paths.push(path.to_string());
+                    }
                 }
                 b'i' => {
-                    if part.starts_with("-include") {
-                        let n = "-include".len();
-                        let path = if part.len() > n {
-                            &part[n..]
-                        } else {
-                            i += 1;
-                            &parts[i]
-                        };
-                        self.opt.includes.push(path.to_string());
+                    if let Some(path) = Self::get_value(parts, &mut i, "include") {
+                        self.opt.includes.push(path);
+                    } else if let Some(path) = Self::get_value(parts, &mut i, "imacros") {
+                        self.opt.imacros.push(path);
+                    } else if let Some(path) = Self::get_value(parts, &mut i, "isystem") {
+                        self.opt.system_paths.push(path);
+                    } else if let Some(path) = Self::get_value(parts, &mut i, "iquote") {
+                        self.opt.quote_paths.push(path);
+                    } else if let Some(path) = Self::get_value(parts, &mut i, "idirafter") {
+                        self.opt.after_paths.push(path);
+                    } else if let Some(path) = Self::get_value(parts, &mut i, "isysroot") {
+                        self.opt.isysroot = Some(PathBuf::from(path));
                     }
                 }
-                b'U' => {
-                    let undef = if part.len() > 2 {
-                        &part[2..]
-                    } else {
+                b'n' => {
+                    if part == "-nostdinc" {
+                        self.opt.nostdinc = true;
+                    } else if part == "-nostdinc++" {
+                        self.opt.nostdincpp = true;
+                    }
+                }
+                b'm' => {
+                    self.parse_machine(part);
+                }
+                b't' if part == "-target" && i + 1 < parts.len() => {
+                    i += 1;
+                    self.opt.target = Some(parts[i].clone());
+                }
+                b't' | b'f' if part == "-trigraphs" || part == "-ftrigraphs" => {
+                    self.opt.trigraphs = Some(true);
+                }
+                b'f' if part == "-fno-trigraphs" => {
+                    self.opt.trigraphs = Some(false);
+                }
+                b'f' if part.starts_with("-finput-charset=") => {
+                    let name = &part["-finput-charset=".len()..];
+                    if let Some(charset) = Encoding::from_name(name) {
+                        self.opt.charset = charset;
+                    }
+                }
+                b'-' => {
+                    if let Some(root) = part.strip_prefix("--sysroot=") {
+                        self.opt.sysroot = Some(PathBuf::from(root));
+                    } else if part == "--sysroot" && i + 1 < parts.len() {
                         i += 1;
-                        &parts[i]
-                    };
-                    self.opt.def.push(Macro::Undef(undef.to_string()));
+                        self.opt.sysroot = Some(PathBuf::from(&parts[i]));
+                    } else {
+                        self.parse_target(parts, &mut i);
+                    }
+                }
+                b'U' => {
+                    if let Some(undef) = Self::get_short_value(parts, &mut i) {
+                        self.opt.def.push(Macro::Undef(undef.to_string()));
+                    }
+                }
+                b's' if part.starts_with("-std=") => {
+                    std = LangStandard::from_std(&part["-std=".len()..]);
+                }
+                b'x' => {
+                    if let Some(lang) = Self::get_short_value(parts, &mut i) {
+                        if let Some(lang) = Language::from_x(lang) {
+                            self.opt.lang = lang;
+                        }
+                    }
                 }
                 _ => {}
             }
 
             i += 1;
         }
+
+        // like gcc, a -std for the wrong language is just ignored
+        self.opt.std = match std {
+            Some(std) if std.get_language() == self.opt.lang => std,
+            _ => LangStandard::default_for(self.opt.lang),
+        };
+    }
+
+    fn parse_msvc_parts(&mut self, parts: &[String]) {
+        let mut file = parts.last().map(PathBuf::from).unwrap();
+        let mut lang = None;
+        let mut std = None;
+        let mut i = 1;
+        while i < parts.len() {
+            let part = &parts[i];
+            if !(part.starts_with('/') || part.starts_with('-')) || part.len() < 2 {
+                i += 1;
+                continue;
+            }
+
+            if let Some(defined) = Self::get_value(parts, &mut i, "D") {
+                self.add_define(&defined);
+            } else if let Some(undef) = Self::get_value(parts, &mut i, "U") {
+                self.opt.def.push(Macro::Undef(undef));
+            } else if let Some(path) = Self::get_value(parts, &mut i, "I") {
+                self.opt.sys_paths.push(path);
+            } else if let Some(path) = Self::get_value(parts, &mut i, "FI") {
+                self.opt.includes.push(path);
+            } else if let Some(s) = part[1..].strip_prefix("std:") {
+                std = LangStandard::from_msvc_std(s);
+            } else if let Some(name) = part[1..].strip_prefix("source-charset:") {
+                if let Some(charset) = Encoding::from_name(name) {
+                    self.opt.charset = charset;
+                }
+            } else if &part[1..] == "utf-8" {
+                self.opt.charset = Encoding::Utf8;
+            } else if &part[1..] == "Zc:trigraphs" {
+                self.opt.trigraphs = Some(true);
+            } else if &part[1..] == "Zc:trigraphs-" {
+                self.opt.trigraphs = Some(false);
+            } else if &part[1..] == "TP" {
+                lang = Some(Language::CPP);
+            } else if &part[1..] == "TC" {
+                lang = Some(Language::C);
+            } else if let Some(path) = Self::get_value(parts, &mut i, "Tp") {
+                file = PathBuf::from(path);
+                lang = Some(Language::CPP);
+            } else if let Some(path) = Self::get_value(parts, &mut i, "Tc") {
+                file = PathBuf::from(path);
+                lang = Some(Language::C);
+            } else if !self.parse_target(parts, &mut i) {
+                self.parse_machine(part);
+            }
+
+            i += 1;
+        }
+
+        // cl compiles .c files as C and everything else as C++
+        self.opt.lang = lang.unwrap_or_else(|| {
+            if file.extension().is_some_and(|e| e == "c") {
+                Language::C
+            } else {
+                Language::CPP
+            }
+        });
+        self.opt.file = file;
+        self.opt.std = match std {
+            Some(std) if std.get_language() == self.opt.lang => std,
+            _ => LangStandard::default_for_msvc(self.opt.lang),
+        };
+    }
+
+    /// Get the value of a one letter option which is either glued to it (-DFOO)
+    /// or in the next part (-D FOO): None if the option is the last part
+    fn get_short_value<'b>(parts: &'b [String], i: &mut usize) -> Option<&'b str> {
+        let part = &parts[*i];
+        if part.len() > 2 {
+            Some(&part[2..])
+        } else if *i + 1 < parts.len() {
+            *i += 1;
+            Some(&parts[*i])
+        } else {
+            None
+        }
+    }
+
+    /// Get the value of an option which is either glued to it (-isystemfoo)
+    /// or in the next part (-isystem foo)
+    /// The option name is given without its leading '-' (or '/' for cl).
+    fn get_value(parts: &[String], i: &mut usize, opt: &str) -> Option<String> {
+        let part = &parts[*i];
+        if !part.get(1..).is_some_and(|p| p.starts_with(opt)) {
+            return None;
+        }
+
+        let n = opt.len() + 1;
+        if part.len() > n {
+            Some(part[n..].to_string())
+        } else if *i + 1 < parts.len() {
+            *i += 1;
+            Some(parts[*i].clone())
+        } else {
+            Some(String::new())
+        }
     }
 
     pub fn get_options(cl: &[u8]) -> PreprocOptions {
@@ -347,44 +1079,676 @@ impl<'a> Args<'a> {
     }
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 struct JsonCommand {
     directory: PathBuf,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     command: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    arguments: Vec<String>,
     file: PathBuf,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    output: Option<PathBuf>,
+    /// The language of the file (set when the entry is added to a database)
+    #[serde(skip)]
+    lang: Language,
+    /// The absolute path of the file (set when the entry is added to a database)
+    #[serde(skip)]
+    path: PathBuf,
 }
 
-#[derive(Debug)]
-pub struct Command {
-    pub opt: PreprocOptions,
-    pub file: PathBuf,
-}
-
-pub struct CompilationDB {
-    vec: Vec<JsonCommand>,
-}
+impl JsonCommand {
+    fn to_command(&self) -> Command {
+        // The current directory is required to find the response files
+        let mut args = Args::new(self.command.as_bytes());
+        args.opt.current_dir = self.directory.clone();
+
+        // The arguments array is already split so prefer it when it's here
+        if self.arguments.is_empty() {
+            args.parse();
+        } else {
+            args.parse_parts(&self.arguments);
+        }
+        Command {
+            opt: args.opt,
+            file: self.file.clone(),
+            output: self.output.clone(),
+        }
+    }
+
+    /// Check that the command can be used: there is a command and an option
+    /// which takes a value can't be the last part of the command line
+    fn check(&self) -> Result<(), String> {
+        let parts = self.get_parts();
+        if parts.is_empty() {
+            return Err("no command or arguments".to_string());
+        }
+        if Driver::from_parts(&parts).is_msvc() {
+            return Ok(());
+        }
+        match parts.last() {
+            Some(last) if ["-D", "-I", "-U", "-x"].contains(&last.as_str()) => {
+                Err(format!("missing argument to {}", last))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Get the output from the command line when it isn't in the entry
+    fn guess_output(&self) -> Option<PathBuf> {
+        let parts = self.get_parts();
+        let msvc = Driver::from_parts(&parts).is_msvc();
+        let mut output = None;
+        let mut iter = parts.iter().skip(1);
+        while let Some(part) = iter.next() {
+            if msvc {
+                if let Some(out) = part
+                    .strip_prefix("/Fo")
+                    .or_else(|| part.strip_prefix("-Fo"))
+                {
+                    output = Some(out);
+                }
+            } else if part == "-o" {
+                output = iter.next().map(|p| p.as_str());
+            } else if let Some(out) = part.strip_prefix("-o") {
+                output = Some(out);
+            }
+        }
+        output.filter(|o| !o.is_empty()).map(PathBuf::from)
+    }
+
+    fn get_full_path(&self) -> PathBuf {
+        if self.file.is_absolute() {
+            self.file.clone()
+        } else {
+            self.directory.join(&self.file)
+        }
+    }
+
+    fn get_parts(&self) -> Vec<String> {
+        if self.arguments.is_empty() {
+            let cl = &self.command;
+            let parts = Args::split_windows(cl);
+            if Driver::from_parts(&parts).is_msvc() {
+                parts
+            } else {
+                Args::new(cl.as_bytes()).get_parts()
+            }
+        } else {
+            self.arguments.clone()
+        }
+    }
+
+    /// The language of the file as seen by the compiler (g++ compiles a .c file as C++)
+    /// It's guessed from the driver, the language options and the extension of the file
+    /// so the response files aren't read.
+    fn guess_language(&self) -> Language {
+        let parts = self.get_parts();
+        if Driver::from_parts(&parts).is_msvc() {
+            let mut lang = None;
+            for part in parts.iter().skip(1) {
+                let opt = part.strip_prefix(['/', '-']).unwrap_or_default();
+                if opt == "TP" || opt.starts_with("Tp") {
+                    lang = Some(Language::CPP);
+                } else if opt == "TC" || opt.starts_with("Tc") {
+                    lang = Some(Language::C);
+                }
+            }
+            return lang.unwrap_or_else(|| {
+                if self.file.extension().is_some_and(|e| e == "c") {
+                    Language::C
+                } else {
+                    Language::CPP
+                }
+            });
+        }
+
+        let mut lang = Args::get_language(parts.first().map_or("", |p| p.as_str()));
+        if lang == Language::C {
+            if let Some(l) = Language::from_extension(&self.file) {
+                lang = l;
+            }
+        }
+        let mut iter = parts.iter().skip(1);
+        while let Some(part) = iter.next() {
+            let x = if part == "-x" {
+                iter.next().map(|p| p.as_str())
+            } else {
+                part.strip_prefix("-x")
+            };
+            if let Some(l) = x.and_then(Language::from_x) {
+                lang = l;
+            }
+        }
+        lang
+    }
+
+    /// Make a command for another file using the options of this one (in language lang):
+    /// the source file and the output are replaced and a header is compiled as a header
+    fn interpolate(&self, file: &Path, lang: Language) -> JsonCommand {
+        let parts = self.get_parts();
+        let msvc = Driver::from_parts(&parts).is_msvc();
+        let full_path = self.get_full_path();
+        let header = is_header(file);
+
+        let mut arguments = Vec::with_capacity(parts.len() + 2);
+        let mut iter = parts.iter().peekable();
+        if let Some(compiler) = iter.next() {
+            arguments.push(compiler.clone());
+        }
+        while let Some(part) = iter.next() {
+            if Path::new(part) == self.file || self.directory.join(part) == full_path {
+                continue;
+            }
+            if msvc {
+                if ["/Fo", "-Fo", "/Tp", "-Tp", "/Tc", "-Tc"]
+                    .iter()
+                    .any(|p| part.starts_with(p))
+                    || part == "/TP"
+                    || part == "/TC"
+                {
+                    continue;
+                }
+            } else if part == "-o"
+                || part == "-x"
+                || part == "-MF"
+                || part == "-MT"
+                || part == "-MQ"
+            {
+                iter.next();
+                continue;
+            } else if part.starts_with("-o") || part.starts_with("-x") {
+                continue;
+            }
+            arguments.push(part.clone());
+        }
+
+        if header {
+            if msvc {
+                arguments.push(if lang == Language::C { "/TC" } else { "/TP" }.to_string());
+            } else {
+                arguments.push("-x".to_string());
+                arguments.push(
+                    if lang == Language::C {
+                        "c-header"
+                    } else {
+                        "c++-header"
+                    }
+                    .to_string(),
+                );
+            }
+        }
+        // A non UTF-8 path can't be in the arguments: the caller fixes the file in the command
+        arguments.push(file.to_string_lossy().into_owned());
+
+        // A precompiled header for a header, else an object next to the donor one
+        let output = if header {
+            let mut gch = file.as_os_str().to_owned();
+            gch.push(".gch");
+            Some(PathBuf::from(gch))
+        } else {
+            self.output.as_ref().and_then(|o| {
+                let ext = o.extension().unwrap_or_else(|| OsStr::new("o"));
+                let name = Path::new(file.file_name()?).with_extension(ext);
+                Some(o.with_file_name(name))
+            })
+        };
+
+        JsonCommand {
+            directory: self.directory.clone(),
+            command: String::new(),
+            arguments,
+            file: file.to_path_buf(),
+            output,
+            lang,
+            path: file.to_path_buf(),
+        }
+    }
+}
+
+/// Make a path absolute (from the current directory) and remove its `.` and `..`
+/// without touching the filesystem
+fn get_absolute_path(path: &Path) -> PathBuf {
+    let path = if path.is_relative() {
+        std::env::current_dir().map_or_else(|_| path.to_path_buf(), |dir| dir.join(path))
+    } else {
+        path.to_path_buf()
+    };
+    let mut res = PathBuf::new();
+    for c in path.components() {
+        match c {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !res.pop() {
+                    res.push("..");
+                }
+            }
+            c => res.push(c),
+        }
+    }
+    res
+}
+
+fn is_header(path: &Path) -> bool {
+    path.extension().is_some_and(|e| {
+        ["h", "hh", "hpp", "hxx", "h++", "H", "inl", "ipp", "tcc"]
+            .iter()
+            .any(|h| e == *h)
+    })
+}
+
+/// The score of a donor for interpolating the command of a file:
+/// a compatible language first, then the same stem (foo.h and foo.cpp),
+/// then the longest common directory and then the closest directory
+fn get_donor_score(file: &Path, donor: &Path, donor_lang: Language) -> (bool, bool, usize, isize) {
+    let lang = Language::from_extension(file);
+    // .h can be C or C++
+    let lang_ok = lang.is_none_or(|l| l == donor_lang);
+
+    let stem = file.file_stem();
+    let same_stem = stem.is_some() && stem == donor.file_stem();
+
+    let dir: Vec<_> = file.parent().map_or(vec![], |p| p.components().collect());
+    let donor_dir: Vec<_> = donor.parent().map_or(vec![], |p| p.components().collect());
+    let common = dir
+        .iter()
+        .zip(donor_dir.iter())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let distance = (dir.len() - common + donor_dir.len() - common) as isize;
+
+    (lang_ok, same_stem, common, -distance)
+}
+
+/// The options taking a value in the next argument which can be confused with a source file
+const OPTIONS_WITH_VALUE: &[&str] = &[
+    "-o",
+    "-MF",
+    "-MT",
+    "-MQ",
+    "-include",
+    "-imacros",
+    "-x",
+    "-Xclang",
+    "-Xpreprocessor",
+];
+
+/// The programs which can be in front of the compiler in a command line
+const COMPILER_WRAPPERS: &[&str] = &["ccache", "sccache", "distcc", "icecc", "time"];
+
+/// Check if a program is a C/C++ compiler: gcc, x86_64-linux-gnu-g++-12, clang-cl.exe, ...
+fn is_compiler(path: &str) -> bool {
+    let name = path.rsplit(['/', '\\']).next().unwrap().to_lowercase();
+    let name = name.strip_suffix(".exe").unwrap_or(&name);
+    // Remove a version suffix
+    let name = match name.rfind('-') {
+        Some(pos)
+            if pos + 1 < name.len()
+                && name[pos + 1..]
+                    .bytes()
+                    .all(|c| c.is_ascii_digit() || c == b'.') =>
+        {
+            &name[..pos]
+        }
+        _ => name,
+    };
+
+    [
+        "gcc", "g++", "cc", "c++", "clang", "clang++", "cl", "clang-cl",
+    ]
+    .iter()
+    .any(|c| name == *c || name.strip_suffix(c).is_some_and(|p| p.ends_with('-')))
+}
+
+/// Split a shell line on &&, || and ; which are outside of the quotes
+fn split_shell_commands(line: &str) -> Vec<&str> {
+    let bytes = line.as_bytes();
+    let mut commands = Vec::new();
+    let mut quote = None;
+    let mut start = 0;
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let c = bytes[i];
+        match quote {
+            Some(q) => {
+                if c == q {
+                    quote = None;
+                } else if c == b'\\' && q == b'"' {
+                    i += 1;
+                }
+            }
+            None => match c {
+                b'\'' | b'"' => quote = Some(c),
+                b'\\' => i += 1,
+                b';' => {
+                    commands.push(&line[start..i]);
+                    start = i + 1;
+                }
+                b'&' | b'|' if bytes.get(i + 1) == Some(&c) => {
+                    commands.push(&line[start..i]);
+                    i += 1;
+                    start = i + 1;
+                }
+                _ => {}
+            },
+        }
+        i += 1;
+    }
+    commands.push(&line[start.min(line.len())..]);
+
+    commands
+        .into_iter()
+        .map(|c| c.trim())
+        .filter(|c| !c.is_empty())
+        .collect()
+}
+
+/// Get the directory in a make message: make[1]: Entering directory '/foo/bar'
+fn get_make_directory<'a>(line: &'a str, msg: &str) -> Option<&'a str> {
+    if !line.starts_with("make") {
+        return None;
+    }
+    let pos = line.find(msg)?;
+    let dir = line[pos + msg.len()..].trim();
+    Some(
+        dir.trim_matches(|c| {
+            c == '\'' || c == '`' || c == '"' || c == '\u{2018}' || c == '\u{2019}'
+        }),
+    )
+}
+
+/// Make the compilation database entries from a compiler invocation:
+/// one entry per source file
+fn get_build_log_entries(parts: &[String], directory: &Path, entries: &mut Vec<JsonCommand>) {
+    let msvc = Driver::from_parts(parts).is_msvc();
+    // cl options don't contain a slash after the first char (unlike absolute Unix paths)
+    let is_opt =
+        |p: &str| p.starts_with('-') || (msvc && p.starts_with('/') && !p[1..].contains('/'));
+
+    if !parts.iter().any(|p| p == "-c" || (msvc && p == "/c")) {
+        // Link only
+        return;
+    }
+
+    let mut sources = Vec::new();
+    let mut output = None;
+    for (i, part) in parts.iter().enumerate().skip(1) {
+        let prev = parts[i - 1].as_str();
+        if prev == "-o" {
+            output = Some(PathBuf::from(part));
+            continue;
+        }
+        if OPTIONS_WITH_VALUE.contains(&prev) {
+            continue;
+        }
+        if let Some(out) = part.strip_prefix("-o") {
+            if !out.is_empty() && !msvc {
+                output = Some(PathBuf::from(out));
+            }
+        }
+        if msvc {
+            if let Some(out) = part
+                .strip_prefix("/Fo")
+                .or_else(|| part.strip_prefix("-Fo"))
+            {
+                output = Some(PathBuf::from(out));
+                continue;
+            }
+            if ["/Tp", "/Tc", "-Tp", "-Tc"]
+                .iter()
+                .any(|t| part.starts_with(t))
+                && part.len() > 3
+            {
+                sources.push((i, PathBuf::from(&part[3..])));
+                continue;
+            }
+        }
+        if !is_opt(part) && Language::from_extension(Path::new(part)).is_some() {
+            sources.push((i, PathBuf::from(part)));
+        }
+    }
+
+    for (i, file) in sources.iter() {
+        // Only keep the current source and put it at the end where Args expects it
+        let mut arguments: Vec<_> = parts
+            .iter()
+            .enumerate()
+            .filter(|(j, _)| sources.iter().all(|(k, _)| k != j))
+            .map(|(_, p)| p.clone())
+            .collect();
+        arguments.push(parts[*i].clone());
+
+        // cl accepts a directory in /Fo
+        let output = output.as_ref().map(|o: &PathBuf| {
+            let s = o.to_str().unwrap();
+            if msvc && (s.ends_with('/') || s.ends_with('\\')) {
+                // The file can be a Windows path so don't use Path::file_name
+                let name = file.to_str().unwrap().rsplit(['/', '\\']).next().unwrap();
+                let name = Path::new(name).with_extension("obj");
+                PathBuf::from(format!("{}{}", s, name.to_str().unwrap()))
+            } else {
+                o.clone()
+            }
+        });
+
+        entries.push(JsonCommand {
+            directory: directory.to_path_buf(),
+            command: String::new(),
+            arguments,
+            file: file.clone(),
+            output,
+            lang: Language::default(),
+            path: PathBuf::new(),
+        });
+    }
+}
+
+#[derive(Debug)]
+pub struct Command {
+    pub opt: PreprocOptions,
+    pub file: PathBuf,
+    pub output: Option<PathBuf>,
+}
+
+pub struct CompilationDB {
+    vec: Vec<JsonCommand>,
+    errors: Vec<FileError>,
+}
 
 impl Iterator for CompilationDB {
     type Item = Command;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if let Some(jc) = self.vec.pop() {
-            let mut opt = Args::get_options(jc.command.as_bytes());
-            opt.current_dir = jc.directory;
-            Some(Command { opt, file: jc.file })
-        } else {
-            None
-        }
+        self.vec.pop().map(|jc| jc.to_command())
     }
 }
 
 impl CompilationDB {
-    pub fn from_json(path: &str) -> CompilationDB {
-        let mut file = File::open(&path).unwrap();
-        let mut data = Vec::new();
-        file.read_to_end(&mut data).unwrap();
-        let vec: Vec<JsonCommand> = serde_json::de::from_slice(&data).unwrap();
-        CompilationDB { vec }
+    fn new(mut vec: Vec<JsonCommand>) -> CompilationDB {
+        for jc in vec.iter_mut() {
+            if jc.output.is_none() {
+                jc.output = jc.guess_output();
+            }
+            jc.lang = jc.guess_language();
+            jc.path = get_absolute_path(&jc.get_full_path());
+        }
+        CompilationDB {
+            vec,
+            errors: Vec::new(),
+        }
+    }
+
+    /// Load a compile_commands.json file
+    /// The entries which can't be decoded are skipped and kept in the errors
+    /// so a bad entry doesn't prevent from using the rest of the database.
+    pub fn from_json<P: AsRef<Path>>(path: P) -> Result<CompilationDB, FileError> {
+        let path = path.as_ref();
+        let data = std::fs::read(path).map_err(|err| FileError::Io {
+            path: path.to_path_buf(),
+            err,
+        })?;
+        let entries: Vec<serde_json::Value> =
+            serde_json::de::from_slice(&data).map_err(|err| FileError::Json {
+                path: path.to_path_buf(),
+                err,
+            })?;
+
+        let mut vec = Vec::with_capacity(entries.len());
+        let mut errors = Vec::new();
+        for (index, entry) in entries.into_iter().enumerate() {
+            let jc = serde_json::from_value::<JsonCommand>(entry).and_then(|jc| {
+                jc.check()
+                    .map(|_| jc)
+                    .map_err(<serde_json::Error as serde::de::Error>::custom)
+            });
+            match jc {
+                Ok(jc) => vec.push(jc),
+                Err(err) => errors.push(FileError::InvalidEntry {
+                    path: path.to_path_buf(),
+                    index,
+                    err,
+                }),
+            }
+        }
+
+        let mut db = CompilationDB::new(vec);
+        db.errors = errors;
+        Ok(db)
+    }
+
+    /// Get the errors for the entries which have been skipped when loading the database
+    pub fn get_errors(&self) -> &[FileError] {
+        &self.errors
+    }
+
+    /// Make a compilation database from the log of a build (make -n, make V=1, ninja -t commands, ...)
+    /// The directory of each command is tracked with the "Entering/Leaving directory" messages
+    /// from make and with the cd commands: a cd chained with && or ; only applies to its line
+    /// whereas a cd alone on its line applies to the following ones.
+    pub fn from_build_log<P: AsRef<Path>>(log: &str, directory: P) -> CompilationDB {
+        let mut vec = Vec::new();
+        let mut dirs = vec![directory.as_ref().to_path_buf()];
+        let mut line = String::new();
+
+        for l in log.lines() {
+            if let Some(l) = l.strip_suffix('\\') {
+                line.push_str(l);
+                line.push(' ');
+                continue;
+            }
+            line.push_str(l);
+            let l = std::mem::take(&mut line).replace('\t', " ");
+            let l = l.trim();
+
+            if let Some(dir) = get_make_directory(l, "Entering directory") {
+                let dir = dirs.last().unwrap().join(dir);
+                dirs.push(dir);
+                continue;
+            }
+            if get_make_directory(l, "Leaving directory").is_some() {
+                if dirs.len() > 1 {
+                    dirs.pop();
+                }
+                continue;
+            }
+
+            // ninja progress ([12/345]), sh -x or libtool prefixes
+            let l = if l.starts_with('[') {
+                l.find("] ").map_or(l, |p| &l[p + 2..])
+            } else {
+                l
+            };
+            let l = l.strip_prefix("+ ").unwrap_or(l);
+            let l = l.strip_prefix("libtool: compile:").unwrap_or(l);
+
+            let commands = split_shell_commands(l);
+            let mut cwd = dirs.last().unwrap().clone();
+            for cmd in commands.iter() {
+                let mut parts = Args::new(cmd.as_bytes()).get_parts();
+                // Skip the environment variables and the wrappers
+                let is_env = |p: &str| p.contains('=') && !p.starts_with('-');
+                let start = parts
+                    .iter()
+                    .position(|p| !is_env(p) && !COMPILER_WRAPPERS.contains(&p.as_str()))
+                    .unwrap_or(parts.len());
+                parts.drain(..start);
+
+                if parts.is_empty() {
+                    continue;
+                }
+                if parts[0] == "cd" {
+                    cwd = cwd.join(parts.get(1).map_or("", |p| p.as_str()));
+                    if commands.len() == 1 {
+                        *dirs.last_mut().unwrap() = cwd.clone();
+                    }
+                } else if is_compiler(&parts[0]) {
+                    get_build_log_entries(&parts, &cwd, &mut vec);
+                }
+            }
+        }
+
+        CompilationDB::new(vec)
+    }
+
+    pub fn from_build_log_file<P: AsRef<Path>, Q: AsRef<Path>>(
+        path: P,
+        directory: Q,
+    ) -> io::Result<CompilationDB> {
+        let data = std::fs::read(path)?;
+        let log = String::from_utf8_lossy(&data);
+        Ok(Self::from_build_log(&log, directory))
+    }
+
+    /// Get the database in the compile_commands.json format
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(&self.vec).unwrap()
+    }
+
+    pub fn write_json<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        std::fs::write(path, self.to_json())
+    }
+
+    /// Get a command for any file (e.g. a header or a new file) even if it isn't in the database:
+    /// the command for the file is returned if any, else the best donor entry is picked
+    /// (by language, file stem and directory proximity) and its command is adapted to the file.
+    /// A relative path is relative to the current directory.
+    pub fn interpolate<P: AsRef<Path>>(&self, file: P) -> Option<Command> {
+        let file = get_absolute_path(file.as_ref());
+        if let Some(jc) = self.vec.iter().find(|jc| jc.path == file) {
+            return Some(jc.to_command());
+        }
+
+        // The first entry wins in case of equality
+        let donor = self
+            .vec
+            .iter()
+            .rev()
+            .max_by_key(|jc| get_donor_score(&file, &jc.path, jc.lang))?;
+
+        let mut cmd = donor.interpolate(&file, donor.lang).to_command();
+        cmd.opt.file = file;
+        Some(cmd)
+    }
+
+    /// Get all the commands used to compile the given file
+    /// The same file can be compiled several times with different options
+    /// (e.g. for different targets), so each entry is kept.
+    pub fn get_commands<P: AsRef<Path>>(&self, file: P) -> Vec<Command> {
+        let file = file.as_ref();
+        self.vec
+            .iter()
+            .filter(|jc| jc.file == file || jc.get_full_path() == file)
+            .map(|jc| jc.to_command())
+            .collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.vec.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.vec.is_empty()
     }
 }
 
@@ -392,6 +1756,7 @@ impl CompilationDB {
 mod tests {
 
     use super::*;
+    use tempdir::TempDir;
 
     #[test]
     fn test_args_i_basic() {
@@ -498,4 +1863,831 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn test_args_include_paths() {
+        let cl = b"/usr/bin/gcc -IA -iquote Q -isystemS -idirafter Z -imacros M.h -include I.h -iquoteR -c foo.c";
+        let opt = Args::get_options(cl);
+
+        assert_eq!(opt.sys_paths, vec!["A"]);
+        assert_eq!(opt.quote_paths, vec!["Q", "R"]);
+        assert_eq!(opt.system_paths, vec!["S"]);
+        assert_eq!(opt.after_paths, vec!["Z"]);
+        assert_eq!(opt.imacros, vec!["M.h"]);
+        assert_eq!(opt.includes, vec!["I.h"]);
+
+        let paths = opt.get_include_paths();
+        assert_eq!(paths.quote, vec![PathBuf::from("Q"), PathBuf::from("R")]);
+        assert_eq!(paths.angled, vec![PathBuf::from("A")]);
+        assert_eq!(paths.system, vec![PathBuf::from("S")]);
+        assert_eq!(paths.after, vec![PathBuf::from("Z")]);
+    }
+
+    #[test]
+    fn test_args_nostdinc() {
+        let defaults = vec![
+            "/usr/include/c++/9",
+            "/usr/lib/gcc/x86_64-linux-gnu/9/include",
+            "/usr/include",
+        ];
+
+        let mut opt = Args::get_options(b"/usr/bin/g++ -isystem S -c foo.cpp");
+        opt.set_default_paths(&defaults);
+        assert_eq!(opt.default_paths, defaults);
+        assert_eq!(
+            opt.get_include_paths().system,
+            vec![
+                PathBuf::from("S"),
+                PathBuf::from("/usr/include/c++/9"),
+                PathBuf::from("/usr/lib/gcc/x86_64-linux-gnu/9/include"),
+                PathBuf::from("/usr/include"),
+            ]
+        );
+
+        let mut opt = Args::get_options(b"/usr/bin/g++ -nostdinc++ -c foo.cpp");
+        opt.set_default_paths(&defaults);
+        assert_eq!(opt.default_paths, &defaults[1..]);
+
+        let mut opt = Args::get_options(b"/usr/bin/gcc -c foo.c");
+        opt.set_default_paths(&defaults);
+        assert_eq!(opt.default_paths, &defaults[1..]);
+
+        let mut opt = Args::get_options(b"/usr/bin/g++ -nostdinc -isystem S -c foo.cpp");
+        opt.set_default_paths(&defaults);
+        assert!(opt.default_paths.is_empty());
+        assert_eq!(opt.get_include_paths().system, vec![PathBuf::from("S")]);
+    }
+
+    #[test]
+    fn test_args_sysroot() {
+        let defaults = vec!["/usr/include"];
+
+        let mut opt =
+            Args::get_options(b"/usr/bin/gcc --sysroot=/sdk -I=/usr/local/include -IA -c foo.c");
+        opt.set_default_paths(&defaults);
+        assert_eq!(opt.sysroot, Some(PathBuf::from("/sdk")));
+        let paths = opt.get_include_paths();
+        assert_eq!(
+            paths.angled,
+            vec![PathBuf::from("/sdk/usr/local/include"), PathBuf::from("A")]
+        );
+        assert_eq!(paths.system, vec![PathBuf::from("/sdk/usr/include")]);
+
+        let mut opt = Args::get_options(b"/usr/bin/clang --sysroot /sdk -isysroot /other -c foo.c");
+        opt.set_default_paths(&defaults);
+        assert_eq!(opt.sysroot, Some(PathBuf::from("/sdk")));
+        assert_eq!(opt.isysroot, Some(PathBuf::from("/other")));
+        assert_eq!(opt.default_paths, vec!["/other/usr/include"]);
+    }
+
+    #[test]
+    fn test_args_response_file() {
+        let tmp = TempDir::new("test").unwrap();
+        std::fs::write(
+            tmp.path().join("rsp.txt"),
+            "-DFOO=\"a b\" -IA\n\t-I 'B C'\n-DBAR=x\\ y @inner.txt @missing.txt\n",
+        )
+        .unwrap();
+        std::fs::write(tmp.path().join("inner.txt"), "-include inc.h\n").unwrap();
+
+        let mut args = Args::new(b"/usr/bin/gcc @rsp.txt -c foo.c");
+        args.opt.current_dir = tmp.path().to_path_buf();
+        args.parse();
+        let opt = args.opt;
+
+        assert_eq!(
+            opt.def,
+            vec![
+                Macro::Defined(("FOO".to_string(), "a b".to_string())),
+                Macro::Defined(("BAR".to_string(), "x y".to_string())),
+            ]
+        );
+        assert_eq!(opt.sys_paths, vec!["A", "B C"]);
+        assert_eq!(opt.includes, vec!["inc.h"]);
+        assert_eq!(opt.file, PathBuf::from("foo.c"));
+
+        // A response file including itself
+        std::fs::write(tmp.path().join("loop.txt"), "-DLOOP @loop.txt").unwrap();
+        let mut args = Args::new(b"/usr/bin/gcc @loop.txt -c foo.c");
+        args.opt.current_dir = tmp.path().to_path_buf();
+        args.parse();
+        assert_eq!(args.opt.def.len(), MAX_RESPONSE_FILE_DEPTH);
+    }
+
+    #[test]
+    fn test_split_windows() {
+        assert_eq!(
+            Args::split_windows(r#"cl "C:\Program Files\inc" a\\b "x\"y" "a\\" "" "q""q""#),
+            vec![
+                "cl",
+                r"C:\Program Files\inc",
+                r"a\\b",
+                "x\"y",
+                "a\\",
+                "",
+                "q\"q"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_args_msvc() {
+        let cl = br#""C:\Program Files\VC\bin\cl.exe" /nologo /DFOO /DBAR#2 /D BAZ=3 /UQUX /I"C:\My Dir\inc" -Isrc /FIpch.h /std:c++20 /c src\main.cpp"#;
+        let opt = Args::get_options(cl);
+
+        assert_eq!(opt.driver, Driver::CL);
+        assert_eq!(
+            opt.def,
+            vec![
+                Macro::Defined(("FOO".to_string(), "1".to_string())),
+                Macro::Defined(("BAR".to_string(), "2".to_string())),
+                Macro::Defined(("BAZ".to_string(), "3".to_string())),
+                Macro::Undef("QUX".to_string()),
+            ]
+        );
+        assert_eq!(opt.sys_paths, vec![r"C:\My Dir\inc", "src"]);
+        assert_eq!(opt.includes, vec!["pch.h"]);
+        assert_eq!(opt.lang, Language::CPP);
+        assert_eq!(opt.std, LangStandard::new(Standard::CPP20, false));
+        assert_eq!(opt.file, PathBuf::from(r"src\main.cpp"));
+        assert!(opt.get_std_defined().contains(&Macro::Defined((
+            "__cplusplus".to_string(),
+            "199711L".to_string()
+        ))));
+
+        let opt = Args::get_options(b"clang-cl /TC /c foo.h");
+        assert_eq!(opt.driver, Driver::ClangCL);
+        assert_eq!(opt.lang, Language::C);
+        assert_eq!(opt.std, LangStandard::default_for_msvc(Language::C));
+
+        let opt = Args::get_options(b"clang-cl.exe /c foo.c /TP");
+        assert_eq!(opt.lang, Language::CPP);
+        assert!(opt.get_std_defined().contains(&Macro::Defined((
+            "__cplusplus".to_string(),
+            "201402L".to_string()
+        ))));
+
+        let opt = Args::get_options(b"clang --driver-mode=cl /DFOO /Tcfoo.x");
+        assert_eq!(opt.driver, Driver::ClangCL);
+        assert_eq!(opt.lang, Language::C);
+        assert_eq!(opt.file, PathBuf::from("foo.x"));
+    }
+
+    #[test]
+    fn test_args_msvc_response_file() {
+        let tmp = TempDir::new("test").unwrap();
+        std::fs::write(
+            tmp.path().join("rsp.txt"),
+            "/DFOO /I\"inc dir\"\r\n/I C:\\inc\\\r\nmain.cpp",
+        )
+        .unwrap();
+
+        let mut args = Args::new(b"clang-cl /DBAR /FIpch.h @rsp.txt");
+        args.opt.current_dir = tmp.path().to_path_buf();
+        args.parse();
+        let opt = args.opt;
+
+        assert_eq!(
+            opt.def,
+            vec![
+                Macro::Defined(("BAR".to_string(), "1".to_string())),
+                Macro::Defined(("FOO".to_string(), "1".to_string())),
+            ]
+        );
+        assert_eq!(opt.sys_paths, vec!["inc dir", r"C:\inc\"]);
+        assert_eq!(opt.includes, vec!["pch.h"]);
+        assert_eq!(opt.file, PathBuf::from("main.cpp"));
+    }
+
+    #[test]
+    fn test_args_unterminated_quote() {
+        let opt = Args::get_options(b"/usr/bin/gcc -DFOO=\"abc -c foo.c");
+        assert_eq!(
+            opt.def,
+            vec![Macro::Defined((
+                "FOO".to_string(),
+                "abc -c foo.c".to_string()
+            ))]
+        );
+
+        let opt = Args::get_options(b"/usr/bin/gcc '-DFOO=abc -c foo.c");
+        assert_eq!(opt.def.len(), 1);
+
+        let opt = Args::get_options(b"/usr/bin/gcc \"-DFOO=\\$x\\");
+        assert_eq!(
+            opt.def,
+            vec![Macro::Defined(("FOO".to_string(), "\\$x".to_string()))]
+        );
+    }
+
+    #[test]
+    fn test_build_log_make() {
+        let log = concat!(
+            "make[1]: Entering directory '/src/lib'\n",
+            "echo \"CC foo.o\"\n",
+            "ccache gcc -DFOO -I../include -c foo.c -o foo.o\n",
+            "gcc -shared -o libfoo.so foo.o\n",
+            "make[2]: Entering directory `/src/lib/sub'\n",
+            "CCACHE_DIR=/tmp/cc g++ -std=c++14 \\\n",
+            "\t-c a.cpp b.cc -o out.o\n",
+            "make[2]: Leaving directory `/src/lib/sub'\n",
+            "make[1]: Leaving directory '/src/lib'\n",
+            "cd tools && /usr/bin/clang -c tool.c; cd .. ; gcc -c main.c\n",
+            "cd app\n",
+            "gcc -c app.c\n",
+        );
+        let db = CompilationDB::from_build_log(log, "/src");
+        let entries: Vec<_> = db
+            .vec
+            .iter()
+            .map(|jc| {
+                (
+                    jc.directory.to_str().unwrap(),
+                    jc.file.to_str().unwrap(),
+                    jc.output.as_ref().map(|o| o.to_str().unwrap()),
+                )
+            })
+            .collect();
+
+        assert_eq!(
+            entries,
+            vec![
+                ("/src/lib", "foo.c", Some("foo.o")),
+                ("/src/lib/sub", "a.cpp", Some("out.o")),
+                ("/src/lib/sub", "b.cc", Some("out.o")),
+                ("/src/tools", "tool.c", None),
+                ("/src/tools/..", "main.c", None),
+                ("/src/app", "app.c", None),
+            ]
+        );
+
+        assert_eq!(
+            db.vec[1].arguments,
+            vec!["g++", "-std=c++14", "-c", "-o", "out.o", "a.cpp"]
+        );
+
+        let cmds: Vec<_> = db.collect();
+        let foo = &cmds[cmds.len() - 1];
+        assert_eq!(foo.opt.sys_paths, vec!["../include"]);
+        assert_eq!(foo.opt.file, PathBuf::from("foo.c"));
+        assert_eq!(foo.opt.current_dir, PathBuf::from("/src/lib"));
+    }
+
+    #[test]
+    fn test_build_log_ninja_msvc() {
+        let log = concat!(
+            "[1/3] cd /build/x && /usr/bin/x86_64-linux-gnu-g++-12 -DBAR -c ../x.cpp -o x.o\n",
+            "[2/3] clang-cl.exe /nologo /DFOO /Foobj\\ /c C:\\src\\y.cpp\n",
+            "[3/3] cl /c /Tpz.inl /Foz.obj\n",
+            "ninja: build stopped: subcommand failed.\n",
+        );
+        let db = CompilationDB::from_build_log(log, "/build");
+        assert_eq!(db.len(), 3);
+
+        assert_eq!(db.vec[0].directory, PathBuf::from("/build/x"));
+        assert_eq!(db.vec[0].file, PathBuf::from("../x.cpp"));
+        assert_eq!(db.vec[1].file, PathBuf::from(r"C:\src\y.cpp"));
+        assert_eq!(db.vec[1].output, Some(PathBuf::from(r"obj\y.obj")));
+        assert_eq!(db.vec[2].file, PathBuf::from("z.inl"));
+        assert_eq!(db.vec[2].output, Some(PathBuf::from("z.obj")));
+    }
+
+    #[test]
+    fn test_build_log_to_json() {
+        let tmp = TempDir::new("test").unwrap();
+        let log = tmp.path().join("build.log");
+        std::fs::write(&log, "cd /src && gcc -DFOO=\"a b\" -c foo.c -o foo.o\n").unwrap();
+        let json = tmp.path().join("compile_commands.json");
+
+        let db = CompilationDB::from_build_log_file(&log, tmp.path()).unwrap();
+        db.write_json(&json).unwrap();
+
+        let mut db = CompilationDB::from_json(&json).unwrap();
+        assert_eq!(db.len(), 1);
+        let cmd = db.next().unwrap();
+        assert_eq!(cmd.file, PathBuf::from("foo.c"));
+        assert_eq!(cmd.output, Some(PathBuf::from("foo.o")));
+        assert_eq!(cmd.opt.current_dir, PathBuf::from("/src"));
+        assert_eq!(
+            cmd.opt.def,
+            vec![Macro::Defined(("FOO".to_string(), "a b".to_string()))]
+        );
+    }
+
+    #[test]
+    fn test_compilation_db_interpolate() {
+        let tmp = TempDir::new("test").unwrap();
+        let db = tmp.path().join("compile_commands.json");
+        std::fs::write(
+            &db,
+            r#"[
+  {
+    "directory": "/src/a",
+    "command": "/usr/bin/clang++ -DA -Iinc -c foo.cpp -o obj/foo.o",
+    "file": "foo.cpp"
+  },
+  {
+    "directory": "/src/b",
+    "arguments": ["/usr/bin/gcc", "-DB", "-x", "c", "-c", "/src/b/bar.c", "-o", "bar.o"],
+    "file": "/src/b/bar.c",
+    "output": "bar.o"
+  },
+  {
+    "directory": "/src/a/sub",
+    "arguments": ["/usr/bin/clang++", "-DBAZ", "-c", "baz.cpp", "-o", "baz.o"],
+    "file": "baz.cpp",
+    "output": "out/baz.o"
+  }
+]"#,
+        )
+        .unwrap();
+        let db = CompilationDB::from_json(&db).unwrap();
+
+        let cmd = db.interpolate("/src/a/foo.cpp").unwrap();
+        assert_eq!(cmd.file, PathBuf::from("foo.cpp"));
+
+        let cmd = db.interpolate("/src/a/foo.h").unwrap();
+        assert_eq!(cmd.file, PathBuf::from("/src/a/foo.h"));
+        assert_eq!(cmd.opt.file, PathBuf::from("/src/a/foo.h"));
+        assert_eq!(cmd.opt.lang, Language::CPP);
+        assert_eq!(cmd.opt.sys_paths, vec!["inc"]);
+        assert_eq!(cmd.opt.current_dir, PathBuf::from("/src/a"));
+        assert_eq!(
+            cmd.opt.def,
+            vec![Macro::Defined(("A".to_string(), "1".to_string()))]
+        );
+        assert_eq!(cmd.output, Some(PathBuf::from("/src/a/foo.h.gch")));
+
+        let cmd = db.interpolate("/src/b/bar.h").unwrap();
+        assert_eq!(cmd.opt.lang, Language::C);
+        assert_eq!(
+            cmd.opt.def,
+            vec![Macro::Defined(("B".to_string(), "1".to_string()))]
+        );
+
+        // A C++ header can't use a C command
+        let cmd = db.interpolate("/src/b/x.hpp").unwrap();
+        assert_eq!(cmd.opt.lang, Language::CPP);
+        assert_eq!(
+            cmd.opt.def,
+            vec![Macro::Defined(("A".to_string(), "1".to_string()))]
+        );
+
+        let cmd = db.interpolate("/src/a/sub/new.cpp").unwrap();
+        assert_eq!(
+            cmd.opt.def,
+            vec![Macro::Defined(("BAZ".to_string(), "1".to_string()))]
+        );
+        assert_eq!(cmd.output, Some(PathBuf::from("out/new.o")));
+
+        let db = CompilationDB::new(Vec::new());
+        assert!(db.interpolate("/src/a/foo.h").is_none());
+    }
+
+    #[test]
+    fn test_compilation_db_interpolate_paths() {
+        let cwd = std::env::current_dir().unwrap();
+        let db = CompilationDB::new(vec![
+            JsonCommand {
+                directory: PathBuf::from("/src/a"),
+                command: "gcc -DA -c foo.c".to_string(),
+                arguments: Vec::new(),
+                file: PathBuf::from("foo.c"),
+                output: None,
+                lang: Language::default(),
+                path: PathBuf::new(),
+            },
+            JsonCommand {
+                directory: PathBuf::from("/src/b"),
+                command: String::new(),
+                arguments: vec!["g++".to_string(), "-DB".to_string(), "foo.c".to_string()],
+                file: PathBuf::from("./foo.c"),
+                output: None,
+                lang: Language::default(),
+                path: PathBuf::new(),
+            },
+        ]);
+        assert_eq!(db.vec[0].lang, Language::C);
+        assert_eq!(db.vec[1].lang, Language::CPP);
+
+        let cmd = db.interpolate("/src/b/../b/foo.c").unwrap();
+        assert_eq!(
+            cmd.opt.def,
+            vec![Macro::Defined(("B".to_string(), "1".to_string()))]
+        );
+
+        // A relative file isn't looked for in the directories of the entries
+        let cmd = db.interpolate("foo.c").unwrap();
+        assert_eq!(cmd.file, cwd.join("foo.c"));
+        assert_eq!(cmd.opt.file, cwd.join("foo.c"));
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::ffi::OsStrExt;
+
+            let file = Path::new(OsStr::from_bytes(b"/src/a/f\xffo.h"));
+            let cmd = db.interpolate(file).unwrap();
+            assert_eq!(cmd.file, file);
+            assert_eq!(cmd.opt.file, file);
+            assert_eq!(
+                cmd.output,
+                Some(PathBuf::from(OsStr::from_bytes(b"/src/a/f\xffo.h.gch")))
+            );
+        }
+    }
+
+    #[test]
+    fn test_compilation_db_interpolate_donor_lang() {
+        let tmp = TempDir::new("test").unwrap();
+        let db = tmp.path().join("compile_commands.json");
+        std::fs::write(
+            &db,
+            r#"[
+  {
+    "directory": "/src",
+    "command": "/usr/bin/g++ -DFOO -c foo.c -o foo.o",
+    "file": "foo.c"
+  },
+  {
+    "directory": "/src/c",
+    "command": "/usr/bin/gcc -DBAR -c bar.c -o bar.o",
+    "file": "bar.c"
+  }
+]"#,
+        )
+        .unwrap();
+        let db = CompilationDB::from_json(&db).unwrap();
+
+        // g++ compiles foo.c as C++ so its header is a C++ one
+        let cmd = db.interpolate("/src/foo.h").unwrap();
+        assert_eq!(cmd.opt.lang, Language::CPP);
+        assert_eq!(
+            cmd.opt.def,
+            vec![Macro::Defined(("FOO".to_string(), "1".to_string()))]
+        );
+
+        // and it isn't a donor for a C file
+        let cmd = db.interpolate("/src/new.c").unwrap();
+        assert_eq!(cmd.opt.lang, Language::C);
+        assert_eq!(
+            cmd.opt.def,
+            vec![Macro::Defined(("BAR".to_string(), "1".to_string()))]
+        );
+    }
+
+    #[test]
+    fn test_args_std() {
+        let opt = Args::get_options(b"/usr/bin/clang-9 -std=gnu99 -c foo.c");
+        assert_eq!(opt.lang, Language::C);
+        assert_eq!(opt.std, LangStandard::new(Standard::C99, true));
+
+        let opt = Args::get_options(b"/usr/bin/clang++ -std=c++2a -c foo.cpp");
+        assert_eq!(opt.lang, Language::CPP);
+        assert_eq!(opt.std, LangStandard::new(Standard::CPP20, false));
+
+        let opt = Args::get_options(b"/usr/bin/gcc -std=c++17 -c foo.c");
+        assert_eq!(opt.lang, Language::C);
+        assert_eq!(opt.std, LangStandard::default_for(Language::C));
+
+        let opt = Args::get_options(b"/usr/bin/gcc -c foo.cc");
+        assert_eq!(opt.lang, Language::CPP);
+        assert_eq!(opt.std, LangStandard::new(Standard::CPP17, true));
+
+        let opt = Args::get_options(b"/usr/bin/clang++ -std=c89 -c foo.c");
+        assert_eq!(opt.lang, Language::CPP);
+        assert_eq!(opt.std, LangStandard::default_for(Language::CPP));
+    }
+
+    #[test]
+    fn test_args_trigraphs() {
+        let opt = Args::get_options(b"/usr/bin/gcc -std=gnu11 -c foo.c");
+        assert_eq!(opt.trigraphs, None);
+
+        let opt = Args::get_options(b"/usr/bin/gcc -trigraphs -c foo.c");
+        assert_eq!(opt.trigraphs, Some(true));
+
+        let opt = Args::get_options(b"/usr/bin/clang -ftrigraphs -fno-trigraphs -c foo.c");
+        assert_eq!(opt.trigraphs, Some(false));
+
+        let opt = Args::get_options(b"cl.exe /Zc:trigraphs /c foo.c");
+        assert_eq!(opt.trigraphs, Some(true));
+    }
+
+    #[test]
+    fn test_args_charset() {
+        let opt = Args::get_options(b"/usr/bin/gcc -c foo.c");
+        assert_eq!(opt.charset, Encoding::Utf8);
+
+        let opt = Args::get_options(b"/usr/bin/gcc -finput-charset=ISO-8859-1 -c foo.c");
+        assert_eq!(opt.charset, Encoding::Latin1);
+
+        let opt = Args::get_options(b"cl.exe /source-charset:windows-1252 /c foo.c");
+        assert_eq!(opt.charset, Encoding::Cp1252);
+    }
+
+    #[test]
+    fn test_args_x() {
+        let opt = Args::get_options(b"/usr/bin/clang -x c++ -std=c++11 -c foo.h");
+        assert_eq!(opt.lang, Language::CPP);
+        assert_eq!(opt.std, LangStandard::new(Standard::CPP11, false));
+
+        let opt = Args::get_options(b"/usr/bin/clang++ -xc -std=c2x -c foo.h");
+        assert_eq!(opt.lang, Language::C);
+        assert_eq!(opt.std, LangStandard::new(Standard::C23, false));
+    }
+
+    #[test]
+    fn test_std_macros() {
+        let defs = LangStandard::new(Standard::CPP20, false).get_defined();
+        assert!(defs.contains(&Macro::Defined((
+            "__cplusplus".to_string(),
+            "202002L".to_string()
+        ))));
+        assert!(defs.contains(&Macro::Defined((
+            "__STRICT_ANSI__".to_string(),
+            "1".to_string()
+        ))));
+
+        let defs = LangStandard::new(Standard::C99, true).get_defined();
+        assert!(defs.contains(&Macro::Defined((
+            "__STDC_VERSION__".to_string(),
+            "199901L".to_string()
+        ))));
+        assert!(!defs.iter().any(|d| match d {
+            Macro::Defined((name, _)) => name == "__cplusplus" || name == "__STRICT_ANSI__",
+            _ => false,
+        }));
+
+        let defs = LangStandard::new(Standard::C89, false).get_defined();
+        assert!(!defs.iter().any(|d| match d {
+            Macro::Defined((name, _)) => name == "__STDC_VERSION__",
+            _ => false,
+        }));
+    }
+
+    #[test]
+    fn test_compilation_db_command_and_arguments() {
+        let tmp = TempDir::new("test").unwrap();
+        let db = tmp.path().join("compile_commands.json");
+        std::fs::write(
+            &db,
+            r#"[
+  {
+    "directory": "/build",
+    "command": "/usr/bin/clang++ -DFOO -IA -o a.o -c a.cpp",
+    "file": "a.cpp"
+  },
+  {
+    "directory": "/build",
+    "arguments": ["/usr/bin/gcc", "-DBAR=1", "-I", "B C", "-o", "b.o", "-c", "b.c"],
+    "file": "b.c",
+    "output": "b.o"
+  }
+]"#,
+        )
+        .unwrap();
+
+        let cmds: Vec<_> = CompilationDB::from_json(&db).unwrap().collect();
+        assert_eq!(cmds.len(), 2);
+
+        let b = &cmds[0];
+        assert_eq!(b.file, PathBuf::from("b.c"));
+        assert_eq!(b.output, Some(PathBuf::from("b.o")));
+        assert_eq!(b.opt.lang, Language::C);
+        assert_eq!(b.opt.current_dir, PathBuf::from("/build"));
+        assert_eq!(b.opt.sys_paths, vec!["B C"]);
+        assert_eq!(
+            b.opt.def,
+            vec![Macro::Defined(("BAR".to_string(), "1".to_string()))]
+        );
+
+        let a = &cmds[1];
+        assert_eq!(a.file, PathBuf::from("a.cpp"));
+        // The output is taken from the command when it isn't in the entry
+        assert_eq!(a.output, Some(PathBuf::from("a.o")));
+        assert_eq!(a.opt.lang, Language::CPP);
+        assert_eq!(a.opt.sys_paths, vec!["A"]);
+    }
+
+    #[test]
+    fn test_compilation_db_same_file() {
+        let tmp = TempDir::new("test").unwrap();
+        let db = tmp.path().join("compile_commands.json");
+        std::fs::write(
+            &db,
+            r#"[
+  {
+    "directory": "/build",
+    "arguments": ["clang", "-DX86", "-o", "x86/foo.o", "-c", "foo.c"],
+    "file": "foo.c",
+    "output": "x86/foo.o"
+  },
+  {
+    "directory": "/build",
+    "arguments": ["clang", "-DARM", "-o", "arm/foo.o", "-c", "foo.c"],
+    "file": "foo.c",
+    "output": "arm/foo.o"
+  },
+  {
+    "directory": "/build",
+    "arguments": ["clang", "-c", "bar.c"],
+    "file": "bar.c"
+  }
+]"#,
+        )
+        .unwrap();
+
+        let db = CompilationDB::from_json(&db).unwrap();
+        assert_eq!(db.len(), 3);
+
+        let cmds = db.get_commands("/build/foo.c");
+        assert_eq!(cmds.len(), 2);
+        assert_eq!(
+            cmds[0].opt.def,
+            vec![Macro::Defined(("X86".to_string(), "1".to_string()))]
+        );
+        assert_eq!(cmds[0].output, Some(PathBuf::from("x86/foo.o")));
+        assert_eq!(
+            cmds[1].opt.def,
+            vec![Macro::Defined(("ARM".to_string(), "1".to_string()))]
+        );
+        assert_eq!(cmds[1].output, Some(PathBuf::from("arm/foo.o")));
+
+        assert_eq!(db.get_commands("bar.c").len(), 1);
+        assert!(db.get_commands("oof.c").is_empty());
+    }
+
+    #[test]
+    fn test_compilation_db_errors() {
+        let tmp = TempDir::new("test").unwrap();
+        let db = tmp.path().join("compile_commands.json");
+
+        match CompilationDB::from_json(&db) {
+            Err(FileError::Io { path, .. }) => assert_eq!(path, db),
+            _ => panic!("Expected an io error"),
+        }
+
+        std::fs::write(&db, r#"[{"directory": "/build", "#).unwrap();
+        match CompilationDB::from_json(&db) {
+            Err(FileError::Json { path, .. }) => assert_eq!(path, db),
+            _ => panic!("Expected a json error"),
+        }
+
+        std::fs::write(
+            &db,
+            r#"[
+  {
+    "directory": "/build",
+    "command": "clang -c a.c"
+  },
+  {
+    "directory": "/build",
+    "arguments": ["clang", "-c", "b.c"],
+    "file": "b.c"
+  }
+]"#,
+        )
+        .unwrap();
+
+        let db = CompilationDB::from_json(&db).unwrap();
+        assert_eq!(db.len(), 1);
+        assert_eq!(db.get_errors().len(), 1);
+        match &db.get_errors()[0] {
+            FileError::InvalidEntry { index, .. } => assert_eq!(*index, 0),
+            _ => panic!("Expected an invalid entry"),
+        }
+        assert_eq!(db.get_commands("b.c").len(), 1);
+    }
+
+    #[test]
+    fn test_compilation_db_output() {
+        let tmp = TempDir::new("test").unwrap();
+        let db = tmp.path().join("compile_commands.json");
+        std::fs::write(
+            &db,
+            r#"[
+  {
+    "directory": "/build",
+    "command": "clang -DX86 -c foo.c -o x86/foo.o",
+    "file": "foo.c"
+  },
+  {
+    "directory": "/build",
+    "command": "clang -DARM -c foo.c -oarm/foo.o",
+    "file": "foo.c"
+  },
+  {
+    "directory": "/build",
+    "command": "cl.exe /c /Fowin/foo.obj foo.c",
+    "file": "foo.c"
+  },
+  {
+    "directory": "/build",
+    "command": "clang -c foo.c -o x86/bar.o",
+    "file": "foo.c",
+    "output": "x86/foo.o"
+  },
+  {
+    "directory": "/build",
+    "file": "foo.c"
+  },
+  {
+    "directory": "/build",
+    "command": " ",
+    "file": "foo.c"
+  }
+]"#,
+        )
+        .unwrap();
+
+        let db = CompilationDB::from_json(&db).unwrap();
+        let outputs: Vec<_> = db
+            .get_commands("foo.c")
+            .into_iter()
+            .map(|c| c.output.unwrap())
+            .collect();
+        assert_eq!(
+            outputs,
+            vec![
+                PathBuf::from("x86/foo.o"),
+                PathBuf::from("arm/foo.o"),
+                PathBuf::from("win/foo.obj"),
+                PathBuf::from("x86/foo.o"),
+            ]
+        );
+
+        let errors: Vec<_> = db
+            .get_errors()
+            .iter()
+            .map(|e| match e {
+                FileError::InvalidEntry { index, err, .. } => (*index, err.to_string()),
+                _ => panic!("Expected an invalid entry"),
+            })
+            .collect();
+        assert_eq!(
+            errors,
+            vec![
+                (4, "no command or arguments".to_string()),
+                (5, "no command or arguments".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_compilation_db_missing_argument() {
+        let tmp = TempDir::new("test").unwrap();
+        let db = tmp.path().join("compile_commands.json");
+        std::fs::write(
+            &db,
+            r#"[
+  {
+    "directory": "/build",
+    "command": "clang -c a.c -I",
+    "file": "a.c"
+  },
+  {
+    "directory": "/build",
+    "arguments": ["clang", "-c", "b.c", "-D"],
+    "file": "b.c"
+  },
+  {
+    "directory": "/build",
+    "arguments": ["clang", "-c", "c.c", "-DFOO"],
+    "file": "c.c"
+  }
+]"#,
+        )
+        .unwrap();
+
+        let db = CompilationDB::from_json(&db).unwrap();
+        let errors: Vec<_> = db
+            .get_errors()
+            .iter()
+            .map(|e| match e {
+                FileError::InvalidEntry { index, err, .. } => (*index, err.to_string()),
+                _ => panic!("Expected an invalid entry"),
+            })
+            .collect();
+        assert_eq!(
+            errors,
+            vec![
+                (0, "missing argument to -I".to_string()),
+                (1, "missing argument to -D".to_string()),
+            ]
+        );
+        assert_eq!(db.len(), 1);
+        assert_eq!(db.get_commands("c.c").len(), 1);
+
+        // The options at the end are ignored when the command is used anyway
+        for cl in &[
+            "clang -c a.c -D",
+            "clang -c a.c -I",
+            "clang -c a.c -U",
+            "clang a.c -x",
+        ] {
+            let opt = Args::get_options(cl.as_bytes());
+            assert!(opt.def.is_empty());
+            assert!(opt.sys_paths.is_empty());
+        }
+    }
 }
@@ -3,6 +3,10 @@
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
 use crate::lexer::errors::LexerError;
 use crate::lexer::lexer::Location;
 use crate::lexer::source::FileId;
@@ -12,6 +16,17 @@ pub struct Span {
     pub file: Option<FileId>,
     pub start: Location,
     pub end: Location,
+    /// Where the span is according to the #line directives and the linemarkers
+    pub presumed: PresumedSpan,
+}
+
+/// The file and the lines of a span as presumed by the #line directives
+/// (the physical ones when there aren't any)
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PresumedSpan {
+    pub file: Option<FileId>,
+    pub start_line: u32,
+    pub end_line: u32,
 }
 
 #[derive(Debug, Clone)]
@@ -32,3 +47,55 @@ pub struct StringlyError {
     pub message: String,
     pub sp: Span,
 }
+
+/// An error when loading a file from the disk (source file, compilation database, ...)
+#[derive(Debug)]
+pub enum FileError {
+    Io {
+        path: PathBuf,
+        err: io::Error,
+    },
+    Json {
+        path: PathBuf,
+        err: serde_json::Error,
+    },
+    /// An entry in a compilation database which can't be decoded as a command
+    InvalidEntry {
+        path: PathBuf,
+        index: usize,
+        err: serde_json::Error,
+    },
+}
+
+impl FileError {
+    pub fn get_path(&self) -> &Path {
+        match self {
+            FileError::Io { path, .. }
+            | FileError::Json { path, .. }
+            | FileError::InvalidEntry { path, .. } => path,
+        }
+    }
+}
+
+impl fmt::Display for FileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FileError::Io { path, err } => write!(f, "can't read {}: {}", path.display(), err),
+            FileError::Json { path, err } => {
+                write!(f, "invalid json in {}: {}", path.display(), err)
+            }
+            FileError::InvalidEntry { path, index, err } => {
+                write!(f, "invalid entry #{} in {}: {}", index, path.display(), err)
+            }
+        }
+    }
+}
+
+impl std::error::Error for FileError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            FileError::Io { err, .. } => Some(err),
+            FileError::Json { err, .. } | FileError::InvalidEntry { err, .. } => Some(err),
+        }
+    }
+}
@@ -3,8 +3,15 @@
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
+use super::dialect::{replace_trigraphs, revert_trigraphs};
+use super::encoding::{decode, Encoding, OffsetMap};
 use super::preprocessor::include::PathIndex;
-use super::source::FileId;
+use super::scan;
+use super::source::{FileContent, FileId};
+use crate::errors::PresumedSpan;
+use std::borrow::Cow;
+use std::ops::Deref;
+use std::sync::Arc;
 
 #[derive(Debug)]
 pub(crate) struct OutBuf {
@@ -47,65 +54,156 @@ impl Default for Position {
     }
 }
 
+/// The bytes read by a buffer: cloning it is cheap
+#[derive(Clone, Debug)]
+pub(crate) enum Source<'a> {
+    /// The slice given to the lexer
+    Borrowed(&'a [u8]),
+    /// A file shared with the other buffers, the command line, a transcoded file, ...
+    Shared(FileContent),
+    /// The macro expansion being read
+    Expansion(Arc<[u8]>),
+}
+
+impl<'a> Default for Source<'a> {
+    fn default() -> Self {
+        Source::Borrowed(&[])
+    }
+}
+
+impl<'a> Deref for Source<'a> {
+    type Target = [u8];
+
+    #[inline(always)]
+    fn deref(&self) -> &[u8] {
+        match self {
+            Source::Borrowed(buf) => buf,
+            Source::Shared(content) => content,
+            Source::Expansion(buf) => buf,
+        }
+    }
+}
+
+/// The location given by a #line directive or a linemarker
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct LineDirective {
+    /// The physical line following the directive
+    phys_line: u32,
+    /// Its presumed number
+    line: u32,
+    /// The presumed file (if one has been given)
+    file: Option<FileId>,
+}
+
 #[derive(Debug)]
-pub struct BufferData {
-    buf: Vec<u8>,
+pub struct BufferData<'a> {
+    buf: Source<'a>,
     position: Position,
     source_id: FileId,
     path_index: PathIndex,
-    fake_source_id: Option<FileId>,
+    line_directive: Option<LineDirective>,
+    offsets: Option<OffsetMap>,
+    /// The macro of a leading #ifndef (or #if !defined) and the position of its #
+    /// (it may be an include guard)
+    guard: Option<(String, usize)>,
 }
 
-impl BufferData {
+impl<'a> BufferData<'a> {
     pub fn new(buf: Vec<u8>, source_id: FileId, path_index: PathIndex) -> Self {
+        Self::from_source(Source::Shared(buf.into()), source_id, path_index)
+    }
+
+    pub(crate) fn from_source(buf: Source<'a>, source_id: FileId, path_index: PathIndex) -> Self {
         Self {
             buf,
             position: Position::default(),
             source_id,
             path_index,
-            fake_source_id: None,
+            line_directive: None,
+            offsets: None,
+            guard: None,
+        }
+    }
+
+    pub fn get_source_id(&self) -> FileId {
+        self.source_id
+    }
+
+    /// Create a buffer from the content of a file: it's used as is
+    /// unless it must be converted to UTF-8
+    pub fn decode(
+        content: FileContent,
+        encoding: Encoding,
+        source_id: FileId,
+        path_index: PathIndex,
+    ) -> Self {
+        match decode(&content, encoding) {
+            Some((buf, offsets)) => {
+                let mut data = Self::new(buf, source_id, path_index);
+                data.offsets = Some(offsets);
+                data
+            }
+            None => Self::from_source(Source::Shared(content), source_id, path_index),
         }
     }
 }
 
+/// Replace the trigraphs in an unread buffer: they're recorded in its offsets
+/// to get the columns in the original file
+fn replace_trigraphs_in(buf: &mut Source, offsets: &mut Option<OffsetMap>) {
+    if let Some((data, positions)) = replace_trigraphs(buf) {
+        *buf = Source::Shared(data.into());
+        offsets
+            .get_or_insert_with(OffsetMap::default)
+            .set_trigraphs(positions);
+    }
+}
+
 #[derive(Clone, Debug, Default, Hash, PartialEq)]
 pub struct FileInfo {
     pub line: u32,
     pub source_id: Option<FileId>,
+    /// The line and the file as set by the #line directives
+    pub presumed_line: u32,
+    pub presumed_file: Option<FileId>,
 }
 
+/// A stack of buffers: the included files are pushed on it and the macro expansions
+/// are read in place of the current buffer.
+/// The data being read is moved out of its buffer into current (and back) so no one
+/// borrows from the stack.
 #[derive(Debug)]
 pub(crate) struct Buffer<'a> {
-    stack: Vec<BufferData>,
+    stack: Vec<BufferData<'a>>,
     preproc: OutBuf,
-    current: &'a [u8],
+    current: Source<'a>,
     len: usize,
     position: Position,
-    saved_position: Position,
-    saved_buf: &'a [u8],
+    /// The data and the position to restore once the macro expansion has been read
+    saved: Option<(Source<'a>, Position)>,
+    trigraphs: bool,
 }
 
 impl<'a> Buffer<'a> {
-    pub(crate) fn new(buf: Vec<u8>, source_id: FileId, path_index: PathIndex) -> Self {
-        let mut ret = Self {
-            stack: Vec::new(),
-            preproc: OutBuf::default(),
-            current: &[],
-            len: buf.len(),
-            position: Position::default(),
-            saved_position: Position::default(),
-            saved_buf: &[],
-        };
-        ret.stack.push(BufferData {
-            buf,
-            position: Position::default(),
+    pub(crate) fn new(buf: &'a [u8], source_id: FileId, path_index: PathIndex) -> Self {
+        Self::from_data(BufferData::from_source(
+            Source::Borrowed(buf),
             source_id,
-            fake_source_id: None,
             path_index,
-        });
-        ret.current =
-            unsafe { &*std::mem::transmute::<&[u8], *const [u8]>(&ret.stack.last().unwrap().buf) };
-        ret
+        ))
+    }
+
+    pub(crate) fn from_data(mut data: BufferData<'a>) -> Self {
+        let current = std::mem::take(&mut data.buf);
+        Self {
+            stack: vec![data],
+            preproc: OutBuf::default(),
+            len: current.len(),
+            current,
+            position: Position::default(),
+            saved: None,
+            trigraphs: false,
+        }
     }
 
     pub(crate) fn switch_to_preproc(&mut self) {
@@ -113,43 +211,70 @@ impl<'a> Buffer<'a> {
             return;
         }
 
-        self.saved_position = self.position.clone();
-        self.saved_buf = self.current;
-        self.current = unsafe { &*std::mem::transmute::<&[u8], *const [u8]>(&self.preproc.buf) };
-        self.position = Position::default();
-        self.len = self.preproc.buf.len();
+        // The vector is kept to write the next expansion
+        let expansion = Source::Expansion(Arc::from(self.preproc.buf.as_slice()));
+        self.preproc.buf.clear();
+        let current = std::mem::replace(&mut self.current, expansion);
+        let position = std::mem::take(&mut self.position);
+        self.saved = Some((current, position));
+        self.len = self.current.len();
     }
 
     #[inline(always)]
     pub(crate) fn preproc_use(&self) -> bool {
-        !self.preproc.buf.is_empty()
+        self.saved.is_some()
     }
 
     #[inline(always)]
     pub(crate) fn get_line_file(&self) -> FileInfo {
+        let line = self.get_line();
         FileInfo {
-            line: self.get_line(),
+            line,
             source_id: self.get_source_id(),
+            presumed_line: self.get_presumed_line(line),
+            presumed_file: self.get_presumed_file(),
+        }
+    }
+
+    /// Replace the trigraphs in the buffers which haven't been read yet and in the next ones
+    pub(crate) fn set_trigraphs(&mut self, trigraphs: bool) {
+        self.trigraphs = trigraphs;
+        if !trigraphs || self.preproc_use() {
+            return;
+        }
+
+        let top = self.stack.len().saturating_sub(1);
+        for (i, data) in self.stack.iter_mut().enumerate() {
+            if i == top {
+                if self.position.pos == 0 {
+                    replace_trigraphs_in(&mut self.current, &mut data.offsets);
+                    self.len = self.current.len();
+                }
+            } else if data.position.pos == 0 {
+                replace_trigraphs_in(&mut data.buf, &mut data.offsets);
+            }
         }
     }
 
-    pub(crate) fn add_buffer(&mut self, buf: BufferData) {
+    pub(crate) fn add_buffer(&mut self, mut buf: BufferData<'a>) {
+        if self.trigraphs {
+            replace_trigraphs_in(&mut buf.buf, &mut buf.offsets);
+        }
+
         let last = self.stack.last_mut().unwrap();
         last.position = self.position.clone();
+        last.buf = std::mem::replace(&mut self.current, std::mem::take(&mut buf.buf));
 
         self.stack.push(buf);
-        let last = self.stack.last().unwrap();
-        self.current = unsafe { &*std::mem::transmute::<&[u8], *const [u8]>(&last.buf) };
         self.position = Position::default();
         self.len = self.current.len()
     }
 
     pub(crate) fn rm_buffer(&mut self) -> bool {
-        if self.preproc_use() {
-            self.current = self.saved_buf;
+        if let Some((current, position)) = self.saved.take() {
+            self.current = current;
             self.len = self.current.len();
-            self.position = self.saved_position.clone();
-            self.preproc.buf.clear();
+            self.position = position;
             return true;
         }
 
@@ -158,9 +283,9 @@ impl<'a> Buffer<'a> {
             return false;
         }
 
-        while let Some(data) = self.stack.last() {
+        while let Some(data) = self.stack.last_mut() {
             if data.position.pos < data.buf.len() {
-                self.current = unsafe { &*std::mem::transmute::<&[u8], *const [u8]>(&data.buf) };
+                self.current = std::mem::take(&mut data.buf);
                 self.len = self.current.len();
                 self.position = data.position.clone();
                 return true;
@@ -168,38 +293,107 @@ impl<'a> Buffer<'a> {
             self.stack.pop();
         }
 
-        !self.stack.is_empty()
+        false
     }
 
+    /// Called when the current char is a \n
     pub(crate) fn add_new_line(&mut self) {
         self.position.line += 1;
         self.position.lpos = self.position.pos + 1;
     }
 
+    /// Called when the \n has just been consumed
+    pub(crate) fn add_new_line_after(&mut self) {
+        self.position.line += 1;
+        self.position.lpos = self.position.pos;
+    }
+
     pub(crate) fn get_line(&self) -> u32 {
         self.position.line
     }
 
-    pub(crate) fn set_line(&mut self, line: u32) {
-        self.position.line = line;
+    pub(crate) fn get_source_id(&self) -> Option<FileId> {
+        self.stack.last().map(|last| last.source_id)
+    }
+
+    /// Set the presumed number of the next line and the presumed file of the current one
+    /// (#line directive or linemarker): without a file the previous one is kept
+    pub(crate) fn set_presumed(&mut self, line: u32, file: Option<FileId>) {
+        let phys_line = self.position.line;
+        if let Some(last) = self.stack.last_mut() {
+            let file = file.or_else(|| last.line_directive.and_then(|d| d.file));
+            last.line_directive = Some(LineDirective {
+                phys_line,
+                line,
+                file,
+            });
+        }
     }
 
-    pub(crate) fn get_source_id(&self) -> Option<FileId> {
-        self.stack
-            .last()
-            .map(|last| last.fake_source_id.unwrap_or(last.source_id))
+    /// Get the presumed number of a physical line in the current file
+    pub(crate) fn get_presumed_line(&self, line: u32) -> u32 {
+        match self.stack.last().and_then(|last| last.line_directive) {
+            Some(d) if line >= d.phys_line => d.line.wrapping_add(line - d.phys_line),
+            _ => line,
+        }
+    }
+
+    /// Get the presumed file: the current one unless a #line directive changed it
+    pub(crate) fn get_presumed_file(&self) -> Option<FileId> {
+        self.stack.last().map(|last| {
+            last.line_directive
+                .and_then(|d| d.file)
+                .unwrap_or(last.source_id)
+        })
+    }
+
+    /// Get the presumed location of the physical lines start..end of the current file:
+    /// the tokens coming from a macro expansion are at the expansion point
+    pub(crate) fn get_presumed_span(&self, start: u32, end: u32) -> PresumedSpan {
+        let (start, end) = match &self.saved {
+            Some((_, position)) => (position.line, position.line),
+            None => (start, end),
+        };
+        PresumedSpan {
+            file: self.get_presumed_file(),
+            start_line: self.get_presumed_line(start),
+            end_line: self.get_presumed_line(end),
+        }
+    }
+
+    /// Save the #ifndef NAME at pos which may guard the current file
+    pub(crate) fn set_guard_candidate(&mut self, name: String, pos: usize) {
+        if let Some(last) = self.stack.last_mut() {
+            last.guard = Some((name, pos));
+        }
+    }
+
+    /// Get the guard candidate when its #ifndef is the one at pos
+    pub(crate) fn take_guard_candidate(&mut self, pos: usize) -> Option<String> {
+        let last = self.stack.last_mut()?;
+        match last.guard {
+            Some((_, p)) if p == pos => last.guard.take().map(|(name, _)| name),
+            _ => None,
+        }
     }
 
     pub(crate) fn get_path_index(&self) -> Option<PathIndex> {
         self.stack.last().map(|last| last.path_index)
     }
 
-    pub(crate) fn set_source_id(&mut self, id: FileId) {
-        let last = self.stack.last_mut().unwrap();
-        last.fake_source_id = Some(id);
+    /// Set the map to the original positions for a transcoded file
+    pub(crate) fn set_offsets(&mut self, offsets: Option<OffsetMap>) {
+        if let Some(last) = self.stack.last_mut() {
+            last.offsets = offsets;
+        }
     }
 
     pub(crate) fn get_column(&self) -> u32 {
+        if !self.preproc_use() {
+            if let Some(offsets) = self.stack.last().and_then(|last| last.offsets.as_ref()) {
+                return offsets.column(self.position.pos, self.position.lpos);
+            }
+        }
         ((self.position.pos + 1) - self.position.lpos) as u32
     }
 
@@ -237,23 +431,50 @@ impl<'a> Buffer<'a> {
         &mut self.preproc
     }
 
+    /// A handle on the data being read (no copy): the slices borrowed from it
+    /// aren't tied to the buffer which can keep moving
+    #[inline(always)]
+    pub(crate) fn source(&self) -> Source<'a> {
+        self.current.clone()
+    }
+
     #[inline(always)]
-    pub(crate) fn slice(&self, start: usize) -> &'a [u8] {
+    pub(crate) fn slice(&self, start: usize) -> &[u8] {
         unsafe { self.current.get_unchecked(start..self.position.pos) }
     }
 
     #[inline(always)]
-    pub(crate) fn slice_p(&self, start: usize, end: usize) -> &'a [u8] {
+    pub(crate) fn slice_p(&self, start: usize, end: usize) -> &[u8] {
         unsafe { self.current.get_unchecked(start..end) }
     }
 
+    /// Get the bytes start..end as written in the file: the replaced trigraphs are put back
+    pub(crate) fn original_slice(&self, start: usize, end: usize) -> Cow<'_, [u8]> {
+        let slice = self.slice_p(start, end);
+        if self.preproc_use() {
+            return Cow::Borrowed(slice);
+        }
+
+        let positions = self
+            .stack
+            .last()
+            .and_then(|last| last.offsets.as_ref())
+            .map_or(&[][..], |offsets| offsets.trigraphs(start, end));
+        if positions.is_empty() {
+            Cow::Borrowed(slice)
+        } else {
+            let positions: Vec<_> = positions.iter().map(|p| p - start).collect();
+            Cow::Owned(revert_trigraphs(slice, &positions))
+        }
+    }
+
     #[inline(always)]
-    pub(crate) fn slice_m_n(&self, start: usize, n: usize) -> &'a [u8] {
+    pub(crate) fn slice_m_n(&self, start: usize, n: usize) -> &[u8] {
         unsafe { self.current.get_unchecked(start..self.position.pos - n) }
     }
 
     #[inline(always)]
-    pub(crate) fn slice_n(&self, start: usize, n: usize) -> &'a [u8] {
+    pub(crate) fn slice_n(&self, start: usize, n: usize) -> &[u8] {
         unsafe { self.current.get_unchecked(start..self.position.pos + n) }
     }
 
@@ -277,11 +498,18 @@ impl<'a> Buffer<'a> {
         unsafe { *self.current.get_unchecked(self.position.pos - n) }
     }
 
-    pub(crate) fn as_str(&self) -> &'a str {
-        unsafe { std::str::from_utf8_unchecked(self.current) }
+    pub(crate) fn as_str(&self) -> &str {
+        unsafe { std::str::from_utf8_unchecked(&self.current) }
+    }
+
+    pub(crate) fn remainder(&self) -> &[u8] {
+        unsafe {
+            self.current
+                .get_unchecked(self.position.pos.min(self.len)..)
+        }
     }
 
-    pub(crate) fn remainder_as_str(&self) -> &'a str {
+    pub(crate) fn remainder_as_str(&self) -> &str {
         unsafe { std::str::from_utf8_unchecked(&self.current[self.position.pos..]) }
     }
 
@@ -290,6 +518,24 @@ impl<'a> Buffer<'a> {
         self.position.pos += 1;
     }
 
+    /// Move n bytes forward counting the new lines on the way
+    #[inline(always)]
+    pub(crate) fn inc_n_lines(&mut self, n: usize) {
+        let start = self.position.pos;
+        let (count, last) = scan::count_lines(&self.current[start..start + n]);
+        if let Some(last) = last {
+            self.position.line += count as u32;
+            self.position.lpos = start + last + 1;
+        }
+        self.position.pos += n;
+    }
+
+    /// Skip the spaces and the tabs
+    #[inline(always)]
+    pub(crate) fn skip_blanks(&mut self) {
+        self.position.pos += scan::skip_blanks(self.remainder());
+    }
+
     #[inline(always)]
     pub(crate) fn dec(&mut self) {
         self.position.pos -= 1;
@@ -307,11 +553,13 @@ impl<'a> Buffer<'a> {
 
     #[inline(always)]
     pub(crate) fn check_char(&mut self) -> bool {
-        if self.position.pos < self.len {
-            true
-        } else {
-            self.rm_buffer()
+        // The data under a macro expansion can have been entirely read too
+        while self.position.pos >= self.len {
+            if !self.rm_buffer() {
+                return false;
+            }
         }
+        true
     }
 
     #[inline(always)]
@@ -332,7 +580,7 @@ mod tests {
 
     #[test]
     fn test_buffer() {
-        let mut buf = Buffer::new(b"abc".to_vec(), FileId(0), PathIndex(0));
+        let mut buf = Buffer::new(b"abc", FileId(0), PathIndex(0));
         assert_eq!(buf.next_char(), b'a');
         buf.inc();
 
@@ -340,6 +588,11 @@ mod tests {
         buf.switch_to_preproc();
 
         assert_eq!(buf.next_char(), b'd');
+        // Getting the source of an expansion doesn't copy it
+        match (buf.source(), &buf.current) {
+            (Source::Expansion(a), Source::Expansion(b)) => assert!(Arc::ptr_eq(&a, b)),
+            _ => panic!("Not reading an expansion"),
+        }
         buf.rm_buffer();
         assert_eq!(buf.next_char(), b'b');
         buf.inc();
@@ -3,7 +3,9 @@
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
-use super::lexer::{Lexer, Token};
+use super::errors::LexerError;
+use super::identifier::Ucn;
+use super::lexer::{Lexer, TLexer, Token};
 use super::preprocessor::context::PreprocContext;
 use super::string::StringType;
 
@@ -19,11 +21,13 @@ pub(crate) enum Kind {
     RRR,
     TTT,
     VVV,
+    Eee, // e, E: GNU escape
     OCT, // octal
     HEX, // x...
     UNS, // u...
     UNL, // U...
     NLI, // \n
+    Cre, // \r
 }
 
 #[rustfmt::skip]
@@ -31,7 +35,7 @@ const ECHARS: [Kind; 256] = [
     // 0 NUL   1 SOH      2 STX      3 ETX      4 EOT      5 ENQ      6 ACK      7 BEL
     Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, //
     // 8 BS    9 HT       A NL       B VT       C NP       D CR       E SO       F SI
-    Kind::NON, Kind::NON, Kind::NLI, Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, //
+    Kind::NON, Kind::NON, Kind::NLI, Kind::NON, Kind::NON, Kind::Cre, Kind::NON, Kind::NON, //
     // 10 DLE  11 DC1     12 DC2     13 DC3     14 DC4     15 NAK     16 SYN     17 ETB
     Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, //
     // 18 CAN  19 EM      1A SUB     1B ESC     1C FS      1D GS      1E RS      1F US
@@ -45,7 +49,7 @@ const ECHARS: [Kind; 256] = [
     // 38  8   39  9      3A  :      3B  ;      3C  <      3D  =      3E  >      3F  ?
     Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::SEL, //
     // 40  @   41  A      42  B      43  C      44  D      45  E      46  F      47  G
-    Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, //
+    Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::Eee, Kind::NON, Kind::NON, //
     // 48  H   49  I      4A  J      4B  K      4C  L      4D  M      4E  N      4F  O
     Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, //
     // 50  P   51  Q      52  R      53  S      54  T      55  U      56  V      57  W
@@ -53,7 +57,7 @@ const ECHARS: [Kind; 256] = [
     // 58  X   59  Y      5A  Z      5B  [      5C  \      5D  ]      5E  ^      5F  _
     Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::SEL, Kind::NON, Kind::NON, Kind::NON, //
     // 60  `   61  a      62  b      63  c      64  d      65  e      66  f      67  g
-    Kind::NON, Kind::AAA, Kind::BBB, Kind::NON, Kind::NON, Kind::NON, Kind::FFF, Kind::NON, //
+    Kind::NON, Kind::AAA, Kind::BBB, Kind::NON, Kind::NON, Kind::Eee, Kind::FFF, Kind::NON, //
     // 68  h   69  i      6A  j      6B  k      6C  l      6D  m      6E  n      6F  o
     Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NNN, Kind::NON, //
     // 70  p   71  q      72  r      73  s      74  t      75  u      76  v      77  w
@@ -100,8 +104,9 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
     }
 
     #[inline(always)]
-    pub(crate) fn get_hex_char(&mut self) -> u32 {
+    pub(crate) fn get_hex_char(&mut self) -> Option<u32> {
         let mut num = 0;
+        let mut n_digits = 0;
         for _ in 0..3 {
             if self.buf.has_char() {
                 let c = self.buf.next_char();
@@ -109,6 +114,7 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
                 if n != 16 {
                     self.buf.inc();
                     num = num << 4 | n;
+                    n_digits += 1;
                 } else {
                     break;
                 }
@@ -116,58 +122,45 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
                 break;
             }
         }
-        num as u32
-    }
-
-    #[inline(always)]
-    pub(crate) fn get_universal_short(&mut self) -> u32 {
-        // it has 4 digits
-        if self.buf.rem() >= 4 {
-            let c1 = self.buf.next_char();
-            let c2 = self.buf.next_char_n(1);
-            let c3 = self.buf.next_char_n(2);
-            let c4 = self.buf.next_char_n(3);
-            self.buf.inc_n(4);
-            // TODO: maybe check if we've hex digits...
-            (Self::get_hex_digit(c1) << 12
-                | Self::get_hex_digit(c2) << 8
-                | Self::get_hex_digit(c3) << 4
-                | Self::get_hex_digit(c4)) as u32
+        if n_digits == 0 {
+            None
         } else {
-            0
+            Some(num as u32)
         }
     }
 
-    #[inline(always)]
-    pub(crate) fn get_universal_long(&mut self) -> u32 {
-        // it has 8 digits
-        if self.buf.rem() >= 8 {
-            let c1 = self.buf.next_char();
-            let c2 = self.buf.next_char_n(1);
-            let c3 = self.buf.next_char_n(2);
-            let c4 = self.buf.next_char_n(3);
-            let c5 = self.buf.next_char_n(4);
-            let c6 = self.buf.next_char_n(5);
-            let c7 = self.buf.next_char_n(6);
-            let c8 = self.buf.next_char_n(7);
-            self.buf.inc_n(8);
-            // TODO: maybe check if we've hex digits...
-            (Self::get_hex_digit(c1) << 28
-                | Self::get_hex_digit(c2) << 24
-                | Self::get_hex_digit(c3) << 20
-                | Self::get_hex_digit(c4) << 16
-                | Self::get_hex_digit(c5) << 12
-                | Self::get_hex_digit(c6) << 8
-                | Self::get_hex_digit(c7) << 4
-                | Self::get_hex_digit(c8)) as u32
-        } else {
-            0
+    /// Get the code point of the `\u` or `\U` escape starting at bpos
+    fn get_ucn_escape(&mut self, bpos: usize, c: u8) -> u32 {
+        self.buf.set_pos(bpos);
+        match Ucn::read(self.buf.remainder()) {
+            Ucn::Valid(code, len) => {
+                self.buf.inc_n(len);
+                if std::char::from_u32(code).is_some() {
+                    code
+                } else {
+                    let ucn = String::from_utf8_lossy(self.buf.slice(bpos)).into_owned();
+                    self.errors.push(LexerError::InvalidUcn {
+                        sp: self.span_n(len),
+                        ucn,
+                    });
+                    u32::from(std::char::REPLACEMENT_CHARACTER)
+                }
+            }
+            Ucn::Incomplete | Ucn::None => {
+                // Take the u or U as is
+                self.buf.inc_n(2);
+                self.errors
+                    .push(LexerError::IncompleteUcnEscape { sp: self.span_n(2) });
+                u32::from(c)
+            }
         }
     }
 
     #[inline(always)]
     pub(crate) fn get_escape(&mut self) -> Option<u32> {
         if self.buf.has_char() {
+            // The backslash has been consumed
+            let bpos = self.buf.pos() - 1;
             let c = self.buf.next_char();
             self.buf.inc();
             let kind = unsafe { ECHARS.get_unchecked(c as usize) };
@@ -180,18 +173,52 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
                 Kind::RRR => Some(0x0D),
                 Kind::TTT => Some(0x09),
                 Kind::VVV => Some(0x0B),
+                Kind::Eee => Some(0x1B),
                 Kind::OCT => {
                     let first = u32::from(c - b'0');
                     Some(self.get_oct_char(first))
                 }
-                Kind::HEX => Some(self.get_hex_char()),
-                Kind::UNS => Some(self.get_universal_short()),
-                Kind::UNL => Some(self.get_universal_long()),
+                Kind::HEX => {
+                    if let Some(x) = self.get_hex_char() {
+                        Some(x)
+                    } else {
+                        self.errors
+                            .push(LexerError::MissingHexDigits { sp: self.span_n(2) });
+                        Some(0)
+                    }
+                }
+                Kind::UNS | Kind::UNL => Some(self.get_ucn_escape(bpos, c)),
                 Kind::NLI => {
-                    self.buf.add_new_line();
+                    self.buf.add_new_line_after();
                     None
                 }
-                Kind::NON => unreachable!(),
+                Kind::Cre if self.buf.has_char() && self.buf.next_char() == b'\n' => {
+                    self.buf.inc();
+                    self.buf.add_new_line_after();
+                    None
+                }
+                Kind::Cre | Kind::NON => {
+                    if c < 0x80 {
+                        self.errors.push(LexerError::UnknownEscape {
+                            sp: self.span_n(2),
+                            c: char::from(c),
+                        });
+                        Some(u32::from(c))
+                    } else {
+                        // Keep the UTF-8 sequence as is
+                        self.buf.dec();
+                        let rem = self.buf.remainder();
+                        let c = String::from_utf8_lossy(&rem[..rem.len().min(4)])
+                            .chars()
+                            .next()
+                            .unwrap_or(std::char::REPLACEMENT_CHARACTER);
+                        self.errors.push(LexerError::UnknownEscape {
+                            sp: self.span_n(1),
+                            c,
+                        });
+                        None
+                    }
+                }
             }
         } else {
             None
@@ -212,6 +239,7 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
     pub(crate) fn get_c_char_u32(&mut self) -> u32 {
         // TODO: LiteralSupport.cpp
         let mut val: u32 = 0;
+        let mut empty = true;
         loop {
             if self.buf.has_char() {
                 let c = self.buf.next_char();
@@ -219,15 +247,27 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
                     self.buf.inc();
                     if let Some(e) = self.get_escape() {
                         val = (val << Self::get_shift(e)) | e;
+                        empty = false;
                     }
                 } else if c == b'\'' {
                     self.buf.inc();
+                    if empty {
+                        self.errors.push(LexerError::EmptyChar { sp: self.span() });
+                    }
+                    break;
+                } else if c == b'\n' {
+                    // The new line isn't consumed to have an Eol
+                    self.errors
+                        .push(LexerError::UnterminatedChar { sp: self.span() });
                     break;
                 } else {
                     self.buf.inc();
                     val = (val << 8) | u32::from(c);
+                    empty = false;
                 }
             } else {
+                self.errors
+                    .push(LexerError::UnterminatedChar { sp: self.span() });
                 break;
             }
         }
@@ -293,7 +333,7 @@ mod tests {
 
     #[test]
     fn test_char() {
-        let mut p = Lexer::<DefaultContext>::new(b"'a' 'b' 'c' '\\t' '\\n' '\\\'' '\\\"' '\\12' '\\1' '\\x12' '\\x1f\\x85' 'abcd' '\\u1a2b' '\\U1a2B3c4D'");
+        let mut p = Lexer::<DefaultContext>::new(b"'a' 'b' 'c' '\\t' '\\n' '\\\'' '\\\"' '\\12' '\\1' '\\x12' '\\x1f\\x85' 'abcd' '\\u1a2b' '\\U0001F60a'");
         assert_eq!(p.next_token(), Token::LiteralChar(u32::from('a')));
         assert_eq!(p.next_token(), Token::LiteralChar(u32::from('b')));
         assert_eq!(p.next_token(), Token::LiteralChar(u32::from('c')));
@@ -307,7 +347,38 @@ mod tests {
         assert_eq!(p.next_token(), Token::LiteralChar(0x1f85));
         assert_eq!(p.next_token(), Token::LiteralChar(0x61626364));
         assert_eq!(p.next_token(), Token::LiteralChar(0x1a2b));
-        assert_eq!(p.next_token(), Token::LiteralChar(0x1a2b3c4d));
+        assert_eq!(p.next_token(), Token::LiteralChar(0x1f60a));
+    }
+
+    #[test]
+    fn test_char_errors() {
+        let mut p =
+            Lexer::<DefaultContext>::new(b"'\\q' '\\e' '' '\\x' '\\U1a2B3c4D' '\\u12' 'a\nb");
+        assert_eq!(p.next_token(), Token::LiteralChar(u32::from('q')));
+        assert_eq!(p.next_token(), Token::LiteralChar(0x1b));
+        assert_eq!(p.next_token(), Token::LiteralChar(0));
+        assert_eq!(p.next_token(), Token::LiteralChar(0));
+        assert_eq!(p.next_token(), Token::LiteralChar(0xfffd));
+        assert_eq!(p.next_token(), Token::LiteralChar(0x753132));
+        assert_eq!(p.next_token(), Token::LiteralChar(u32::from('a')));
+        assert_eq!(p.next_token(), Token::Eol);
+        assert_eq!(p.next_token(), p.id("b"));
+        assert_eq!(p.next_token(), Token::Eof);
+
+        let errors = p.get_errors();
+        assert_eq!(errors.len(), 6);
+        assert!(matches!(
+            errors[0],
+            LexerError::UnknownEscape { c: 'q', .. }
+        ));
+        assert!(matches!(errors[1], LexerError::EmptyChar { .. }));
+        assert!(matches!(errors[2], LexerError::MissingHexDigits { .. }));
+        assert!(matches!(errors[3], LexerError::InvalidUcn { .. }));
+        assert!(matches!(errors[4], LexerError::IncompleteUcnEscape { .. }));
+        assert!(matches!(errors[5], LexerError::UnterminatedChar { .. }));
+
+        let sp = errors[0].stringly().sp;
+        assert_eq!((sp.start.column, sp.end.column), (2, 4));
     }
 
     #[test]
@@ -3,39 +3,181 @@
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
+use super::errors::LexerError;
 use super::lexer::{Lexer, Token};
 use super::preprocessor::context::PreprocContext;
+use super::scan;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum DocKind {
+    /// ///, //!, /** */ and /*! */ document the next declaration
+    Leading,
+    /// ///<, //!<, /**< */ and /*!< */ document the previous one
+    Trailing,
+}
+
+/// Get the kind of documentation comment and its text (without the markers)
+/// from the contents of a comment (i.e. what's after // or between /* and */)
+pub(crate) fn get_doc_kind(comment: &[u8], single: bool) -> Option<(DocKind, &[u8])> {
+    let marker = if single { b'/' } else { b'*' };
+    match comment {
+        [m, b'<', text @ ..] if *m == marker || *m == b'!' => Some((DocKind::Trailing, text)),
+        [b'!', text @ ..] => Some((DocKind::Leading, text)),
+        // //// and /*** are just some decorations
+        [m, text @ ..] if *m == marker && text.first() != Some(&marker) => {
+            Some((DocKind::Leading, text))
+        }
+        _ => None,
+    }
+}
+
+/// Remove the leading * in the lines of a /** */ comment and the surrounding blanks
+pub(crate) fn clean_doc(text: &[u8], single: bool) -> String {
+    let text = String::from_utf8_lossy(text);
+    let lines: Vec<_> = text
+        .lines()
+        .map(|line| {
+            let line = if single {
+                line
+            } else {
+                let line = line.trim_start();
+                line.strip_prefix('*').unwrap_or(line)
+            };
+            let line = line.strip_prefix(' ').unwrap_or(line);
+            line.trim_end()
+        })
+        .collect();
+
+    let start = lines
+        .iter()
+        .position(|l| !l.is_empty())
+        .unwrap_or(lines.len());
+    let end = lines
+        .iter()
+        .rposition(|l| !l.is_empty())
+        .map_or(start, |n| n + 1);
+    lines[start..end].join("\n")
+}
+
+/// The documentation comments around the last token returned by next_useful
+#[derive(Clone, Debug, Default)]
+pub(crate) struct DocComments {
+    leading: Vec<String>,
+    trailing: Vec<String>,
+    last_leading: Option<String>,
+    last_trailing: Option<String>,
+}
+
+impl DocComments {
+    pub(crate) fn add(&mut self, kind: DocKind, text: String) {
+        match kind {
+            DocKind::Leading => self.leading.push(text),
+            DocKind::Trailing => self.trailing.push(text),
+        }
+    }
+
+    /// A new token has been read
+    #[inline(always)]
+    pub(crate) fn set_token(&mut self) {
+        self.last_leading = Self::join(&mut self.leading);
+        self.last_trailing = Self::join(&mut self.trailing);
+    }
+
+    fn join(docs: &mut Vec<String>) -> Option<String> {
+        if docs.is_empty() {
+            None
+        } else {
+            let doc = docs.join("\n");
+            docs.clear();
+            Some(doc)
+        }
+    }
+
+    pub(crate) fn take_leading(&mut self) -> Option<String> {
+        self.last_leading.take()
+    }
+
+    pub(crate) fn take_trailing(&mut self) -> Option<String> {
+        self.last_trailing.take()
+    }
+}
 
 impl<'a, PC: PreprocContext> Lexer<'a, PC> {
-    pub(crate) fn get_multiline_comment(&mut self) -> Token {
-        self.buf.inc();
-        let spos = self.buf.pos();
-        loop {
-            if self.buf.has_char() {
-                let c = self.buf.next_char();
-                if c == b'/' {
-                    // TODO: we can have a continuation line between '*' and '/'
-                    let c = self.buf.prev_char();
-                    if c == b'*' && self.buf.pos() != spos {
-                        let comment = self.buf.slice_m_n(spos, 1);
-                        self.buf.inc();
-                        self.comment = Some(comment);
-                        return Token::Comment;
-                    }
-                    self.buf.inc();
-                } else if c == b'\n' {
-                    self.buf.add_new_line();
-                    self.buf.inc();
-                } else {
-                    self.buf.inc();
-                }
+    /// Called on each comment read by next_useful
+    pub(crate) fn add_doc_comment(&mut self) {
+        if let Some((start, end)) = self.comment {
+            // The comment starts with // or /*
+            let single = self.buf.slice_p(self.start.pos + 1, self.start.pos + 2) == b"/";
+            if let Some((kind, text)) = get_doc_kind(self.buf.slice_p(start, end), single) {
+                self.docs.add(kind, clean_doc(text, single));
+            }
+        }
+    }
+
+    /// A trailing documentation comment ending a line documents the declaration
+    /// ending with the token just before it (e.g. int a; ///< doc):
+    /// get it now so the parser can attach it before reading the next token.
+    /// The comment itself is read as usual so the next token gets it too.
+    pub(crate) fn peek_trailing_doc(&mut self) {
+        let rem = self.buf.remainder();
+        let n = rem
+            .iter()
+            .take_while(|c| **c == b' ' || **c == b'\t')
+            .count();
+        let rem = &rem[n..];
+        if rem.len() < 4 || rem[0] != b'/' {
+            return;
+        }
+
+        let single = rem[1] == b'/';
+        let text = if single {
+            let end = rem.iter().position(|c| *c == b'\n').unwrap_or(rem.len());
+            &rem[2..end]
+        } else if rem[1] == b'*' {
+            let end = if let Some(end) = rem[2..].windows(2).position(|w| w == b"*/") {
+                end + 2
             } else {
-                break;
+                return;
+            };
+            let after = &rem[end + 2..];
+            let eol = after
+                .iter()
+                .take_while(|c| **c == b' ' || **c == b'\t' || **c == b'\r')
+                .count();
+            if eol != after.len() && after[eol] != b'\n' {
+                // Something else on the line: the comment goes with the next token
+                return;
             }
+            &rem[2..end]
+        } else {
+            return;
+        };
+
+        if let Some((DocKind::Trailing, text)) = get_doc_kind(text, single) {
+            let doc = clean_doc(text, single);
+            self.docs.last_trailing = Some(match self.docs.last_trailing.take() {
+                Some(prev) => format!("{}\n{}", prev, doc),
+                None => doc,
+            });
         }
+    }
 
-        let comment = self.buf.slice(spos);
-        self.comment = Some(comment);
+    pub(crate) fn get_multiline_comment(&mut self) -> Token {
+        self.buf.inc();
+        let spos = self.buf.pos();
+        // TODO: we can have a continuation line between '*' and '/'
+        let rem = self.buf.remainder();
+        if let Some(end) = scan::find_comment_end(rem) {
+            self.buf.inc_n_lines(end);
+            self.comment = Some((spos, self.buf.pos()));
+            self.buf.inc_n(2);
+        } else {
+            self.errors
+                .push(LexerError::UnterminatedComment { sp: self.span_n(2) });
+            let rem = self.buf.remainder();
+            self.buf.inc_n_lines(rem.len());
+            self.comment = Some((spos, self.buf.pos()));
+        }
         Token::Comment
     }
 
@@ -43,75 +185,64 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
         let spos = self.buf.pos() + 1;
         self.buf.inc();
         loop {
-            if self.buf.has_char() {
-                let c = self.buf.next_char();
-                self.buf.inc();
-                if c == b'\\' {
-                    self.buf.inc();
-                } else if c == b'\n' {
+            let rem = self.buf.remainder();
+            match scan::find_byte2(rem, b'\\', b'\n') {
+                Some(n) if rem[n] == b'\\' => {
+                    self.buf.inc_n((n + 2).min(rem.len()));
+                }
+                Some(n) => {
                     //self.buf.add_new_line();
-                    let comment = self.buf.slice_m_n(spos, 1);
-                    self.buf.dec();
-                    self.comment = Some(comment);
-                    return Token::Comment;
+                    self.buf.inc_n(n);
+                    break;
+                }
+                None => {
+                    self.buf.inc_n(rem.len());
+                    break;
                 }
-            } else {
-                break;
             }
         }
 
-        let comment = self.buf.slice(spos);
-        self.comment = Some(comment);
+        self.comment = Some((spos, self.buf.pos()));
         Token::Comment
     }
 
     #[inline(always)]
     pub(crate) fn skip_multiline_comment(&mut self) {
-        let spos = self.buf.pos();
-        loop {
-            if self.buf.has_char() {
-                let c = self.buf.next_char();
-                if c == b'/' {
-                    let c = self.buf.prev_char();
-                    if c == b'*' && self.buf.pos() != spos {
-                        self.buf.inc();
-                        break;
-                    }
-                    self.buf.inc();
-                } else if c == b'\n' {
-                    self.buf.inc();
-                    self.buf.add_new_line();
-                } else {
-                    self.buf.inc();
-                }
-            } else {
-                break;
-            }
+        let rem = self.buf.remainder();
+        if let Some(end) = scan::find_comment_end(rem) {
+            self.buf.inc_n_lines(end + 2);
+        } else {
+            // The /* has just been consumed
+            self.errors
+                .push(LexerError::UnterminatedComment { sp: self.span_n(2) });
+            let rem = self.buf.remainder();
+            self.buf.inc_n_lines(rem.len());
         }
     }
 
     #[inline(always)]
     pub(crate) fn skip_single_comment(&mut self) {
         loop {
-            if self.buf.has_char() {
-                let c = self.buf.next_char();
-                if c == b'\n' {
-                    // no buf.add_new_line here (will be done later)
-                    break;
-                } else if c == b'\\' {
-                    self.buf.inc();
+            let rem = self.buf.remainder();
+            match scan::find_byte2(rem, b'\\', b'\n') {
+                Some(n) if rem[n] == b'\\' => {
+                    self.buf.inc_n(n + 1);
                     if self.buf.has_char() {
-                        let c = self.buf.next_char();
-                        if c == b'\n' {
+                        if self.buf.next_char() == b'\n' {
                             self.buf.add_new_line();
                         }
+                        self.buf.inc();
                     }
-                    self.buf.inc();
-                } else {
-                    self.buf.inc();
                 }
-            } else {
-                break;
+                Some(n) => {
+                    // no buf.add_new_line here (will be done later)
+                    self.buf.inc_n(n);
+                    break;
+                }
+                None => {
+                    self.buf.inc_n(rem.len());
+                    break;
+                }
             }
         }
     }
@@ -144,4 +275,19 @@ mod tests {
         assert_eq!(p.next_token(), Token::Comment);
         assert_eq!(p.get_comment().unwrap(), b"/ ");
     }
+
+    #[test]
+    fn test_unterminated_comment() {
+        let mut p = Lexer::<DefaultContext>::new(b"int /* abc\n def");
+        assert_eq!(p.next_token(), Token::Int);
+        assert_eq!(p.next_token(), Token::Comment);
+        assert_eq!(p.get_comment().unwrap(), b" abc\n def");
+        assert_eq!(p.next_token(), Token::Eof);
+
+        let errors = p.get_errors();
+        assert_eq!(errors.len(), 1);
+        let err = errors[0].stringly();
+        assert_eq!(err.message, "unterminated /* comment");
+        assert_eq!((err.sp.start.column, err.sp.end.column), (5, 7));
+    }
 }
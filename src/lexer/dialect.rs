@@ -0,0 +1,399 @@
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use bitflags::bitflags;
+
+use crate::args::{LangStandard, Standard};
+
+bitflags! {
+    /// The sets of keywords: a keyword is recognized when one of its sets is enabled
+    pub struct Keywords: u32 {
+        /// The keywords common to all the languages (including the reserved ones: _Bool, __inline__, ...)
+        const ALL = 0b1;
+        /// inline and restrict
+        const C99 = 0b10;
+        /// alignas, bool, constexpr, nullptr, typeof, ...
+        const C23 = 0b100;
+        const CPP = 0b1000;
+        const CPP11 = 0b1_0000;
+        const CPP20 = 0b10_0000;
+        /// typeof and asm in C
+        const GNU = 0b100_0000;
+        /// __int64, __forceinline, ...
+        const MS = 0b1000_0000;
+    }
+}
+
+impl From<LangStandard> for Keywords {
+    fn from(std: LangStandard) -> Self {
+        use Standard::*;
+        let mut keywords = Keywords::ALL;
+        if std.is_cpp() {
+            keywords |= Keywords::CPP;
+            if std.at_least(CPP11) {
+                keywords |= Keywords::CPP11;
+            }
+            if std.at_least(CPP20) {
+                keywords |= Keywords::CPP20;
+            }
+        } else {
+            if std.at_least(C99) {
+                keywords |= Keywords::C99;
+            }
+            if std.at_least(C23) {
+                keywords |= Keywords::C23;
+            }
+            if std.gnu {
+                keywords |= Keywords::GNU;
+            }
+        }
+        keywords
+    }
+}
+
+/// The lexical features depending on the language mode
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Dialect {
+    /// ??= ??( ??/ ... are replaced before lexing
+    pub trigraphs: bool,
+    /// <: :> <% %> %: and %:%: are the same tokens as [ ] { } # and ##
+    pub digraphs: bool,
+    /// and, or, not, ... are operators (in C, they're macros defined in iso646.h)
+    pub alt_tokens: bool,
+    /// <:: is lexed as < :: when not followed by : or > (C++11)
+    pub lower_colon_colon: bool,
+    /// The keyword sets to use
+    pub keywords: Keywords,
+}
+
+impl From<LangStandard> for Dialect {
+    fn from(std: LangStandard) -> Self {
+        use Standard::*;
+        Self {
+            // Like gcc: only in strict mode and removed in C23 and C++17
+            trigraphs: !std.gnu && matches!(std.std, C89 | C99 | C11 | C17 | CPP98 | CPP11 | CPP14),
+            // Digraphs have been introduced in C95
+            digraphs: std.gnu || std.std != C89,
+            alt_tokens: std.is_cpp(),
+            lower_colon_colon: std.at_least(CPP11),
+            keywords: Keywords::from(std),
+        }
+    }
+}
+
+impl Dialect {
+    /// Get the dialect for a standard with an explicit -trigraphs or -fno-trigraphs (if any)
+    pub fn new(std: LangStandard, trigraphs: Option<bool>) -> Self {
+        let mut dialect = Self::from(std);
+        if let Some(trigraphs) = trigraphs {
+            dialect.trigraphs = trigraphs;
+        }
+        dialect
+    }
+}
+
+#[inline(always)]
+fn get_trigraph(c: u8) -> Option<u8> {
+    Some(match c {
+        b'=' => b'#',
+        b'(' => b'[',
+        b')' => b']',
+        b'<' => b'{',
+        b'>' => b'}',
+        b'/' => b'\\',
+        b'\'' => b'^',
+        b'!' => b'|',
+        b'-' => b'~',
+        _ => return None,
+    })
+}
+
+/// Get the last char of the trigraph replaced by c
+#[inline(always)]
+fn get_trigraph_end(c: u8) -> Option<u8> {
+    Some(match c {
+        b'#' => b'=',
+        b'[' => b'(',
+        b']' => b')',
+        b'{' => b'<',
+        b'}' => b'>',
+        b'\\' => b'/',
+        b'^' => b'\'',
+        b'|' => b'!',
+        b'~' => b'-',
+        _ => return None,
+    })
+}
+
+/// Replace the trigraphs (translation phase 1)
+/// None is returned when there are no trigraphs in the buffer, else the new buffer
+/// and the positions of the replaced trigraphs in it (to be recorded in its OffsetMap).
+pub(crate) fn replace_trigraphs(buf: &[u8]) -> Option<(Vec<u8>, Vec<usize>)> {
+    let first = buf
+        .windows(3)
+        .position(|w| w[0] == b'?' && w[1] == b'?' && get_trigraph(w[2]).is_some())?;
+
+    let mut out = Vec::with_capacity(buf.len());
+    let mut positions = Vec::new();
+    out.extend_from_slice(&buf[..first]);
+
+    let mut i = first;
+    while i < buf.len() {
+        let c = buf[i];
+        if c == b'?' && buf.get(i + 1) == Some(&b'?') {
+            if let Some(r) = buf.get(i + 2).and_then(|c| get_trigraph(*c)) {
+                positions.push(out.len());
+                out.push(r);
+                i += 3;
+                continue;
+            }
+        }
+        out.push(c);
+        i += 1;
+    }
+
+    Some((out, positions))
+}
+
+/// Put back the trigraphs at the given positions (sorted and relative to the start of buf):
+/// they're reverted in the raw strings
+pub(crate) fn revert_trigraphs(buf: &[u8], positions: &[usize]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(buf.len() + 2 * positions.len());
+    let mut start = 0;
+    for &pos in positions {
+        out.extend_from_slice(&buf[start..pos]);
+        out.extend_from_slice(b"??");
+        out.push(get_trigraph_end(buf[pos]).unwrap_or(buf[pos]));
+        start = pos + 1;
+    }
+    out.extend_from_slice(&buf[start..]);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::lexer::preprocessor::context::DefaultContext;
+    use crate::lexer::{Lexer, TLexer, Token};
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_dialect_std() {
+        let d = |std, gnu| Dialect::from(LangStandard::new(std, gnu));
+        assert!(d(Standard::C89, false).trigraphs);
+        assert!(!d(Standard::C89, false).digraphs);
+        assert!(!d(Standard::C89, true).trigraphs);
+        assert!(d(Standard::C89, true).digraphs);
+        assert!(!d(Standard::C23, false).trigraphs);
+        assert!(!d(Standard::C11, false).alt_tokens);
+        assert!(d(Standard::CPP98, false).trigraphs);
+        assert!(!d(Standard::CPP98, false).lower_colon_colon);
+        assert!(d(Standard::CPP14, false).trigraphs);
+        assert!(!d(Standard::CPP17, false).trigraphs);
+        assert!(d(Standard::CPP17, false).alt_tokens);
+        assert!(d(Standard::CPP17, true).lower_colon_colon);
+
+        let std = LangStandard::new(Standard::CPP17, true);
+        assert!(Dialect::new(std, Some(true)).trigraphs);
+    }
+
+    #[test]
+    fn test_replace_trigraphs() {
+        assert_eq!(replace_trigraphs(b"a ? b : c"), None);
+        assert_eq!(
+            replace_trigraphs(b"what??!"),
+            Some((b"what|".to_vec(), vec![4]))
+        );
+
+        let src = b"??=define X ??( ??) ??< ??> ??' ??- ???=";
+        let (buf, positions) = replace_trigraphs(src).unwrap();
+        assert_eq!(buf, b"#define X [ ] { } ^ ~ ?#".to_vec());
+        assert_eq!(positions, vec![0, 10, 12, 14, 16, 18, 20, 23]);
+        assert_eq!(revert_trigraphs(&buf, &positions), src.to_vec());
+    }
+
+    #[test]
+    fn test_trigraphs() {
+        let src = b"??=define FOO(x) x ??!??! 1\nFOO(a??(0??)) ??/\n??<";
+        let mut p = Lexer::<DefaultContext>::new(src);
+        p.set_standard(LangStandard::new(Standard::CPP14, false));
+        assert_eq!(p.next_token(), Token::PreprocDefine);
+        assert_eq!(p.next_token(), p.id("a"));
+        assert_eq!(p.next_token(), Token::LeftBrack);
+        assert_eq!(p.next_token(), Token::from_number("0").unwrap());
+        assert_eq!(p.next_token(), Token::RightBrack);
+        assert_eq!(p.next_token(), Token::OrOr);
+        assert_eq!(p.next_token(), Token::from_number("1").unwrap());
+        assert_eq!(p.next_token(), Token::LeftBrace);
+
+        // Trigraphs are ignored in C++17
+        let mut p = Lexer::<DefaultContext>::new(b"a ??! b");
+        p.set_standard(LangStandard::new(Standard::CPP17, false));
+        assert_eq!(p.next_token(), p.id("a"));
+        assert_eq!(p.next_token(), Token::Question);
+        assert_eq!(p.next_token(), Token::Question);
+        assert_eq!(p.next_token(), Token::Not);
+    }
+
+    #[test]
+    fn test_trigraphs_span() {
+        let mut p = Lexer::<DefaultContext>::new(b"a??(0??) = b;\n??< c");
+        p.set_standard(LangStandard::new(Standard::CPP14, false));
+        assert_eq!(p.next_useful(), p.id("a"));
+        assert_eq!(p.next_useful(), Token::LeftBrack);
+        assert_eq!(p.span().start.column, 2);
+        assert_eq!(p.span().end.column, 5);
+        assert_eq!(p.next_useful(), Token::from_number("0").unwrap());
+        assert_eq!(p.next_useful(), Token::RightBrack);
+        assert_eq!(p.span().start.column, 6);
+        assert_eq!(p.next_useful(), Token::Equal);
+        assert_eq!(p.span().start.column, 10);
+        assert_eq!(p.next_useful(), p.id("b"));
+        assert_eq!(p.span().start.column, 12);
+        assert_eq!(p.next_useful(), Token::SemiColon);
+        assert_eq!(p.next_useful(), Token::LeftBrace);
+        assert_eq!(p.next_useful(), p.id("c"));
+        let sp = p.span();
+        assert_eq!((sp.start.line, sp.start.column), (2, 5));
+    }
+
+    #[test]
+    fn test_trigraphs_raw_string() {
+        let mut p = Lexer::<DefaultContext>::new(b"R\"x(a??=b??/)x\" ??<");
+        p.set_standard(LangStandard::new(Standard::CPP11, false));
+        assert_eq!(p.next_useful(), Token::LiteralRString("a??=b??/".into()));
+        assert_eq!(p.next_useful(), Token::LeftBrace);
+    }
+
+    #[test]
+    fn test_digraphs() {
+        let mut p = Lexer::<DefaultContext>::new(b"<: :> <% %> a<::b>c<::>");
+        assert_eq!(p.next_token(), Token::LeftBrack);
+        assert_eq!(p.next_token(), Token::RightBrack);
+        assert_eq!(p.next_token(), Token::LeftBrace);
+        assert_eq!(p.next_token(), Token::RightBrace);
+        assert_eq!(p.next_token(), p.id("a"));
+        assert_eq!(p.next_token(), Token::Lower);
+        assert_eq!(p.next_token(), Token::ColonColon);
+        assert_eq!(p.next_token(), p.id("b"));
+        assert_eq!(p.next_token(), Token::Greater);
+        assert_eq!(p.next_token(), p.id("c"));
+        assert_eq!(p.next_token(), Token::LeftBrack);
+        assert_eq!(p.next_token(), Token::RightBrack);
+
+        // No digraphs in C89
+        let mut p = Lexer::<DefaultContext>::new(b"<: %>");
+        p.set_standard(LangStandard::new(Standard::C89, false));
+        assert_eq!(p.next_token(), Token::Lower);
+        assert_eq!(p.next_token(), Token::Colon);
+        assert_eq!(p.next_token(), Token::Modulo);
+        assert_eq!(p.next_token(), Token::Greater);
+    }
+
+    #[test]
+    fn test_digraphs_preproc() {
+        let src = concat!(
+            "%:define STR(x) %:x\n",
+            "%:define CAT(x, y) x %:%: y\n",
+            "%: if 1\n",
+            "CAT(a, b) STR(c)\n",
+            "%:else\n",
+            "d\n",
+            "%:endif\n",
+        );
+        let mut p = Lexer::<DefaultContext>::new(src.as_bytes());
+        let tokens: Vec<_> = std::iter::from_fn(|| match p.next_token() {
+            Token::Eof => None,
+            tok => Some(tok),
+        })
+        .filter(|tok| matches!(tok, Token::Identifier(_) | Token::LiteralString(_)))
+        .collect();
+        assert_eq!(tokens, vec![p.id("ab"), Token::LiteralString("c".into())]);
+    }
+
+    #[test]
+    fn test_alt_tokens() {
+        let mut p = Lexer::<DefaultContext>::new(b"a and not b");
+        assert_eq!(p.next_token(), p.id("a"));
+        assert_eq!(p.next_token(), Token::AndKw);
+        assert_eq!(p.next_token(), Token::NotKw);
+
+        let mut p = Lexer::<DefaultContext>::new(b"a and not b");
+        p.set_standard(LangStandard::new(Standard::C11, false));
+        assert_eq!(p.next_token(), p.id("a"));
+        assert_eq!(p.next_token(), p.id("and"));
+        assert_eq!(p.next_token(), p.id("not"));
+    }
+
+    #[test]
+    fn test_keywords() {
+        let src = b"class new this template module requires final restrict";
+        let mut p = Lexer::<DefaultContext>::new(src);
+        p.set_standard(LangStandard::new(Standard::C11, false));
+        for id in &[
+            "class", "new", "this", "template", "module", "requires", "final",
+        ] {
+            assert_eq!(p.next_token(), p.id(id));
+        }
+        assert_eq!(p.next_token(), Token::Restrict);
+
+        let mut p = Lexer::<DefaultContext>::new(src);
+        p.set_standard(LangStandard::new(Standard::CPP20, false));
+        assert_eq!(p.next_token(), Token::Class);
+        assert_eq!(p.next_token(), Token::New);
+        assert_eq!(p.next_token(), Token::This);
+        assert_eq!(p.next_token(), Token::Template);
+        assert_eq!(p.next_token(), p.id("module"));
+        assert_eq!(p.next_token(), Token::Requires);
+        assert_eq!(p.next_token(), p.id("final"));
+        assert_eq!(p.next_token(), p.id("restrict"));
+
+        let mut p = Lexer::<DefaultContext>::new(b"requires char8_t nullptr");
+        p.set_standard(LangStandard::new(Standard::CPP17, false));
+        assert_eq!(p.next_token(), p.id("requires"));
+        assert_eq!(p.next_token(), p.id("char8_t"));
+        assert_eq!(p.next_token(), Token::Nullptr);
+    }
+
+    #[test]
+    fn test_keywords_c() {
+        let src = b"_Bool bool typeof __typeof__ __inline__ __restrict inline";
+        let mut p = Lexer::<DefaultContext>::new(src);
+        p.set_standard(LangStandard::new(Standard::C89, false));
+        assert_eq!(p.next_token(), Token::Bool);
+        assert_eq!(p.next_token(), p.id("bool"));
+        assert_eq!(p.next_token(), p.id("typeof"));
+        assert_eq!(p.next_token(), Token::Typeof);
+        assert_eq!(p.next_token(), Token::Inline);
+        assert_eq!(p.next_token(), Token::MSRestrict);
+        assert_eq!(p.next_token(), p.id("inline"));
+
+        let mut p = Lexer::<DefaultContext>::new(src);
+        p.set_standard(LangStandard::new(Standard::C99, true));
+        assert_eq!(p.next_token(), Token::Bool);
+        assert_eq!(p.next_token(), p.id("bool"));
+        assert_eq!(p.next_token(), Token::Typeof);
+
+        let mut p = Lexer::<DefaultContext>::new(b"bool nullptr typeof_unqual");
+        p.set_standard(LangStandard::new(Standard::C23, false));
+        assert_eq!(p.next_token(), Token::Bool);
+        assert_eq!(p.next_token(), Token::Nullptr);
+        assert_eq!(p.next_token(), Token::TypeofUnqual);
+    }
+
+    #[test]
+    fn test_keywords_ms() {
+        let mut p = Lexer::<DefaultContext>::new(b"__int64 __forceinline");
+        assert_eq!(p.next_token(), p.id("__int64"));
+
+        let mut p = Lexer::<DefaultContext>::new(b"__int64 __forceinline");
+        let mut dialect = p.get_dialect();
+        dialect.keywords |= Keywords::MS;
+        p.set_dialect(dialect);
+        assert_eq!(p.next_token(), Token::MSInt64);
+        assert_eq!(p.next_token(), Token::Inline);
+    }
+}
@@ -0,0 +1,278 @@
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+/// The encoding of a source file
+/// A BOM always wins over the encoding given on the command line.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Encoding {
+    #[default]
+    Utf8,
+    Utf16LE,
+    Utf16BE,
+    /// ISO-8859-1
+    Latin1,
+    /// Windows-1252
+    Cp1252,
+}
+
+impl Encoding {
+    /// Get the encoding from its name as used in -finput-charset or /source-charset
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = name.to_ascii_lowercase().replace('_', "-");
+        Some(match name.as_str() {
+            "utf-8" | "utf8" | "65001" | ".65001" => Encoding::Utf8,
+            "utf-16le" | "utf16le" | "ucs-2le" | "1200" | ".1200" => Encoding::Utf16LE,
+            "utf-16be" | "utf16be" | "ucs-2be" | "1201" | ".1201" => Encoding::Utf16BE,
+            "iso-8859-1" | "iso8859-1" | "latin1" | "latin-1" | "l1" | "28591" | ".28591" => {
+                Encoding::Latin1
+            }
+            "cp1252" | "windows-1252" | "1252" | ".1252" => Encoding::Cp1252,
+            _ => return None,
+        })
+    }
+}
+
+/// Map the positions in a transcoded buffer to the ones in the original file
+/// The checkpoints are only added where the two positions stop moving together
+/// so an ASCII file in UTF-16 needs only one of them.
+/// The trigraphs replaced after the decoding are recorded too.
+#[derive(Clone, Debug, PartialEq)]
+pub struct OffsetMap {
+    /// (position in the UTF-8 buffer, position in the original file)
+    checkpoints: Vec<(usize, usize)>,
+    unit: usize,
+    /// The sorted positions of the chars replacing a trigraph
+    trigraphs: Vec<usize>,
+}
+
+impl Default for OffsetMap {
+    fn default() -> Self {
+        Self::new(1)
+    }
+}
+
+impl OffsetMap {
+    fn new(unit: usize) -> Self {
+        Self {
+            checkpoints: Vec::new(),
+            unit,
+            trigraphs: Vec::new(),
+        }
+    }
+
+    /// Record the trigraphs replaced in the buffer (see replace_trigraphs)
+    pub(crate) fn set_trigraphs(&mut self, trigraphs: Vec<usize>) {
+        self.trigraphs = trigraphs;
+    }
+
+    /// Get the positions of the replaced trigraphs in start..end
+    pub(crate) fn trigraphs(&self, start: usize, end: usize) -> &[usize] {
+        let s = self.trigraphs.partition_point(|p| *p < start);
+        let e = self.trigraphs.partition_point(|p| *p < end);
+        &self.trigraphs[s..e]
+    }
+
+    /// Called at the start of each decoded char
+    fn add(&mut self, pos: usize, orig: usize) {
+        if self.original(pos) != orig || self.checkpoints.is_empty() {
+            self.checkpoints.push((pos, orig));
+        }
+    }
+
+    /// Get the position in the original file
+    pub fn original(&self, pos: usize) -> usize {
+        // Each trigraph before pos is 2 bytes longer in the decoded buffer
+        let pos = pos + 2 * self.trigraphs.partition_point(|p| *p < pos);
+        let i = self.checkpoints.partition_point(|(p, _)| *p <= pos);
+        if i == 0 {
+            return pos * self.unit;
+        }
+        let (start, orig) = self.checkpoints[i - 1];
+        orig + (pos - start) * self.unit
+    }
+
+    /// Get the column (1-based and counted in code units) in the original file
+    /// of the char at pos on the line starting at lpos
+    pub fn column(&self, pos: usize, lpos: usize) -> u32 {
+        let len = self.original(pos).saturating_sub(self.original(lpos));
+        (len / self.unit + 1) as u32
+    }
+}
+
+/// The chars 0x80 to 0x9F in Windows-1252 (the unassigned ones are kept as C1 controls)
+#[rustfmt::skip]
+const CP1252: [char; 32] = [
+    '\u{20AC}', '\u{81}', '\u{201A}', '\u{192}', '\u{201E}', '\u{2026}', '\u{2020}', '\u{2021}',
+    '\u{2C6}', '\u{2030}', '\u{160}', '\u{2039}', '\u{152}', '\u{8D}', '\u{17D}', '\u{8F}',
+    '\u{90}', '\u{2018}', '\u{2019}', '\u{201C}', '\u{201D}', '\u{2022}', '\u{2013}', '\u{2014}',
+    '\u{2DC}', '\u{2122}', '\u{161}', '\u{203A}', '\u{153}', '\u{9D}', '\u{17E}', '\u{178}',
+];
+
+fn push_char(out: &mut Vec<u8>, c: char) {
+    let mut tmp = [0; 4];
+    out.extend_from_slice(c.encode_utf8(&mut tmp).as_bytes());
+}
+
+fn decode_single_byte(data: &[u8], encoding: Encoding) -> (Vec<u8>, OffsetMap) {
+    let mut out = Vec::with_capacity(data.len() + data.len() / 8);
+    let mut map = OffsetMap::new(1);
+    for (i, c) in data.iter().enumerate() {
+        map.add(out.len(), i);
+        if c.is_ascii() {
+            out.push(*c);
+        } else if encoding == Encoding::Cp1252 && *c < 0xA0 {
+            push_char(&mut out, CP1252[(*c - 0x80) as usize]);
+        } else {
+            push_char(&mut out, char::from(*c));
+        }
+    }
+    (out, map)
+}
+
+fn decode_utf16(data: &[u8], start: usize, encoding: Encoding) -> (Vec<u8>, OffsetMap) {
+    let units = data[start..].chunks_exact(2).map(|c| {
+        if encoding == Encoding::Utf16LE {
+            u16::from_le_bytes([c[0], c[1]])
+        } else {
+            u16::from_be_bytes([c[0], c[1]])
+        }
+    });
+
+    let mut out = Vec::with_capacity(data.len() / 2 + 1);
+    let mut map = OffsetMap::new(2);
+    let mut orig = start;
+    for c in std::char::decode_utf16(units) {
+        map.add(out.len(), orig);
+        let c = c.unwrap_or(std::char::REPLACEMENT_CHARACTER);
+        orig += c.len_utf16() * 2;
+        push_char(&mut out, c);
+    }
+    (out, map)
+}
+
+/// Convert the content of a file to UTF-8
+/// None is returned when the data can be used as is.
+pub fn decode(data: &[u8], encoding: Encoding) -> Option<(Vec<u8>, OffsetMap)> {
+    if data.starts_with(b"\xEF\xBB\xBF") {
+        let mut map = OffsetMap::new(1);
+        map.add(0, 3);
+        return Some((data[3..].to_vec(), map));
+    }
+
+    Some(if data.starts_with(b"\xFF\xFE") {
+        decode_utf16(data, 2, Encoding::Utf16LE)
+    } else if data.starts_with(b"\xFE\xFF") {
+        decode_utf16(data, 2, Encoding::Utf16BE)
+    } else {
+        match encoding {
+            Encoding::Utf8 => return None,
+            Encoding::Utf16LE | Encoding::Utf16BE => decode_utf16(data, 0, encoding),
+            Encoding::Latin1 | Encoding::Cp1252 => decode_single_byte(data, encoding),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::lexer::preprocessor::context::DefaultContext;
+    use crate::lexer::{Lexer, TLexer, Token};
+    use pretty_assertions::assert_eq;
+
+    fn utf16le(s: &str, bom: bool) -> Vec<u8> {
+        let mut out = if bom { vec![0xFF, 0xFE] } else { vec![] };
+        for u in s.encode_utf16() {
+            out.extend_from_slice(&u.to_le_bytes());
+        }
+        out
+    }
+
+    #[test]
+    fn test_decode_utf8() {
+        assert_eq!(decode(b"int a;", Encoding::Utf8), None);
+
+        let (buf, map) = decode(b"\xEF\xBB\xBFint a;", Encoding::Utf8).unwrap();
+        assert_eq!(buf, b"int a;");
+        assert_eq!(map.original(0), 3);
+        assert_eq!(map.original(4), 7);
+        assert_eq!(map.column(4, 0), 5);
+    }
+
+    #[test]
+    fn test_decode_utf16() {
+        let (buf, map) = decode(&utf16le("a é\n€ b", true), Encoding::Utf8).unwrap();
+        assert_eq!(String::from_utf8(buf).unwrap(), "a é\n€ b");
+        assert_eq!(map.original(0), 2);
+        assert_eq!(map.original(2), 6);
+        // after é (2 bytes in UTF-8)
+        assert_eq!(map.original(4), 8);
+        // b on the second line
+        assert_eq!(map.column(9, 5), 3);
+
+        let mut be = vec![0xFE, 0xFF];
+        for u in "x\u{1F600}y".encode_utf16() {
+            be.extend_from_slice(&u.to_be_bytes());
+        }
+        let (buf, map) = decode(&be, Encoding::Utf8).unwrap();
+        assert_eq!(String::from_utf8(buf).unwrap(), "x\u{1F600}y");
+        assert_eq!(map.original(5), 8);
+
+        let (buf, _) = decode(&utf16le("int", false), Encoding::Utf16LE).unwrap();
+        assert_eq!(buf, b"int");
+    }
+
+    #[test]
+    fn test_offsets_trigraphs() {
+        let (buf, mut map) = decode(&utf16le("a??(b", true), Encoding::Utf8).unwrap();
+        let (buf, positions) = crate::lexer::dialect::replace_trigraphs(&buf).unwrap();
+        assert_eq!(buf, b"a[b");
+        map.set_trigraphs(positions);
+        assert_eq!(map.trigraphs(0, 3), &[1]);
+        assert_eq!(map.trigraphs(2, 3), &[] as &[usize]);
+        // b is the 5th char after the BOM
+        assert_eq!(map.original(2), 10);
+        assert_eq!(map.column(2, 0), 5);
+    }
+
+    #[test]
+    fn test_decode_single_byte() {
+        let (buf, map) = decode(b"\"caf\xe9\" \x80", Encoding::Latin1).unwrap();
+        assert_eq!(String::from_utf8(buf).unwrap(), "\"caf\u{e9}\" \u{80}");
+        assert_eq!(map.column(7, 0), 7);
+
+        let (buf, _) = decode(b"\x80 \x93x\x94", Encoding::Cp1252).unwrap();
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            "\u{20AC} \u{201C}x\u{201D}"
+        );
+    }
+
+    #[test]
+    fn test_encoding_from_name() {
+        assert_eq!(Encoding::from_name("UTF-8"), Some(Encoding::Utf8));
+        assert_eq!(Encoding::from_name("iso-8859-1"), Some(Encoding::Latin1));
+        assert_eq!(Encoding::from_name("windows-1252"), Some(Encoding::Cp1252));
+        assert_eq!(Encoding::from_name("utf-16le"), Some(Encoding::Utf16LE));
+        assert_eq!(Encoding::from_name("ebcdic"), None);
+    }
+
+    #[test]
+    fn test_lexer_columns() {
+        let (buf, map) = decode(&utf16le("int a;\n  \"\u{e9}\" b;", true), Encoding::Utf8).unwrap();
+        let mut p = Lexer::<DefaultContext>::new(&buf);
+        p.buf.set_offsets(Some(map));
+
+        assert_eq!(p.next_useful(), Token::Int);
+        assert_eq!(p.next_useful(), p.id("a"));
+        assert_eq!(p.span().start.column, 5);
+        assert_eq!(p.next_useful(), Token::SemiColon);
+        assert_eq!(p.next_useful(), Token::LiteralString("\u{e9}".into()));
+        assert_eq!(p.next_useful(), p.id("b"));
+        let span = p.span();
+        assert_eq!(span.start.line, 2);
+        assert_eq!(span.start.column, 7);
+    }
+}
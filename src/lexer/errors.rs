@@ -3,13 +3,154 @@
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
+use super::numeric::NumberError;
 use crate::errors::{Span, StringlyError};
 
 #[derive(Clone, Debug)]
 pub enum LexerError {
-    ErrorDirective { sp: Span, msg: String },
-    EndifWithoutPreceedingIf { sp: Span },
-    FileIncludeError { sp: Span, file: String },
+    ErrorDirective {
+        sp: Span,
+        msg: String,
+    },
+    EndifWithoutPreceedingIf {
+        sp: Span,
+    },
+    FileIncludeError {
+        sp: Span,
+        file: String,
+    },
+    InvalidIncludeName {
+        sp: Span,
+        file: String,
+    },
+    ExpectedIncludeName {
+        sp: Span,
+    },
+    InvalidNumber {
+        sp: Span,
+        spelling: String,
+        err: NumberError,
+    },
+    IntegerTooLarge {
+        sp: Span,
+        spelling: String,
+    },
+    IntegerSoLarge {
+        sp: Span,
+        spelling: String,
+    },
+    FloatOutOfRange {
+        sp: Span,
+        spelling: String,
+        typ: &'static str,
+    },
+    InvalidUtf8 {
+        sp: Span,
+    },
+    InvalidUcn {
+        sp: Span,
+        ucn: String,
+    },
+    UcnBasicChar {
+        sp: Span,
+        ucn: String,
+    },
+    IncompleteUcn {
+        sp: Span,
+    },
+    InvalidIdentifierChar {
+        sp: Span,
+        c: char,
+        start: bool,
+    },
+    StrayChar {
+        sp: Span,
+        c: char,
+    },
+    UnterminatedString {
+        sp: Span,
+    },
+    UnterminatedChar {
+        sp: Span,
+    },
+    EmptyChar {
+        sp: Span,
+    },
+    UnterminatedComment {
+        sp: Span,
+    },
+    UnterminatedRawString {
+        sp: Span,
+        delim: String,
+    },
+    RawStringDelimiterTooLong {
+        sp: Span,
+    },
+    InvalidRawStringDelimiter {
+        sp: Span,
+        c: char,
+    },
+    UnknownEscape {
+        sp: Span,
+        c: char,
+    },
+    MissingHexDigits {
+        sp: Span,
+    },
+    IncompleteUcnEscape {
+        sp: Span,
+    },
+    ExpectedValueInExpression {
+        sp: Span,
+    },
+    InvalidTokenInExpression {
+        sp: Span,
+    },
+    MissingParenInExpression {
+        sp: Span,
+    },
+    InvalidLineNumber {
+        sp: Span,
+    },
+    InvalidLineFilename {
+        sp: Span,
+    },
+    InvalidPragmaOperator {
+        sp: Span,
+    },
+    PragmaError {
+        sp: Span,
+        msg: String,
+    },
+    InvalidPragmaPack {
+        sp: Span,
+    },
+    PragmaPackShow {
+        sp: Span,
+        pack: Option<u32>,
+    },
+    PoisonedIdentifier {
+        sp: Span,
+        name: String,
+    },
+    MissingParenAfterOperator {
+        sp: Span,
+        name: String,
+        paren: char,
+    },
+    ExpectedIdentifierInOperator {
+        sp: Span,
+        name: String,
+    },
+}
+
+/// Show a char in a message: the invisible ones are written as code points
+fn printable(c: char) -> String {
+    if c.is_control() || c.is_whitespace() {
+        format!("<U+{:04X}>", c as u32)
+    } else {
+        format!("'{}'", c)
+    }
 }
 
 impl LexerError {
@@ -23,6 +164,123 @@ impl LexerError {
             FileIncludeError { sp, file } => {
                 (*sp, format!("can't open file {} for inclusion", file))
             }
+            InvalidIncludeName { sp, file } => (
+                *sp,
+                format!("invalid file name {} for inclusion (not UTF-8)", file),
+            ),
+            ExpectedIncludeName { sp } => (
+                *sp,
+                "expected \"FILENAME\" or <FILENAME> in #include".to_owned(),
+            ),
+            InvalidNumber { sp, spelling, err } => {
+                (*sp, format!("invalid numeric literal {}: {}", spelling, err))
+            }
+            IntegerTooLarge { sp, spelling } => (
+                *sp,
+                format!(
+                    "integer literal {} is too large to be represented in any integer type",
+                    spelling
+                ),
+            ),
+            IntegerSoLarge { sp, spelling } => (
+                *sp,
+                format!(
+                    "integer literal {} is too large to be represented in a signed integer type, interpreting as unsigned",
+                    spelling
+                ),
+            ),
+            FloatOutOfRange { sp, spelling, typ } => (
+                *sp,
+                format!("floating literal {} is out of range for type {}", spelling, typ),
+            ),
+            InvalidUtf8 { sp } => (*sp, "invalid UTF-8 sequence".to_owned()),
+            InvalidUcn { sp, ucn } => (
+                *sp,
+                format!(
+                    "universal character name {} doesn't designate a valid character",
+                    ucn
+                ),
+            ),
+            UcnBasicChar { sp, ucn } => (
+                *sp,
+                format!(
+                    "universal character name {} designates a basic or control character",
+                    ucn
+                ),
+            ),
+            IncompleteUcn { sp } => (
+                *sp,
+                "incomplete universal character name; treating as '\\' followed by identifier"
+                    .to_owned(),
+            ),
+            InvalidIdentifierChar { sp, c, start } => (
+                *sp,
+                format!(
+                    "character <U+{:04X}> not allowed {} identifier",
+                    *c as u32,
+                    if *start { "at the start of an" } else { "in an" }
+                ),
+            ),
+            StrayChar { sp, c } => (*sp, format!("stray {} in program", printable(*c))),
+            UnterminatedString { sp } => (*sp, "missing terminating '\"' character".to_owned()),
+            UnterminatedChar { sp } => (*sp, "missing terminating ' character".to_owned()),
+            EmptyChar { sp } => (*sp, "empty character constant".to_owned()),
+            UnterminatedComment { sp } => (*sp, "unterminated /* comment".to_owned()),
+            UnterminatedRawString { sp, delim } => (
+                *sp,
+                format!("raw string missing terminating delimiter ){}\"", delim),
+            ),
+            RawStringDelimiterTooLong { sp } => (
+                *sp,
+                "raw string delimiter longer than 16 characters".to_owned(),
+            ),
+            InvalidRawStringDelimiter { sp, c } => (
+                *sp,
+                format!("invalid character {} in raw string delimiter", printable(*c)),
+            ),
+            UnknownEscape { sp, c } => (
+                *sp,
+                if c.is_control() || c.is_whitespace() {
+                    format!("unknown escape sequence '\\' followed by {}", printable(*c))
+                } else {
+                    format!("unknown escape sequence '\\{}'", c)
+                },
+            ),
+            MissingHexDigits { sp } => (*sp, "\\x used with no following hex digits".to_owned()),
+            IncompleteUcnEscape { sp } => (*sp, "incomplete universal character name".to_owned()),
+            ExpectedValueInExpression { sp } => (*sp, "expected value in expression".to_owned()),
+            InvalidTokenInExpression { sp } => {
+                (*sp, "invalid token in preprocessor expression".to_owned())
+            }
+            MissingParenInExpression { sp } => (*sp, "missing ')' in expression".to_owned()),
+            InvalidLineNumber { sp } => (
+                *sp,
+                "#line directive requires a simple digit sequence".to_owned(),
+            ),
+            InvalidLineFilename { sp } => (*sp, "invalid filename for #line directive".to_owned()),
+            InvalidPragmaOperator { sp } => (
+                *sp,
+                "_Pragma takes a string literal and __pragma a token list (in parentheses)"
+                    .to_owned(),
+            ),
+            PragmaError { sp, msg } => (*sp, format!("#pragma GCC error: {}", msg)),
+            InvalidPragmaPack { sp } => (*sp, "malformed #pragma pack ignored".to_owned()),
+            PragmaPackShow { sp, pack } => (
+                *sp,
+                match pack {
+                    Some(pack) => format!("value of #pragma pack(show) == {}", pack),
+                    None => "value of #pragma pack(show) is the default one".to_owned(),
+                },
+            ),
+            PoisonedIdentifier { sp, name } => {
+                (*sp, format!("attempt to use poisoned identifier: {}", name))
+            }
+            MissingParenAfterOperator { sp, name, paren } => {
+                (*sp, format!("missing '{}' after {}", paren, name))
+            }
+            ExpectedIdentifierInOperator { sp, name } => {
+                (*sp, format!("{} requires an identifier", name))
+            }
         };
         StringlyError { message, sp }
     }
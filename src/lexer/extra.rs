@@ -3,18 +3,24 @@
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
+use hashbrown::HashMap;
+
 use crate::errors::Span;
-use crate::lexer::{TLexer, Token};
+use crate::lexer::{Symbol, TLexer, Token};
 
+/// Tokens saved from a lexer (e.g. for a lookahead) with their spans
+/// in order to be replayed with the right locations
 #[derive(Clone, Debug)]
 pub struct SavedLexer {
-    toks: Vec<Token>,
+    toks: Vec<(Token, Span)>,
     pos: usize,
+    /// The strings of the saved identifiers
+    names: HashMap<Symbol, Box<str>>,
 }
 
 impl TLexer for SavedLexer {
     fn next_useful(&mut self) -> Token {
-        if let Some(tok) = self.toks.get(self.pos) {
+        if let Some((tok, _)) = self.toks.get(self.pos) {
             self.pos += 1;
             tok.clone()
         } else {
@@ -23,20 +29,36 @@ impl TLexer for SavedLexer {
     }
 
     fn span(&self) -> Span {
-        Span::default()
+        // The span of the last replayed token
+        self.pos
+            .checked_sub(1)
+            .and_then(|pos| self.toks.get(pos))
+            .map_or_else(Span::default, |(_, sp)| *sp)
+    }
+
+    fn resolve(&self, sym: Symbol) -> &str {
+        &self.names[&sym]
     }
 }
 
 impl SavedLexer {
-    pub fn new(toks: Vec<Token>) -> Self {
-        Self { toks, pos: 0 }
+    pub fn new(toks: Vec<(Token, Span)>, names: HashMap<Symbol, Box<str>>) -> Self {
+        Self {
+            toks,
+            pos: 0,
+            names,
+        }
     }
 
-    pub fn push(&mut self, tok: Token) {
-        self.toks.push(tok);
+    /// Push a token from the lexer the others have been saved from:
+    /// an identifier pushed here is only resolved through this lexer (see CombinedLexers)
+    pub fn push(&mut self, tok: Token, sp: Span) {
+        self.toks.push((tok, sp));
     }
 }
 
+/// Replay the tokens saved from a lexer (the first one) before going on with this lexer
+/// (the second one), which resolves the symbols of both
 pub struct CombinedLexers<'l1, 'l2> {
     first: &'l1 mut dyn TLexer,
     second: &'l2 mut dyn TLexer,
@@ -61,11 +83,31 @@ impl<'l1, 'l2> TLexer for CombinedLexers<'l1, 'l2> {
 
     fn span(&self) -> Span {
         if self.state {
-            Span::default()
+            self.first.span()
         } else {
             self.second.span()
         }
     }
+
+    fn resolve(&self, sym: Symbol) -> &str {
+        self.second.resolve(sym)
+    }
+
+    fn take_doc(&mut self) -> Option<String> {
+        if self.state {
+            self.first.take_doc()
+        } else {
+            self.second.take_doc()
+        }
+    }
+
+    fn take_trailing_doc(&mut self) -> Option<String> {
+        if self.state {
+            self.first.take_trailing_doc()
+        } else {
+            self.second.take_trailing_doc()
+        }
+    }
 }
 
 impl<'l1, 'l2> CombinedLexers<'l1, 'l2> {
@@ -89,25 +131,93 @@ mod tests {
     fn test_saved_lexer() {
         let mut l = Lexer::<DefaultContext>::new(b"(1 + 2 * 3) + (4 - 5))");
         let (_, saved) = l.save_until(Token::RightParen);
+        let toks: Vec<_> = saved.toks.iter().map(|(tok, _)| tok.clone()).collect();
 
         assert_eq!(
-            saved.toks,
+            toks,
             vec![
                 Token::LeftParen,
-                Token::LiteralInt(1),
+                Token::from_number("1").unwrap(),
                 Token::Plus,
-                Token::LiteralInt(2),
+                Token::from_number("2").unwrap(),
                 Token::Star,
-                Token::LiteralInt(3),
+                Token::from_number("3").unwrap(),
                 Token::RightParen,
                 Token::Plus,
                 Token::LeftParen,
-                Token::LiteralInt(4),
+                Token::from_number("4").unwrap(),
                 Token::Minus,
-                Token::LiteralInt(5),
+                Token::from_number("5").unwrap(),
                 Token::RightParen,
                 Token::RightParen
             ]
         );
     }
+
+    fn get_spans<L: TLexer>(lexer: &mut L) -> Vec<(Token, usize, usize, u32)> {
+        let mut spans = Vec::new();
+        loop {
+            let tok = lexer.next_useful();
+            if tok == Token::Eof {
+                return spans;
+            }
+            let sp = lexer.span();
+            spans.push((tok, sp.start.pos, sp.end.pos, sp.start.line));
+        }
+    }
+
+    #[test]
+    fn test_saved_lexer_span() {
+        let source = b"(a +\n  bc) * d;";
+        let expected = get_spans(&mut Lexer::<DefaultContext>::new(source));
+
+        let mut l = Lexer::<DefaultContext>::new(source);
+        let (_, mut saved) = l.save_until(Token::SemiColon);
+        assert_eq!(get_spans(&mut saved), expected);
+    }
+
+    #[test]
+    fn test_combined_lexers_span() {
+        let source = b"(a + b)\n  * c;";
+        let expected = get_spans(&mut Lexer::<DefaultContext>::new(source));
+
+        let mut l = Lexer::<DefaultContext>::new(source);
+        let (_, mut saved) = l.save_until(Token::RightParen);
+        let tok = l.next_useful();
+        saved.push(tok, l.span());
+
+        let mut combined = CombinedLexers::new(&mut saved, &mut l);
+        assert_eq!(get_spans(&mut combined), expected);
+    }
+
+    #[test]
+    fn test_saved_lexer_resolve() {
+        let mut l = Lexer::<DefaultContext>::new(b"a + b) c");
+        let (_, mut saved) = l.save_until(Token::RightParen);
+        drop(l);
+        let mut ids = Vec::new();
+        loop {
+            match saved.next_useful() {
+                Token::Identifier(id) => ids.push(saved.resolve(id).to_string()),
+                Token::Eof | Token::RightParen => break,
+                _ => {}
+            }
+        }
+        assert_eq!(ids, vec!["a", "b"]);
+
+        let mut l = Lexer::<DefaultContext>::new(b"(a + b) c");
+        let (_, mut saved) = l.save_until(Token::RightParen);
+        let tok = l.next_useful();
+        saved.push(tok, l.span());
+        let mut combined = CombinedLexers::new(&mut saved, &mut l);
+        let mut ids = Vec::new();
+        loop {
+            match combined.next_useful() {
+                Token::Identifier(id) => ids.push(combined.resolve(id).to_string()),
+                Token::Eof => break,
+                _ => {}
+            }
+        }
+        assert_eq!(ids, vec!["a", "b", "c"]);
+    }
 }
@@ -0,0 +1,338 @@
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::borrow::Cow;
+use unicode_normalization::{is_nfc_quick, IsNormalized, UnicodeNormalization};
+use unicode_xid::UnicodeXID;
+
+use super::errors::LexerError;
+use super::lexer::{Kind, Lexer, TLexer, CHARS};
+use super::preprocessor::context::PreprocContext;
+use super::scan;
+
+/// A universal character name (\uXXXX, \UXXXXXXXX or \u{X...}) read in a buffer
+pub(crate) enum Ucn {
+    /// The code point and the length of the UCN
+    Valid(u32, usize),
+    /// A backslash followed by u or U but not by the expected hex digits
+    Incomplete,
+    /// Not a UCN
+    None,
+}
+
+impl Ucn {
+    /// Read the UCN at the start of buf
+    pub(crate) fn read(buf: &[u8]) -> Ucn {
+        let (start, n, delimited) = match buf {
+            [b'\\', b'u', b'{', ..] => (3, 8, true),
+            [b'\\', b'u', ..] => (2, 4, false),
+            [b'\\', b'U', ..] => (2, 8, false),
+            _ => return Ucn::None,
+        };
+
+        let mut code = 0u32;
+        let mut len = 0;
+        for c in buf.iter().skip(start) {
+            if let Some(d) = char::from(*c).to_digit(16) {
+                if len == n {
+                    if delimited {
+                        return Ucn::Incomplete;
+                    }
+                    break;
+                }
+                code = (code << 4) | d;
+                len += 1;
+            } else {
+                break;
+            }
+        }
+
+        if delimited {
+            if len != 0 && buf.get(start + len) == Some(&b'}') {
+                Ucn::Valid(code, start + len + 1)
+            } else {
+                Ucn::Incomplete
+            }
+        } else if len == n {
+            Ucn::Valid(code, start + n)
+        } else {
+            Ucn::Incomplete
+        }
+    }
+}
+
+/// Get the length of the UTF-8 sequence starting with the given byte
+#[inline(always)]
+fn utf8_len(c: u8) -> usize {
+    match c {
+        0xC2..=0xDF => 2,
+        0xE0..=0xEF => 3,
+        0xF0..=0xF4 => 4,
+        _ => 0,
+    }
+}
+
+/// Decode the UTF-8 char at the start of buf
+fn decode_utf8(buf: &[u8]) -> Option<(char, usize)> {
+    let len = utf8_len(*buf.first()?);
+    let bytes = buf.get(..len)?;
+    let s = std::str::from_utf8(bytes).ok()?;
+    s.chars().next().map(|c| (c, len))
+}
+
+/// Check if an identifier can start with the given char (C++23 and C23 rules)
+#[inline(always)]
+pub(crate) fn is_id_start(c: char) -> bool {
+    c == '_' || c.is_xid_start()
+}
+
+/// Check if an identifier can contain the given char (C++23 and C23 rules)
+#[inline(always)]
+pub(crate) fn is_id_continue(c: char) -> bool {
+    c.is_xid_continue()
+}
+
+/// Normalize an identifier (NFC) to have only one representation for a name
+pub(crate) fn normalize(id: String) -> String {
+    if id.is_ascii() || is_nfc_quick(id.chars()) == IsNormalized::Yes {
+        id
+    } else {
+        id.nfc().collect()
+    }
+}
+
+impl<'a, PC: PreprocContext> Lexer<'a, PC> {
+    /// Check if the backslash which has just been consumed starts a UCN
+    #[inline(always)]
+    pub(crate) fn is_after_ucn_start(&self) -> bool {
+        let buf = self.buf.slice_n(self.buf.pos() - 1, self.buf.rem());
+        matches!(Ucn::read(buf), Ucn::Valid(..))
+    }
+
+    /// Check if the next char is the beginning of an identifier containing non-ASCII chars or UCNs
+    #[inline(always)]
+    pub(crate) fn is_extended_id_start(&self) -> bool {
+        let buf = self.buf.remainder();
+        match buf.first() {
+            Some(b'\\') => matches!(Ucn::read(buf), Ucn::Valid(..)),
+            Some(c) => *c >= 0x80,
+            None => false,
+        }
+    }
+
+    /// Get the identifier (or a name after a # like a macro name) starting at the current position
+    pub(crate) fn get_name(&mut self) -> Cow<'_, str> {
+        if self.buf.has_char() {
+            let c = self.buf.next_char();
+            if (*unsafe { CHARS.get_unchecked(c as usize) } != Kind::NON && !c.is_ascii_digit())
+                || self.is_extended_id_start()
+            {
+                self.buf.inc();
+                return self.get_identifier_str();
+            }
+        }
+        Cow::Borrowed("")
+    }
+
+    /// Get the identifier (the first char has already been consumed)
+    pub(crate) fn get_identifier_str(&mut self) -> Cow<'_, str> {
+        let spos = self.buf.pos() - 1;
+        let first = self.buf.prev_char();
+        if first < 0x80 && first != b'\\' {
+            self.buf.inc_n(scan::identifier_len(self.buf.remainder()));
+
+            if !self.is_extended_id_start() {
+                // Only ASCII chars
+                return Cow::Borrowed(unsafe {
+                    std::str::from_utf8_unchecked(self.buf.slice(spos))
+                });
+            }
+        } else {
+            self.buf.dec();
+        }
+
+        let prefix = unsafe { std::str::from_utf8_unchecked(self.buf.slice(spos)) };
+        Cow::Owned(normalize(self.get_extended_identifier(prefix.to_string())))
+    }
+
+    /// Get the remaining part of an identifier containing UCNs or UTF-8 encoded chars
+    fn get_extended_identifier(&mut self, mut id: String) -> String {
+        while self.buf.has_char() {
+            let c = self.buf.next_char();
+            let (ch, len) = if c == b'\\' {
+                match Ucn::read(self.buf.remainder()) {
+                    Ucn::Valid(code, len) => {
+                        let ucn =
+                            unsafe { std::str::from_utf8_unchecked(&self.buf.remainder()[..len]) };
+                        if let Some(ch) = std::char::from_u32(code) {
+                            if ch.is_ascii() || ch.is_control() {
+                                self.errors.push(LexerError::UcnBasicChar {
+                                    sp: self.span(),
+                                    ucn: ucn.to_string(),
+                                });
+                            }
+                            (ch, len)
+                        } else {
+                            self.errors.push(LexerError::InvalidUcn {
+                                sp: self.span(),
+                                ucn: ucn.to_string(),
+                            });
+                            self.buf.inc_n(len);
+                            continue;
+                        }
+                    }
+                    Ucn::Incomplete | Ucn::None => break,
+                }
+            } else if c < 0x80 {
+                if *unsafe { CHARS.get_unchecked(c as usize) } == Kind::NON {
+                    break;
+                }
+                (char::from(c), 1)
+            } else if let Some((ch, len)) = decode_utf8(self.buf.remainder()) {
+                (ch, len)
+            } else {
+                self.errors
+                    .push(LexerError::InvalidUtf8 { sp: self.span() });
+                self.buf.inc();
+                continue;
+            };
+
+            if id.is_empty() {
+                if !is_id_continue(ch) {
+                    self.errors.push(LexerError::InvalidIdentifierChar {
+                        sp: self.span(),
+                        c: ch,
+                        start: false,
+                    });
+                    self.buf.inc_n(len);
+                    continue;
+                } else if !is_id_start(ch) {
+                    self.errors.push(LexerError::InvalidIdentifierChar {
+                        sp: self.span(),
+                        c: ch,
+                        start: true,
+                    });
+                }
+            } else if !is_id_continue(ch) {
+                break;
+            }
+
+            id.push(ch);
+            self.buf.inc_n(len);
+        }
+
+        id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::lexer::preprocessor::context::DefaultContext;
+    use crate::lexer::Token;
+    use crate::mk_id;
+    use crate::parser::names::Qualified;
+    use crate::parser::{Context, DeclarationListParser};
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_ucn_read() {
+        assert!(matches!(Ucn::read(b"\\u00e9abc"), Ucn::Valid(0xe9, 6)));
+        assert!(matches!(Ucn::read(b"\\U0001F600"), Ucn::Valid(0x1f600, 10)));
+        assert!(matches!(Ucn::read(b"\\u{1F600}x"), Ucn::Valid(0x1f600, 9)));
+        assert!(matches!(Ucn::read(b"\\u00e"), Ucn::Incomplete));
+        assert!(matches!(Ucn::read(b"\\u{}"), Ucn::Incomplete));
+        assert!(matches!(Ucn::read(b"\\n"), Ucn::None));
+    }
+
+    #[test]
+    fn test_identifier_unicode() {
+        let mut p = Lexer::<DefaultContext>::new("café x_é ñandú _ü 変数".as_bytes());
+        assert_eq!(p.next_token(), p.id("café"));
+        assert_eq!(p.next_token(), p.id("x_é"));
+        assert_eq!(p.next_token(), p.id("ñandú"));
+        assert_eq!(p.next_token(), p.id("_ü"));
+        assert_eq!(p.next_token(), p.id("変数"));
+        assert_eq!(p.next_token(), Token::Eof);
+        assert!(p.get_errors().is_empty());
+    }
+
+    #[test]
+    fn test_identifier_ucn() {
+        let mut p =
+            Lexer::<DefaultContext>::new(b"caf\\u00e9 \\u00e9t\\u00e9 \\U0001D4AEx a\\u{E9}b int");
+        assert_eq!(p.next_token(), p.id("café"));
+        assert_eq!(p.next_token(), p.id("été"));
+        assert_eq!(p.next_token(), p.id("𝒮x"));
+        assert_eq!(p.next_token(), p.id("aéb"));
+        assert_eq!(p.next_token(), Token::Int);
+        assert!(p.get_errors().is_empty());
+    }
+
+    #[test]
+    fn test_identifier_normalization() {
+        // Decomposed e + U+0301 and precomposed U+00E9
+        let mut p = Lexer::<DefaultContext>::new("cafe\u{301} caf\u{e9}".as_bytes());
+        assert_eq!(p.next_token(), p.id("caf\u{e9}"));
+        assert_eq!(p.next_token(), p.id("caf\u{e9}"));
+
+        let mut l = Lexer::<DefaultContext>::new("int cafe\u{301}; int x = caf\\u00e9;".as_bytes());
+        let p = DeclarationListParser::new(&mut l);
+        let mut context = Context::default();
+        p.parse(None, &mut context).unwrap();
+        assert!(context.search(Some(&mk_id!("caf\u{e9}"))).is_some());
+    }
+
+    #[test]
+    fn test_identifier_macro() {
+        let mut p = Lexer::<DefaultContext>::new(
+            "#define caf\\u00e9 1\n#ifdef cafe\u{301}\ncafé\n#endif\n".as_bytes(),
+        );
+        let tokens: Vec<_> = std::iter::from_fn(|| match p.next_token() {
+            Token::Eof => None,
+            tok => Some(tok),
+        })
+        .filter(|tok| matches!(tok, Token::Identifier(_) | Token::LiteralInteger(_)))
+        .collect();
+        assert_eq!(tokens, vec![Token::from_number("1").unwrap()]);
+    }
+
+    #[test]
+    fn test_identifier_errors() {
+        let mut p = Lexer::<DefaultContext>::new(
+            b"a\\u0041 b\\u00 c\\uD800d \xff e \xe2\x80\x9cf\xe2\x80\x9d \xcc\x81g",
+        );
+        assert_eq!(p.next_token(), p.id("aA"));
+        assert_eq!(p.next_token(), p.id("b"));
+        assert_eq!(p.next_token(), Token::Backslash);
+        assert_eq!(p.next_token(), p.id("u00"));
+        assert_eq!(p.next_token(), p.id("cd"));
+        assert_eq!(p.next_token(), p.id("e"));
+        assert_eq!(p.next_token(), p.id("f"));
+        assert_eq!(p.next_token(), p.id("\u{301}g"));
+        assert_eq!(p.next_token(), Token::Eof);
+
+        let errors: Vec<_> = p
+            .get_errors()
+            .iter()
+            .map(|e| e.stringly().message)
+            .collect();
+        assert_eq!(
+            errors,
+            vec![
+                "universal character name \\u0041 designates a basic or control character"
+                    .to_string(),
+                "incomplete universal character name; treating as '\\' followed by identifier"
+                    .to_string(),
+                "universal character name \\uD800 doesn't designate a valid character".to_string(),
+                "invalid UTF-8 sequence".to_string(),
+                "character <U+201C> not allowed in an identifier".to_string(),
+                "character <U+201D> not allowed in an identifier".to_string(),
+                "character <U+0301> not allowed at the start of an identifier".to_string(),
+            ]
+        );
+    }
+}
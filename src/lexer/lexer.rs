@@ -5,21 +5,23 @@
 
 use hashbrown::HashMap;
 use lazy_static::lazy_static;
-use std::fs::{self, File};
-use std::io::Read;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
 use super::buffer::{Buffer, BufferData, Position};
+use super::comment::DocComments;
+use super::dialect::{Dialect, Keywords};
 use super::errors::LexerError;
 use super::extra::SavedLexer;
+use super::numeric::{FloatNumber, IntNumber};
 use super::preprocessor::cache::IfCache;
 use super::preprocessor::context::PreprocContext;
 use super::preprocessor::include::PathIndex;
-use super::source::{FileId, SourceMutex};
+use super::source::{self, FileId, SourceMutex};
 use super::string::StringType;
+use super::symbol::{Symbol, SymbolTable};
 use crate::args;
-use crate::errors::Span;
+use crate::errors::{FileError, Span};
 
 #[derive(PartialEq)]
 pub(super) enum Kind {
@@ -62,22 +64,22 @@ pub(super) const CHARS: [Kind; 256] = [
     Kind::KEY, Kind::KEY, Kind::KEY, Kind::KEY, Kind::KEY, Kind::KEY, Kind::KEY, Kind::KEY, //
     // 78  x   79  y      7A  z      7B  {      7C  |      7D  }      7E  ~      7F DEL
     Kind::KEY, Kind::KEY, Kind::KEY, Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, //
-    Kind::IDN, Kind::IDN, Kind::IDN, Kind::IDN, Kind::IDN, Kind::IDN, Kind::IDN, Kind::IDN, //
-    Kind::IDN, Kind::IDN, Kind::IDN, Kind::IDN, Kind::IDN, Kind::IDN, Kind::IDN, Kind::IDN, //
-    Kind::IDN, Kind::IDN, Kind::IDN, Kind::IDN, Kind::IDN, Kind::IDN, Kind::IDN, Kind::IDN, //
-    Kind::IDN, Kind::IDN, Kind::IDN, Kind::IDN, Kind::IDN, Kind::IDN, Kind::IDN, Kind::IDN, //
-    Kind::IDN, Kind::IDN, Kind::IDN, Kind::IDN, Kind::IDN, Kind::IDN, Kind::IDN, Kind::IDN, //
-    Kind::IDN, Kind::IDN, Kind::IDN, Kind::IDN, Kind::IDN, Kind::IDN, Kind::IDN, Kind::IDN, //
-    Kind::IDN, Kind::IDN, Kind::IDN, Kind::IDN, Kind::IDN, Kind::IDN, Kind::IDN, Kind::IDN, //
-    Kind::IDN, Kind::IDN, Kind::IDN, Kind::IDN, Kind::IDN, Kind::IDN, Kind::IDN, Kind::IDN, //
-    Kind::IDN, Kind::IDN, Kind::IDN, Kind::IDN, Kind::IDN, Kind::IDN, Kind::IDN, Kind::IDN, //
-    Kind::IDN, Kind::IDN, Kind::IDN, Kind::IDN, Kind::IDN, Kind::IDN, Kind::IDN, Kind::IDN, //
-    Kind::IDN, Kind::IDN, Kind::IDN, Kind::IDN, Kind::IDN, Kind::IDN, Kind::IDN, Kind::IDN, //
-    Kind::IDN, Kind::IDN, Kind::IDN, Kind::IDN, Kind::IDN, Kind::IDN, Kind::IDN, Kind::IDN, //
-    Kind::IDN, Kind::IDN, Kind::IDN, Kind::IDN, Kind::IDN, Kind::IDN, Kind::IDN, Kind::IDN, //
-    Kind::IDN, Kind::IDN, Kind::IDN, Kind::IDN, Kind::IDN, Kind::IDN, Kind::IDN, Kind::IDN, //
-    Kind::IDN, Kind::IDN, Kind::IDN, Kind::IDN, Kind::IDN, Kind::IDN, Kind::IDN, Kind::IDN, //
-    Kind::IDN, Kind::IDN, Kind::IDN, Kind::IDN, Kind::IDN, Kind::IDN, Kind::IDN, Kind::IDN, //
+    Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, //
+    Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, //
+    Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, //
+    Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, //
+    Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, //
+    Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, //
+    Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, //
+    Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, //
+    Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, //
+    Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, //
+    Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, //
+    Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, //
+    Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, //
+    Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, //
+    Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, //
+    Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, Kind::NON, //
 ];
 
 macro_rules! mk_maps {
@@ -118,123 +120,168 @@ mk_maps! {
     "undef", PreprocUndef
 }
 
-mk_maps! {
-    CPP_KEYWORDS, cpp_kw_to_str,
-    "alignas", Alignas,
-    "alignof", Alignof,
-    "and", AndKw,
-    "and_eq", AndEq,
-    "asm", Asm,
-    "auto", Auto,
-    "bitand", BitAnd,
-    "bitor", BitOr,
-    "bool", Bool,
-    "break", Break,
-    "case", Case,
-    "catch", Catch,
-    "__cdecl", Cdecl,
-    "char", Char,
-    "char8_t", Char8T,
-    "char16_t", Char16T,
-    "char32_t", Char32T,
-    "class", Class,
-    "__clrcall", Clrcall,
-    "co_await", CoAwait,
-    "co_return", CoReturn,
-    "co_yield", CoYield,
-    "compl", Compl,
-    "concept", Concept,
-    "const", Const,
-    "consteval", Consteval,
-    "constexpr", Constexpr,
-    "constinit", Constinit,
-    "const_cast", ConstCast,
-    "continue", Continue,
-    "_Complex", Complex,
-    "decltype", Decltype,
-    "default", Default,
-    "delete", Delete,
-    "do", Do,
-    "double", Double,
-    "dynamic_cast", DynamicCast,
-    "else", Else,
-    "endif", Endif,
-    "enum", Enum,
-    "explicit", Explicit,
-    "export", Export,
-    "extern", Extern,
-    "false", False,
-    "__fastcall", Fastcall,
-    "final", Final,
-    "float", Float,
-    "for", For,
-    "friend", Friend,
-    "__func__", Func,
-    "__FUNCTION__", Function,
-    "goto", Goto,
-    "if", If,
-    "_Imaginary", Imaginary,
-    "import", Import,
-    "inline", Inline,
-    "int", Int,
-    "long", Long,
-    "module", Module,
-    "mutable", Mutable,
-    "namespace", Namespace,
-    "new", New,
-    "noexcept", Noexcept,
-    "not", NotKw,
-    "not_eq", NotEq,
-    "nullptr", Nullptr,
-    "operator", Operator,
-    "or", OrKw,
-    "or_eq", OrEq,
-    "override", Override,
-    "__PRETTY_FUNCTION__", PrettyFunction,
-    "private", Private,
-    "protected", Protected,
-    "public", Public,
-    "requires", Requires,
-    "register", Register,
-    "reinterpret_cast", ReinterpretCast,
-    "restrict", Restrict,
-    "return", Return,
-    "__restrict", MSRestrict,
-    "short", Short,
-    "signed", Signed,
-    "sizeof", Sizeof,
-    "__sptr", MSSptr,
-    "static", Static,
-    "static_assert", StaticAssert,
-    "_Static_assert", CStaticAssert,
-    "static_cast", StaticCast,
-    "__stdcall", Stdcall,
-    "struct", Struct,
-    "switch", Switch,
-    "template", Template,
-    "this", This,
-    "__thiscall", Thiscall,
-    "thread_local", ThreadLocal,
-    "throw", Throw,
-    "true", True,
-    "try", Try,
-    "typedef", Typedef,
-    "typeid", Typeid,
-    "typename", Typename,
-    "_unaligned", MS1Unaligned,
-    "__unaligned", MSUnaligned,
-    "union", Union,
-    "unsigned", Unsigned,
-    "__uptr", MSUptr,
-    "using", Using,
-    "__vectorcall", Vectorcall,
-    "virtual", Virtual,
-    "void", Void,
-    "volatile", Volatile,
-    "wchar_t", WcharT,
-    "while", While,
-    "xor", XorKw,
-    "xor_eq", XorEq
+macro_rules! mk_keywords {
+    ( $name: ident, $( $tok_val: expr, $tok_name: path, $( $set: ident )|+ ), *) => {
+        lazy_static! {
+            static ref $name: HashMap<&'static str, (Token, Keywords)> = {
+                use Token::*;
+                let mut map = HashMap::new();
+                $(map.insert($tok_val, ($tok_name, $(Keywords::$set)|+));)*
+                    map
+            };
+        }
+    }
+}
+
+// final, override, import and module are identifiers with a special meaning
+// in some contexts so the parser handles them.
+mk_keywords! {
+    KEYWORDS,
+    "alignas", Alignas, C23 | CPP11,
+    "alignof", Alignof, C23 | CPP11,
+    "__alignof", Alignof, ALL,
+    "__alignof__", Alignof, ALL,
+    "_Alignas", Alignas, ALL,
+    "_Alignof", Alignof, ALL,
+    "and", AndKw, CPP,
+    "and_eq", AndEq, CPP,
+    "asm", Asm, CPP | GNU,
+    "__asm", Asm, ALL,
+    "__asm__", Asm, ALL,
+    "_Atomic", Atomic, ALL,
+    "auto", Auto, ALL,
+    "bitand", BitAnd, CPP,
+    "bitor", BitOr, CPP,
+    "bool", Bool, C23 | CPP,
+    "_Bool", Bool, ALL,
+    "break", Break, ALL,
+    "case", Case, ALL,
+    "catch", Catch, CPP,
+    "__cdecl", Cdecl, ALL,
+    "char", Char, ALL,
+    "char8_t", Char8T, CPP20,
+    "char16_t", Char16T, CPP11,
+    "char32_t", Char32T, CPP11,
+    "class", Class, CPP,
+    "__clrcall", Clrcall, ALL,
+    "co_await", CoAwait, CPP20,
+    "co_return", CoReturn, CPP20,
+    "co_yield", CoYield, CPP20,
+    "compl", Compl, CPP,
+    "concept", Concept, CPP20,
+    "const", Const, ALL,
+    "__const", Const, ALL,
+    "__const__", Const, ALL,
+    "consteval", Consteval, CPP20,
+    "constexpr", Constexpr, C23 | CPP11,
+    "constinit", Constinit, CPP20,
+    "const_cast", ConstCast, CPP,
+    "continue", Continue, ALL,
+    "_Complex", Complex, ALL,
+    "__complex__", Complex, ALL,
+    "decltype", Decltype, CPP11,
+    "__decltype", Decltype, CPP,
+    "default", Default, ALL,
+    "delete", Delete, CPP,
+    "do", Do, ALL,
+    "double", Double, ALL,
+    "dynamic_cast", DynamicCast, CPP,
+    "else", Else, ALL,
+    "enum", Enum, ALL,
+    "explicit", Explicit, CPP,
+    "export", Export, CPP,
+    "extern", Extern, ALL,
+    "false", False, C23 | CPP,
+    "__fastcall", Fastcall, ALL,
+    "float", Float, ALL,
+    "for", For, ALL,
+    "__forceinline", Inline, MS,
+    "friend", Friend, CPP,
+    "__func__", Func, ALL,
+    "__FUNCTION__", Function, ALL,
+    "_Generic", Generic, ALL,
+    "goto", Goto, ALL,
+    "if", If, ALL,
+    "_Imaginary", Imaginary, ALL,
+    "inline", Inline, C99 | CPP,
+    "__inline", Inline, ALL,
+    "__inline__", Inline, ALL,
+    "int", Int, ALL,
+    "__int8", MSInt8, MS,
+    "__int16", MSInt16, MS,
+    "__int32", MSInt32, MS,
+    "__int64", MSInt64, MS,
+    "long", Long, ALL,
+    "mutable", Mutable, CPP,
+    "namespace", Namespace, CPP,
+    "new", New, CPP,
+    "noexcept", Noexcept, CPP11,
+    "_Noreturn", Noreturn, ALL,
+    "not", NotKw, CPP,
+    "not_eq", NotEq, CPP,
+    "nullptr", Nullptr, C23 | CPP11,
+    "operator", Operator, CPP,
+    "or", OrKw, CPP,
+    "or_eq", OrEq, CPP,
+    "_Pragma", PragmaOperator, ALL,
+    "__pragma", MSPragma, MS,
+    "__PRETTY_FUNCTION__", PrettyFunction, ALL,
+    "private", Private, CPP,
+    "protected", Protected, CPP,
+    "public", Public, CPP,
+    "requires", Requires, CPP20,
+    "register", Register, ALL,
+    "reinterpret_cast", ReinterpretCast, CPP,
+    "restrict", Restrict, C99,
+    "return", Return, ALL,
+    "__restrict", MSRestrict, ALL,
+    "__restrict__", MSRestrict, ALL,
+    "short", Short, ALL,
+    "signed", Signed, ALL,
+    "__signed", Signed, ALL,
+    "__signed__", Signed, ALL,
+    "sizeof", Sizeof, ALL,
+    "__sptr", MSSptr, ALL,
+    "static", Static, ALL,
+    "static_assert", StaticAssert, C23 | CPP11,
+    "_Static_assert", CStaticAssert, ALL,
+    "static_cast", StaticCast, CPP,
+    "__stdcall", Stdcall, ALL,
+    "struct", Struct, ALL,
+    "switch", Switch, ALL,
+    "template", Template, CPP,
+    "this", This, CPP,
+    "__thiscall", Thiscall, ALL,
+    "thread_local", ThreadLocal, C23 | CPP11,
+    "_Thread_local", ThreadLocal, ALL,
+    "__thread", ThreadLocal, ALL,
+    "throw", Throw, CPP,
+    "true", True, C23 | CPP,
+    "try", Try, CPP,
+    "typedef", Typedef, ALL,
+    "typeid", Typeid, CPP,
+    "typename", Typename, CPP,
+    "typeof", Typeof, C23 | GNU,
+    "__typeof", Typeof, ALL,
+    "__typeof__", Typeof, ALL,
+    "typeof_unqual", TypeofUnqual, C23,
+    "__typeof_unqual__", TypeofUnqual, ALL,
+    "_unaligned", MS1Unaligned, MS,
+    "__unaligned", MSUnaligned, ALL,
+    "union", Union, ALL,
+    "unsigned", Unsigned, ALL,
+    "__uptr", MSUptr, ALL,
+    "using", Using, CPP,
+    "__vectorcall", Vectorcall, ALL,
+    "virtual", Virtual, CPP,
+    "void", Void, ALL,
+    "volatile", Volatile, ALL,
+    "__volatile", Volatile, ALL,
+    "__volatile__", Volatile, ALL,
+    "wchar_t", WcharT, CPP,
+    "while", While, ALL,
+    "xor", XorKw, CPP,
+    "xor_eq", XorEq, CPP
 }
 
 // TODO: group token by kind, for example put all the literal together
@@ -278,30 +325,21 @@ pub enum Token {
     MinusEqual,
     Arrow,
     ArrowStar,
-    LiteralDouble(f64),
-    LiteralFloat(f64),
-    LiteralLongDouble(f64),
-    LiteralFloatUD(Box<(f64, String)>),
+    LiteralFloating(Box<FloatNumber>),
     Dot,
     DotStar,
     Ellipsis,
-    LiteralInt(u64),
-    LiteralUInt(u64),
-    LiteralLong(u64),
-    LiteralLongLong(u64),
-    LiteralULong(u64),
-    LiteralULongLong(u64),
-    LiteralIntUD(Box<(u64, String)>),
-    LiteralString(String),
-    LiteralLString(String),
-    LiteralUString(String),
-    LiteralUUString(String),
-    LiteralU8String(String),
-    LiteralRString(String),
-    LiteralLRString(String),
-    LiteralURString(String),
-    LiteralUURString(String),
-    LiteralU8RString(String),
+    LiteralInteger(Box<IntNumber>),
+    LiteralString(Box<str>),
+    LiteralLString(Box<str>),
+    LiteralUString(Box<str>),
+    LiteralUUString(Box<str>),
+    LiteralU8String(Box<str>),
+    LiteralRString(Box<str>),
+    LiteralLRString(Box<str>),
+    LiteralURString(Box<str>),
+    LiteralUURString(Box<str>),
+    LiteralU8RString(Box<str>),
     LiteralStringUD(Box<(String, String)>),
     LiteralLStringUD(Box<(String, String)>),
     LiteralUStringUD(Box<(String, String)>),
@@ -328,7 +366,7 @@ pub enum Token {
     RightShiftEqual,
     Question,
     At,
-    Identifier(String),
+    Identifier(Symbol),
     LeftBrack,
     DoubleLeftBrack,
     Backslash,
@@ -347,6 +385,7 @@ pub enum Token {
     AndKw,
     AndEq,
     Asm,
+    Atomic,
     Auto,
     BitAnd,
     BitOr,
@@ -380,39 +419,37 @@ pub enum Token {
     Double,
     DynamicCast,
     Else,
-    Endif,
     Enum,
     Explicit,
     Export,
     Extern,
     False,
     Fastcall,
-    Final,
     Float,
     For,
     Friend,
     Func,
     Function,
+    Generic,
     Goto,
     If,
     Imaginary,
-    Import,
     Inline,
     Int,
     Long,
-    Module,
     Mutable,
     Namespace,
     New,
     Noexcept,
+    Noreturn,
     NotKw,
     NotEq,
     Nullptr,
     Operator,
     OrKw,
     OrEq,
-    Override,
     PrettyFunction,
+    PragmaOperator,
     Private,
     Protected,
     Public,
@@ -441,6 +478,8 @@ pub enum Token {
     Typedef,
     Typeid,
     Typename,
+    Typeof,
+    TypeofUnqual,
     Union,
     Unsigned,
     Using,
@@ -470,6 +509,11 @@ pub enum Token {
     MSSptr,
     MS1Unaligned,
     MSUnaligned,
+    MSInt8,
+    MSInt16,
+    MSInt32,
+    MSInt64,
+    MSPragma,
 }
 
 #[derive(Clone, Debug, Copy, Default)]
@@ -490,7 +534,29 @@ impl Location {
 }
 
 impl Token {
-    pub(crate) fn get_string(self) -> Option<String> {
+    pub(crate) fn from_preproc_keyword(name: &str) -> Option<Token> {
+        PREPROC_KEYWORDS.get(name).cloned()
+    }
+
+    /// Check if the token is an alternative token (and, or, not, ...)
+    pub fn is_alt_token(&self) -> bool {
+        matches!(
+            self,
+            Token::AndKw
+                | Token::AndEq
+                | Token::BitAnd
+                | Token::BitOr
+                | Token::Compl
+                | Token::NotKw
+                | Token::NotEq
+                | Token::OrKw
+                | Token::OrEq
+                | Token::XorKw
+                | Token::XorEq
+        )
+    }
+
+    pub(crate) fn get_string(self) -> Option<Box<str>> {
         match self {
             Self::LiteralString(s)
             | Self::LiteralLString(s)
@@ -515,11 +581,18 @@ pub trait TLexer {
 
         // TODO: tune the capacity
         let mut stole = Vec::with_capacity(64);
+        let mut names = HashMap::default();
         loop {
             let tok = self.next_useful();
             if (tok == term && level == 0) || tok == Token::Eof {
-                stole.push(tok.clone());
-                return (tok, SavedLexer::new(stole));
+                stole.push((tok.clone(), self.span()));
+                return (tok, SavedLexer::new(stole, names));
+            }
+
+            if let Token::Identifier(id) = tok {
+                names
+                    .entry(id)
+                    .or_insert_with(|| Box::from(self.resolve(id)));
             }
 
             match tok {
@@ -535,19 +608,44 @@ pub trait TLexer {
                 _ => {}
             }
 
-            stole.push(tok);
+            let sp = self.span();
+            stole.push((tok, sp));
         }
     }
 
     fn span(&self) -> Span;
+
+    /// Get the string of an identifier produced by this lexer
+    fn resolve(&self, sym: Symbol) -> &str;
+
+    /// Get the documentation comments (///, /** */, ...) written before the last token
+    fn take_doc(&mut self) -> Option<String> {
+        None
+    }
+
+    /// Get the trailing documentation comments (///<, /**< */, ...) written before the last token
+    /// or just after it on the same line: they document the declaration before
+    fn take_trailing_doc(&mut self) -> Option<String> {
+        None
+    }
 }
 
 pub struct Lexer<'a, PC: PreprocContext> {
     pub(crate) buf: Buffer<'a>,
     pub(crate) context: PC,
-    pub(crate) comment: Option<&'a [u8]>,
+    // The bounds of the last comment in the current buffer
+    pub(crate) comment: Option<(usize, usize)>,
     pub(crate) start: Location,
     pub(crate) errors: Vec<LexerError>,
+    pub(crate) std: args::LangStandard,
+    pub(crate) dialect: Dialect,
+    // In raw mode, the macros aren't expanded out of the directives
+    // and the included files aren't entered (see LosslessLexer)
+    pub(crate) raw: bool,
+    pub(crate) in_directive: bool,
+    pub(crate) docs: DocComments,
+    // The identifiers produced by this lexer
+    pub(crate) symbols: SymbolTable,
 }
 
 impl<'a, PC: PreprocContext> TLexer for Lexer<'a, PC> {
@@ -556,21 +654,38 @@ impl<'a, PC: PreprocContext> TLexer for Lexer<'a, PC> {
             let tok = self.next_token();
             //eprintln!("{:?} -- {:?} -- {:?}", tok, self.span(), self.context.get_path(self.buf.get_source_id().unwrap()));
             match tok {
-                Token::Comment | Token::Eol => {}
+                Token::Eol => {}
+                Token::Comment => self.add_doc_comment(),
                 _ => {
+                    self.docs.set_token();
+                    self.peek_trailing_doc();
                     return tok;
                 }
             }
         }
     }
 
+    fn take_doc(&mut self) -> Option<String> {
+        self.docs.take_leading()
+    }
+
+    fn take_trailing_doc(&mut self) -> Option<String> {
+        self.docs.take_trailing()
+    }
+
     fn span(&self) -> Span {
+        let end = self.location();
         Span {
             file: self.buf.get_source_id(),
             start: self.start,
-            end: self.location(),
+            end,
+            presumed: self.buf.get_presumed_span(self.start.line, end.line),
         }
     }
+
+    fn resolve(&self, sym: Symbol) -> &str {
+        self.symbols.resolve(sym)
+    }
 }
 
 macro_rules! get_operator {
@@ -625,21 +740,33 @@ macro_rules! get_basic_operator {
 impl<'a, PC: PreprocContext> Lexer<'a, PC> {
     pub fn new(buf: &'a [u8]) -> Self {
         Self {
-            buf: Buffer::new(buf.to_vec(), FileId(0), PathIndex(0)),
+            buf: Buffer::new(buf, FileId(0), PathIndex(0)),
             context: PC::default(),
             comment: None,
             start: Location::dummy(),
             errors: Vec::new(),
+            std: args::LangStandard::default_for(args::Language::CPP),
+            dialect: Dialect::from(args::LangStandard::default_for(args::Language::CPP)),
+            raw: false,
+            in_directive: false,
+            docs: DocComments::default(),
+            symbols: SymbolTable::default(),
         }
     }
 
     pub fn new_with_context(buf: &'a [u8], source_id: FileId, context: PC) -> Self {
         Self {
-            buf: Buffer::new(buf.to_vec(), source_id, PathIndex(0)),
+            buf: Buffer::new(buf, source_id, PathIndex(0)),
             context,
             comment: None,
             start: Location::dummy(),
             errors: Vec::new(),
+            std: args::LangStandard::default_for(args::Language::CPP),
+            dialect: Dialect::from(args::LangStandard::default_for(args::Language::CPP)),
+            raw: false,
+            in_directive: false,
+            docs: DocComments::default(),
+            symbols: SymbolTable::default(),
         }
     }
 
@@ -648,27 +775,32 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
         source: SourceMutex,
         if_cache: Arc<IfCache>,
         opt: args::PreprocOptions,
-    ) -> Self {
-        let path = PathBuf::from(file); //std::fs::canonicalize(file).unwrap();
-                                        //let path = std::fs::canonicalize(file).unwrap();
-        let file_size = fs::metadata(&path).map_or(1024 * 1024, |m| m.len() as usize);
-        let mut file = File::open(&path).unwrap();
-        let mut data = Vec::with_capacity(file_size + 1);
-        file.read_to_end(&mut data).unwrap();
+    ) -> Result<Self, FileError> {
+        let path = PathBuf::from(file);
+        let (source_id, content) =
+            source::load_file(&source, &path).map_err(|err| FileError::Io {
+                path: path.clone(),
+                err,
+            })?;
 
         let mut context = PC::new_with_if_cache(if_cache);
         context.set_source(source);
-        let source_id = context.get_id(&path);
-        let mut buffer = Buffer::new(data, source_id, PathIndex(0));
+        let data = BufferData::decode(content, opt.charset, source_id, PathIndex(0));
+        let mut buffer = Buffer::from_data(data);
+        let mut dialect = Dialect::new(opt.std, opt.trigraphs);
+        if opt.driver.is_msvc() {
+            dialect.keywords |= Keywords::MS;
+        }
+        buffer.set_trigraphs(dialect.trigraphs);
 
-        context.set_sys_paths(&opt.sys_paths);
+        context.set_include_paths(&opt.get_include_paths());
+        context.set_charset(opt.charset);
+        if let Some(caps) = opt.capabilities.as_ref() {
+            context.set_capabilities(caps.clone());
+        }
 
         let mut cl = Vec::with_capacity(16384);
-        if opt.lang == args::Language::CPP {
-            // TODO: be more smart here
-            cl.extend_from_slice(b"#define __cplusplus 201703L\n");
-        }
-        for mac in opt.def.iter() {
+        for mac in opt.get_std_defined().iter().chain(opt.def.iter()) {
             match mac {
                 args::Macro::Defined((name, data)) => {
                     cl.extend_from_slice(b"#define ");
@@ -685,10 +817,46 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
             }
         }
 
-        for inc in opt.includes.iter() {
+        let mut errors = Vec::new();
+        if !opt.imacros.is_empty() {
+            // -imacros: the files are preprocessed before the -include ones
+            // but only the macros are kept, so just drop all the tokens
+            Self::add_includes(&mut cl, &opt.imacros, &opt.current_dir);
+            let mut lexer = Lexer::<PC>::new_with_context(&cl, FileId(0), context);
+            lexer.set_standard(opt.std);
+            lexer.set_dialect(dialect);
+            lexer.consume_all();
+            errors = lexer.errors;
+            context = lexer.context;
+            cl.clear();
+        }
+
+        Self::add_includes(&mut cl, &opt.includes, &opt.current_dir);
+
+        if !cl.is_empty() {
+            buffer.add_buffer(BufferData::new(cl, FileId(0), PathIndex(0)));
+        }
+
+        Ok(Self {
+            buf: buffer,
+            context,
+            comment: None,
+            start: Location::dummy(),
+            errors,
+            std: opt.std,
+            dialect,
+            raw: false,
+            in_directive: false,
+            docs: DocComments::default(),
+            symbols: SymbolTable::default(),
+        })
+    }
+
+    fn add_includes(cl: &mut Vec<u8>, includes: &[String], current_dir: &Path) {
+        for inc in includes.iter() {
             let path = PathBuf::from(inc);
             if path.is_relative() {
-                let p = opt.current_dir.join(path);
+                let p = current_dir.join(path);
                 if p.exists() {
                     cl.extend_from_slice(b"#include \"");
                     cl.extend_from_slice(p.to_str().unwrap().as_bytes());
@@ -702,22 +870,12 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
             cl.push(b'\"');
             cl.push(b'\n');
         }
-
-        if !cl.is_empty() {
-            buffer.add_buffer(BufferData::new(cl, FileId(0), PathIndex(0)));
-        }
-
-        Self {
-            buf: buffer,
-            context,
-            comment: None,
-            start: Location::dummy(),
-            errors: Vec::new(),
-        }
     }
 
-    pub fn get_comment(&self) -> &Option<&'a [u8]> {
-        &self.comment
+    /// The last comment read (valid until the next token)
+    pub fn get_comment(&self) -> Option<&[u8]> {
+        self.comment
+            .map(|(start, end)| self.buf.slice_p(start, end))
     }
 
     pub fn get_context(&self) -> &PC {
@@ -728,6 +886,26 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
         &self.errors
     }
 
+    pub fn get_standard(&self) -> args::LangStandard {
+        self.std
+    }
+
+    /// Set the language standard (and the dialect which depends on it)
+    pub fn set_standard(&mut self, std: args::LangStandard) {
+        self.std = std;
+        self.set_dialect(Dialect::from(std));
+    }
+
+    pub fn get_dialect(&self) -> Dialect {
+        self.dialect
+    }
+
+    /// Set the lexical features: the trigraphs are replaced in the buffers which haven't been read yet
+    pub fn set_dialect(&mut self, dialect: Dialect) {
+        self.dialect = dialect;
+        self.buf.set_trigraphs(dialect.trigraphs);
+    }
+
     pub fn consume_tokens(&mut self, n: usize) {
         for _ in 0..n {
             self.next_token();
@@ -760,35 +938,61 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
         );
     }
 
+    #[inline(always)]
+    fn can_expand(&self) -> bool {
+        !self.raw || self.in_directive
+    }
+
     pub(crate) fn get_identifier(&mut self) -> Option<Token> {
-        let id = self.get_identifier_str();
-        if !self.buf.preproc_use() && self.macro_eval(id) {
+        let id = self.get_identifier_sym();
+        if self.symbols.resolve(id).is_empty() {
+            // Only invalid chars have been skipped
+            return None;
+        }
+
+        if !self.buf.preproc_use() && self.can_expand() && self.macro_eval_sym(id) {
             self.buf.switch_to_preproc();
             None
         } else {
-            Some(Token::Identifier(id.to_string()))
+            Some(Token::Identifier(id))
         }
     }
 
-    pub(crate) fn get_identifier_str(&mut self) -> &'a str {
-        let spos = self.buf.pos() - 1;
-        loop {
-            if self.buf.has_char() {
-                let c = self.buf.next_char();
-                let kind = unsafe { CHARS.get_unchecked(c as usize) };
-                if *kind == Kind::NON {
-                    break;
-                }
-                self.buf.inc();
-            } else {
-                break;
-            }
-        }
+    /// Get the identifier starting at the previous char from the symbol table
+    fn get_identifier_sym(&mut self) -> Symbol {
+        // The identifier may borrow the buffer so the table is moved out while interning
+        let mut symbols = std::mem::take(&mut self.symbols);
+        let id = symbols.intern(&self.get_identifier_str());
+        self.symbols = symbols;
+        id
+    }
+
+    /// Get the identifier token for a name (the tests compare the tokens with it)
+    #[cfg(test)]
+    pub(crate) fn id(&mut self, name: &str) -> Token {
+        Token::Identifier(self.symbols.intern(name))
+    }
 
-        unsafe { std::str::from_utf8_unchecked(&self.buf.slice(spos)) }
+    /// Expand the macro named by an identifier (if any)
+    fn macro_eval_sym(&mut self, id: Symbol) -> bool {
+        // The name is borrowed from the table which is moved out during the expansion:
+        // nothing is interned while the arguments are read
+        let symbols = std::mem::take(&mut self.symbols);
+        let res = self.macro_eval(symbols.resolve(id));
+        debug_assert!(self.symbols.is_empty());
+        self.symbols = symbols;
+        res
     }
 
     pub(crate) fn get_preproc_keyword(&mut self, pos: Position) -> Token {
+        if self.buf.has_char() && self.buf.next_char().is_ascii_digit() {
+            // # 123 "foo.h" is a linemarker
+            self.in_directive = true;
+            self.get_line_directive();
+            self.in_directive = false;
+            return Token::PreprocLine;
+        }
+
         let spos = self.buf.pos();
         loop {
             if self.buf.has_char() {
@@ -803,20 +1007,24 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
             }
         }
 
-        let id = unsafe { std::str::from_utf8_unchecked(&self.buf.slice(spos)) };
+        let id = unsafe { std::str::from_utf8_unchecked(self.buf.slice(spos)) };
         if let Some(keyword) = PREPROC_KEYWORDS.get(id) {
-            self.preproc_parse(keyword.clone(), pos)
+            self.in_directive = true;
+            let tok = self
+                .preproc_parse(keyword.clone(), pos)
                 .unwrap_or_else(|error| {
                     self.errors.push(error.clone());
                     eprintln!("ERRRRRRRRRRor {:?}", error);
                     Token::Eof
-                })
+                });
+            self.in_directive = false;
+            tok
         } else {
-            Token::Identifier(id.to_string())
+            Token::Identifier(self.symbols.intern(id))
         }
     }
 
-    pub(crate) fn get_preproc_name(&mut self) -> &'a [u8] {
+    pub(crate) fn get_preproc_name(&mut self) -> &[u8] {
         let spos = self.buf.pos();
         loop {
             if self.buf.has_char() {
@@ -831,33 +1039,33 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
             }
         }
 
-        &self.buf.slice(spos)
+        self.buf.slice(spos)
     }
 
     pub(crate) fn get_identifier_or_keyword(&mut self) -> Option<Token> {
-        let spos = self.buf.pos() - 1;
-        loop {
-            if self.buf.has_char() {
-                let c = self.buf.next_char();
-                let kind = unsafe { CHARS.get_unchecked(c as usize) };
-                if *kind == Kind::NON {
-                    break;
-                }
-
-                self.buf.inc();
-            } else {
-                break;
-            }
-        }
-
-        let id = unsafe { std::str::from_utf8_unchecked(&self.buf.slice(spos)) };
-        if !self.buf.preproc_use() && self.macro_eval(id) {
+        let id = self.get_identifier_sym();
+        if !self.buf.preproc_use() && self.can_expand() && self.macro_eval_sym(id) {
             self.buf.switch_to_preproc();
             None
-        } else if let Some(keyword) = CPP_KEYWORDS.get(id) {
-            Some(keyword.clone())
         } else {
-            Some(Token::Identifier(id.to_string()))
+            match KEYWORDS.get(self.symbols.resolve(id)) {
+                Some((keyword @ Token::PragmaOperator, sets))
+                | Some((keyword @ Token::MSPragma, sets))
+                    if self.dialect.keywords.intersects(*sets)
+                        && !self.raw
+                        && !self.in_directive =>
+                {
+                    self.get_pragma_operator(*keyword == Token::PragmaOperator);
+                    None
+                }
+                Some((keyword, sets))
+                    if self.dialect.keywords.intersects(*sets)
+                        && (self.dialect.alt_tokens || !keyword.is_alt_token()) =>
+                {
+                    Some(keyword.clone())
+                }
+                _ => Some(Token::Identifier(id)),
+            }
         }
     }
 
@@ -925,9 +1133,15 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
                 if c == b'=' {
                     self.buf.inc();
                     Token::ModuloEqual
-                } else if c == b'>' {
+                } else if c == b'>' && self.dialect.digraphs {
                     self.buf.inc();
                     Token::RightBrace
+                } else if c == b':' && self.dialect.digraphs {
+                    // %: is #
+                    let mut pos = self.buf.raw_pos();
+                    pos.pos -= 1;
+                    self.buf.inc();
+                    self.get_preproc(pos)
                 } else {
                     Token::Modulo
                 }
@@ -948,9 +1162,12 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
                 } else if c == b'=' {
                     self.buf.inc();
                     Token::LowerEqual
-                } else if c == b'%' {
+                } else if c == b'%' && self.dialect.digraphs {
                     self.buf.inc();
                     Token::LeftBrace
+                } else if c == b':' && self.dialect.digraphs {
+                    self.buf.inc();
+                    Token::LeftBrack
                 } else {
                     Token::Lower
                 }
@@ -975,9 +1192,22 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
                     } else {
                         Token::LowerEqual
                     }
-                } else if c == b'%' {
+                } else if c == b'%' && self.dialect.digraphs {
                     self.buf.inc();
                     Token::LeftBrace
+                } else if c == b':' && self.dialect.digraphs {
+                    // <:: is < :: when not followed by : or >
+                    if self.dialect.lower_colon_colon
+                        && self.buf.has_char_n(1)
+                        && self.buf.next_char_n(1) == b':'
+                        && !(self.buf.has_char_n(2)
+                            && matches!(self.buf.next_char_n(2), b':' | b'>'))
+                    {
+                        Token::Lower
+                    } else {
+                        self.buf.inc();
+                        Token::LeftBrack
+                    }
                 } else {
                     Token::Lower
                 }
@@ -985,6 +1215,20 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
         }
     }
 
+    pub(crate) fn get_colon(&mut self) -> Token {
+        if self.buf.has_char() {
+            let c = self.buf.next_char();
+            if c == b':' {
+                self.buf.inc();
+                return Token::ColonColon;
+            } else if c == b'>' && self.dialect.digraphs {
+                self.buf.inc();
+                return Token::RightBrack;
+            }
+        }
+        Token::Colon
+    }
+
     pub(crate) fn get_greater(&mut self) -> Token {
         match self.buf.rem() {
             #[cold]
@@ -1048,6 +1292,15 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
                 self.buf.add_new_line();
                 self.buf.inc();
                 return None;
+            } else if c == b'\r' && self.buf.has_char_n(1) && self.buf.next_char_n(1) == b'\n' {
+                // continuation line with a CRLF
+                self.buf.inc();
+                self.buf.add_new_line();
+                self.buf.inc();
+                return None;
+            } else if c == b'u' || c == b'U' {
+                self.errors
+                    .push(LexerError::IncompleteUcn { sp: self.span() });
             }
         }
         Some(Token::Backslash)
@@ -1058,10 +1311,10 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
         if self.buf.has_char() {
             let c = self.buf.next_char();
             let kind = unsafe { CHARS.get_unchecked(c as usize) };
-            if *kind != Kind::NON {
+            if *kind != Kind::NON || self.is_extended_id_start() {
                 // we've a suffix
                 self.buf.inc();
-                Some(self.get_identifier_str().to_string())
+                Some(self.get_identifier_str().into_owned())
             } else {
                 None
             }
@@ -1083,6 +1336,21 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
         }
     }
 
+    /// The span of the last n bytes read on the current line
+    pub(crate) fn span_n(&self, n: usize) -> Span {
+        let end = self.location();
+        Span {
+            file: self.buf.get_source_id(),
+            start: Location {
+                pos: end.pos - n,
+                line: end.line,
+                column: end.column.saturating_sub(n as u32),
+            },
+            end,
+            presumed: self.buf.get_presumed_span(end.line, end.line),
+        }
+    }
+
     pub fn next_token(&mut self) -> Token {
         loop {
             self.start = self.location();
@@ -1092,7 +1360,7 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
                 match c {
                     b'\t' => skip_whites!(self),
                     b'\n' => {
-                        self.buf.add_new_line();
+                        self.buf.add_new_line_after();
                         // TODO: useless in general but useful to know the a #if condition is finished
                         // Probably remove it and find a way for the condition stuff
                         return Token::Eol;
@@ -1146,10 +1414,10 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
                         return self.get_slash();
                     }
                     b'0'..=b'9' => {
-                        return self.get_number(u64::from(c - b'0'));
+                        return self.get_number();
                     }
                     b':' => {
-                        return get_operator!(self, b':', Colon, ColonColon);
+                        return self.get_colon();
                     }
                     b';' => {
                         return Token::SemiColon;
@@ -1199,7 +1467,11 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
                         return get_operator!(self, b'[', LeftBrack, DoubleLeftBrack);
                     }
                     b'\\' => {
-                        if let Some(tok) = self.get_backslash() {
+                        if self.is_after_ucn_start() {
+                            if let Some(tok) = self.get_identifier() {
+                                return tok;
+                            }
+                        } else if let Some(tok) = self.get_backslash() {
                             return tok;
                         }
                     }
@@ -1233,12 +1505,19 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
                     b'~' => {
                         return Token::Tilde;
                     }
-                    b'\x7F'..=b'\xFF' => {
+                    b'\x80'..=b'\xFF' => {
                         if let Some(tok) = self.get_identifier() {
                             return tok;
                         }
                     }
-                    _ => {}
+                    b'\r' | b'\x0B' | b'\x0C' => {}
+                    _ => {
+                        // `, control chars...: report them and go on with the next token
+                        self.errors.push(LexerError::StrayChar {
+                            sp: self.span(),
+                            c: char::from(c),
+                        });
+                    }
                 }
             } else {
                 return Token::Eof;
@@ -1252,17 +1531,19 @@ mod tests {
 
     use super::*;
     use crate::lexer::preprocessor::context::DefaultContext;
+    use crate::lexer::source;
     use pretty_assertions::assert_eq;
+    use tempdir::TempDir;
 
     #[test]
     fn test_keywords() {
         let mut p = Lexer::<DefaultContext>::new(b"while foa whila for While For static_cast");
         assert_eq!(p.next_token(), Token::While);
-        assert_eq!(p.next_token(), Token::Identifier("foa".to_string()));
-        assert_eq!(p.next_token(), Token::Identifier("whila".to_string()));
+        assert_eq!(p.next_token(), p.id("foa"));
+        assert_eq!(p.next_token(), p.id("whila"));
         assert_eq!(p.next_token(), Token::For);
-        assert_eq!(p.next_token(), Token::Identifier("While".to_string()));
-        assert_eq!(p.next_token(), Token::Identifier("For".to_string()));
+        assert_eq!(p.next_token(), p.id("While"));
+        assert_eq!(p.next_token(), p.id("For"));
         assert_eq!(p.next_token(), Token::StaticCast);
     }
 
@@ -1271,38 +1552,197 @@ mod tests {
         let mut p = Lexer::<DefaultContext>::new(
             b"hello world whilee Roo Lar uoo Uar u851 hello_world_WORLD_HELLO123",
         );
-        assert_eq!(p.next_token(), Token::Identifier("hello".to_string()));
-        assert_eq!(p.next_token(), Token::Identifier("world".to_string()));
-        assert_eq!(p.next_token(), Token::Identifier("whilee".to_string()));
-        assert_eq!(p.next_token(), Token::Identifier("Roo".to_string()));
-        assert_eq!(p.next_token(), Token::Identifier("Lar".to_string()));
-        assert_eq!(p.next_token(), Token::Identifier("uoo".to_string()));
-        assert_eq!(p.next_token(), Token::Identifier("Uar".to_string()));
-        assert_eq!(p.next_token(), Token::Identifier("u851".to_string()));
-        assert_eq!(
-            p.next_token(),
-            Token::Identifier("hello_world_WORLD_HELLO123".to_string())
-        );
+        assert_eq!(p.next_token(), p.id("hello"));
+        assert_eq!(p.next_token(), p.id("world"));
+        assert_eq!(p.next_token(), p.id("whilee"));
+        assert_eq!(p.next_token(), p.id("Roo"));
+        assert_eq!(p.next_token(), p.id("Lar"));
+        assert_eq!(p.next_token(), p.id("uoo"));
+        assert_eq!(p.next_token(), p.id("Uar"));
+        assert_eq!(p.next_token(), p.id("u851"));
+        assert_eq!(p.next_token(), p.id("hello_world_WORLD_HELLO123"));
     }
 
     #[test]
     fn test_identifiers_utf8() {
-        let mut p = Lexer::<DefaultContext>::new("🌹 🌵 🌻 🌷🌷🌷🌷🌷🌷".as_bytes());
-        assert_eq!(p.next_token(), Token::Identifier("🌹".to_string()));
-        assert_eq!(p.next_token(), Token::Identifier("🌵".to_string()));
-        assert_eq!(p.next_token(), Token::Identifier("🌻".to_string()));
-        assert_eq!(
-            p.next_token(),
-            Token::Identifier("🌷🌷🌷🌷🌷🌷".to_string())
+        let mut p = Lexer::<DefaultContext>::new("été ñ123 Ωμέγα 🌹 a🌵".as_bytes());
+        assert_eq!(p.next_token(), p.id("été"));
+        assert_eq!(p.next_token(), p.id("ñ123"));
+        assert_eq!(p.next_token(), p.id("Ωμέγα"));
+        // Emojis aren't XID_Start or XID_Continue
+        assert_eq!(p.next_token(), p.id("a"));
+        assert_eq!(p.next_token(), Token::Eof);
+        assert_eq!(p.get_errors().len(), 2);
+    }
+
+    #[test]
+    fn test_standard_macros() {
+        let tmp = TempDir::new("test").unwrap();
+        let file = tmp.path().join("foo.cpp");
+        std::fs::write(
+            &file,
+            concat!(
+                "#if __cplusplus >= 202002L\n",
+                "cpp20\n",
+                "#elif defined(__cplusplus)\n",
+                "cpp\n",
+                "#elif __STDC_VERSION__ >= 199901L\n",
+                "c99\n",
+                "#else\n",
+                "c89\n",
+                "#endif\n",
+            ),
+        )
+        .unwrap();
+        let file = file.to_str().unwrap();
+
+        let check = |std: &str, expected: &str| {
+            let std = args::LangStandard::from_std(std).unwrap();
+            let opt = args::PreprocOptions {
+                lang: std.get_language(),
+                std,
+                ..Default::default()
+            };
+            let mut p = Lexer::<DefaultContext>::new_from_file(
+                file,
+                source::get_source_mutex(),
+                Arc::new(IfCache::default()),
+                opt,
+            )
+            .unwrap();
+            let tok = loop {
+                match p.next_useful() {
+                    Token::Identifier(id) => break p.resolve(id).to_string(),
+                    Token::Eof => break String::new(),
+                    _ => {}
+                }
+            };
+            assert_eq!(tok, expected);
+        };
+
+        check("c++20", "cpp20");
+        check("gnu++14", "cpp");
+        check("c99", "c99");
+        check("c89", "c89");
+    }
+
+    #[test]
+    fn test_new_from_missing_file() {
+        let tmp = TempDir::new("test").unwrap();
+        let file = tmp.path().join("missing.cpp");
+        let res = Lexer::<DefaultContext>::new_from_file(
+            file.to_str().unwrap(),
+            source::get_source_mutex(),
+            Arc::new(IfCache::default()),
+            args::PreprocOptions::default(),
         );
+
+        match res {
+            Err(FileError::Io { path, .. }) => assert_eq!(path, file),
+            _ => panic!("Expected an io error"),
+        }
+    }
+
+    #[test]
+    fn test_imacros() {
+        let tmp = TempDir::new("test").unwrap();
+        std::fs::write(tmp.path().join("macros.h"), "#define FOO 1\ndropped\n").unwrap();
+        std::fs::write(tmp.path().join("inc.h"), "kept\n").unwrap();
+        let file = tmp.path().join("foo.cpp");
+        std::fs::write(&file, "#ifdef FOO\nok\n#endif\n").unwrap();
+
+        let opt = args::PreprocOptions {
+            imacros: vec!["macros.h".to_string()],
+            includes: vec!["inc.h".to_string()],
+            current_dir: tmp.path().to_path_buf(),
+            ..Default::default()
+        };
+        let mut p = Lexer::<DefaultContext>::new_from_file(
+            file.to_str().unwrap(),
+            source::get_source_mutex(),
+            Arc::new(IfCache::default()),
+            opt,
+        )
+        .unwrap();
+
+        let mut ids = Vec::new();
+        loop {
+            match p.next_useful() {
+                Token::Identifier(id) => ids.push(p.resolve(id).to_string()),
+                Token::Eof => break,
+                _ => {}
+            }
+        }
+        assert_eq!(ids, vec!["kept", "ok"]);
+    }
+
+    #[test]
+    fn test_imacros_dialect() {
+        let tmp = TempDir::new("test").unwrap();
+        std::fs::write(tmp.path().join("macros.h"), "??=define FOO 1\n").unwrap();
+        let file = tmp.path().join("foo.c");
+        std::fs::write(&file, "#ifdef FOO\nok\n#endif\n").unwrap();
+
+        let opt = args::PreprocOptions {
+            imacros: vec!["macros.h".to_string()],
+            current_dir: tmp.path().to_path_buf(),
+            trigraphs: Some(true),
+            ..Default::default()
+        };
+        let mut p = Lexer::<DefaultContext>::new_from_file(
+            file.to_str().unwrap(),
+            source::get_source_mutex(),
+            Arc::new(IfCache::default()),
+            opt,
+        )
+        .unwrap();
+
+        let mut ids = Vec::new();
+        loop {
+            match p.next_useful() {
+                Token::Identifier(id) => ids.push(p.resolve(id).to_string()),
+                Token::Eof => break,
+                _ => {}
+            }
+        }
+        assert_eq!(ids, vec!["ok"]);
+    }
+
+    #[test]
+    fn test_line_directive_from_file() {
+        let tmp = TempDir::new("test").unwrap();
+        let file = tmp.path().join("parser.c");
+        std::fs::write(&file, "#line 42 \"foo.y\"\n__LINE__ __FILE__\n").unwrap();
+        let mut p = Lexer::<DefaultContext>::new_from_file(
+            file.to_str().unwrap(),
+            source::get_source_mutex(),
+            Arc::new(IfCache::default()),
+            args::PreprocOptions::default(),
+        )
+        .unwrap();
+
+        // Skip the predefined macros
+        while p.next_token() != Token::PreprocLine {}
+        // __LINE__ __FILE__
+        assert_eq!(p.next_token(), Token::from_number("42").unwrap());
+        assert_eq!(p.next_token(), Token::LiteralString("foo.y".into()));
+        assert_eq!(p.next_token(), Token::Eol);
+        assert_eq!(p.next_token(), Token::Eof);
+        assert!(p.get_errors().is_empty());
+    }
+
+    #[test]
+    fn test_token_size() {
+        // The biggest payloads are the boxed string literals (fat pointers)
+        assert!(std::mem::size_of::<Token>() <= 24);
     }
 
     #[test]
     fn test_divide() {
         let mut p = Lexer::<DefaultContext>::new(b"a / b");
-        assert_eq!(p.next_token(), Token::Identifier("a".to_string()));
+        assert_eq!(p.next_token(), p.id("a"));
         assert_eq!(p.next_token(), Token::Divide);
-        assert_eq!(p.next_token(), Token::Identifier("b".to_string()));
+        assert_eq!(p.next_token(), p.id("b"));
     }
 
     #[test]
@@ -1347,4 +1787,23 @@ mod tests {
         assert_eq!(p.next_token(), Token::Ellipsis);
         assert_eq!(p.next_token(), Token::LowerEqualGreater);
     }
+
+    #[test]
+    fn test_stray_chars() {
+        let mut p = Lexer::<DefaultContext>::new(b"a ` b @ c\x01\r\nd \\\r\n e");
+        assert_eq!(p.next_token(), p.id("a"));
+        assert_eq!(p.next_token(), p.id("b"));
+        assert_eq!(p.next_token(), Token::At);
+        assert_eq!(p.next_token(), p.id("c"));
+        assert_eq!(p.next_token(), Token::Eol);
+        assert_eq!(p.next_token(), p.id("d"));
+        assert_eq!(p.next_token(), p.id("e"));
+        assert_eq!(p.next_token(), Token::Eof);
+
+        let errors: Vec<_> = p.get_errors().iter().map(|e| e.stringly()).collect();
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].message, "stray '`' in program");
+        assert_eq!(errors[1].message, "stray <U+0001> in program");
+        assert_eq!((errors[0].sp.start.column, errors[0].sp.end.column), (3, 4));
+    }
 }
@@ -0,0 +1,600 @@
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::collections::VecDeque;
+use std::ops::Range;
+
+use super::lexer::{Lexer, Token};
+use super::preprocessor::context::PreprocContext;
+use super::source::FileId;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TriviaKind {
+    /// Spaces, tabs and the other blank characters
+    Whitespace,
+    /// A line feed (with the preceding carriage return if any)
+    Newline,
+    /// A backslash followed by a newline
+    LineContinuation,
+    Comment,
+    /// A region skipped by a conditional directive (e.g. #if 0)
+    Skipped,
+    /// Some bytes ignored by the lexer
+    Unknown,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Trivia {
+    pub kind: TriviaKind,
+    pub range: Range<usize>,
+}
+
+/// A token with its byte range in the source and its trivia
+/// The trailing trivia are the ones on the same line as the token (newline included),
+/// the other ones are the leading trivia of the next token.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LosslessToken {
+    pub tok: Token,
+    pub range: Range<usize>,
+    pub leading: Vec<Trivia>,
+    pub trailing: Vec<Trivia>,
+}
+
+impl LosslessToken {
+    /// The range of the token including its trivia
+    pub fn full_range(&self) -> Range<usize> {
+        let start = self
+            .leading
+            .first()
+            .map_or(self.range.start, |t| t.range.start);
+        let end = self.trailing.last().map_or(self.range.end, |t| t.range.end);
+        start..end
+    }
+}
+
+enum Item {
+    Token(Token, Range<usize>),
+    Trivia(Trivia),
+}
+
+/// A lexer yielding all the tokens written in a buffer with their trivia:
+/// the concatenation of the tokens and trivia is the buffer itself.
+/// The directives are evaluated (so the skipped regions are known) and each one
+/// is a token spanning its whole line, the macros aren't expanded out of
+/// the directives and the included files aren't entered.
+/// The last token is always Token::Eof with an empty range.
+pub struct LosslessLexer<'a, PC: PreprocContext> {
+    lexer: Lexer<'a, PC>,
+    src: &'a [u8],
+    items: VecDeque<Item>,
+    trivia: Vec<Trivia>,
+    pending: Option<LosslessToken>,
+    eof: bool,
+}
+
+impl<'a, PC: PreprocContext> Iterator for LosslessLexer<'a, PC> {
+    type Item = LosslessToken;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.eof {
+                return self.pending.take();
+            }
+
+            let (tok, range) = self.next_token();
+            self.eof = tok == Token::Eof;
+
+            let mut trivia = std::mem::take(&mut self.trivia);
+            let prev = self.pending.take().map(|mut prev| {
+                let n = trivia
+                    .iter()
+                    .position(|t| t.kind == TriviaKind::Newline)
+                    .map_or(trivia.len(), |n| n + 1);
+                let leading = trivia.split_off(n);
+                prev.trailing = std::mem::replace(&mut trivia, leading);
+                prev
+            });
+
+            self.pending = Some(LosslessToken {
+                tok,
+                range,
+                leading: trivia,
+                trailing: Vec::new(),
+            });
+
+            if prev.is_some() {
+                return prev;
+            }
+        }
+    }
+}
+
+impl<'a, PC: PreprocContext> LosslessLexer<'a, PC> {
+    pub fn new(buf: &'a [u8]) -> Self {
+        Self::from_lexer(buf, Lexer::new(buf))
+    }
+
+    pub fn new_with_context(buf: &'a [u8], source_id: FileId, context: PC) -> Self {
+        Self::from_lexer(buf, Lexer::new_with_context(buf, source_id, context))
+    }
+
+    fn from_lexer(src: &'a [u8], mut lexer: Lexer<'a, PC>) -> Self {
+        lexer.raw = true;
+        Self {
+            lexer,
+            src,
+            items: VecDeque::new(),
+            trivia: Vec::new(),
+            pending: None,
+            eof: false,
+        }
+    }
+
+    /// Get the underlying lexer (e.g. to get the errors or the defined macros)
+    pub fn get_lexer(&self) -> &Lexer<'a, PC> {
+        &self.lexer
+    }
+
+    fn next_token(&mut self) -> (Token, Range<usize>) {
+        loop {
+            match self.items.pop_front() {
+                Some(Item::Token(tok, range)) => return (tok, range),
+                Some(Item::Trivia(trivia)) => self.trivia.push(trivia),
+                None => self.lex(),
+            }
+        }
+    }
+
+    fn lex(&mut self) {
+        let len = self.src.len();
+        let pos = self.lexer.buf.pos().min(len);
+
+        // The start of a directive must be found before lexing it because
+        // the start of the last token lexed in the directive is kept by the lexer
+        let hash = self.skip_blanks(pos);
+        if self.get_hash_len(hash) != 0 {
+            self.lexer.next_token();
+            let end = self.lexer.buf.pos().clamp(hash, len);
+            self.add_blanks(pos, hash);
+            self.add_directive(hash, end);
+            return;
+        }
+
+        let tok = self.lexer.next_token();
+        let start = self.lexer.start.pos.clamp(pos, len);
+        let end = self.lexer.buf.pos().clamp(start, len);
+
+        self.add_blanks(pos, start);
+
+        match tok {
+            Token::Eof => self.items.push_back(Item::Token(Token::Eof, len..len)),
+            Token::Eol => self.add_newline(start..end),
+            Token::Comment => self.add_trivia(TriviaKind::Comment, start..end),
+            _ => self.items.push_back(Item::Token(tok, start..end)),
+        }
+    }
+
+    fn add_trivia(&mut self, kind: TriviaKind, range: Range<usize>) {
+        if range.is_empty() {
+            return;
+        }
+
+        if kind == TriviaKind::Whitespace || kind == TriviaKind::Unknown {
+            if let Some(Item::Trivia(last)) = self.items.back_mut() {
+                if last.kind == kind && last.range.end == range.start {
+                    last.range.end = range.end;
+                    return;
+                }
+            }
+        }
+
+        self.items.push_back(Item::Trivia(Trivia { kind, range }));
+    }
+
+    fn add_newline(&mut self, range: Range<usize>) {
+        // Move the \r of a \r\n from the blanks to the newline
+        let mut start = range.start;
+        if start > 0 && self.src[start - 1] == b'\r' {
+            if let Some(Item::Trivia(last)) = self.items.back_mut() {
+                if last.kind == TriviaKind::Whitespace && last.range.end == start {
+                    last.range.end -= 1;
+                    start -= 1;
+                    if last.range.is_empty() {
+                        self.items.pop_back();
+                    }
+                }
+            }
+        }
+        self.add_trivia(TriviaKind::Newline, start..range.end);
+    }
+
+    fn add_blanks(&mut self, start: usize, end: usize) {
+        let mut pos = start;
+        while pos < end {
+            let c = self.src[pos];
+            let rem = &self.src[pos..end];
+            let (kind, n) = if rem.starts_with(b"\\\n") {
+                (TriviaKind::LineContinuation, 2)
+            } else if rem.starts_with(b"\\\r\n") {
+                (TriviaKind::LineContinuation, 3)
+            } else if c == b'\n' {
+                (TriviaKind::Newline, 1)
+            } else if Self::is_blank(c) {
+                let n = rem.iter().take_while(|c| Self::is_blank(**c)).count();
+                (TriviaKind::Whitespace, n)
+            } else {
+                (TriviaKind::Unknown, 1)
+            };
+            self.add_trivia(kind, pos..pos + n);
+            pos += n;
+        }
+    }
+
+    fn skip_blanks(&self, start: usize) -> usize {
+        let mut pos = start;
+        while let Some(&c) = self.src.get(pos) {
+            let rem = &self.src[pos..];
+            if rem.starts_with(b"\\\n") {
+                pos += 2;
+            } else if c == b' ' || c == b'\t' {
+                pos += 1;
+            } else {
+                break;
+            }
+        }
+        pos
+    }
+
+    fn is_blank(c: u8) -> bool {
+        matches!(c, b' ' | b'\t' | b'\r' | b'\x0b' | b'\x0c')
+    }
+
+    /// The lexer has consumed the directive starting at start: the directive itself
+    /// and, when its condition is false, the skipped region and the directive ending it
+    fn add_directive(&mut self, start: usize, end: usize) {
+        let eol = self.get_line_end(start);
+        let tok_end = if eol > start && self.src[eol - 1] == b'\r' {
+            eol - 1
+        } else {
+            eol
+        };
+
+        self.items
+            .push_back(Item::Token(self.get_directive_kind(start), start..tok_end));
+
+        if end <= tok_end {
+            // The lexer stopped in the line (e.g. after #endif) so just skip the rest
+            self.skip_to(tok_end);
+            return;
+        }
+
+        let nl_end = (eol + 1).min(end);
+        if self.src[tok_end..nl_end].contains(&b'\n') {
+            self.add_trivia(TriviaKind::Newline, tok_end..nl_end);
+        } else {
+            self.add_trivia(TriviaKind::Whitespace, tok_end..nl_end);
+        }
+
+        if end <= nl_end {
+            return;
+        }
+
+        // The lines in [nl_end, end) have been skipped and the last one
+        // is the directive which has stopped the skipping (if any)
+        let mut last = nl_end;
+        let mut pos = nl_end;
+        while pos < end {
+            last = pos;
+            pos = self.get_line_end(pos) + 1;
+        }
+
+        let hash = last
+            + self.src[last..end]
+                .iter()
+                .take_while(|c| Self::is_blank(**c))
+                .count();
+        if hash < end && self.get_hash_len(hash) != 0 {
+            self.add_trivia(TriviaKind::Skipped, nl_end..last);
+            self.add_trivia(TriviaKind::Whitespace, last..hash);
+            self.add_directive(hash, end);
+        } else {
+            self.add_trivia(TriviaKind::Skipped, nl_end..end);
+        }
+    }
+
+    /// Get the length of the # (or %: digraph) at pos (0 if none)
+    fn get_hash_len(&self, pos: usize) -> usize {
+        match self.src.get(pos..).unwrap_or_default() {
+            [b'#', ..] => 1,
+            [b'%', b':', ..] if self.lexer.dialect.digraphs => 2,
+            _ => 0,
+        }
+    }
+
+    fn get_directive_kind(&self, start: usize) -> Token {
+        let src = &self.src[start + self.get_hash_len(start)..];
+        let spaces = src.iter().take_while(|c| Self::is_blank(**c)).count();
+        let src = &src[spaces..];
+        let n = src
+            .iter()
+            .take_while(|c| c.is_ascii_alphanumeric() || **c == b'_')
+            .count();
+
+        if src.first().is_some_and(|c| c.is_ascii_digit()) {
+            // # 123 "foo.h" is a line marker
+            return Token::PreprocLine;
+        }
+
+        std::str::from_utf8(&src[..n])
+            .ok()
+            .and_then(Token::from_preproc_keyword)
+            .unwrap_or(Token::None)
+    }
+
+    /// Get the position of the newline ending the logical line starting at start
+    /// (or the end of the buffer)
+    fn get_line_end(&self, start: usize) -> usize {
+        let src = self.src;
+        let len = src.len();
+        let mut pos = start;
+        let mut in_comment = false;
+
+        while pos < len {
+            let rem = &src[pos..];
+            if rem.starts_with(b"\\\n") {
+                pos += 2;
+            } else if rem.starts_with(b"\\\r\n") {
+                pos += 3;
+            } else if src[pos] == b'\n' {
+                break;
+            } else if in_comment {
+                pos += 1;
+            } else if rem.starts_with(b"//") {
+                in_comment = true;
+                pos += 2;
+            } else if rem.starts_with(b"/*") {
+                pos = rem[2..]
+                    .windows(2)
+                    .position(|w| w == b"*/")
+                    .map_or(len, |n| pos + n + 4);
+            } else if src[pos] == b'\"' || src[pos] == b'\'' {
+                let delim = src[pos];
+                pos += 1;
+                while pos < len && src[pos] != delim && src[pos] != b'\n' {
+                    pos += if src[pos] == b'\\' { 2 } else { 1 };
+                }
+                if pos < len && src[pos] == delim {
+                    pos += 1;
+                }
+            } else {
+                pos += 1;
+            }
+        }
+
+        pos.min(len)
+    }
+
+    fn skip_to(&mut self, target: usize) {
+        let buf = &mut self.lexer.buf;
+        while buf.pos() < target && buf.has_char() {
+            if buf.next_char() == b'\n' {
+                buf.add_new_line();
+            }
+            buf.inc();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::lexer::preprocessor::context::DefaultContext;
+    use pretty_assertions::assert_eq;
+
+    fn get_tokens(src: &[u8]) -> Vec<LosslessToken> {
+        LosslessLexer::<DefaultContext>::new(src).collect()
+    }
+
+    fn concat(src: &[u8], toks: &[LosslessToken]) -> String {
+        let mut out = Vec::new();
+        for tok in toks {
+            for t in tok.leading.iter() {
+                out.extend_from_slice(&src[t.range.clone()]);
+            }
+            out.extend_from_slice(&src[tok.range.clone()]);
+            for t in tok.trailing.iter() {
+                out.extend_from_slice(&src[t.range.clone()]);
+            }
+        }
+        String::from_utf8(out).unwrap()
+    }
+
+    fn get_trivia(src: &[u8], trivia: &[Trivia]) -> Vec<(TriviaKind, String)> {
+        trivia
+            .iter()
+            .map(|t| {
+                (
+                    t.kind,
+                    String::from_utf8_lossy(&src[t.range.clone()]).to_string(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_lossless_trivia() {
+        let src = b"int a; // comment\n  /* b */ b\\\n c;";
+        let mut lexer = LosslessLexer::<DefaultContext>::new(src);
+        let toks: Vec<_> = lexer.by_ref().collect();
+
+        let kinds: Vec<_> = toks.iter().map(|t| t.tok.clone()).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                Token::Int,
+                lexer.lexer.id("a"),
+                Token::SemiColon,
+                lexer.lexer.id("b"),
+                lexer.lexer.id("c"),
+                Token::SemiColon,
+                Token::Eof,
+            ]
+        );
+
+        assert_eq!(
+            get_trivia(src, &toks[0].trailing),
+            vec![(TriviaKind::Whitespace, " ".to_string())]
+        );
+        assert_eq!(
+            get_trivia(src, &toks[2].trailing),
+            vec![
+                (TriviaKind::Whitespace, " ".to_string()),
+                (TriviaKind::Comment, "// comment".to_string()),
+                (TriviaKind::Newline, "\n".to_string()),
+            ]
+        );
+        assert_eq!(
+            get_trivia(src, &toks[3].leading),
+            vec![
+                (TriviaKind::Whitespace, "  ".to_string()),
+                (TriviaKind::Comment, "/* b */".to_string()),
+                (TriviaKind::Whitespace, " ".to_string()),
+            ]
+        );
+        assert_eq!(
+            get_trivia(src, &toks[3].trailing),
+            vec![
+                (TriviaKind::LineContinuation, "\\\n".to_string()),
+                (TriviaKind::Whitespace, " ".to_string()),
+            ]
+        );
+        assert_eq!(toks[6].range, src.len()..src.len());
+        assert_eq!(concat(src, &toks), std::str::from_utf8(src).unwrap());
+    }
+
+    #[test]
+    fn test_lossless_skipped() {
+        let src = b"#if 0\nfoo\n  #  if 1\n#endif\n#else // bar\nbar\n#endif\n";
+        let mut lexer = LosslessLexer::<DefaultContext>::new(src);
+        let toks: Vec<_> = lexer.by_ref().collect();
+
+        let kinds: Vec<_> = toks
+            .iter()
+            .map(|t| {
+                (
+                    t.tok.clone(),
+                    String::from_utf8_lossy(&src[t.range.clone()]),
+                )
+            })
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                (Token::PreprocIf, "#if 0".into()),
+                (Token::PreprocElse, "#else // bar".into()),
+                (lexer.lexer.id("bar"), "bar".into()),
+                (Token::PreprocEndif, "#endif".into()),
+                (Token::Eof, "".into()),
+            ]
+        );
+        assert_eq!(
+            get_trivia(src, &toks[1].leading),
+            vec![(TriviaKind::Skipped, "foo\n  #  if 1\n#endif\n".to_string())]
+        );
+        assert_eq!(concat(src, &toks), std::str::from_utf8(src).unwrap());
+    }
+
+    #[test]
+    fn test_lossless_digraph_directive() {
+        let src = b"%:if 0\nfoo\n%:else\nbar\n%:endif\n";
+        let mut lexer = LosslessLexer::<DefaultContext>::new(src);
+        let toks: Vec<_> = lexer.by_ref().collect();
+
+        let kinds: Vec<_> = toks
+            .iter()
+            .map(|t| {
+                (
+                    t.tok.clone(),
+                    String::from_utf8_lossy(&src[t.range.clone()]),
+                )
+            })
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                (Token::PreprocIf, "%:if 0".into()),
+                (Token::PreprocElse, "%:else".into()),
+                (lexer.lexer.id("bar"), "bar".into()),
+                (Token::PreprocEndif, "%:endif".into()),
+                (Token::Eof, "".into()),
+            ]
+        );
+        assert_eq!(concat(src, &toks), std::str::from_utf8(src).unwrap());
+    }
+
+    #[test]
+    fn test_lossless_no_expansion() {
+        let src = b"#define FOO(x) x + \\\n  1\n#include <nonexistent.h>\nFOO(2)\n";
+        let mut lexer = LosslessLexer::<DefaultContext>::new(src);
+        let toks: Vec<_> = lexer.by_ref().collect();
+
+        let kinds: Vec<_> = toks.iter().map(|t| t.tok.clone()).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                Token::PreprocDefine,
+                Token::PreprocInclude,
+                lexer.lexer.id("FOO"),
+                Token::LeftParen,
+                Token::from_number("2").unwrap(),
+                Token::RightParen,
+                Token::Eof,
+            ]
+        );
+        assert_eq!(&src[toks[0].range.clone()], b"#define FOO(x) x + \\\n  1");
+        assert!(lexer.get_lexer().get_errors().is_empty());
+        assert!(lexer.get_lexer().get_context().get("FOO").is_some());
+        assert_eq!(concat(src, &toks), std::str::from_utf8(src).unwrap());
+    }
+
+    #[test]
+    fn test_lossless_roundtrip() {
+        let src = concat!(
+            "// Header\r\n",
+            "#ifndef FOO_H\r\n",
+            "#define FOO_H\r\n",
+            "\r\n",
+            "#if defined(BAR) && \\\n",
+            "    BAR > 1\r\n",
+            "int bar = `BAR`;\r\n",
+            "#elif 0\r\n",
+            "int baz = 0;\r\n",
+            "#else\r\n",
+            "const char* s = \"a \\\" /* b\"; /* multi\r\n",
+            "   line */ char c = '\\'';\r\n",
+            "#endif\r\n",
+            "#  pragma once\n",
+            "# 12 \"foo.h\"\n",
+            "\t\tx\x0c= y\\\n",
+            "+ 1;\n",
+            "#endif // FOO_H\n",
+            "#if 1\n",
+            "unterminated",
+        )
+        .as_bytes();
+        let toks = get_tokens(src);
+
+        assert_eq!(concat(src, &toks), std::str::from_utf8(src).unwrap());
+
+        let mut pos = 0;
+        for tok in toks.iter() {
+            let range = tok.full_range();
+            assert_eq!(range.start, pos);
+            pos = range.end;
+        }
+        assert_eq!(pos, src.len());
+    }
+}
@@ -0,0 +1,58 @@
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::fs::File;
+use std::io;
+use std::os::unix::io::AsRawFd;
+
+/// A read-only private mapping of a whole file
+#[derive(Debug)]
+pub(crate) struct Mmap {
+    ptr: *const u8,
+    len: usize,
+}
+
+// The mapping is read-only so it can be shared between threads
+unsafe impl Send for Mmap {}
+unsafe impl Sync for Mmap {}
+
+impl Mmap {
+    /// Map the file (which mustn't be empty)
+    ///
+    /// # Safety
+    ///
+    /// The file mustn't be modified or truncated while the mapping is alive:
+    /// the slice would change under our feet or accessing it could raise a SIGBUS.
+    pub(crate) unsafe fn new(file: &File, len: usize) -> io::Result<Self> {
+        let ptr = libc::mmap(
+            std::ptr::null_mut(),
+            len,
+            libc::PROT_READ,
+            libc::MAP_PRIVATE,
+            file.as_raw_fd(),
+            0,
+        );
+        if ptr == libc::MAP_FAILED {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(Self {
+                ptr: ptr as *const u8,
+                len,
+            })
+        }
+    }
+
+    pub(crate) fn as_slice(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.ptr, self.len) }
+    }
+}
+
+impl Drop for Mmap {
+    fn drop(&mut self) {
+        unsafe {
+            libc::munmap(self.ptr as *mut libc::c_void, self.len);
+        }
+    }
+}
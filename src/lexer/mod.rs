@@ -7,13 +7,23 @@ pub mod lexer;
 pub use self::lexer::*;
 
 pub mod buffer;
+pub mod dialect;
+pub mod encoding;
 pub mod errors;
 pub mod extra;
+pub mod lossless;
+pub mod numeric;
 pub mod preprocessor;
 pub mod source;
+pub mod symbol;
+pub use self::symbol::{Symbol, SymbolTable};
 
 mod cchar;
 mod comment;
+mod identifier;
+#[cfg(unix)]
+mod mmap;
 mod number;
+mod scan;
 mod string;
 mod tools;
@@ -0,0 +1,892 @@
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use hashbrown::HashMap;
+use lazy_static::lazy_static;
+use std::fmt;
+
+// The literal types are computed for a LP64 data model
+const INT_BITS: u32 = 32;
+const LONG_BITS: u32 = 64;
+const LONG_LONG_BITS: u32 = 64;
+
+/// The radix of a numeric literal
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Base {
+    Bin,
+    Oct,
+    Dec,
+    Hex,
+}
+
+impl Base {
+    pub fn radix(self) -> u32 {
+        match self {
+            Base::Bin => 2,
+            Base::Oct => 8,
+            Base::Dec => 10,
+            Base::Hex => 16,
+        }
+    }
+
+    fn to_str(self) -> &'static str {
+        match self {
+            Base::Bin => "binary",
+            Base::Oct => "octal",
+            Base::Dec => "decimal",
+            Base::Hex => "hexadecimal",
+        }
+    }
+}
+
+/// An unsigned integer of arbitrary size
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BigUint {
+    // Little-endian and without trailing zeros
+    limbs: Vec<u32>,
+}
+
+impl BigUint {
+    pub fn is_zero(&self) -> bool {
+        self.limbs.is_empty()
+    }
+
+    /// The number of bits needed to represent the number
+    pub fn bits(&self) -> u32 {
+        if let Some(last) = self.limbs.last() {
+            32 * (self.limbs.len() as u32) - last.leading_zeros()
+        } else {
+            0
+        }
+    }
+
+    pub fn to_u64(&self) -> Option<u64> {
+        if self.bits() <= 64 {
+            Some(self.low_u64())
+        } else {
+            None
+        }
+    }
+
+    pub fn to_u128(&self) -> Option<u128> {
+        if self.bits() <= 128 {
+            Some(
+                self.limbs
+                    .iter()
+                    .rev()
+                    .fold(0, |acc, limb| (acc << 32) | u128::from(*limb)),
+            )
+        } else {
+            None
+        }
+    }
+
+    /// The value modulo 2^64
+    pub fn low_u64(&self) -> u64 {
+        let low = u64::from(self.limbs.first().copied().unwrap_or(0));
+        let high = u64::from(self.limbs.get(1).copied().unwrap_or(0));
+        (high << 32) | low
+    }
+
+    /// self = self * mul + add
+    fn mul_add(&mut self, mul: u32, add: u32) {
+        let mut carry = u64::from(add);
+        for limb in self.limbs.iter_mut() {
+            let x = u64::from(*limb) * u64::from(mul) + carry;
+            *limb = x as u32;
+            carry = x >> 32;
+        }
+        if carry != 0 {
+            self.limbs.push(carry as u32);
+        }
+    }
+
+    /// self = self / div and return the remainder
+    fn div_rem(&mut self, div: u32) -> u32 {
+        let mut rem = 0;
+        for limb in self.limbs.iter_mut().rev() {
+            let x = (rem << 32) | u64::from(*limb);
+            *limb = (x / u64::from(div)) as u32;
+            rem = x % u64::from(div);
+        }
+        while self.limbs.last() == Some(&0) {
+            self.limbs.pop();
+        }
+        rem as u32
+    }
+
+    /// The 64 most significant bits with a sticky bit for the lost ones and the shift
+    fn get_top_u64(&self) -> (u64, u32) {
+        let bits = self.bits();
+        if bits <= 64 {
+            return (self.low_u64(), 0);
+        }
+
+        let shift = bits - 64;
+        let mut top = 0;
+        for i in (0..64).rev() {
+            top = (top << 1) | u64::from(self.bit(shift + i));
+        }
+        let sticky = (0..shift).any(|i| self.bit(i));
+
+        (top | u64::from(sticky), shift)
+    }
+
+    fn bit(&self, n: u32) -> bool {
+        (self.limbs[(n / 32) as usize] >> (n % 32)) & 1 == 1
+    }
+}
+
+impl From<u64> for BigUint {
+    fn from(n: u64) -> Self {
+        let mut limbs = vec![n as u32, (n >> 32) as u32];
+        while limbs.last() == Some(&0) {
+            limbs.pop();
+        }
+        Self { limbs }
+    }
+}
+
+impl fmt::Display for BigUint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_zero() {
+            return write!(f, "0");
+        }
+
+        let mut n = self.clone();
+        let mut chunks = Vec::new();
+        while !n.is_zero() {
+            chunks.push(n.div_rem(1_000_000_000));
+        }
+
+        let mut chunks = chunks.iter().rev();
+        write!(f, "{}", chunks.next().unwrap())?;
+        for chunk in chunks {
+            write!(f, "{:09}", chunk)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum IntSuffix {
+    None,
+    U,
+    L,
+    UL,
+    LL,
+    Ull,
+    // MSVC
+    I128,
+    UI128,
+    // C23
+    BitInt,
+    UBitInt,
+}
+
+lazy_static! {
+    static ref INT_SUFFIXES: HashMap<&'static str, IntSuffix> = {
+        let mut map = HashMap::with_capacity(48);
+        map.insert("u", IntSuffix::U);
+        map.insert("U", IntSuffix::U);
+        map.insert("l", IntSuffix::L);
+        map.insert("L", IntSuffix::L);
+        map.insert("ul", IntSuffix::UL);
+        map.insert("Ul", IntSuffix::UL);
+        map.insert("uL", IntSuffix::UL);
+        map.insert("UL", IntSuffix::UL);
+        map.insert("lu", IntSuffix::UL);
+        map.insert("lU", IntSuffix::UL);
+        map.insert("Lu", IntSuffix::UL);
+        map.insert("LU", IntSuffix::UL);
+        map.insert("ll", IntSuffix::LL);
+        map.insert("LL", IntSuffix::LL);
+        map.insert("llu", IntSuffix::Ull);
+        map.insert("llU", IntSuffix::Ull);
+        map.insert("LLu", IntSuffix::Ull);
+        map.insert("LLU", IntSuffix::Ull);
+        map.insert("ull", IntSuffix::Ull);
+        map.insert("Ull", IntSuffix::Ull);
+        map.insert("uLL", IntSuffix::Ull);
+        map.insert("ULL", IntSuffix::Ull);
+        map.insert("i128", IntSuffix::I128);
+        map.insert("I128", IntSuffix::I128);
+        map.insert("ui128", IntSuffix::UI128);
+        map.insert("Ui128", IntSuffix::UI128);
+        map.insert("uI128", IntSuffix::UI128);
+        map.insert("UI128", IntSuffix::UI128);
+        map.insert("wb", IntSuffix::BitInt);
+        map.insert("WB", IntSuffix::BitInt);
+        map.insert("uwb", IntSuffix::UBitInt);
+        map.insert("Uwb", IntSuffix::UBitInt);
+        map.insert("uWB", IntSuffix::UBitInt);
+        map.insert("UWB", IntSuffix::UBitInt);
+        map.insert("wbu", IntSuffix::UBitInt);
+        map.insert("wbU", IntSuffix::UBitInt);
+        map.insert("WBu", IntSuffix::UBitInt);
+        map.insert("WBU", IntSuffix::UBitInt);
+        map
+    };
+}
+
+/// The type of an integer literal
+#[derive(Clone, Debug, PartialEq)]
+pub enum IntType {
+    Int,
+    UInt,
+    Long,
+    ULong,
+    LongLong,
+    ULongLong,
+    Int128,
+    UInt128,
+    /// _BitInt(N)
+    BitInt(u32),
+    /// unsigned _BitInt(N)
+    UBitInt(u32),
+    UserDefined(String),
+}
+
+impl IntType {
+    pub fn is_unsigned(&self) -> bool {
+        matches!(
+            self,
+            IntType::UInt
+                | IntType::ULong
+                | IntType::ULongLong
+                | IntType::UInt128
+                | IntType::UBitInt(_)
+        )
+    }
+
+    /// The number of bits available for the value (sign excluded)
+    fn value_bits(&self) -> u32 {
+        match self {
+            IntType::Int => INT_BITS - 1,
+            IntType::UInt => INT_BITS,
+            IntType::Long => LONG_BITS - 1,
+            IntType::ULong => LONG_BITS,
+            IntType::LongLong => LONG_LONG_BITS - 1,
+            IntType::ULongLong => LONG_LONG_BITS,
+            IntType::Int128 => 127,
+            IntType::UInt128 => 128,
+            IntType::BitInt(n) => n - 1,
+            IntType::UBitInt(n) => *n,
+            IntType::UserDefined(_) => u32::MAX,
+        }
+    }
+
+    fn get(value: &BigUint, base: Base, suffix: IntSuffix) -> (Self, Option<Overflow>) {
+        use IntType::*;
+
+        let bits = value.bits();
+        let dec = base == Base::Dec;
+        let candidates: &[IntType] = match suffix {
+            IntSuffix::None if dec => &[Int, Long, LongLong],
+            IntSuffix::None => &[Int, UInt, Long, ULong, LongLong, ULongLong],
+            IntSuffix::U => &[UInt, ULong, ULongLong],
+            IntSuffix::L if dec => &[Long, LongLong],
+            IntSuffix::L => &[Long, ULong, LongLong, ULongLong],
+            IntSuffix::UL => &[ULong, ULongLong],
+            IntSuffix::LL if dec => &[LongLong],
+            IntSuffix::LL => &[LongLong, ULongLong],
+            IntSuffix::Ull => &[ULongLong],
+            IntSuffix::I128 => &[Int128],
+            IntSuffix::UI128 => &[UInt128],
+            // The width is the smallest one which can represent the value
+            IntSuffix::BitInt => return (BitInt(std::cmp::max(bits + 1, 2)), None),
+            IntSuffix::UBitInt => return (UBitInt(std::cmp::max(bits, 1)), None),
+        };
+
+        if let Some(typ) = candidates.iter().find(|t| bits <= t.value_bits()) {
+            return (typ.clone(), None);
+        }
+
+        match suffix {
+            IntSuffix::None | IntSuffix::L | IntSuffix::LL if bits <= ULongLong.value_bits() => {
+                (ULongLong, Some(Overflow::Unsigned))
+            }
+            _ => (candidates.last().unwrap().clone(), Some(Overflow::TooLarge)),
+        }
+    }
+}
+
+/// An integer literal
+#[derive(Clone, Debug, PartialEq)]
+pub struct IntNumber {
+    /// The literal as written in the source (with the digit separators and the suffix)
+    pub spelling: String,
+    pub base: Base,
+    pub value: BigUint,
+    pub typ: IntType,
+    suffix_len: usize,
+}
+
+impl IntNumber {
+    /// The token for an invalid literal: the spelling is kept but the value is 0
+    pub(crate) fn new_invalid(spelling: &str) -> Self {
+        Self {
+            spelling: spelling.to_string(),
+            base: Base::Dec,
+            value: BigUint::default(),
+            typ: IntType::Int,
+            suffix_len: 0,
+        }
+    }
+
+    pub fn suffix(&self) -> &str {
+        &self.spelling[self.spelling.len() - self.suffix_len..]
+    }
+
+    /// The value modulo 2^64 (i.e. the value when it fits in an unsigned long long)
+    pub fn to_u64(&self) -> u64 {
+        self.value.low_u64()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum FloatSuffix {
+    None,
+    F,
+    L,
+    F16,
+    BF16,
+    F32,
+    F64,
+    F128,
+}
+
+lazy_static! {
+    static ref FLOAT_SUFFIXES: HashMap<&'static str, FloatSuffix> = {
+        let mut map = HashMap::with_capacity(20);
+        map.insert("f", FloatSuffix::F);
+        map.insert("F", FloatSuffix::F);
+        map.insert("l", FloatSuffix::L);
+        map.insert("L", FloatSuffix::L);
+        map.insert("f16", FloatSuffix::F16);
+        map.insert("F16", FloatSuffix::F16);
+        map.insert("bf16", FloatSuffix::BF16);
+        map.insert("BF16", FloatSuffix::BF16);
+        map.insert("f32", FloatSuffix::F32);
+        map.insert("F32", FloatSuffix::F32);
+        map.insert("f64", FloatSuffix::F64);
+        map.insert("F64", FloatSuffix::F64);
+        map.insert("f128", FloatSuffix::F128);
+        map.insert("F128", FloatSuffix::F128);
+        // GCC __float128
+        map.insert("q", FloatSuffix::F128);
+        map.insert("Q", FloatSuffix::F128);
+        map
+    };
+}
+
+/// The type of a floating literal
+#[derive(Clone, Debug, PartialEq)]
+pub enum FloatType {
+    Float,
+    Double,
+    LongDouble,
+    Float16,
+    BFloat16,
+    Float32,
+    Float64,
+    Float128,
+    UserDefined(String),
+}
+
+impl FloatType {
+    fn get(suffix: Option<FloatSuffix>, spelling: &str) -> Self {
+        match suffix {
+            Some(FloatSuffix::None) => FloatType::Double,
+            Some(FloatSuffix::F) => FloatType::Float,
+            Some(FloatSuffix::L) => FloatType::LongDouble,
+            Some(FloatSuffix::F16) => FloatType::Float16,
+            Some(FloatSuffix::BF16) => FloatType::BFloat16,
+            Some(FloatSuffix::F32) => FloatType::Float32,
+            Some(FloatSuffix::F64) => FloatType::Float64,
+            Some(FloatSuffix::F128) => FloatType::Float128,
+            None => FloatType::UserDefined(spelling.to_string()),
+        }
+    }
+
+    pub(crate) fn to_str(&self) -> &'static str {
+        match self {
+            FloatType::Float => "float",
+            FloatType::Double => "double",
+            FloatType::LongDouble => "long double",
+            FloatType::Float16 => "_Float16",
+            FloatType::BFloat16 => "__bf16",
+            FloatType::Float32 => "_Float32",
+            FloatType::Float64 => "_Float64",
+            FloatType::Float128 => "_Float128",
+            FloatType::UserDefined(_) => "user-defined",
+        }
+    }
+
+    /// The smallest value rounded to infinity
+    fn get_overflow_threshold(&self) -> Option<f64> {
+        match self {
+            FloatType::Float16 => Some(65520.),
+            FloatType::BFloat16 => Some(3.396_177_529_230_46e38),
+            FloatType::Float | FloatType::Float32 => Some(3.402_823_567_797_336_6e38),
+            FloatType::Double | FloatType::Float64 => Some(f64::INFINITY),
+            _ => None,
+        }
+    }
+}
+
+/// A floating literal: its exact value is mantissa * 10^exponent in decimal
+/// or mantissa * 2^exponent in hexadecimal
+#[derive(Clone, Debug, PartialEq)]
+pub struct FloatNumber {
+    /// The literal as written in the source (with the digit separators and the suffix)
+    pub spelling: String,
+    pub base: Base,
+    pub mantissa: BigUint,
+    pub exponent: i64,
+    pub typ: FloatType,
+    suffix_len: usize,
+}
+
+impl FloatNumber {
+    pub fn suffix(&self) -> &str {
+        &self.spelling[self.spelling.len() - self.suffix_len..]
+    }
+
+    /// The nearest double
+    pub fn to_f64(&self) -> f64 {
+        if self.mantissa.is_zero() {
+            return 0.;
+        }
+
+        if self.base == Base::Dec {
+            // The std parser is correctly rounded
+            format!("{}e{}", self.mantissa, self.exponent)
+                .parse()
+                .unwrap()
+        } else {
+            let (top, shift) = self.mantissa.get_top_u64();
+            ldexp(top as f64, self.exponent + i64::from(shift))
+        }
+    }
+
+    fn is_out_of_range(&self) -> bool {
+        self.typ
+            .get_overflow_threshold()
+            .is_some_and(|threshold| self.to_f64() >= threshold)
+    }
+}
+
+/// x * 2^exp
+fn ldexp(x: f64, exp: i64) -> f64 {
+    let mut x = x;
+    let mut exp = exp;
+    while exp > 1000 {
+        x *= 2f64.powi(1000);
+        exp -= 1000;
+        if x.is_infinite() {
+            return x;
+        }
+    }
+    while exp < -1000 {
+        x *= 2f64.powi(-1000);
+        exp += 1000;
+        if x == 0. {
+            return x;
+        }
+    }
+    x * 2f64.powi(exp as i32)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Number {
+    Int(IntNumber),
+    Float(FloatNumber),
+}
+
+/// A literal which doesn't fit in its type
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Overflow {
+    /// A signed decimal literal which only fits in an unsigned long long
+    Unsigned,
+    /// An integer literal too large for any allowed type
+    TooLarge,
+    /// A floating literal out of the range of its type
+    OutOfRange,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum NumberError {
+    InvalidDigit { digit: char, base: Base },
+    NoDigits { base: Base },
+    NoExponentDigits,
+    MissingHexExponent,
+    InvalidSuffix { suffix: String, float: bool },
+}
+
+impl fmt::Display for NumberError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NumberError::InvalidDigit { digit, base } => {
+                write!(f, "invalid digit '{}' in {} constant", digit, base.to_str())
+            }
+            NumberError::NoDigits { base } => write!(f, "{} constant has no digits", base.to_str()),
+            NumberError::NoExponentDigits => write!(f, "exponent has no digits"),
+            NumberError::MissingHexExponent => {
+                write!(f, "hexadecimal floating constant requires an exponent")
+            }
+            NumberError::InvalidSuffix { suffix, float } => write!(
+                f,
+                "invalid suffix \"{}\" on {} constant",
+                suffix,
+                if *float { "floating" } else { "integer" }
+            ),
+        }
+    }
+}
+
+/// Read the digits (and the separators) valid in the given radix
+fn get_digits(s: &[u8], pos: &mut usize, radix: u32, digits: &mut Vec<u8>) {
+    while let Some(c) = s.get(*pos) {
+        if let Some(d) = char::from(*c).to_digit(radix) {
+            digits.push(d as u8);
+        } else if *c != b'\'' {
+            break;
+        }
+        *pos += 1;
+    }
+}
+
+fn get_value(digits: &[u8], radix: u32) -> BigUint {
+    let mut value = BigUint::default();
+    for d in digits {
+        value.mul_add(radix, u32::from(*d));
+    }
+    value
+}
+
+/// The suffixes of the literal operators of the standard library (chrono, complex)
+const STD_UD_SUFFIXES: &[&str] = &["h", "min", "s", "ms", "us", "ns", "y", "d", "i", "il", "if"];
+
+/// Check if an unknown suffix is a valid ud-suffix: C++ only and starting with an underscore
+/// (the other ones are reserved to the standard library)
+fn is_ud_suffix(suffix: &str, cpp: bool) -> bool {
+    cpp && (suffix.starts_with('_') || STD_UD_SUFFIXES.contains(&suffix))
+}
+
+/// Compute the exact value and the type of a numeric literal from its spelling
+/// (a pp-number starting with a digit or with a dot followed by a digit)
+/// The user-defined literals are only accepted in C++.
+pub(crate) fn parse_number(
+    spelling: &str,
+    cpp: bool,
+) -> Result<(Number, Option<Overflow>), NumberError> {
+    let s = spelling.as_bytes();
+    let (mut base, mut pos) = match s {
+        [b'0', b'x', ..] | [b'0', b'X', ..] => (Base::Hex, 2),
+        [b'0', b'b', ..] | [b'0', b'B', ..] => (Base::Bin, 2),
+        _ => (Base::Dec, 0),
+    };
+    // Octal and binary digits are checked once we know the literal isn't a floating one
+    let radix = if base == Base::Hex { 16 } else { 10 };
+
+    let mut digits = Vec::new();
+    get_digits(s, &mut pos, radix, &mut digits);
+    let n_int = digits.len();
+
+    let mut is_float = false;
+    if base != Base::Bin && s.get(pos) == Some(&b'.') {
+        is_float = true;
+        pos += 1;
+        get_digits(s, &mut pos, radix, &mut digits);
+    }
+    let n_frac = (digits.len() - n_int) as i64;
+
+    if digits.is_empty() {
+        return Err(NumberError::NoDigits { base });
+    }
+
+    let exp_char = if base == Base::Hex { b'p' } else { b'e' };
+    let exponent = match s.get(pos) {
+        Some(c) if base != Base::Bin && c.to_ascii_lowercase() == exp_char => {
+            is_float = true;
+            pos += 1;
+            let sign = match s.get(pos) {
+                Some(b'-') => {
+                    pos += 1;
+                    -1
+                }
+                Some(b'+') => {
+                    pos += 1;
+                    1
+                }
+                _ => 1,
+            };
+            let mut exp_digits = Vec::new();
+            get_digits(s, &mut pos, 10, &mut exp_digits);
+            if exp_digits.is_empty() {
+                return Err(NumberError::NoExponentDigits);
+            }
+            // Saturate: such exponents give 0 or infinity anyway
+            let exp = exp_digits
+                .iter()
+                .fold(0i64, |acc, d| (acc * 10 + i64::from(*d)).min(1 << 40));
+            sign * exp
+        }
+        _ => {
+            if is_float && base == Base::Hex {
+                return Err(NumberError::MissingHexExponent);
+            }
+            0
+        }
+    };
+
+    let suffix = &spelling[pos..];
+
+    if is_float {
+        let (mantissa, exponent) = if base == Base::Hex {
+            (get_value(&digits, 16), exponent - 4 * n_frac)
+        } else {
+            (get_value(&digits, 10), exponent - n_frac)
+        };
+        let suf = if suffix.is_empty() {
+            Some(FloatSuffix::None)
+        } else {
+            FLOAT_SUFFIXES.get(suffix).copied()
+        };
+        if suf.is_none() && !is_ud_suffix(suffix, cpp) {
+            return Err(NumberError::InvalidSuffix {
+                suffix: suffix.to_string(),
+                float: true,
+            });
+        }
+        let number = FloatNumber {
+            spelling: spelling.to_string(),
+            base,
+            mantissa,
+            exponent,
+            typ: FloatType::get(suf, suffix),
+            suffix_len: suffix.len(),
+        };
+        let overflow = if number.is_out_of_range() {
+            Some(Overflow::OutOfRange)
+        } else {
+            None
+        };
+
+        return Ok((Number::Float(number), overflow));
+    }
+
+    if base == Base::Dec && digits.len() > 1 && digits[0] == 0 {
+        base = Base::Oct;
+    }
+
+    let radix = base.radix();
+    if let Some(d) = digits.iter().find(|d| u32::from(**d) >= radix) {
+        return Err(NumberError::InvalidDigit {
+            digit: char::from_digit(u32::from(*d), 10).unwrap(),
+            base,
+        });
+    }
+
+    let value = get_value(&digits, radix);
+    let (typ, overflow) = if suffix.is_empty() {
+        IntType::get(&value, base, IntSuffix::None)
+    } else if let Some(suf) = INT_SUFFIXES.get(suffix) {
+        IntType::get(&value, base, *suf)
+    } else if is_ud_suffix(suffix, cpp) {
+        (IntType::UserDefined(suffix.to_string()), None)
+    } else {
+        return Err(NumberError::InvalidSuffix {
+            suffix: suffix.to_string(),
+            float: false,
+        });
+    };
+
+    Ok((
+        Number::Int(IntNumber {
+            spelling: spelling.to_string(),
+            base,
+            value,
+            typ,
+            suffix_len: suffix.len(),
+        }),
+        overflow,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn int(spelling: &str) -> (IntType, Option<u128>, Option<Overflow>) {
+        if let (Number::Int(n), overflow) = parse_number(spelling, true).unwrap() {
+            (n.typ, n.value.to_u128(), overflow)
+        } else {
+            panic!("Not an integer: {}", spelling);
+        }
+    }
+
+    fn float(spelling: &str) -> FloatNumber {
+        if let (Number::Float(n), _) = parse_number(spelling, true).unwrap() {
+            n
+        } else {
+            panic!("Not a float: {}", spelling);
+        }
+    }
+
+    #[test]
+    fn test_biguint() {
+        let mut n = BigUint::default();
+        for d in "340282366920938463463374607431768211456".bytes() {
+            n.mul_add(10, u32::from(d - b'0'));
+        }
+        assert_eq!(n.bits(), 129);
+        assert_eq!(n.to_u128(), None);
+        assert_eq!(n.low_u64(), 0);
+        assert_eq!(n.to_string(), "340282366920938463463374607431768211456");
+        assert_eq!(BigUint::from(1_000_000_000).to_string(), "1000000000");
+    }
+
+    #[test]
+    fn test_int_types() {
+        assert_eq!(int("2147483647"), (IntType::Int, Some(2147483647), None));
+        assert_eq!(int("2147483648"), (IntType::Long, Some(2147483648), None));
+        assert_eq!(int("0x80000000"), (IntType::UInt, Some(0x8000_0000), None));
+        assert_eq!(
+            int("0xFFFF'FFFF'FFFF'FFFF"),
+            (IntType::ULong, Some(u64::MAX.into()), None)
+        );
+        assert_eq!(
+            int("18446744073709551615"),
+            (
+                IntType::ULongLong,
+                Some(u64::MAX.into()),
+                Some(Overflow::Unsigned)
+            )
+        );
+        assert_eq!(
+            int("18446744073709551616"),
+            (IntType::LongLong, Some(1 << 64), Some(Overflow::TooLarge))
+        );
+        assert_eq!(int("0xFFu"), (IntType::UInt, Some(255), None));
+        assert_eq!(int("1LL"), (IntType::LongLong, Some(1), None));
+        assert_eq!(
+            int("18446744073709551616ui128"),
+            (IntType::UInt128, Some(1 << 64), None)
+        );
+        assert_eq!(int("3wb"), (IntType::BitInt(3), Some(3), None));
+        assert_eq!(int("0uwb"), (IntType::UBitInt(1), Some(0), None));
+        assert_eq!(
+            int("12_km"),
+            (IntType::UserDefined("_km".to_string()), Some(12), None)
+        );
+    }
+
+    #[test]
+    fn test_int_spelling() {
+        if let (Number::Int(n), _) = parse_number("0X1'fF'00ull", true).unwrap() {
+            assert_eq!(n.base, Base::Hex);
+            assert_eq!(n.suffix(), "ull");
+            assert_eq!(n.to_u64(), 0x1ff00);
+        } else {
+            panic!();
+        }
+
+        if let (Number::Int(n), _) = parse_number("0777", true).unwrap() {
+            assert_eq!(n.base, Base::Oct);
+            assert_eq!(n.to_u64(), 0o777);
+        } else {
+            panic!();
+        }
+    }
+
+    #[test]
+    fn test_float_exact() {
+        let n = float("1.79769313486231570814527423731704357e+308L");
+        assert_eq!(n.typ, FloatType::LongDouble);
+        assert_eq!(
+            n.mantissa.to_string(),
+            "179769313486231570814527423731704357"
+        );
+        assert_eq!(n.exponent, 273);
+        assert_eq!(n.to_f64(), 1.797_693_134_862_315_7e308);
+
+        let n = float("0x1.8p-3f16");
+        assert_eq!(n.typ, FloatType::Float16);
+        assert_eq!(n.base, Base::Hex);
+        assert_eq!(n.mantissa.to_string(), "24");
+        assert_eq!(n.exponent, -7);
+        assert_eq!(n.to_f64(), 0.1875);
+
+        assert_eq!(float("1e-400").to_f64(), 0.);
+        assert_eq!(float("0x1p-1074").to_f64(), 5e-324);
+        assert_eq!(float("1.5bf16").typ, FloatType::BFloat16);
+        assert_eq!(float("1.5F128").typ, FloatType::Float128);
+        assert_eq!(float("09.5").to_f64(), 9.5);
+    }
+
+    #[test]
+    fn test_number_errors() {
+        assert_eq!(
+            parse_number("0789", true),
+            Err(NumberError::InvalidDigit {
+                digit: '8',
+                base: Base::Oct
+            })
+        );
+        assert_eq!(
+            parse_number("0b102", true),
+            Err(NumberError::InvalidDigit {
+                digit: '2',
+                base: Base::Bin
+            })
+        );
+        assert_eq!(
+            parse_number("0x", true),
+            Err(NumberError::NoDigits { base: Base::Hex })
+        );
+        assert_eq!(
+            parse_number("1e+", true),
+            Err(NumberError::NoExponentDigits)
+        );
+        assert_eq!(
+            parse_number("0x1.8", true),
+            Err(NumberError::MissingHexExponent)
+        );
+        assert_eq!(
+            parse_number("1e39f", true).unwrap().1,
+            Some(Overflow::OutOfRange)
+        );
+        assert_eq!(parse_number("1e308", true).unwrap().1, None);
+
+        // The user-defined literals are C++ only and their suffix starts with an underscore
+        assert!(parse_number("12_km", true).is_ok());
+        assert!(parse_number("10ms", true).is_ok());
+        assert_eq!(
+            parse_number("12_km", false),
+            Err(NumberError::InvalidSuffix {
+                suffix: "_km".to_string(),
+                float: false
+            })
+        );
+        assert_eq!(
+            parse_number("12km", true),
+            Err(NumberError::InvalidSuffix {
+                suffix: "km".to_string(),
+                float: false
+            })
+        );
+        assert_eq!(
+            parse_number("1.5xyz", true),
+            Err(NumberError::InvalidSuffix {
+                suffix: "xyz".to_string(),
+                float: true
+            })
+        );
+    }
+}
@@ -4,7 +4,9 @@
 // copied, modified, or distributed except according to those terms.
 
 use super::context::PreprocContext;
-use crate::lexer::lexer::{Lexer, Token};
+use crate::lexer::errors::LexerError;
+use crate::lexer::lexer::{Lexer, TLexer, Token};
+use crate::toolchain::Capability;
 
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Operator {
@@ -491,6 +493,25 @@ fn precedence(op: Operator) -> (u32, Associativity) {
     }
 }
 
+/// The identifiers which aren't replaced by 0 in a condition
+#[derive(Clone, Copy)]
+enum IdOperator {
+    Defined,
+    HasInclude { next: bool },
+    Capability(Capability),
+}
+
+impl IdOperator {
+    fn new(id: &str) -> Option<Self> {
+        match id {
+            "defined" => Some(IdOperator::Defined),
+            "__has_include" => Some(IdOperator::HasInclude { next: false }),
+            "__has_include_next" => Some(IdOperator::HasInclude { next: true }),
+            _ => Capability::from_operator(id).map(IdOperator::Capability),
+        }
+    }
+}
+
 #[inline(always)]
 fn check_precedence(left: Operator, right: Operator) -> bool {
     // a + b * c => prec(*) < prec(+) so * has precedence on +
@@ -509,6 +530,11 @@ pub struct Condition<'a, 'b, PC: PreprocContext> {
     operands: Vec<Int>,
     operators: Vec<Operator>,
     last: LastKind,
+    // The number of open parenthesis and of ? waiting for a :
+    parens: usize,
+    questions: usize,
+    // The first error in the expression: the rest of the line is skipped
+    error: Option<LexerError>,
 }
 
 impl<'a, 'b, PC: PreprocContext> Condition<'a, 'b, PC> {
@@ -518,11 +544,63 @@ impl<'a, 'b, PC: PreprocContext> Condition<'a, 'b, PC> {
             operands: Vec::with_capacity(16),
             operators: Vec::with_capacity(16),
             last: LastKind::Operator,
+            parens: 0,
+            questions: 0,
+            error: None,
+        }
+    }
+
+    /// Check that the last thing pushed was an operand or an operator
+    #[inline(always)]
+    fn check_last(&mut self, expected: LastKind) -> bool {
+        if self.last == expected {
+            return true;
+        }
+        if self.error.is_none() {
+            let sp = self.lexer.span();
+            self.error = Some(if expected == LastKind::Operand {
+                LexerError::ExpectedValueInExpression { sp }
+            } else {
+                LexerError::InvalidTokenInExpression { sp }
+            });
+        }
+        false
+    }
+
+    #[inline(always)]
+    fn invalid_token(&mut self) {
+        if self.error.is_none() {
+            self.error = Some(LexerError::InvalidTokenInExpression {
+                sp: self.lexer.span(),
+            });
+        }
+    }
+
+    /// Report the error and skip the rest of the condition which is evaluated to 0
+    fn skip_invalid(&mut self, err: LexerError) -> Int {
+        self.lexer.errors.push(err);
+        loop {
+            let tok = self.lexer.next_token();
+            if tok == Token::Eol || tok == Token::Eof {
+                return Int::Unsigned(0);
+            }
         }
     }
 
     #[inline(always)]
     fn push_operator(&mut self, op: Operator) {
+        let unary = matches!(
+            op,
+            Operator::Plus | Operator::Minus | Operator::Not | Operator::BitNeg
+        );
+        if !self.check_last(if unary {
+            LastKind::Operator
+        } else {
+            LastKind::Operand
+        }) {
+            return;
+        }
+
         loop {
             if let Some(top) = self.operators.last() {
                 if *top != Operator::Parenthesis && check_precedence(*top, op) {
@@ -539,6 +617,10 @@ impl<'a, 'b, PC: PreprocContext> Condition<'a, 'b, PC> {
 
     #[inline(always)]
     fn push_and(&mut self) {
+        if !self.check_last(LastKind::Operand) {
+            return;
+        }
+
         loop {
             if let Some(top) = self.operators.last() {
                 if *top != Operator::Parenthesis && check_precedence(*top, Operator::And) {
@@ -562,6 +644,10 @@ impl<'a, 'b, PC: PreprocContext> Condition<'a, 'b, PC> {
 
     #[inline(always)]
     fn push_or(&mut self) {
+        if !self.check_last(LastKind::Operand) {
+            return;
+        }
+
         loop {
             if let Some(top) = self.operators.last() {
                 if *top != Operator::Parenthesis && check_precedence(*top, Operator::Or) {
@@ -613,25 +699,49 @@ impl<'a, 'b, PC: PreprocContext> Condition<'a, 'b, PC> {
         }
     }
 
+    #[inline(always)]
+    fn push_operand(&mut self, x: Int) {
+        if self.check_last(LastKind::Operator) {
+            self.operands.push(x);
+            self.last = LastKind::Operand;
+        }
+    }
+
     #[inline(always)]
     fn handle_id(&mut self, id: &str) {
-        if id == "defined" {
-            let x = self.lexer.get_defined(self.is_lazy_op());
-            self.operands.push(Int::Unsigned(x));
-        } else {
-            self.operands.push(Int::Unsigned(0));
+        self.handle_id_operator(IdOperator::new(id));
+    }
+
+    fn handle_id_operator(&mut self, op: Option<IdOperator>) {
+        let lazy = self.is_lazy_op();
+        let x = match op {
+            Some(IdOperator::Defined) => Ok(self.lexer.get_defined(lazy)),
+            Some(IdOperator::HasInclude { next }) => self.lexer.get_has_include(next, lazy),
+            Some(IdOperator::Capability(kind)) => self.lexer.get_has_capability(kind, lazy),
+            None => Ok(0),
+        };
+        match x {
+            Ok(x) => self.push_operand(Int::Unsigned(x)),
+            Err(err) => {
+                if self.error.is_none() {
+                    self.error = Some(err);
+                }
+                self.push_zero();
+            }
         }
-        self.last = LastKind::Operand;
     }
 
     #[inline(always)]
     fn push_zero(&mut self) {
-        self.operands.push(Int::Unsigned(0));
-        self.last = LastKind::Operand;
+        self.push_operand(Int::Unsigned(0));
     }
 
     fn eval(&mut self) -> Int {
         loop {
+            if let Some(err) = self.error.take() {
+                return self.skip_invalid(err);
+            }
+
             let tok = self.lexer.next_token();
             match tok {
                 Token::Plus => {
@@ -695,8 +805,7 @@ impl<'a, 'b, PC: PreprocContext> Condition<'a, 'b, PC> {
                 | Token::LiteralUUChar(x)
                 | Token::LiteralUChar(x)
                 | Token::LiteralU8Char(x) => {
-                    self.operands.push(Int::Unsigned(x as u64));
-                    self.last = LastKind::Operand;
+                    self.push_operand(Int::Unsigned(x as u64));
                 }
                 Token::Xor => {
                     self.push_operator(Operator::BitXor);
@@ -722,25 +831,25 @@ impl<'a, 'b, PC: PreprocContext> Condition<'a, 'b, PC> {
                     if self.is_lazy_op() {
                         self.lexer.skip_until_matching_paren();
                         self.push_zero();
-                    } else {
+                    } else if self.check_last(LastKind::Operator) {
                         self.operators.push(Operator::Parenthesis);
-                        self.last = LastKind::Operator;
+                        self.parens += 1;
                     }
                 }
                 Token::RightParen => {
-                    self.flush_until_paren();
+                    if self.parens == 0 {
+                        self.invalid_token();
+                    } else if self.check_last(LastKind::Operand) {
+                        self.flush_until_paren();
+                        self.parens -= 1;
+                    }
                 }
-                Token::LiteralInt(x)
-                | Token::LiteralUInt(x)
-                | Token::LiteralLong(x)
-                | Token::LiteralLongLong(x)
-                | Token::LiteralULong(x)
-                | Token::LiteralULongLong(x) => {
-                    self.operands.push(Int::Unsigned(x));
-                    self.last = LastKind::Operand;
+                Token::LiteralInteger(x) => {
+                    self.push_operand(Int::Unsigned(x.to_u64()));
                 }
                 Token::Identifier(id) => {
-                    self.handle_id(&id);
+                    let op = IdOperator::new(self.lexer.resolve(id));
+                    self.handle_id_operator(op);
                 }
                 Token::AndKw => {
                     if self.last == LastKind::Operand {
@@ -785,13 +894,11 @@ impl<'a, 'b, PC: PreprocContext> Condition<'a, 'b, PC> {
                     }
                 }
                 Token::NotKw => {
-                    // TODO: this is not correct
-                    // we need to check next token to decide
+                    // In C, not is lexed as an identifier so here it's an operator
                     self.push_operator(Operator::Not);
                 }
                 Token::Compl => {
-                    // TODO: this is not correct
-                    // we need to check next token to decide
+                    // In C, compl is lexed as an identifier so here it's an operator
                     self.push_operator(Operator::BitNeg);
                 }
                 Token::True => {
@@ -803,22 +910,40 @@ impl<'a, 'b, PC: PreprocContext> Condition<'a, 'b, PC> {
                 Token::Comment => {}
                 Token::Question => {
                     self.push_operator(Operator::Question);
+                    self.questions += 1;
                 }
                 Token::Colon => {
-                    self.push_operator(Operator::Colon);
+                    if self.questions == 0 {
+                        self.invalid_token();
+                    } else {
+                        self.questions -= 1;
+                        self.push_operator(Operator::Colon);
+                    }
                 }
                 Token::Eol | Token::Eof => {
+                    // The line is finished so nothing to skip
+                    if self.last == LastKind::Operator {
+                        self.lexer
+                            .errors
+                            .push(LexerError::ExpectedValueInExpression {
+                                sp: self.lexer.span(),
+                            });
+                        return Int::Unsigned(0);
+                    }
+                    if self.parens != 0 {
+                        self.lexer
+                            .errors
+                            .push(LexerError::MissingParenInExpression {
+                                sp: self.lexer.span(),
+                            });
+                        return Int::Unsigned(0);
+                    }
                     self.flush();
                     return self.operands.pop().unwrap();
                 }
                 _ => {
-                    unreachable!(
-                        "Got token {:?} at line ?? in file {:?}",
-                        tok,
-                        self.lexer
-                            .context
-                            .get_path(self.lexer.buf.get_source_id().unwrap())
-                    );
+                    // A string, a stray backslash, a keyword...
+                    self.invalid_token();
                 }
             }
         }
@@ -834,6 +959,7 @@ mod tests {
 
     use super::*;
     use crate::lexer::preprocessor::context::DefaultContext;
+    use crate::toolchain::Capabilities;
     use pretty_assertions::assert_eq;
 
     #[test]
@@ -1014,4 +1140,107 @@ mod tests {
 
         assert_eq!(res, Int::Unsigned(0));
     }
+
+    #[test]
+    fn test_condition_errors() {
+        let inputs: &[(&[u8], &str)] = &[
+            (b"\nint", "expected value in expression"),
+            (b"1 +\nint", "expected value in expression"),
+            (b"1 * (2 +) 3\nint", "expected value in expression"),
+            (b"(1\nint", "missing ')' in expression"),
+            (b"1 2\nint", "invalid token in preprocessor expression"),
+            (b"(1))\nint", "invalid token in preprocessor expression"),
+            (b"1 : 2\nint", "invalid token in preprocessor expression"),
+            (
+                b"\"a\" + 1\nint",
+                "invalid token in preprocessor expression",
+            ),
+            (b"__has_attribute\nint", "missing '(' after __has_attribute"),
+            (
+                b"__has_feature()\nint",
+                "__has_feature requires an identifier",
+            ),
+            (
+                b"__has_builtin(a b)\nint",
+                "missing ')' after __has_builtin",
+            ),
+            (
+                b"__has_include(<a.h>\nint",
+                "missing ')' after __has_include",
+            ),
+            (
+                b"__has_include_next(a.h)\nint",
+                "expected \"FILENAME\" or <FILENAME> in #include",
+            ),
+        ];
+
+        for (input, msg) in inputs {
+            let mut lexer = Lexer::<DefaultContext>::new(input);
+            let mut cond = Condition::new(&mut lexer);
+            let res = cond.eval();
+
+            assert_eq!(res, Int::Unsigned(0));
+            assert_eq!(lexer.next_token(), Token::Int);
+            let errors = lexer.get_errors();
+            assert_eq!(errors.len(), 1);
+            assert_eq!(errors[0].stringly().message, *msg);
+        }
+    }
+
+    #[test]
+    fn test_condition_capabilities() {
+        let caps = Capabilities::parse(concat!(
+            "attribute always_inline\n",
+            "cpp_attribute nodiscard 201907\n",
+            "builtin __builtin_expect\n",
+            "feature cxx_rtti\n",
+        ));
+        let inputs: &[(&[u8], bool)] = &[
+            (b"__has_attribute(always_inline)", true),
+            (b"__has_attribute(__always_inline__)", true),
+            (b"__has_attribute(packed)", false),
+            (b"__has_cpp_attribute(nodiscard) >= 201603L", true),
+            (b"__has_cpp_attribute(nodiscard) > 201907", false),
+            (b"__has_cpp_attribute( gnu :: always_inline )", true),
+            (b"__has_builtin(__builtin_expect)", true),
+            (b"__has_builtin(__builtin_trap)", false),
+            (
+                b"__has_feature(cxx_rtti) && __has_extension(cxx_rtti)",
+                true,
+            ),
+            (b"__has_feature(cxx_exceptions)", false),
+            (
+                b"defined(__has_builtin) && defined __has_cpp_attribute",
+                true,
+            ),
+            (b"0 && __has_attribute(always_inline)", false),
+        ];
+
+        for (input, expected) in inputs {
+            let mut lexer = Lexer::<DefaultContext>::new(input);
+            lexer.context.set_capabilities(caps.clone());
+            let mut cond = Condition::new(&mut lexer);
+            let res = cond.eval_as_bool();
+
+            assert_eq!(res, *expected, "{}", std::str::from_utf8(input).unwrap());
+            assert!(lexer.get_errors().is_empty());
+        }
+    }
+
+    #[test]
+    fn test_condition_default_capabilities() {
+        let inputs: &[&[u8]] = &[
+            b"__has_builtin(__builtin_expect)",
+            b"__has_attribute(always_inline)",
+        ];
+
+        for input in inputs {
+            let mut lexer = Lexer::<DefaultContext>::new(input);
+            let mut cond = Condition::new(&mut lexer);
+            let res = cond.eval_as_bool();
+
+            assert!(res, "{}", std::str::from_utf8(input).unwrap());
+            assert!(lexer.get_errors().is_empty());
+        }
+    }
 }
@@ -3,17 +3,20 @@
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
-use hashbrown::HashMap;
+use hashbrown::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
 use super::cache::IfCache;
-use super::include::{DefaultIncludeLocator, IncludeLocator, PathIndex};
+use super::include::{DefaultIncludeLocator, IncludeLocator, IncludePaths, PathIndex};
 use super::macros::{
     Macro, MacroCounter, MacroFile, MacroFunction, MacroLine, MacroObject, MacroType,
 };
+use super::pragma::{Pragma, PragmaState};
 use crate::lexer::buffer::{BufferData, Position};
+use crate::lexer::encoding::Encoding;
 use crate::lexer::source::{FileId, SourceMutex};
+use crate::toolchain::{self, Capabilities};
 
 /// Indicate the state of the if statement
 /// Eval: indicates that we're evaluating the tokens
@@ -70,6 +73,63 @@ pub trait PreprocContext: Default + IncludeLocator {
 
     fn new_with_if_cache(if_cache: Arc<IfCache>) -> Self;
 
+    /// Mark the file as included only once (#pragma once)
+    fn set_once(&mut self, _file: FileId) {}
+
+    /// Check if the file contains a #pragma once
+    fn is_once(&self, _file: FileId) -> bool {
+        false
+    }
+
+    /// Save the macro guarding the file: the whole file is in a #ifndef NAME ... #endif
+    fn set_guard(&mut self, _file: FileId, _name: String) {}
+
+    /// Get the macro guarding the file (if one has been found)
+    fn get_guard(&self, _file: FileId) -> Option<&str> {
+        None
+    }
+
+    /// Check if including the file again is useless:
+    /// it has a #pragma once or its guard is still defined
+    fn skip_include(&mut self, file: FileId) -> bool {
+        if self.is_once(file) {
+            return true;
+        }
+        if let Some(name) = self.get_guard(file) {
+            let name = name.to_string();
+            self.defined(&name)
+        } else {
+            false
+        }
+    }
+
+    /// Called on each pragma (#pragma, _Pragma or __pragma) once the built-in ones
+    /// (once, push_macro, pop_macro, pack and GCC system_header, poison, warning, error)
+    /// have been handled
+    fn pragma(&mut self, _pragma: &Pragma) {}
+
+    /// Get what has been set by the built-in pragmas
+    fn get_pragmas(&self) -> Option<&PragmaState> {
+        None
+    }
+
+    fn get_pragmas_mut(&mut self) -> Option<&mut PragmaState> {
+        None
+    }
+
+    /// Save the current definition of the macro (#pragma push_macro)
+    fn push_macro(&mut self, _name: &str) {}
+
+    /// Restore the last saved definition of the macro (#pragma pop_macro)
+    fn pop_macro(&mut self, _name: &str) {}
+
+    /// Set the table used to evaluate __has_attribute, __has_builtin, ...
+    fn set_capabilities(&mut self, _caps: Capabilities) {}
+
+    fn get_capabilities(&self) -> Option<&Capabilities> {
+        None
+    }
+
     fn toto(&self) -> Vec<IfState> {
         Vec::new()
     }
@@ -125,7 +185,7 @@ impl IncludeLocator for EmptyContext {
         _next: bool,
         _current: FileId,
         _path_index: PathIndex,
-    ) -> Option<BufferData> {
+    ) -> Option<BufferData<'static>> {
         None
     }
 
@@ -156,27 +216,44 @@ pub struct Context<IL: IncludeLocator> {
     if_cache: Arc<IfCache>,
     include: IL,
     buffer: Option<()>,
+    /// The files with a #pragma once (unique ids)
+    once: HashSet<FileId>,
+    /// The include guards (unique ids)
+    guards: HashMap<FileId, String>,
+    pragmas: PragmaState,
+    /// The definitions saved by #pragma push_macro (None when the macro wasn't defined)
+    pushed_macros: HashMap<String, Vec<Option<Macro>>>,
+    /// None means toolchain::default_capabilities()
+    capabilities: Option<Capabilities>,
 }
 
 pub type DefaultContext = Context<DefaultIncludeLocator>;
 
+/// The macros whose value depends on the location: __LINE__, __FILE__ and __COUNTER__
+fn get_builtin_macros() -> HashMap<String, Macro> {
+    let mut map = HashMap::default();
+    map.insert("__LINE__".to_string(), Macro::Line(MacroLine::new()));
+    map.insert("__FILE__".to_string(), Macro::File(MacroFile::new()));
+    map.insert(
+        "__COUNTER__".to_string(),
+        Macro::Counter(MacroCounter::new()),
+    );
+    map
+}
+
 impl<IL: IncludeLocator> Default for Context<IL> {
     fn default() -> Self {
         Self {
-            macros: {
-                let mut map = HashMap::default();
-                map.insert("__LINE__".to_string(), Macro::Line(MacroLine::new()));
-                map.insert("__FILE__".to_string(), Macro::File(MacroFile::new()));
-                map.insert(
-                    "__COUNTER__".to_string(),
-                    Macro::Counter(MacroCounter::new()),
-                );
-                map
-            },
+            macros: get_builtin_macros(),
             if_stack: Vec::new(),
             if_cache: Arc::new(IfCache::default()),
             include: IL::default(),
             buffer: None,
+            once: HashSet::default(),
+            guards: HashMap::default(),
+            pragmas: PragmaState::default(),
+            pushed_macros: HashMap::default(),
+            capabilities: None,
         }
     }
 }
@@ -189,6 +266,20 @@ impl<IL: IncludeLocator> Context<IL> {
             if_cache: Arc::new(IfCache::default()),
             include,
             buffer: None,
+            once: HashSet::default(),
+            guards: HashMap::default(),
+            pragmas: PragmaState::default(),
+            pushed_macros: HashMap::default(),
+            capabilities: None,
+        }
+    }
+
+    /// The id 0 isn't a real file (a buffer or no source)
+    fn get_file_key(&self, file: FileId) -> Option<FileId> {
+        if file.0 == 0 {
+            None
+        } else {
+            Some(self.include.get_unique_id(file))
         }
     }
 }
@@ -274,14 +365,97 @@ impl<IL: IncludeLocator> PreprocContext for Context<IL> {
 
     fn new_with_if_cache(if_cache: Arc<IfCache>) -> Self {
         Self {
-            macros: HashMap::default(),
+            macros: get_builtin_macros(),
             if_stack: Vec::new(),
             if_cache,
             include: IL::default(),
             buffer: None,
+            once: HashSet::default(),
+            guards: HashMap::default(),
+            pragmas: PragmaState::default(),
+            pushed_macros: HashMap::default(),
+            capabilities: None,
         }
     }
 
+    fn set_once(&mut self, file: FileId) {
+        if let Some(file) = self.get_file_key(file) {
+            self.once.insert(file);
+        }
+    }
+
+    fn is_once(&self, file: FileId) -> bool {
+        self.get_file_key(file)
+            .is_some_and(|file| self.once.contains(&file))
+    }
+
+    fn set_guard(&mut self, file: FileId, name: String) {
+        if let Some(file) = self.get_file_key(file) {
+            self.guards.insert(file, name);
+        }
+    }
+
+    fn get_guard(&self, file: FileId) -> Option<&str> {
+        let file = self.get_file_key(file)?;
+        self.guards.get(&file).map(|name| name.as_str())
+    }
+
+    fn skip_include(&mut self, file: FileId) -> bool {
+        let file = match self.get_file_key(file) {
+            Some(file) => file,
+            None => return false,
+        };
+        self.once.contains(&file)
+            || self
+                .guards
+                .get(&file)
+                .is_some_and(|name| self.macros.contains_key(name))
+    }
+
+    fn get_pragmas(&self) -> Option<&PragmaState> {
+        Some(&self.pragmas)
+    }
+
+    fn get_pragmas_mut(&mut self) -> Option<&mut PragmaState> {
+        Some(&mut self.pragmas)
+    }
+
+    fn push_macro(&mut self, name: &str) {
+        let mac = self.macros.get(name).cloned();
+        self.pushed_macros
+            .entry(name.to_string())
+            .or_default()
+            .push(mac);
+    }
+
+    fn pop_macro(&mut self, name: &str) {
+        match self
+            .pushed_macros
+            .get_mut(name)
+            .and_then(|stack| stack.pop())
+        {
+            Some(Some(mac)) => {
+                self.macros.insert(name.to_string(), mac);
+            }
+            Some(None) => {
+                self.macros.remove(name);
+            }
+            None => {}
+        }
+    }
+
+    fn set_capabilities(&mut self, caps: Capabilities) {
+        self.capabilities = Some(caps);
+    }
+
+    fn get_capabilities(&self) -> Option<&Capabilities> {
+        Some(
+            self.capabilities
+                .as_ref()
+                .unwrap_or_else(|| toolchain::default_capabilities()),
+        )
+    }
+
     fn toto(&self) -> Vec<IfState> {
         self.if_stack.clone()
     }
@@ -295,10 +469,21 @@ impl<IL: IncludeLocator> IncludeLocator for Context<IL> {
         next: bool,
         current: FileId,
         path_index: PathIndex,
-    ) -> Option<BufferData> {
+    ) -> Option<BufferData<'static>> {
         self.include.find(angle, path, next, current, path_index)
     }
 
+    fn exists(
+        &mut self,
+        angle: bool,
+        path: &str,
+        next: bool,
+        current: FileId,
+        path_index: PathIndex,
+    ) -> bool {
+        self.include.exists(angle, path, next, current, path_index)
+    }
+
     fn get_id(&mut self, path: &PathBuf) -> FileId {
         self.include.get_id(path)
     }
@@ -314,4 +499,16 @@ impl<IL: IncludeLocator> IncludeLocator for Context<IL> {
     fn set_sys_paths<P: AsRef<Path>>(&mut self, paths: &[P]) {
         self.include.set_sys_paths(paths);
     }
+
+    fn set_include_paths(&mut self, paths: &IncludePaths) {
+        self.include.set_include_paths(paths);
+    }
+
+    fn set_charset(&mut self, charset: Encoding) {
+        self.include.set_charset(charset);
+    }
+
+    fn get_unique_id(&self, id: FileId) -> FileId {
+        self.include.get_unique_id(id)
+    }
 }
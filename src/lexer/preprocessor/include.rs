@@ -3,23 +3,42 @@
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
-use std::fs::{self, File};
-use std::io::Read;
 use std::path::{Path, PathBuf};
 
 use crate::lexer::buffer::BufferData;
+use crate::lexer::encoding::Encoding;
 use crate::lexer::errors::LexerError;
 use crate::lexer::lexer::{Lexer, TLexer};
 use crate::lexer::preprocessor::PreprocContext;
-use crate::lexer::source::{FileId, SourceMutex};
+use crate::lexer::source::{self, FileContent, FileId, SourceMutex};
 
+/// Position + 1 in the search chain of the directory where a file has been found
+/// (0 when the file hasn't been found using the chain)
 #[derive(Debug, Clone, Copy, Default)]
 pub struct PathIndex(pub usize);
 
+/// The different kinds of include directories
+/// The search chain is made of quote, angled, system and after paths (in this order).
+/// A quoted include starts with the current directory and then uses the full chain,
+/// an angled include starts with the angled paths.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct IncludePaths {
+    /// -iquote
+    pub quote: Vec<PathBuf>,
+    /// -I
+    pub angled: Vec<PathBuf>,
+    /// -isystem and the default system paths
+    pub system: Vec<PathBuf>,
+    /// -idirafter
+    pub after: Vec<PathBuf>,
+}
+
 #[derive(Clone, Copy, Debug, PartialEq)]
 enum IncludeType<'a> {
     Quote(&'a str),
     Angle(&'a str),
+    /// A path which isn't valid UTF-8
+    Invalid(&'a [u8]),
     Other,
 }
 
@@ -31,17 +50,50 @@ pub trait IncludeLocator: Default {
         next: bool,
         current: FileId,
         path_index: PathIndex,
-    ) -> Option<BufferData>;
+    ) -> Option<BufferData<'static>>;
+
+    /// Check if a file can be included without loading it
+    fn exists(
+        &mut self,
+        angle: bool,
+        path: &str,
+        next: bool,
+        current: FileId,
+        path_index: PathIndex,
+    ) -> bool {
+        self.find(angle, path, next, current, path_index).is_some()
+    }
+
     fn get_id(&mut self, path: &PathBuf) -> FileId;
     fn get_path(&self, id: FileId) -> PathBuf;
     fn set_source(&mut self, source: SourceMutex);
     fn set_sys_paths<P: AsRef<Path>>(&mut self, paths: &[P]);
+
+    /// Set the encoding of the included files without a BOM
+    fn set_charset(&mut self, _charset: Encoding) {}
+
+    /// Get the same id for the different paths leading to the same file
+    fn get_unique_id(&self, id: FileId) -> FileId {
+        id
+    }
+
+    fn set_include_paths(&mut self, paths: &IncludePaths) {
+        let paths: Vec<_> = paths
+            .angled
+            .iter()
+            .chain(paths.system.iter())
+            .chain(paths.after.iter())
+            .collect();
+        self.set_sys_paths(&paths);
+    }
 }
 
 #[derive(Clone, Debug, Default)]
 pub struct DefaultIncludeLocator {
     sys: Vec<PathBuf>,
+    angled_start: usize,
     source: Option<SourceMutex>,
+    charset: Encoding,
 }
 
 impl DefaultIncludeLocator {
@@ -50,95 +102,106 @@ impl DefaultIncludeLocator {
 
         Self {
             sys,
+            angled_start: 0,
             source: Some(source),
+            charset: Encoding::Utf8,
         }
     }
 
-    fn read_file(path: &PathBuf) -> Option<Vec<u8>> {
-        //eprintln!("PATH {:?}", path);
-        let file_size = fs::metadata(&path).map_or(1024 * 1024, |m| m.len() as usize);
-        if let Ok(mut file) = File::open(path) {
-            let mut data = Vec::with_capacity(file_size + 1);
-            file.read_to_end(&mut data).unwrap();
-            Some(data)
-        } else {
-            None
-        }
+    pub fn new_with_paths(paths: &IncludePaths, source: SourceMutex) -> Self {
+        let mut locator = Self::default();
+        locator.set_include_paths(paths);
+        locator.source = Some(source);
+        locator
     }
 
-    fn get_file(file: PathBuf, source: Option<&SourceMutex>, path_index: PathIndex) -> BufferData {
+    fn get_file(&self, file: PathBuf, path_index: PathIndex) -> Option<BufferData<'static>> {
         // TODO: how to deal with that
         //let file = std::fs::canonicalize(file).unwrap();
-        let id = source
-            .as_ref()
-            .map_or(FileId(0), |s| s.lock().unwrap().get_id(&file));
-        BufferData::new(Self::read_file(&file).unwrap(), id, path_index)
+        let (id, content) = if let Some(source) = self.source.as_ref() {
+            source::load_file(source, &file).ok()?
+        } else {
+            (FileId(0), FileContent::read(&file).ok()?)
+        };
+        Some(BufferData::decode(content, self.charset, id, path_index))
     }
 
-    fn find_angle(&self, path: &str, next: bool, path_index: PathIndex) -> Option<BufferData> {
+    /// Get the path of an included file and its index in the search chain
+    fn resolve(
+        &self,
+        angle: bool,
+        path: &str,
+        next: bool,
+        current: FileId,
+        path_index: PathIndex,
+    ) -> Option<(PathBuf, PathIndex)> {
+        if angle {
+            self.resolve_angle(path, next, path_index)
+        } else {
+            self.resolve_quote(path, next, current, path_index)
+        }
+    }
+
+    fn resolve_angle(
+        &self,
+        path: &str,
+        next: bool,
+        path_index: PathIndex,
+    ) -> Option<(PathBuf, PathIndex)> {
         let path = PathBuf::from(path);
 
         if path.is_absolute() {
-            return Some(Self::get_file(path, self.source.as_ref(), PathIndex(0)));
+            return Some((path, PathIndex(0)));
         }
 
-        let index = if next { path_index.0 + 1 } else { 0 };
+        // include_next in a file which hasn't been found in the chain is just an include
+        let index = if next && path_index.0 != 0 {
+            path_index.0
+        } else {
+            self.angled_start
+        };
 
-        for (n, dir) in self.sys.get(index..).unwrap().iter().enumerate() {
-            let file = dir.join(&path);
+        self.find_in_chain(&path, index)
+    }
+
+    fn find_in_chain(&self, path: &Path, index: usize) -> Option<(PathBuf, PathIndex)> {
+        for (n, dir) in self.sys.get(index..).unwrap_or(&[]).iter().enumerate() {
+            let file = dir.join(path);
             if file.is_file() {
-                return Some(Self::get_file(
-                    file,
-                    self.source.as_ref(),
-                    PathIndex(index + n + 1),
-                ));
+                return Some((file, PathIndex(index + n + 1)));
             }
         }
 
         None
     }
 
-    fn find_quote(
+    fn resolve_quote(
         &self,
         path: &str,
         next: bool,
         current: FileId,
         path_index: PathIndex,
-    ) -> Option<BufferData> {
+    ) -> Option<(PathBuf, PathIndex)> {
         let path = PathBuf::from(path);
 
         if path.is_absolute() {
-            return Some(Self::get_file(path, self.source.as_ref(), PathIndex(0)));
+            return Some((path, PathIndex(0)));
         }
 
-        let index = if next { path_index.0 + 1 } else { 0 };
-
-        let index = if index == 0 {
-            if current.0 != 0 {
-                let current = self.get_path(current);
-                let current = current.parent().unwrap().to_path_buf();
-                let file = current.join(&path);
-                if file.is_file() {
-                    return Some(Self::get_file(file, self.source.as_ref(), PathIndex(0)));
-                }
-            }
-            1
-        } else {
-            index
-        };
+        if next && path_index.0 != 0 {
+            return self.find_in_chain(&path, path_index.0);
+        }
 
-        for (n, dir) in self.sys.get(index - 1..).unwrap().iter().enumerate() {
-            let file = dir.join(&path);
+        if current.0 != 0 {
+            let current = self.get_path(current);
+            let current = current.parent().unwrap().to_path_buf();
+            let file = current.join(&path);
             if file.is_file() {
-                return Some(Self::get_file(
-                    file,
-                    self.source.as_ref(),
-                    PathIndex(index + n),
-                ));
+                return Some((file, PathIndex(0)));
             }
         }
 
-        None
+        self.find_in_chain(&path, 0)
     }
 }
 
@@ -150,12 +213,21 @@ impl IncludeLocator for DefaultIncludeLocator {
         next: bool,
         current: FileId,
         path_index: PathIndex,
-    ) -> Option<BufferData> {
-        if angle {
-            self.find_angle(path, next, path_index)
-        } else {
-            self.find_quote(path, next, current, path_index)
-        }
+    ) -> Option<BufferData<'static>> {
+        let (file, path_index) = self.resolve(angle, path, next, current, path_index)?;
+        self.get_file(file, path_index)
+    }
+
+    fn exists(
+        &mut self,
+        angle: bool,
+        path: &str,
+        next: bool,
+        current: FileId,
+        path_index: PathIndex,
+    ) -> bool {
+        self.resolve(angle, path, next, current, path_index)
+            .is_some_and(|(file, _)| file.is_file())
     }
 
     fn get_id(&mut self, path: &PathBuf) -> FileId {
@@ -167,8 +239,13 @@ impl IncludeLocator for DefaultIncludeLocator {
     fn get_path(&self, id: FileId) -> PathBuf {
         self.source
             .as_ref()
-            .map(|s| s.lock().unwrap().get_path(id))
-            .unwrap()
+            .map_or_else(PathBuf::new, |s| s.lock().unwrap().get_path(id))
+    }
+
+    fn get_unique_id(&self, id: FileId) -> FileId {
+        self.source
+            .as_ref()
+            .map_or(id, |s| s.lock().unwrap().get_unique_id(id))
     }
 
     fn set_source(&mut self, source: SourceMutex) {
@@ -177,39 +254,55 @@ impl IncludeLocator for DefaultIncludeLocator {
 
     fn set_sys_paths<P: AsRef<Path>>(&mut self, paths: &[P]) {
         self.sys = paths.iter().map(|s| s.as_ref().to_path_buf()).collect();
+        self.angled_start = 0;
+    }
+
+    fn set_charset(&mut self, charset: Encoding) {
+        self.charset = charset;
+    }
+
+    fn set_include_paths(&mut self, paths: &IncludePaths) {
+        self.sys = paths
+            .quote
+            .iter()
+            .chain(paths.angled.iter())
+            .chain(paths.system.iter())
+            .chain(paths.after.iter())
+            .cloned()
+            .collect();
+        self.angled_start = paths.quote.len();
     }
 }
 
 impl<'a, PC: PreprocContext> Lexer<'a, PC> {
-    pub(crate) fn get_include_content(&mut self, term: u8) -> &'a [u8] {
+    pub(crate) fn get_include_content<'s>(&mut self, src: &'s [u8], term: u8) -> &'s [u8] {
         let spos = self.buf.pos();
         loop {
             if self.buf.has_char() {
                 let c = self.buf.next_char();
                 if c == term {
-                    let s = if self.buf.prev_char() == b' ' {
+                    return if self.buf.prev_char() == b' ' {
                         let p = self.buf.pos() + 1;
                         self.buf.dec_n(2);
                         skip_whites_back!(self);
-                        let s = self.buf.slice_n(spos, 1);
+                        let s = &src[spos..=self.buf.pos()];
                         self.buf.set_pos(p);
                         s
                     } else {
-                        let s = self.buf.slice(spos);
+                        let s = &src[spos..self.buf.pos()];
                         self.buf.inc();
                         s
                     };
-                    return &s;
                 } else {
                     self.buf.inc();
                 }
             } else {
-                return self.buf.slice(spos);
+                return &src[spos..self.buf.pos()];
             }
         }
     }
 
-    fn get_path(&mut self) -> IncludeType<'a> {
+    fn get_path<'s>(&mut self, src: &'s [u8]) -> IncludeType<'s> {
         skip_whites!(self);
         if self.buf.has_char() {
             let c = self.buf.next_char();
@@ -217,80 +310,143 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
                 // Quoted path
                 self.buf.inc();
                 skip_whites!(self);
-                let path = self.get_include_content(b'\"');
-                let path = std::str::from_utf8(path).unwrap();
-                return IncludeType::Quote(path);
+                let path = self.get_include_content(src, b'\"');
+                return std::str::from_utf8(path)
+                    .map_or(IncludeType::Invalid(path), IncludeType::Quote);
             } else if c == b'<' {
                 // Angled path
                 self.buf.inc();
                 skip_whites!(self);
-                let path = self.get_include_content(b'>');
-                let path = std::str::from_utf8(path).unwrap();
-                return IncludeType::Angle(path);
+                let path = self.get_include_content(src, b'>');
+                return std::str::from_utf8(path)
+                    .map_or(IncludeType::Invalid(path), IncludeType::Angle);
             }
         }
         IncludeType::Other
     }
 
-    pub(crate) fn get_include(&mut self, next: bool) -> Result<(), LexerError> {
-        match self.get_path() {
-            IncludeType::Quote(path) => {
-                let source_id = self.buf.get_source_id().unwrap();
-                let path_index = self.buf.get_path_index().unwrap();
-                let buf = self
-                    .context
-                    .find(false, path, next, source_id, path_index)
-                    .ok_or_else(|| LexerError::FileIncludeError {
-                        sp: self.span(),
-                        file: path.to_string(),
-                    })?;
-                self.buf.add_buffer(buf);
-            }
-            IncludeType::Angle(path) => {
-                let source_id = self.buf.get_source_id().unwrap();
-                let path_index = self.buf.get_path_index().unwrap();
-                let buf = self
-                    .context
-                    .find(true, path, next, source_id, path_index)
-                    .ok_or_else(|| LexerError::FileIncludeError {
-                        sp: self.span(),
-                        file: path.to_string(),
-                    })?;
-                self.buf.add_buffer(buf);
+    fn find_include(&mut self, angle: bool, path: &str, next: bool) -> Result<(), LexerError> {
+        let source_id = self.buf.get_source_id().unwrap();
+        let path_index = self.buf.get_path_index().unwrap();
+        let buf = self
+            .context
+            .find(angle, path, next, source_id, path_index)
+            .ok_or_else(|| LexerError::FileIncludeError {
+                sp: self.span(),
+                file: path.to_string(),
+            })?;
+        if self.context.skip_include(buf.get_source_id()) {
+            // #pragma once or a guard which is still defined: nothing to read
+            return Ok(());
+        }
+        self.buf.add_buffer(buf);
+        Ok(())
+    }
+
+    /// Check if a header exists without including it
+    fn has_include(&mut self, angle: bool, path: &str, next: bool) -> bool {
+        let source_id = self.buf.get_source_id().unwrap();
+        let path_index = self.buf.get_path_index().unwrap();
+        self.context
+            .exists(angle, path, next, source_id, path_index)
+    }
+
+    /// Evaluate __has_include(...) or __has_include_next(...) in a condition:
+    /// the operand is a header name (maybe coming from a macro expansion)
+    /// looked up in the search chain used by #include
+    pub(crate) fn get_has_include(&mut self, next: bool, skip: bool) -> Result<u64, LexerError> {
+        let name = if next {
+            "__has_include_next"
+        } else {
+            "__has_include"
+        };
+        skip_whites!(self);
+        if !self.buf.has_char() || self.buf.next_char() != b'(' {
+            return Err(LexerError::MissingParenAfterOperator {
+                sp: self.span(),
+                name: name.to_string(),
+                paren: '(',
+            });
+        }
+        self.buf.inc();
+
+        let src = self.buf.source();
+        let found = match self.get_path(&src) {
+            IncludeType::Quote(path) => !skip && self.has_include(false, path, next),
+            IncludeType::Angle(path) => !skip && self.has_include(true, path, next),
+            IncludeType::Invalid(path) => return Err(self.invalid_include(path)),
+            IncludeType::Other => {
+                skip_whites!(self);
+                let id = self.get_preproc_identifier(&src);
+                // The operand can't be expanded when it's already in an expansion
+                if !self.buf.preproc_use() && self.macro_eval(id) {
+                    self.buf.switch_to_preproc();
+                    let expansion = self.buf.source();
+                    let path = self.get_path(&expansion);
+                    self.buf.rm_buffer();
+
+                    match path {
+                        IncludeType::Quote(path) => !skip && self.has_include(false, path, next),
+                        IncludeType::Angle(path) => !skip && self.has_include(true, path, next),
+                        IncludeType::Invalid(path) => return Err(self.invalid_include(path)),
+                        IncludeType::Other => {
+                            return Err(LexerError::ExpectedIncludeName { sp: self.span() });
+                        }
+                    }
+                } else {
+                    return Err(LexerError::ExpectedIncludeName { sp: self.span() });
+                }
             }
+        };
+
+        skip_whites!(self);
+        if self.buf.has_char() && self.buf.next_char() == b')' {
+            self.buf.inc();
+        } else {
+            return Err(LexerError::MissingParenAfterOperator {
+                sp: self.span(),
+                name: name.to_string(),
+                paren: ')',
+            });
+        }
+
+        Ok(found as u64)
+    }
+
+    fn invalid_include(&self, path: &[u8]) -> LexerError {
+        LexerError::InvalidIncludeName {
+            sp: self.span(),
+            file: String::from_utf8_lossy(path).to_string(),
+        }
+    }
+
+    pub(crate) fn get_include(&mut self, next: bool) -> Result<(), LexerError> {
+        if self.raw {
+            skip_until!(self, b'\n');
+            return Ok(());
+        }
+
+        let src = self.buf.source();
+        match self.get_path(&src) {
+            IncludeType::Quote(path) => self.find_include(false, path, next)?,
+            IncludeType::Angle(path) => self.find_include(true, path, next)?,
+            IncludeType::Invalid(path) => return Err(self.invalid_include(path)),
             IncludeType::Other => {
                 skip_whites!(self);
-                let id = self.get_preproc_identifier();
+                let id = self.get_preproc_identifier(&src);
                 if self.macro_eval(id) {
                     self.buf.switch_to_preproc();
-                    let path = self.get_path();
+                    // The expansion is small and it's given back to the buffer just below
+                    let expansion = self.buf.source();
+                    let path = self.get_path(&expansion);
                     self.buf.rm_buffer();
-                    let source_id = self.buf.get_source_id().unwrap();
-                    let path_index = self.buf.get_path_index().unwrap();
 
                     match path {
-                        IncludeType::Quote(path) => {
-                            let buf = self
-                                .context
-                                .find(false, path, next, source_id, path_index)
-                                .ok_or_else(|| LexerError::FileIncludeError {
-                                    sp: self.span(),
-                                    file: path.to_string(),
-                                })?;
-                            self.buf.add_buffer(buf);
-                        }
-                        IncludeType::Angle(path) => {
-                            let buf = self
-                                .context
-                                .find(true, path, next, source_id, path_index)
-                                .ok_or_else(|| LexerError::FileIncludeError {
-                                    sp: self.span(),
-                                    file: path.to_string(),
-                                })?;
-                            self.buf.add_buffer(buf);
-                        }
-                        _ => {
-                            unreachable!();
+                        IncludeType::Quote(path) => self.find_include(false, path, next)?,
+                        IncludeType::Angle(path) => self.find_include(true, path, next)?,
+                        IncludeType::Invalid(path) => return Err(self.invalid_include(path)),
+                        IncludeType::Other => {
+                            return Err(LexerError::ExpectedIncludeName { sp: self.span() });
                         }
                     }
                 } else {
@@ -305,6 +461,8 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
 #[cfg(test)]
 mod tests {
 
+    use std::fs::File;
+    use std::io::Read;
     use std::sync::{Arc, Mutex};
     use tempdir::TempDir;
 
@@ -354,7 +512,7 @@ mod tests {
             _next: bool,
             _current: FileId,
             _path_index: PathIndex,
-        ) -> Option<BufferData> {
+        ) -> Option<BufferData<'static>> {
             let buf = if angle {
                 match path {
                     "path1" => b"#define foo 123\n".to_vec(),
@@ -526,13 +684,13 @@ mod tests {
         assert_eq!(p.next_token(), Token::PreprocInclude);
         assert_eq!(p.next_token(), Token::PreprocDefine);
         assert_eq!(p.next_token(), Token::Eol);
-        assert_eq!(p.next_token(), Token::Identifier("inc_foo".to_string()));
+        assert_eq!(p.next_token(), p.id("inc_foo"));
 
         lexer_for_file!(p, "#include \"foo.h\"\ntest", &foo, id, context.clone());
         assert_eq!(p.next_token(), Token::PreprocInclude);
         assert_eq!(p.next_token(), Token::PreprocDefine);
         assert_eq!(p.next_token(), Token::Eol);
-        assert_eq!(p.next_token(), Token::Identifier("cur_foo".to_string()));
+        assert_eq!(p.next_token(), p.id("cur_foo"));
 
         lexer_for_file!(
             p,
@@ -541,16 +699,17 @@ mod tests {
             id,
             context.clone()
         );
+        // include_next in the main file is just an include
         assert_eq!(p.next_token(), Token::PreprocIncludeNext);
         assert_eq!(p.next_token(), Token::PreprocDefine);
         assert_eq!(p.next_token(), Token::Eol);
-        assert_eq!(p.next_token(), Token::Identifier("inc_foo".to_string()));
+        assert_eq!(p.next_token(), p.id("cur_foo"));
 
         lexer_for_file!(p, "#include_next <foo.h>\ntest", &foo, id, context.clone());
         assert_eq!(p.next_token(), Token::PreprocIncludeNext);
         assert_eq!(p.next_token(), Token::PreprocDefine);
         assert_eq!(p.next_token(), Token::Eol);
-        assert_eq!(p.next_token(), Token::Identifier("sys_foo".to_string()));
+        assert_eq!(p.next_token(), p.id("inc_foo"));
 
         lexer_for_file!(
             p,
@@ -562,7 +721,7 @@ mod tests {
         assert_eq!(p.next_token(), Token::PreprocIncludeNext);
         assert_eq!(p.next_token(), Token::PreprocDefine);
         assert_eq!(p.next_token(), Token::Eol);
-        assert_eq!(p.next_token(), Token::Identifier("inc_bar".to_string()));
+        assert_eq!(p.next_token(), p.id("inc_bar"));
 
         lexer_for_file!(p, "#include <oof.h>\ntest", &foo, id, context.clone());
         assert_eq!(p.next_token(), Token::PreprocInclude);
@@ -570,7 +729,106 @@ mod tests {
         assert_eq!(p.next_token(), Token::PreprocDefine);
         assert_eq!(p.next_token(), Token::Eol);
         assert_eq!(p.next_token(), Token::Eol);
-        assert_eq!(p.next_token(), Token::Identifier("sys_foo".to_string()));
+        assert_eq!(p.next_token(), p.id("sys_foo"));
+    }
+
+    #[test]
+    fn test_include_paths() {
+        let tmp = TempDir::new("test").unwrap();
+        let dirs: Vec<_> = ["cur", "quote", "angled", "system", "after"]
+            .iter()
+            .map(|d| {
+                let d = tmp.path().join(d);
+                std::fs::create_dir_all(&d).unwrap();
+                d
+            })
+            .collect();
+        let (cur, quote, angled, system, after) =
+            (&dirs[0], &dirs[1], &dirs[2], &dirs[3], &dirs[4]);
+
+        std::fs::write(quote.join("q.h"), "#define test quote_q\n").unwrap();
+        std::fs::write(angled.join("q.h"), "#define test angled_q\n").unwrap();
+        std::fs::write(angled.join("a.h"), "#include_next <a.h>\n").unwrap();
+        std::fs::write(system.join("a.h"), "#define test system_a\n").unwrap();
+        std::fs::write(system.join("s.h"), "#define test system_s\n").unwrap();
+        std::fs::write(after.join("s.h"), "#define test after_s\n").unwrap();
+        std::fs::write(after.join("z.h"), "#define test after_z\n").unwrap();
+
+        let source = Arc::new(Mutex::new(SourceLocator::default()));
+        let paths = IncludePaths {
+            quote: vec![quote.clone()],
+            angled: vec![angled.clone()],
+            system: vec![system.clone()],
+            after: vec![after.clone()],
+        };
+        let include = DefaultIncludeLocator::new_with_paths(&paths, source.clone());
+        let mut context = DefaultContext::new(include);
+
+        let foo = cur.join("foo.c");
+        std::fs::write(&foo, "").unwrap();
+        let foo = std::fs::canonicalize(foo).unwrap();
+        let id = context.get_id(&foo);
+
+        for (code, expected) in &[
+            ("#include \"q.h\"\ntest", "quote_q"),
+            ("#include <q.h>\ntest", "angled_q"),
+            ("#include <a.h>\ntest", "system_a"),
+            ("#include <s.h>\ntest", "system_s"),
+            ("#include \"z.h\"\ntest", "after_z"),
+        ] {
+            lexer_for_file!(p, code, &foo, id, context.clone());
+            let tok = loop {
+                match p.next_token() {
+                    Token::Identifier(id) => break p.resolve(id).to_string(),
+                    Token::Eof => panic!("No identifier for {}", code),
+                    _ => {}
+                }
+            };
+            assert_eq!(tok, *expected);
+        }
+    }
+
+    #[test]
+    fn test_include_encodings() {
+        let tmp = TempDir::new("test").unwrap();
+        let dir = tmp.path();
+
+        let mut utf16 = vec![0xFF, 0xFE];
+        for u in "#define B b_utf16\n".encode_utf16() {
+            utf16.extend_from_slice(&u.to_le_bytes());
+        }
+        std::fs::write(dir.join("a.h"), b"\xEF\xBB\xBF#define A a_bom\n").unwrap();
+        std::fs::write(dir.join("b.h"), utf16).unwrap();
+        std::fs::write(dir.join("c.h"), b"#define C \"caf\xe9\"\n").unwrap();
+
+        let source = Arc::new(Mutex::new(SourceLocator::default()));
+        let include = DefaultIncludeLocator::new(vec![dir], source);
+        let mut context = DefaultContext::new(include);
+        context.set_charset(Encoding::Latin1);
+
+        let foo = dir.join("foo.c");
+        let id = context.get_id(&foo);
+        lexer_for_file!(
+            p,
+            "#include <a.h>\n#include <b.h>\n#include <c.h>\nA B C\n",
+            &foo,
+            id,
+            context
+        );
+        let toks: Vec<_> = std::iter::from_fn(|| match p.next_useful() {
+            Token::Eof => None,
+            tok => Some(tok),
+        })
+        .filter(|tok| matches!(tok, Token::Identifier(_) | Token::LiteralString(_)))
+        .collect();
+        assert_eq!(
+            toks,
+            vec![
+                p.id("a_bom"),
+                p.id("b_utf16"),
+                Token::LiteralString("caf\u{e9}".into()),
+            ]
+        );
     }
 
     #[test]
@@ -604,4 +862,329 @@ mod tests {
             panic!("mismatch. Was: {:?}", p.errors[0]);
         }
     }
+
+    #[test]
+    fn test_include_invalid_name() {
+        let mut p = Lexer::<Context<TestIncludeLocator>>::new(b"#include \"foo\xff.h\"\nbar\n");
+        p.consume_all();
+        assert_eq!(p.errors.len(), 1);
+        if let LexerError::InvalidIncludeName { file, .. } = &p.errors[0] {
+            assert_eq!(file, "foo\u{fffd}.h");
+        } else {
+            panic!("mismatch. Was: {:?}", p.errors[0]);
+        }
+    }
+
+    #[test]
+    fn test_include_macro_not_a_path() {
+        let mut p = Lexer::<Context<TestIncludeLocator>>::new(
+            concat!("#define FOO foo\n", "#include FOO\n", "bar\n").as_bytes(),
+        );
+        p.consume_all();
+        assert_eq!(p.errors.len(), 1);
+        assert!(
+            matches!(p.errors[0], LexerError::ExpectedIncludeName { .. }),
+            "mismatch. Was: {:?}",
+            p.errors[0]
+        );
+    }
+
+    fn get_identifiers<PC: PreprocContext>(p: &mut Lexer<PC>) -> Vec<String> {
+        let mut ids = Vec::new();
+        loop {
+            match p.next_useful() {
+                Token::Eof => return ids,
+                Token::Identifier(id) => ids.push(p.resolve(id).to_string()),
+                _ => {}
+            }
+        }
+    }
+
+    #[test]
+    fn test_include_guard_not_defined() {
+        let tmp = TempDir::new("test").unwrap();
+        let cur = tmp.path().join("cur");
+        std::fs::create_dir_all(&cur).unwrap();
+
+        std::fs::write(
+            cur.join("guard.h"),
+            concat!(
+                "#if !defined(GUARD_H)\n",
+                "#define GUARD_H\n",
+                "guard\n",
+                "#endif\n",
+            ),
+        )
+        .unwrap();
+        std::fs::write(
+            cur.join("not_guard.h"),
+            concat!(
+                "#if !defined(NOT_GUARD_H) && 1\n",
+                "#define NOT_GUARD_H\n",
+                "#endif\n",
+            ),
+        )
+        .unwrap();
+
+        let source = Arc::new(Mutex::new(SourceLocator::default()));
+        let include = DefaultIncludeLocator::new(Vec::<PathBuf>::new(), source);
+        let mut context = DefaultContext::new(include);
+
+        let foo = cur.join("foo.c");
+        std::fs::write(&foo, "").unwrap();
+        let foo = std::fs::canonicalize(foo).unwrap();
+        let id = context.get_id(&foo);
+
+        lexer_for_file!(
+            p,
+            concat!(
+                "#include \"guard.h\"\n",
+                "#include \"guard.h\"\n",
+                "#include \"not_guard.h\"\n",
+                "end\n",
+            ),
+            &foo,
+            id,
+            context
+        );
+        assert_eq!(get_identifiers(&mut p), vec!["guard", "end",]);
+
+        let guard = p.context.get_id(&cur.join("guard.h"));
+        let not_guard = p.context.get_id(&cur.join("not_guard.h"));
+        assert_eq!(p.context.get_guard(guard), Some("GUARD_H"));
+        assert_eq!(p.context.get_guard(not_guard), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_include_once() {
+        let tmp = TempDir::new("test").unwrap();
+        let cur = tmp.path().join("cur");
+        let sub = cur.join("sub");
+        std::fs::create_dir_all(&sub).unwrap();
+
+        std::fs::write(cur.join("once.h"), "// header\n#pragma once\nonce\n").unwrap();
+        std::fs::write(
+            cur.join("guard.h"),
+            concat!(
+                "/* License */\n",
+                "#ifndef GUARD_H\n",
+                "#define GUARD_H\n",
+                "guard\n",
+                "#endif // GUARD_H\n",
+                "\n",
+            ),
+        )
+        .unwrap();
+        std::os::unix::fs::symlink(cur.join("guard.h"), cur.join("link.h")).unwrap();
+
+        let source = Arc::new(Mutex::new(SourceLocator::default()));
+        let include = DefaultIncludeLocator::new(Vec::<PathBuf>::new(), source);
+        let mut context = DefaultContext::new(include);
+
+        let foo = cur.join("foo.c");
+        std::fs::write(&foo, "").unwrap();
+        let foo = std::fs::canonicalize(foo).unwrap();
+        let id = context.get_id(&foo);
+
+        lexer_for_file!(
+            p,
+            concat!(
+                "#include \"once.h\"\n",
+                "#include \"sub/../once.h\"\n",
+                "#include \"guard.h\"\n",
+                "#include \"link.h\"\n",
+                "#undef GUARD_H\n",
+                "#include \"sub/../guard.h\"\n",
+                "end\n",
+            ),
+            &foo,
+            id,
+            context
+        );
+        assert_eq!(
+            get_identifiers(&mut p),
+            vec!["once", "guard", "guard", "end",]
+        );
+
+        let guard = p.context.get_id(&cur.join("guard.h"));
+        let link = p.context.get_id(&cur.join("link.h"));
+        assert_eq!(p.context.get_guard(guard), Some("GUARD_H"));
+        assert_eq!(p.context.get_guard(link), Some("GUARD_H"));
+        let once = p.context.get_id(&cur.join("once.h"));
+        assert!(p.context.is_once(once));
+        assert!(!p.context.is_once(guard));
+    }
+
+    #[test]
+    fn test_include_not_a_guard() {
+        let tmp = TempDir::new("test").unwrap();
+        let cur = tmp.path().join("cur");
+        std::fs::create_dir_all(&cur).unwrap();
+
+        let headers = [
+            // Some code before the #ifndef
+            ("before.h", "int a;\n#ifndef B\n#define B\n#endif\n"),
+            // Some code after the #endif
+            ("after.h", "#ifndef A\n#define A\n#endif\nint a;\n"),
+            // An #else
+            ("else.h", "#ifndef E\n#define E\n#else\n#endif\n"),
+            // Two blocks
+            ("two.h", "#ifndef T\n#define T\n#endif\n#ifndef U\n#endif\n"),
+        ];
+        for (name, code) in headers.iter() {
+            std::fs::write(cur.join(name), code).unwrap();
+        }
+
+        let source = Arc::new(Mutex::new(SourceLocator::default()));
+        let include = DefaultIncludeLocator::new(Vec::<PathBuf>::new(), source);
+        let mut context = DefaultContext::new(include);
+
+        let foo = cur.join("foo.c");
+        std::fs::write(&foo, "").unwrap();
+        let foo = std::fs::canonicalize(foo).unwrap();
+        let id = context.get_id(&foo);
+
+        let code: String = headers
+            .iter()
+            .map(|(name, _)| format!("#include \"{}\"\n", name))
+            .collect();
+        lexer_for_file!(p, code, &foo, id, context);
+        p.consume_all();
+        assert!(p.errors.is_empty(), "{:?}", p.errors);
+
+        for (name, _) in headers.iter() {
+            let id = p.context.get_id(&cur.join(name));
+            assert_eq!(p.context.get_guard(id), None, "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_has_include() {
+        let tmp = TempDir::new("test").unwrap();
+        let dirs: Vec<_> = ["cur", "angled", "system"]
+            .iter()
+            .map(|d| {
+                let d = tmp.path().join(d);
+                std::fs::create_dir_all(&d).unwrap();
+                d
+            })
+            .collect();
+        let (cur, angled, system) = (&dirs[0], &dirs[1], &dirs[2]);
+
+        std::fs::write(cur.join("local.h"), "").unwrap();
+        std::fs::write(
+            angled.join("a.h"),
+            "#if __has_include_next(<a.h>)\nnext_a\n#endif\n",
+        )
+        .unwrap();
+        std::fs::write(
+            angled.join("b.h"),
+            "#if !__has_include_next(<b.h>)\nno_next_b\n#endif\n",
+        )
+        .unwrap();
+        std::fs::write(system.join("a.h"), "").unwrap();
+
+        let source = Arc::new(Mutex::new(SourceLocator::default()));
+        let paths = IncludePaths {
+            angled: vec![angled.clone()],
+            system: vec![system.clone()],
+            ..Default::default()
+        };
+        let include = DefaultIncludeLocator::new_with_paths(&paths, source);
+        let mut context = DefaultContext::new(include);
+
+        let foo = cur.join("foo.c");
+        std::fs::write(&foo, "").unwrap();
+        let foo = std::fs::canonicalize(foo).unwrap();
+        let id = context.get_id(&foo);
+
+        lexer_for_file!(
+            p,
+            concat!(
+                "#define HDR <a.h>\n",
+                "#define LOCAL \"local.h\"\n",
+                "#if __has_include(\"local.h\")\n",
+                "local\n",
+                "#endif\n",
+                "#if __has_include(<local.h>) || __has_include(\"missing.h\")\n",
+                "angled_local\n",
+                "#endif\n",
+                "#if __has_include( <a.h> ) && !__has_include(<missing.h>)\n",
+                "a\n",
+                "#endif\n",
+                "#if __has_include(HDR) && __has_include(LOCAL)\n",
+                "macro\n",
+                "#endif\n",
+                "#if defined(__has_include) && defined __has_include_next\n",
+                "defined\n",
+                "#endif\n",
+                "#ifdef __has_include\n",
+                "ifdef\n",
+                "#endif\n",
+                "#if 1 || __has_include(<missing.h>)\n",
+                "lazy\n",
+                "#endif\n",
+                "#include <a.h>\n",
+                "#include <b.h>\n",
+            ),
+            &foo,
+            id,
+            context
+        );
+        assert_eq!(
+            get_identifiers(&mut p),
+            vec![
+                "local",
+                "a",
+                "macro",
+                "defined",
+                "ifdef",
+                "lazy",
+                "next_a",
+                "no_next_b",
+            ]
+        );
+        assert!(p.errors.is_empty(), "{:?}", p.errors);
+    }
+
+    #[test]
+    fn test_include_locator_exists() {
+        let tmp = TempDir::new("test").unwrap();
+        let (cur, angled, system) = (
+            tmp.path().join("cur"),
+            tmp.path().join("angled"),
+            tmp.path().join("system"),
+        );
+        for d in [&cur, &angled, &system].iter() {
+            std::fs::create_dir_all(d).unwrap();
+        }
+        std::fs::write(cur.join("local.h"), "").unwrap();
+        std::fs::write(angled.join("a.h"), "").unwrap();
+        std::fs::write(system.join("a.h"), "").unwrap();
+        std::fs::create_dir_all(angled.join("dir.h")).unwrap();
+
+        let source = Arc::new(Mutex::new(SourceLocator::default()));
+        let paths = IncludePaths {
+            angled: vec![angled.clone()],
+            system: vec![system.clone()],
+            ..Default::default()
+        };
+        let mut include = DefaultIncludeLocator::new_with_paths(&paths, Arc::clone(&source));
+        let current = include.get_id(&cur.join("foo.c"));
+
+        assert!(include.exists(false, "local.h", false, current, PathIndex(0)));
+        assert!(!include.exists(true, "local.h", false, current, PathIndex(0)));
+        assert!(include.exists(true, "a.h", false, current, PathIndex(0)));
+        assert!(include.exists(true, "a.h", true, current, PathIndex(1)));
+        assert!(!include.exists(true, "a.h", true, current, PathIndex(2)));
+        assert!(!include.exists(true, "dir.h", false, current, PathIndex(0)));
+        assert!(!include.exists(true, "missing.h", false, current, PathIndex(0)));
+        let abs = system.join("a.h");
+        assert!(include.exists(true, abs.to_str().unwrap(), false, current, PathIndex(0)));
+
+        // The headers have neither been loaded nor given an id
+        let next = include.get_id(&cur.join("bar.c"));
+        assert_eq!(next, FileId(current.0 + 1));
+    }
 }
@@ -115,7 +115,7 @@ pub(crate) enum MacroNode<'a> {
 
 impl<'a, PC: PreprocContext> Lexer<'a, PC> {
     #[inline(always)]
-    pub(crate) fn get_define_argument(&mut self) -> MacroDefArg<'a> {
+    pub(crate) fn get_define_argument<'s>(&mut self, src: &'s [u8]) -> MacroDefArg<'s> {
         let spos = self.buf.pos();
         loop {
             if self.buf.has_char() {
@@ -138,15 +138,13 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
                             self.buf.inc_n(3);
                             return MacroDefArg::VaArgs;
                         } else {
-                            let id = unsafe {
-                                std::str::from_utf8_unchecked(&self.buf.slice_p(spos, epos))
-                            };
+                            let id = unsafe { std::str::from_utf8_unchecked(&src[spos..epos]) };
                             self.buf.inc_n(3);
                             return MacroDefArg::NamedVaArgs(id);
                         }
                     }
                     return MacroDefArg::Normal(unsafe {
-                        std::str::from_utf8_unchecked(&self.buf.slice_p(spos, epos))
+                        std::str::from_utf8_unchecked(&src[spos..epos])
                     });
                 }
                 self.buf.inc();
@@ -155,11 +153,14 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
             }
         }
 
-        MacroDefArg::Normal(unsafe { std::str::from_utf8_unchecked(&self.buf.slice(spos)) })
+        MacroDefArg::Normal(unsafe { std::str::from_utf8_unchecked(&src[spos..self.buf.pos()]) })
     }
 
     #[inline(always)]
-    pub(crate) fn get_macro_arguments(&mut self) -> (HashMap<&'a str, usize>, Option<usize>) {
+    pub(crate) fn get_macro_arguments<'s>(
+        &mut self,
+        src: &'s [u8],
+    ) -> (HashMap<&'s str, usize>, Option<usize>) {
         let mut args = HashMap::default();
         let mut n = 0;
         let mut va_args = None;
@@ -172,7 +173,7 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
         }
 
         loop {
-            let arg = self.get_define_argument();
+            let arg = self.get_define_argument(src);
             match arg {
                 MacroDefArg::Normal(id) => {
                     args.insert(id, n);
@@ -214,64 +215,64 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
     }
 
     #[inline(always)]
-    pub(crate) fn next_arg_token(&mut self) -> MacroArgToken<'a> {
+    pub(crate) fn next_arg_token<'s>(&mut self, src: &'s [u8]) -> MacroArgToken<'s> {
         loop {
             if self.buf.has_char() {
                 let c = self.buf.next_char();
                 let kind = unsafe { *MCHARS.get_unchecked(c as usize) };
                 match kind {
                     Kind::IDE => {
-                        return MacroArgToken::Id(self.get_preproc_identifier());
+                        return MacroArgToken::Id(self.get_preproc_identifier(src));
                     }
                     Kind::IDL => {
                         let p = self.buf.pos();
                         self.buf.inc();
                         if self.get_special_string_char(StringType::L).is_some() {
-                            let s = self.buf.slice(p);
+                            let s = &src[p..self.buf.pos()];
                             return MacroArgToken::String(s);
                         } else {
                             self.buf.dec();
-                            return MacroArgToken::Id(self.get_preproc_identifier());
+                            return MacroArgToken::Id(self.get_preproc_identifier(src));
                         }
                     }
                     Kind::IDR => {
                         let p = self.buf.pos();
                         self.buf.inc();
                         if self.get_special_string_char(StringType::R).is_some() {
-                            let s = self.buf.slice(p);
+                            let s = &src[p..self.buf.pos()];
                             return MacroArgToken::String(s);
                         } else {
                             self.buf.dec();
-                            return MacroArgToken::Id(self.get_preproc_identifier());
+                            return MacroArgToken::Id(self.get_preproc_identifier(src));
                         }
                     }
                     Kind::IDU => {
                         let p = self.buf.pos();
                         self.buf.inc();
                         if self.get_special_string_char(StringType::UU).is_some() {
-                            let s = self.buf.slice(p);
+                            let s = &src[p..self.buf.pos()];
                             return MacroArgToken::String(s);
                         } else {
                             self.buf.dec();
-                            return MacroArgToken::Id(self.get_preproc_identifier());
+                            return MacroArgToken::Id(self.get_preproc_identifier(src));
                         }
                     }
                     Kind::IDu => {
                         let p = self.buf.pos();
                         self.buf.inc();
                         if self.get_special_string_char(StringType::U).is_some() {
-                            let s = self.buf.slice(p);
+                            let s = &src[p..self.buf.pos()];
                             return MacroArgToken::String(s);
                         } else {
                             self.buf.dec();
-                            return MacroArgToken::Id(self.get_preproc_identifier());
+                            return MacroArgToken::Id(self.get_preproc_identifier(src));
                         }
                     }
                     Kind::NUM => {
                         let p = self.buf.pos();
                         self.buf.inc();
-                        self.skip_number(c);
-                        let s = self.buf.slice(p);
+                        self.skip_pp_number();
+                        let s = &src[p..self.buf.pos()];
                         return MacroArgToken::None(s);
                     }
                     Kind::SPA => {
@@ -283,12 +284,12 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
                         let p = self.buf.pos();
                         self.buf.inc();
                         self.skip_by_delim(c);
-                        let s = self.buf.slice(p);
+                        let s = &src[p..self.buf.pos()];
                         return MacroArgToken::String(s);
                     }
                     Kind::RET => {
                         self.buf.inc();
-                        self.buf.add_new_line();
+                        self.buf.add_new_line_after();
                     }
                     Kind::SLA => {
                         self.buf.inc();
@@ -325,7 +326,7 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
                     Kind::NON => {
                         let p = self.buf.pos();
                         self.skip_arg_none();
-                        let s = self.buf.slice(p);
+                        let s = &src[p..self.buf.pos()];
                         return MacroArgToken::None(s);
                     }
                 }
@@ -338,13 +339,17 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
     }
 
     #[inline(always)]
-    pub(crate) fn get_macro_tokens(&mut self, n_args: usize) -> Vec<Vec<MacroNode<'a>>> {
+    pub(crate) fn get_macro_tokens<'s>(
+        &mut self,
+        src: &'s [u8],
+        n_args: usize,
+    ) -> Vec<Vec<MacroNode<'s>>> {
         let mut args = Vec::with_capacity(n_args);
         let mut stack = Vec::new();
         let mut arg = Vec::new();
 
         loop {
-            let tok = self.next_arg_token();
+            let tok = self.next_arg_token(src);
             match tok {
                 MacroArgToken::None(s) => {
                     arg.push(MacroNode::Nothing(s));
@@ -395,11 +400,12 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
     }
 
     #[inline(always)]
-    pub(crate) fn get_arguments(
+    pub(crate) fn get_arguments<'s>(
         &mut self,
+        src: &'s [u8],
         n_args: usize,
         va_args: Option<&usize>,
-    ) -> Option<Vec<Vec<MacroNode<'a>>>> {
+    ) -> Option<Vec<Vec<MacroNode<'s>>>> {
         let spos = self.buf.pos();
         skip_whites!(self);
         if self.buf.has_char() {
@@ -413,7 +419,7 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
             return None;
         }
 
-        let mut args = self.get_macro_tokens(n_args);
+        let mut args = self.get_macro_tokens(src, n_args);
         if let Some(va_pos) = va_args {
             if args.len() < n_args - 1 {
                 self.buf.set_pos(spos);
@@ -534,6 +540,15 @@ impl<'a> MacroNode<'a> {
         }
     }
 
+    /// Check if the variable arguments have no tokens (before expansion)
+    pub(crate) fn is_empty_va(nodes: &[MacroNode<'a>]) -> bool {
+        let is_white = |nodes: &[MacroNode<'a>]| nodes.iter().all(|n| *n == MacroNode::Space);
+        match nodes {
+            [MacroNode::VaArgs(va)] => va.len() <= 1 && va.iter().all(|arg| is_white(arg)),
+            _ => is_white(nodes),
+        }
+    }
+
     pub(crate) fn make_expr(nodes: &[MacroNode<'a>], out: &mut OutBuf) {
         let len = nodes.len();
         for (pos, node) in nodes.iter().enumerate() {
@@ -635,7 +650,8 @@ mod tests {
     fn test_arg1() {
         let mut p = Lexer::<DefaultContext>::new(b"(a,b,c)");
         let exp = vec![vec![Id("a")], vec![Id("b")], vec![Id("c")]];
-        let res = p.get_arguments(3, None).unwrap();
+        let src = p.buf.source();
+        let res = p.get_arguments(&src, 3, None).unwrap();
 
         assert_eq!(res, exp);
     }
@@ -653,7 +669,8 @@ mod tests {
             vec![Space, Id("c")],
             vec![Space, Id("bar"), Args(vec![])],
         ];
-        let res = p.get_arguments(4, None).unwrap();
+        let src = p.buf.source();
+        let res = p.get_arguments(&src, 4, None).unwrap();
 
         assert_eq!(res, exp);
     }
@@ -668,7 +685,8 @@ mod tests {
             vec![],
             vec![],
         ];
-        let res = p.get_arguments(5, None).unwrap();
+        let src = p.buf.source();
+        let res = p.get_arguments(&src, 5, None).unwrap();
 
         assert_eq!(res, exp);
     }
@@ -676,7 +694,8 @@ mod tests {
     #[test]
     fn test_make_expr1() {
         let mut p = Lexer::<DefaultContext>::new(b"(   a /* comment */  , b + 1)");
-        let args = Args(p.get_arguments(2, None).unwrap());
+        let src = p.buf.source();
+        let args = Args(p.get_arguments(&src, 2, None).unwrap());
         let mut out = OutBuf::default();
         MacroNode::make_expr(&vec![args], &mut out);
         let res = std::str::from_utf8(&out.buf).unwrap();
@@ -688,7 +707,8 @@ mod tests {
     #[test]
     fn test_make_expr2() {
         let mut p = Lexer::<DefaultContext>::new(b"(a, b, foo(x+1, y * 2, bar (z,t)))");
-        let args = Args(p.get_arguments(3, None).unwrap());
+        let src = p.buf.source();
+        let args = Args(p.get_arguments(&src, 3, None).unwrap());
         let mut out = OutBuf::default();
         MacroNode::make_expr(&vec![args], &mut out);
         let res = std::str::from_utf8(&out.buf).unwrap();
@@ -705,7 +725,8 @@ mod tests {
             vec![Space, Id("b")],
             vec![Space, Id("c")],
         ];
-        let res = p.get_arguments(3, None).unwrap();
+        let src = p.buf.source();
+        let res = p.get_arguments(&src, 3, None).unwrap();
 
         assert_eq!(res, exp);
     }
@@ -19,10 +19,18 @@ pub struct MacroObject {
     pub(crate) file_info: FileInfo,
 }
 
+/// The replacement list of a __VA_OPT__(...)
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct VaOpt {
+    pub(crate) out: Vec<u8>,
+    pub(crate) actions: Vec<Action>,
+}
+
 #[derive(Clone)]
 pub struct MacroFunction {
     out: Vec<u8>,
     actions: Vec<Action>,
+    va_opts: Vec<VaOpt>,
     pub(crate) n_args: usize,
     pub(crate) in_use: Cell<bool>,
     pub(crate) va_args: Option<usize>,
@@ -72,6 +80,51 @@ pub enum Action {
     Concat(usize),
     Stringify(usize),
     Chunk(usize),
+    /// `, ## __VA_ARGS__`: the comma is removed when the variable arguments are empty (GNU)
+    CommaVaArgs(usize),
+    /// __VA_OPT__(...) (the index in va_opts)
+    VaOpt(usize),
+    /// __VA_OPT__(...) as an operand of ##
+    ConcatVaOpt(usize),
+    /// #__VA_OPT__(...)
+    StringifyVaOpt(usize),
+}
+
+/// Make the content of a string literal from some tokens:
+/// the whites are collapsed and the " and \ in the literals are escaped
+fn stringify(tokens: &[u8], out: &mut Vec<u8>) {
+    let mut delim = None;
+    let mut escaped = false;
+    let mut white = false;
+    for &c in tokens.trim_ascii() {
+        if let Some(d) = delim {
+            if c == b'"' || c == b'\\' {
+                out.push(b'\\');
+            }
+            out.push(c);
+            if escaped {
+                escaped = false;
+            } else if c == b'\\' {
+                escaped = true;
+            } else if c == d {
+                delim = None;
+            }
+        } else if c.is_ascii_whitespace() {
+            white = true;
+        } else {
+            if white {
+                out.push(b' ');
+                white = false;
+            }
+            if c == b'"' || c == b'\'' {
+                delim = Some(c);
+                if c == b'"' {
+                    out.push(b'\\');
+                }
+            }
+            out.push(c);
+        }
+    }
 }
 
 impl MacroFunction {
@@ -79,6 +132,7 @@ impl MacroFunction {
     pub(crate) fn new(
         out: Vec<u8>,
         actions: Vec<Action>,
+        va_opts: Vec<VaOpt>,
         n_args: usize,
         va_args: Option<usize>,
         file_info: FileInfo,
@@ -86,6 +140,7 @@ impl MacroFunction {
         Self {
             out,
             actions,
+            va_opts,
             n_args,
             in_use: Cell::new(false),
             va_args,
@@ -105,10 +160,28 @@ impl MacroFunction {
         info: &FileInfo,
         out: &mut OutBuf,
     ) {
-        let mut out_pos = 0;
         let mut output = OutBuf::default();
+        self.substitute(&self.out, &self.actions, args, context, info, &mut output);
+
+        let mut lexer = Lexer::<EmptyContext>::new(&output.buf);
+        self.in_use.set(true);
+        lexer.macro_final_eval(out, context, info);
+        self.in_use.set(false);
+    }
+
+    /// Replace the parameters in a replacement list (the macro one or a __VA_OPT__ one)
+    fn substitute<'a, PC: PreprocContext>(
+        &self,
+        out: &[u8],
+        actions: &[Action],
+        args: &[Vec<MacroNode<'a>>],
+        context: &PC,
+        info: &FileInfo,
+        output: &mut OutBuf,
+    ) {
+        let mut out_pos = 0;
 
-        for action in self.actions.iter() {
+        for action in actions.iter() {
             match action {
                 Action::Arg(pos) => {
                     // gcc/clang are smart: they add whites only when it's required
@@ -119,7 +192,7 @@ impl MacroFunction {
                         }
                     }
                     if let Some(arg) = args.get(*pos) {
-                        MacroNode::eval_nodes(arg, context, info, &mut output, false);
+                        MacroNode::eval_nodes(arg, context, info, output, false);
                         if let Some(last) = output.buf.last() {
                             if *last != b' ' {
                                 output.buf.push(b' ');
@@ -131,27 +204,77 @@ impl MacroFunction {
                     }
                 }
                 Action::Concat(pos) => {
-                    MacroNode::make_expr(&args[*pos], &mut output);
+                    MacroNode::make_expr(&args[*pos], output);
                 }
                 Action::Stringify(pos) => {
-                    MacroNode::make_string(&args[*pos], &mut output);
+                    MacroNode::make_string(&args[*pos], output);
                 }
                 Action::Chunk(pos) => {
                     output
                         .buf
-                        .extend_from_slice(unsafe { &self.out.get_unchecked(out_pos..*pos) });
+                        .extend_from_slice(unsafe { out.get_unchecked(out_pos..*pos) });
                     out_pos = *pos;
                 }
+                Action::CommaVaArgs(pos) => {
+                    // The comma is the next char in the replacement list
+                    out_pos += 1;
+                    if !MacroNode::is_empty_va(&args[*pos]) {
+                        output.buf.push(b',');
+                        MacroNode::make_expr(&args[*pos], output);
+                    }
+                }
+                Action::VaOpt(i) | Action::ConcatVaOpt(i) | Action::StringifyVaOpt(i) => {
+                    // Without variable arguments, __VA_OPT__(...) is a placemarker
+                    let mut content = OutBuf::default();
+                    if self.has_va_tokens(args, context, info) {
+                        let va_opt = &self.va_opts[*i];
+                        self.substitute(
+                            &va_opt.out,
+                            &va_opt.actions,
+                            args,
+                            context,
+                            info,
+                            &mut content,
+                        );
+                    }
+
+                    match action {
+                        Action::VaOpt(_) => {
+                            if !content.buf.is_empty() {
+                                if output.buf.last().is_some_and(|c| *c != b' ') {
+                                    output.buf.push(b' ');
+                                }
+                                output.buf.extend_from_slice(&content.buf);
+                                output.buf.push(b' ');
+                            }
+                        }
+                        Action::ConcatVaOpt(_) => {
+                            output.buf.extend_from_slice(&content.buf);
+                        }
+                        _ => {
+                            stringify(&content.buf, &mut output.buf);
+                        }
+                    }
+                }
             }
         }
         output
             .buf
-            .extend_from_slice(unsafe { &self.out.get_unchecked(out_pos..) });
+            .extend_from_slice(unsafe { out.get_unchecked(out_pos..) });
+    }
 
-        let mut lexer = Lexer::<EmptyContext>::new(&output.buf);
-        self.in_use.set(true);
-        lexer.macro_final_eval(out, context, info);
-        self.in_use.set(false);
+    /// Check if the variable arguments aren't empty once expanded
+    fn has_va_tokens<'a, PC: PreprocContext>(
+        &self,
+        args: &[Vec<MacroNode<'a>>],
+        context: &PC,
+        info: &FileInfo,
+    ) -> bool {
+        let mut va = OutBuf::default();
+        if let Some(arg) = self.va_args.and_then(|pos| args.get(pos)) {
+            MacroNode::eval_nodes(arg, context, info, &mut va, false);
+        }
+        va.buf.iter().any(|c| !c.is_ascii_whitespace())
     }
 
     #[inline(always)]
@@ -237,7 +360,7 @@ impl MacroLine {
             }
         }
 
-        tools::extend_with_u32(&mut out.buf, info.line);
+        tools::extend_with_u32(&mut out.buf, info.presumed_line);
         out.buf.push(b' ');
     }
 }
@@ -259,10 +382,20 @@ impl MacroFile {
             }
         }
 
-        let path = context.get_path(info.source_id.unwrap());
-        let path = path.to_str().unwrap();
-        out.buf.extend_from_slice(path.as_bytes());
-        out.buf.push(b' ');
+        // The file is the one set by #line (if any) and it's expanded to a string literal
+        let path = info
+            .presumed_file
+            .or(info.source_id)
+            .map(|id| context.get_path(id))
+            .unwrap_or_default();
+        out.buf.push(b'"');
+        for c in path.to_string_lossy().bytes() {
+            if c == b'"' || c == b'\\' {
+                out.buf.push(b'\\');
+            }
+            out.buf.push(c);
+        }
+        out.buf.extend_from_slice(b"\" ");
     }
 }
 
@@ -411,11 +544,11 @@ mod tests {
         assert_eq!(p.next_token(), Token::While);
         assert_eq!(p.next_token(), Token::Eol);
         assert_eq!(p.next_token(), Token::PreprocDefine);
-        assert_eq!(p.next_token(), Token::Identifier("lewhi".to_string()));
+        assert_eq!(p.next_token(), p.id("lewhi"));
         assert_eq!(p.next_token(), Token::Eol);
         assert_eq!(p.next_token(), Token::PreprocDefine);
-        assert_eq!(p.next_token(), Token::Identifier("bar".to_string()));
-        assert_eq!(p.next_token(), Token::Identifier("foo".to_string()));
+        assert_eq!(p.next_token(), p.id("bar"));
+        assert_eq!(p.next_token(), p.id("foo"));
         assert_eq!(p.next_token(), Token::Eol);
     }
 
@@ -752,4 +885,134 @@ mod tests {
 
         assert_eq!(eval!("E", p), "F ");
     }
+
+    #[test]
+    fn test_eval_va_opt() {
+        // The examples in C23 6.10.5.1
+        let mut p = Lexer::<DefaultContext>::new(
+            concat!(
+                "#define F(...) f(0 __VA_OPT__(,) __VA_ARGS__)\n",
+                "#define G(X, ...) f(0, X __VA_OPT__(,) __VA_ARGS__)\n",
+                "#define SDEF(sname, ...) S sname __VA_OPT__(= { __VA_ARGS__ })\n",
+                "#define EMP\n",
+                "#define H2(X, Y, ...) __VA_OPT__(X ## Y,) __VA_ARGS__\n",
+                "#define H3(X, ...) #__VA_OPT__(X##X X##X)\n",
+                "#define H4(X, ...) __VA_OPT__(a X ## X) ## b\n",
+                "#define H5A(...) __VA_OPT__()/**/__VA_OPT__()\n",
+                "#define H5B(X) a ## X ## b\n",
+                "#define H5C(X) H5B(X)\n",
+                "#define test1 F(a, b, c)\n",
+                "#define test2 F()\n",
+                "#define test3 F(EMP)\n",
+                "#define test4 G(a, b, c)\n",
+                "#define test5 G(a, )\n",
+                "#define test6 G(a)\n",
+                "#define test7 SDEF(foo);\n",
+                "#define test8 SDEF(bar, 1, 2);\n",
+                "#define test9 H2(a, b, c, d)\n",
+                "#define test10 H3(, 0)\n",
+                "#define test11 H4(, 1)\n",
+                "#define test12 H5C(H5A())\n",
+            )
+            .as_bytes(),
+        );
+        p.consume_all();
+        assert!(p.get_errors().is_empty());
+
+        assert_eq!(eval!("test1", p), "f(0 , a, b, c ) ");
+        assert_eq!(eval!("test2", p), "f(0 ) ");
+        assert_eq!(eval!("test3", p), "f(0 ) ");
+        assert_eq!(eval!("test4", p), "f(0, a , b, c ) ");
+        assert_eq!(eval!("test5", p), "f(0, a ) ");
+        assert_eq!(eval!("test6", p), "f(0, a ) ");
+        assert_eq!(eval!("test7", p), "S foo ; ");
+        assert_eq!(eval!("test8", p), "S bar = { 1, 2 } ; ");
+        assert_eq!(eval!("test9", p), "ab, c, d ");
+        assert_eq!(eval!("test10", p), "\"\" ");
+        assert_eq!(eval!("test11", p), "a b ");
+        assert_eq!(eval!("test12", p), "ab ");
+    }
+
+    #[test]
+    fn test_eval_va_opt_more() {
+        let mut p = Lexer::<DefaultContext>::new(
+            concat!(
+                "#define A 1\n",
+                "#define F(x, ...) #__VA_OPT__(x \"a\\n\" '\"'   __VA_ARGS__)\n",
+                "#define G(x, ...) x ## __VA_OPT__(y z) ## x\n",
+                "#define H(...) -__VA_OPT__(-)-\n",
+                "#define I(x, ...) __VA_OPT__((x) + ) x\n",
+                "#define test1 F(A, b)\n",
+                "#define test2 F(A)\n",
+                "#define test3 G(A, 0)\n",
+                "#define test4 G(A)\n",
+                "#define test5 H(1)\n",
+                "#define test6 I(A, 1)\n",
+            )
+            .as_bytes(),
+        );
+        p.consume_all();
+        assert!(p.get_errors().is_empty());
+
+        assert_eq!(eval!("test1", p), "\"1 \\\"a\\\\n\\\" '\\\"' b\" ");
+        assert_eq!(eval!("test2", p), "\"\" ");
+        assert_eq!(eval!("test3", p), "Ay zA ");
+        assert_eq!(eval!("test4", p), "AA ");
+        assert_eq!(eval!("test5", p), "- - - ");
+        assert_eq!(eval!("test6", p), "( 1 ) + 1 ");
+    }
+
+    #[test]
+    fn test_eval_gnu_comma() {
+        let mut p = Lexer::<DefaultContext>::new(
+            concat!(
+                "#define e(fmt, ...) f(fmt, ## __VA_ARGS__)\n",
+                "#define n(fmt, args...) f(fmt,##args)\n",
+                "#define A 1\n",
+                "#define test1 e(x)\n",
+                "#define test2 e(x,)\n",
+                "#define test3 e(x, A, b)\n",
+                "#define test4 n(x)\n",
+                "#define test5 n(x, A)\n",
+                "#define test6 e(x, , )\n",
+            )
+            .as_bytes(),
+        );
+        p.consume_all();
+        assert!(p.get_errors().is_empty());
+
+        assert_eq!(eval!("test1", p), "f( x ) ");
+        assert_eq!(eval!("test2", p), "f( x ) ");
+        assert_eq!(eval!("test3", p), "f( x , 1 ,b) ");
+        assert_eq!(eval!("test4", p), "f( x ) ");
+        assert_eq!(eval!("test5", p), "f( x , 1 ) ");
+        assert_eq!(eval!("test6", p), "f( x ,,) ");
+    }
+
+    #[test]
+    fn test_va_opt_errors() {
+        let mut p = Lexer::<DefaultContext>::new(
+            concat!(
+                "#define F(x, ...) __VA_OPT__ x\n",
+                "#define G(...) __VA_OPT__(a\n",
+                "#define test1 F(1, 2)\n",
+            )
+            .as_bytes(),
+        );
+        p.consume_all();
+
+        let errors: Vec<_> = p
+            .get_errors()
+            .iter()
+            .map(|e| e.stringly().message)
+            .collect();
+        assert_eq!(
+            errors,
+            vec![
+                "missing '(' after __VA_OPT__",
+                "missing ')' after __VA_OPT__",
+            ]
+        );
+        assert_eq!(eval!("test1", p), "1 ");
+    }
 }
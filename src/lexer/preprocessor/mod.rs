@@ -9,6 +9,7 @@ pub use self::context::*;
 pub mod cache;
 pub mod include;
 pub mod macros;
+pub mod pragma;
 
 mod condition;
 mod macro_args;
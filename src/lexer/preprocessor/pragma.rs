@@ -0,0 +1,594 @@
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use hashbrown::HashSet;
+
+use super::context::EmptyContext;
+use crate::args::LangStandard;
+use crate::errors::Span;
+use crate::lexer::dialect::Dialect;
+use crate::lexer::errors::LexerError;
+use crate::lexer::lexer::{Lexer, TLexer, Token};
+use crate::lexer::preprocessor::PreprocContext;
+use crate::lexer::source::FileId;
+use crate::lexer::SymbolTable;
+
+/// The first token of a pragma is its namespace when it's one of these
+const NAMESPACES: [&str; 5] = ["GCC", "clang", "STDC", "omp", "acc"];
+
+/// A pragma coming from a #pragma directive, a _Pragma("...") or a __pragma(...)
+#[derive(Clone, Debug)]
+pub struct Pragma<'a> {
+    /// GCC, clang, STDC, omp or acc
+    pub namespace: Option<String>,
+    /// The tokens after the namespace (the macros aren't expanded)
+    pub tokens: Vec<Token>,
+    pub sp: Span,
+    /// The table of the lexer to resolve the identifiers in the tokens
+    pub symbols: &'a SymbolTable,
+}
+
+impl<'a> Pragma<'a> {
+    fn new(mut tokens: Vec<Token>, sp: Span, symbols: &'a SymbolTable) -> Self {
+        let namespace = match tokens.first() {
+            Some(Token::Identifier(id))
+                if tokens.len() > 1 && NAMESPACES.contains(&symbols.resolve(*id)) =>
+            {
+                Some(symbols.resolve(*id).to_string())
+            }
+            _ => None,
+        };
+        if namespace.is_some() {
+            tokens.remove(0);
+        }
+
+        Self {
+            namespace,
+            tokens,
+            sp,
+            symbols,
+        }
+    }
+
+    /// Get the string of an identifier token
+    pub fn get_identifier(&self, tok: &Token) -> Option<&'a str> {
+        match tok {
+            Token::Identifier(id) => Some(self.symbols.resolve(*id)),
+            _ => None,
+        }
+    }
+}
+
+/// What has been set by the built-in pragmas
+#[derive(Clone, Debug, Default)]
+pub struct PragmaState {
+    /// The files with a #pragma GCC system_header (unique ids)
+    pub system_headers: HashSet<FileId>,
+    /// The identifiers poisoned with #pragma GCC poison
+    pub poisoned: HashSet<String>,
+    /// The messages of the #pragma GCC warning
+    pub warnings: Vec<(Span, String)>,
+    /// The current alignment set by #pragma pack (None for the default one)
+    pub pack: Option<u32>,
+    /// Each change of the alignment: the pragma and the new alignment
+    pub packs: Vec<(Span, Option<u32>)>,
+    /// The alignments saved by #pragma pack(push...) with their optional identifier
+    pack_stack: Vec<(Option<String>, Option<u32>)>,
+}
+
+impl PragmaState {
+    pub fn is_poisoned(&self, name: &str) -> bool {
+        !self.poisoned.is_empty() && self.poisoned.contains(name)
+    }
+
+    pub fn is_system_header(&self, file: FileId) -> bool {
+        self.system_headers.contains(&file)
+    }
+
+    /// Handle the arguments of #pragma pack: (), (n), (show),
+    /// (push[, id][, n]) or (pop[, id][, n])
+    /// The diagnostic to report is returned: the alignment for (show) or the malformed pragma.
+    fn pack(&mut self, pragma: &Pragma, args: &[Token]) -> Option<LexerError> {
+        let sp = pragma.sp;
+        if let [Token::LeftParen, tok, Token::RightParen] = args {
+            if pragma.get_identifier(tok) == Some("show") {
+                return Some(LexerError::PragmaPackShow {
+                    sp,
+                    pack: self.pack,
+                });
+            }
+        }
+
+        match self.set_pack(pragma, args) {
+            Some(()) => None,
+            None => Some(LexerError::InvalidPragmaPack { sp }),
+        }
+    }
+
+    fn set_pack(&mut self, pragma: &Pragma, args: &[Token]) -> Option<()> {
+        let args = match args {
+            [Token::LeftParen, args @ .., Token::RightParen] => args,
+            _ => return None,
+        };
+        let args: Vec<&[Token]> = if args.is_empty() {
+            Vec::new()
+        } else {
+            args.split(|tok| *tok == Token::Comma).collect()
+        };
+
+        let pack = match args.as_slice() {
+            [] => None,
+            [[tok]] if pragma.get_identifier(tok).is_none() => Some(get_alignment(tok)?),
+            [[action], rest @ ..] => {
+                let (id, n) = match rest {
+                    [] => (None, None),
+                    [[tok]] => match pragma.get_identifier(tok) {
+                        Some(id) => (Some(id), None),
+                        None => (None, Some(get_alignment(tok)?)),
+                    },
+                    [[id], [n]] => (Some(pragma.get_identifier(id)?), Some(get_alignment(n)?)),
+                    _ => return None,
+                };
+                match pragma.get_identifier(action)? {
+                    "push" => self.pack_stack.push((id.map(String::from), self.pack)),
+                    "pop" => {
+                        if let Some(id) = id {
+                            // Pop everything until the alignment pushed with this identifier
+                            let pos = self
+                                .pack_stack
+                                .iter()
+                                .rposition(|(i, _)| i.as_deref() == Some(id))?;
+                            self.pack = self.pack_stack[pos].1;
+                            self.pack_stack.truncate(pos);
+                        } else if let Some((_, pack)) = self.pack_stack.pop() {
+                            self.pack = pack;
+                        }
+                    }
+                    _ => return None,
+                }
+                n.or(self.pack)
+            }
+            _ => return None,
+        };
+
+        self.pack = pack;
+        self.packs.push((pragma.sp, pack));
+        Some(())
+    }
+}
+
+/// The alignments are 1, 2, 4, 8 or 16
+fn get_alignment(tok: &Token) -> Option<u32> {
+    match tok {
+        Token::LiteralInteger(n) => match n.spelling.parse::<u32>() {
+            Ok(n @ 1) | Ok(n @ 2) | Ok(n @ 4) | Ok(n @ 8) | Ok(n @ 16) => Some(n),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Lex some code with the language of the pragma (the macros aren't expanded)
+/// and put the identifiers in the symbol table of the lexer
+fn get_tokens(
+    code: &[u8],
+    std: LangStandard,
+    mut dialect: Dialect,
+    symbols: &mut SymbolTable,
+) -> Vec<Token> {
+    let mut lexer = Lexer::<EmptyContext>::new(code);
+    lexer.set_standard(std);
+    // The trigraphs have already been replaced in the pragma
+    dialect.trigraphs = false;
+    lexer.set_dialect(dialect);
+    lexer.symbols = std::mem::take(symbols);
+    let mut tokens = Vec::new();
+    loop {
+        match lexer.next_token() {
+            Token::Eof => {
+                *symbols = std::mem::take(&mut lexer.symbols);
+                return tokens;
+            }
+            Token::Eol | Token::Comment => {}
+            tok => tokens.push(tok),
+        }
+    }
+}
+
+impl<'a, PC: PreprocContext> Lexer<'a, PC> {
+    /// Handle #pragma: the rest of the line (with its continuations) is the pragma
+    pub(crate) fn get_pragma(&mut self) {
+        let spos = self.buf.pos();
+        loop {
+            skip_until!(self, b'\n');
+            if self.buf.has_char()
+                && (self.buf.prev_char() == b'\\'
+                    || (self.buf.prev_char() == b'\r' && self.buf.prev_char_n(2) == b'\\'))
+            {
+                self.buf.inc();
+                self.buf.add_new_line_after();
+            } else {
+                break;
+            }
+        }
+
+        let tokens = get_tokens(
+            self.buf.slice(spos),
+            self.std,
+            self.dialect,
+            &mut self.symbols,
+        );
+        let sp = self.span();
+        if self.buf.has_char() {
+            // we're on the \n so consume it
+            self.buf.inc();
+            self.buf.add_new_line_after();
+        }
+        self.handle_pragma(tokens, sp);
+    }
+
+    /// Handle _Pragma("...") (string is true) and __pragma(...)
+    pub(crate) fn get_pragma_operator(&mut self, string: bool) {
+        let start = self.start;
+        skip_whites!(self);
+        if !self.buf.has_char() || self.buf.next_char() != b'(' {
+            self.errors
+                .push(LexerError::InvalidPragmaOperator { sp: self.span() });
+            return;
+        }
+        self.buf.inc();
+
+        let mut tokens = Vec::new();
+        let mut level = 0;
+        loop {
+            match self.next_token() {
+                Token::Eof => {
+                    self.start = start;
+                    self.errors
+                        .push(LexerError::InvalidPragmaOperator { sp: self.span() });
+                    return;
+                }
+                Token::Eol | Token::Comment => {}
+                Token::RightParen if level == 0 => break,
+                tok => {
+                    if tok == Token::LeftParen {
+                        level += 1;
+                    } else if tok == Token::RightParen {
+                        level -= 1;
+                    }
+                    tokens.push(tok);
+                }
+            }
+        }
+        self.start = start;
+        let sp = self.span();
+
+        if string {
+            // The string is destringized (its encoding prefix is dropped) and then lexed
+            tokens = match tokens.as_slice() {
+                [Token::LiteralString(s)]
+                | [Token::LiteralLString(s)]
+                | [Token::LiteralUString(s)]
+                | [Token::LiteralUUString(s)]
+                | [Token::LiteralU8String(s)] => {
+                    get_tokens(s.as_bytes(), self.std, self.dialect, &mut self.symbols)
+                }
+                _ => {
+                    self.errors.push(LexerError::InvalidPragmaOperator { sp });
+                    return;
+                }
+            };
+        }
+        self.handle_pragma(tokens, sp);
+    }
+
+    fn handle_pragma(&mut self, tokens: Vec<Token>, sp: Span) {
+        // The pragma borrows the symbol table so it's moved out while the pragma is handled
+        let symbols = std::mem::take(&mut self.symbols);
+        let pragma = Pragma::new(tokens, sp, &symbols);
+        self.builtin_pragma(&pragma);
+        self.context.pragma(&pragma);
+        self.symbols = symbols;
+    }
+
+    fn builtin_pragma(&mut self, pragma: &Pragma) {
+        let file = self.buf.get_source_id().unwrap();
+        let (name, args) = match pragma.tokens.split_first() {
+            Some((tok, args)) => match pragma.get_identifier(tok) {
+                Some(name) => (name, args),
+                None => return,
+            },
+            None => return,
+        };
+
+        match (pragma.namespace.as_deref(), name, args) {
+            (None, "once", []) => self.context.set_once(file),
+            (
+                None,
+                "push_macro",
+                [Token::LeftParen, Token::LiteralString(mac), Token::RightParen],
+            ) => {
+                self.context.push_macro(mac);
+            }
+            (
+                None,
+                "pop_macro",
+                [Token::LeftParen, Token::LiteralString(mac), Token::RightParen],
+            ) => {
+                self.context.pop_macro(mac);
+            }
+            (None, "pack", args) => {
+                if let Some(err) = self
+                    .context
+                    .get_pragmas_mut()
+                    .and_then(|state| state.pack(pragma, args))
+                {
+                    self.errors.push(err);
+                }
+            }
+            (Some("GCC"), "system_header", []) => {
+                let file = self.context.get_unique_id(file);
+                if let Some(state) = self.context.get_pragmas_mut() {
+                    state.system_headers.insert(file);
+                }
+            }
+            (Some("GCC"), "poison", names) => {
+                if let Some(state) = self.context.get_pragmas_mut() {
+                    for name in names.iter().filter_map(|tok| pragma.get_identifier(tok)) {
+                        state.poisoned.insert(name.to_string());
+                    }
+                }
+            }
+            (Some("GCC"), "warning", [Token::LiteralString(msg)]) => {
+                if let Some(state) = self.context.get_pragmas_mut() {
+                    state.warnings.push((pragma.sp, msg.to_string()));
+                }
+            }
+            (Some("GCC"), "error", [Token::LiteralString(msg)]) => {
+                self.errors.push(LexerError::PragmaError {
+                    sp: pragma.sp,
+                    msg: msg.to_string(),
+                });
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::args::Standard;
+    use crate::lexer::dialect::Keywords;
+    use crate::lexer::preprocessor::context::DefaultContext;
+    use pretty_assertions::assert_eq;
+
+    fn tokens(code: &[u8], symbols: &mut SymbolTable) -> Vec<Token> {
+        let std = LangStandard::default();
+        get_tokens(code, std, Dialect::from(std), symbols)
+    }
+
+    fn get_identifiers<PC: PreprocContext>(p: &mut Lexer<PC>) -> Vec<String> {
+        let mut ids = Vec::new();
+        loop {
+            match p.next_useful() {
+                Token::Eof => return ids,
+                Token::Identifier(id) => ids.push(p.resolve(id).to_string()),
+                _ => {}
+            }
+        }
+    }
+
+    #[test]
+    fn test_pragma_namespace() {
+        let mut symbols = SymbolTable::default();
+        let toks = tokens(b"GCC poison foo", &mut symbols);
+        let pragma = Pragma::new(toks, Span::default(), &symbols);
+        assert_eq!(pragma.namespace.as_deref(), Some("GCC"));
+        let names: Vec<_> = pragma
+            .tokens
+            .iter()
+            .filter_map(|tok| pragma.get_identifier(tok))
+            .collect();
+        assert_eq!(names, vec!["poison", "foo"]);
+
+        let toks = tokens(b"comment(lib, \"foo.lib\")", &mut symbols);
+        let pragma = Pragma::new(toks, Span::default(), &symbols);
+        assert_eq!(pragma.namespace, None);
+        assert_eq!(pragma.tokens.len(), 6);
+
+        // A pragma named like a namespace
+        let toks = tokens(b"omp", &mut symbols);
+        let pragma = Pragma::new(toks, Span::default(), &symbols);
+        assert_eq!(pragma.namespace, None);
+    }
+
+    #[test]
+    fn test_pragma_push_pop_macro() {
+        let mut p = Lexer::<DefaultContext>::new(
+            concat!(
+                "#define A one\n",
+                "#pragma push_macro(\"A\")\n",
+                "#undef A\n",
+                "#define A two\n",
+                "A\n",
+                "_Pragma(\"push_macro(\\\"A\\\")\")\n",
+                "#undef A\n",
+                "A\n",
+                "#pragma pop_macro(\"A\")\n",
+                "A\n",
+                "#pragma pop_macro(\"A\")\n",
+                "A\n",
+                "#pragma pop_macro(\"A\")\n",
+                "A\n",
+            )
+            .as_bytes(),
+        );
+        assert_eq!(
+            get_identifiers(&mut p),
+            vec!["two", "A", "two", "one", "one",]
+        );
+        assert!(p.errors.is_empty(), "{:?}", p.errors);
+    }
+
+    #[test]
+    fn test_pragma_operator() {
+        let mut p = Lexer::<DefaultContext>::new(
+            concat!(
+                "#define DO_PRAGMA(x) _Pragma(#x)\n",
+                "#define POISON(x) DO_PRAGMA(GCC poison x)\n",
+                "a POISON(foo bar) b\n",
+                "_Pragma(\"GCC warning \\\"hello\\\"\")\n",
+                "foo\n",
+                "_Pragma(42)\n",
+                "c\n",
+            )
+            .as_bytes(),
+        );
+        assert_eq!(get_identifiers(&mut p), vec!["a", "b", "foo", "c",]);
+
+        let state = p.context.get_pragmas().unwrap();
+        assert!(state.is_poisoned("foo"));
+        assert!(state.is_poisoned("bar"));
+        assert_eq!(state.warnings.len(), 1);
+        assert_eq!(state.warnings[0].1, "hello");
+        assert_eq!(state.warnings[0].0.start.line, 4);
+
+        assert_eq!(p.errors.len(), 2);
+        assert!(
+            matches!(&p.errors[0], LexerError::PoisonedIdentifier { name, sp } if name == "foo" && sp.start.line == 5),
+            "{:?}",
+            p.errors[0]
+        );
+        assert!(
+            matches!(p.errors[1], LexerError::InvalidPragmaOperator { .. }),
+            "{:?}",
+            p.errors[1]
+        );
+    }
+
+    #[test]
+    fn test_pragma_operator_prefix() {
+        let mut p = Lexer::<DefaultContext>::new(
+            concat!(
+                "_Pragma(L\"GCC poison foo\")\n",
+                "_Pragma(u8\"GCC poison bar\")\n",
+                "_Pragma(U\"GCC poison baz\")\n",
+            )
+            .as_bytes(),
+        );
+        p.consume_all();
+
+        let state = p.context.get_pragmas().unwrap();
+        assert!(state.is_poisoned("foo"));
+        assert!(state.is_poisoned("bar"));
+        assert!(state.is_poisoned("baz"));
+        assert!(p.errors.is_empty(), "{:?}", p.errors);
+    }
+
+    #[test]
+    fn test_pragma_dialect() {
+        // and is an identifier in C (and an operator in C++)
+        let mut p =
+            Lexer::<DefaultContext>::new(b"_Pragma(\"GCC poison and\")\n#pragma GCC poison or\n");
+        p.set_standard(LangStandard::new(Standard::C11, false));
+        p.consume_all();
+
+        let state = p.context.get_pragmas().unwrap();
+        assert!(state.is_poisoned("and"));
+        assert!(state.is_poisoned("or"));
+    }
+
+    #[test]
+    fn test_pragma_gcc() {
+        let mut p = Lexer::<DefaultContext>::new(
+            concat!(
+                "#pragma GCC system_header\n",
+                "#pragma GCC error \"stop\"\n",
+                "#pragma GCC \\\n",
+                "    poison baz\n",
+                "#pragma GCC diagnostic push\n",
+                "baz\n",
+            )
+            .as_bytes(),
+        );
+        p.consume_all();
+        assert_eq!(p.errors.len(), 2);
+        match &p.errors[0] {
+            LexerError::PragmaError { msg, sp } => {
+                assert_eq!(msg, "stop");
+                assert_eq!(sp.start.line, 2);
+            }
+            err => panic!("mismatch. Was: {:?}", err),
+        }
+        match &p.errors[1] {
+            LexerError::PoisonedIdentifier { name, sp } => {
+                assert_eq!(name, "baz");
+                assert_eq!(sp.start.line, 6);
+            }
+            err => panic!("mismatch. Was: {:?}", err),
+        }
+        assert!(p.context.get_pragmas().unwrap().is_system_header(FileId(0)));
+    }
+
+    #[test]
+    fn test_pragma_pack() {
+        let mut p = Lexer::<DefaultContext>::new(
+            concat!(
+                "#pragma pack(4)\n",
+                "#pragma pack(push, 2)\n",
+                "#pragma pack(push, r1, 8)\n",
+                "#pragma pack(push)\n",
+                "#pragma pack(show)\n",
+                "#pragma pack(pop, r1)\n",
+                "#pragma pack(3)\n",
+                "__pragma(pack(pop))\n",
+                "#pragma pack()\n",
+            )
+            .as_bytes(),
+        );
+        let mut dialect = p.get_dialect();
+        dialect.keywords |= Keywords::MS;
+        p.set_dialect(dialect);
+        p.consume_all();
+
+        let state = p.context.get_pragmas().unwrap();
+        let packs: Vec<_> = state
+            .packs
+            .iter()
+            .map(|(sp, pack)| (sp.start.line, *pack))
+            .collect();
+        assert_eq!(
+            packs,
+            vec![
+                (1, Some(4)),
+                (2, Some(2)),
+                (3, Some(8)),
+                (4, Some(8)),
+                (6, Some(2)),
+                (8, Some(4)),
+                (9, None),
+            ]
+        );
+        assert_eq!(state.pack, None);
+
+        let errors: Vec<_> = p
+            .errors
+            .iter()
+            .map(|e| {
+                let e = e.stringly();
+                (e.sp.start.line, e.message)
+            })
+            .collect();
+        assert_eq!(
+            errors,
+            vec![
+                (5, "value of #pragma pack(show) == 8".to_string()),
+                (7, "malformed #pragma pack ignored".to_string()),
+            ]
+        );
+    }
+}
@@ -4,14 +4,17 @@
 // copied, modified, or distributed except according to those terms.
 
 use hashbrown::HashMap;
+use std::path::PathBuf;
 
 use super::condition::Condition;
-use super::context::{IfKind, IfState, PreprocContext};
-use super::macros::{Action, Macro, MacroFunction, MacroObject, MacroType};
+use super::context::{EmptyContext, IfKind, IfState, PreprocContext};
+use super::macros::{Action, Macro, MacroFunction, MacroObject, MacroType, VaOpt};
 use crate::lexer::buffer::{FileInfo, OutBuf, Position};
 use crate::lexer::errors::LexerError;
 use crate::lexer::lexer::{Lexer, TLexer, Token};
+use crate::lexer::scan;
 use crate::lexer::string::StringType;
+use crate::toolchain::Capability;
 
 #[derive(Clone, Debug, Copy, PartialEq, PartialOrd)]
 #[repr(u8)]
@@ -87,6 +90,7 @@ pub(crate) const PPCHARS: [Kind; 256] = [
 pub enum LastKind {
     None,
     Arg(usize),
+    VaOpt(usize),
     Concat,
     Id,
     Space,
@@ -162,26 +166,81 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
                 Token::PreprocDefine
             }
             Token::PreprocPragma => {
-                skip_until!(self, b'\n');
-                // we're on the \n so consume it
-                self.buf.inc();
-                self.buf.add_new_line();
+                self.get_pragma();
                 Token::PreprocPragma
             }
+            Token::PreprocLine => {
+                self.get_line_directive();
+                Token::PreprocLine
+            }
             Token::PreprocError => {
                 let spos = self.buf.pos();
                 skip_until!(self, b'\n');
-                let sl = self.buf.slice(spos);
+                let msg = String::from_utf8_lossy(self.buf.slice(spos)).to_string();
                 let span = self.span();
                 self.buf.inc();
-                self.buf.add_new_line();
-                let msg = String::from_utf8_lossy(&sl).to_string();
+                self.buf.add_new_line_after();
                 return Err(LexerError::ErrorDirective { sp: span, msg });
             }
             _ => instr,
         })
     }
 
+    /// Handle `#line N "file"` and the linemarkers `# N "file" flags...`:
+    /// the next line is the line N of the file
+    pub(crate) fn get_line_directive(&mut self) {
+        let tok = self.next_line_token();
+        let line = match &tok {
+            Token::LiteralInteger(n) if n.spelling.bytes().all(|c| c.is_ascii_digit()) => {
+                // The digits are always decimal (even with a leading 0)
+                n.spelling.parse::<u32>().ok()
+            }
+            _ => None,
+        };
+        let line = if let Some(line) = line {
+            line
+        } else {
+            self.errors
+                .push(LexerError::InvalidLineNumber { sp: self.span() });
+            self.skip_line_directive(tok);
+            return;
+        };
+
+        let (file, tok) = match self.next_line_token() {
+            Token::LiteralString(name) => {
+                let id = self.context.get_id(&PathBuf::from(&*name));
+                (Some(id), self.next_line_token())
+            }
+            tok @ Token::Eol | tok @ Token::Eof => (None, tok),
+            tok => {
+                self.errors
+                    .push(LexerError::InvalidLineFilename { sp: self.span() });
+                self.skip_line_directive(tok);
+                return;
+            }
+        };
+
+        // The flags of the linemarkers (1: enter, 2: return, 3: system, 4: extern "C")
+        // aren't used
+        self.skip_line_directive(tok);
+        self.buf.set_presumed(line, file);
+    }
+
+    fn next_line_token(&mut self) -> Token {
+        loop {
+            let tok = self.next_token();
+            if tok != Token::Comment {
+                return tok;
+            }
+        }
+    }
+
+    fn skip_line_directive(&mut self, mut tok: Token) {
+        while tok != Token::Eol && tok != Token::Eof {
+            tok = self.next_token();
+        }
+    }
+
     #[inline(always)]
     pub(crate) fn skip_until_matching_paren(&mut self) {
         // Used to skip unevaluated part of or/and operator in condition
@@ -203,7 +262,7 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
     }
 
     #[inline(always)]
-    pub(crate) fn get_preproc_identifier(&mut self) -> &'a str {
+    pub(crate) fn get_preproc_identifier<'s>(&mut self, src: &'s [u8]) -> &'s str {
         let spos = self.buf.pos();
         loop {
             if self.buf.has_char() {
@@ -218,7 +277,7 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
             }
         }
 
-        unsafe { std::str::from_utf8_unchecked(&self.buf.slice(spos)) }
+        unsafe { std::str::from_utf8_unchecked(&src[spos..self.buf.pos()]) }
     }
 
     #[inline(always)]
@@ -241,13 +300,31 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
         }
     }
 
+    /// Get the length of the # (or of the digraph %:) at the current position (0 if none)
+    #[inline(always)]
+    pub(crate) fn get_hash_len(&self) -> usize {
+        if self.buf.has_char() {
+            let c = self.buf.next_char();
+            if c == b'#' {
+                return 1;
+            } else if c == b'%'
+                && self.dialect.digraphs
+                && self.buf.has_char_n(1)
+                && self.buf.next_char_n(1) == b':'
+            {
+                return 2;
+            }
+        }
+        0
+    }
+
     #[inline(always)]
     pub(crate) fn skip_none(&mut self) {
         loop {
             if self.buf.has_char() {
                 let c = self.buf.next_char();
                 let kind = unsafe { *PPCHARS.get_unchecked(c as usize) };
-                if kind != Kind::NON {
+                if kind != Kind::NON || (c == b'%' && self.get_hash_len() == 2) {
                     break;
                 }
                 self.buf.inc();
@@ -258,26 +335,28 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
     }
 
     #[inline(always)]
-    pub(crate) fn skip_spaces_or_hash(&mut self) -> MacroToken<'a> {
+    pub(crate) fn skip_spaces_or_hash(&mut self) -> MacroToken<'static> {
         loop {
             if self.buf.has_char() {
                 let c = self.buf.next_char();
                 let kind = unsafe { *PPCHARS.get_unchecked(c as usize) };
                 if kind != Kind::SPA {
-                    if c != b'#' {
+                    let n = self.get_hash_len();
+                    if n == 0 {
                         return MacroToken::Space;
                     }
 
-                    self.buf.inc();
+                    self.buf.inc_n(n);
                     if self.buf.has_char() {
-                        let c = self.buf.next_char();
-                        if c == b'#' {
-                            self.buf.inc();
+                        let n = self.get_hash_len();
+                        if n != 0 {
+                            self.buf.inc_n(n);
                             skip_whites!(self);
                             return MacroToken::Concat;
                         }
                         return MacroToken::WhiteStringify;
                     }
+                    continue;
                 }
                 self.buf.inc();
             } else {
@@ -288,64 +367,64 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
     }
 
     #[inline(always)]
-    pub(crate) fn next_macro_token(&mut self) -> MacroToken<'a> {
+    pub(crate) fn next_macro_token<'s>(&mut self, src: &'s [u8]) -> MacroToken<'s> {
         loop {
             if self.buf.has_char() {
                 let c = self.buf.next_char();
                 let kind = unsafe { *PPCHARS.get_unchecked(c as usize) };
                 match kind {
                     Kind::IDE => {
-                        return MacroToken::Id(self.get_preproc_identifier());
+                        return MacroToken::Id(self.get_preproc_identifier(src));
                     }
                     Kind::IDL => {
                         let p = self.buf.pos();
                         self.buf.inc();
                         if self.get_special_string_char(StringType::L).is_some() {
-                            let s = self.buf.slice(p);
+                            let s = &src[p..self.buf.pos()];
                             return MacroToken::None(s);
                         } else {
                             self.buf.dec();
-                            return MacroToken::Id(self.get_preproc_identifier());
+                            return MacroToken::Id(self.get_preproc_identifier(src));
                         }
                     }
                     Kind::IDR => {
                         let p = self.buf.pos();
                         self.buf.inc();
                         if self.get_special_string_char(StringType::R).is_some() {
-                            let s = self.buf.slice(p);
+                            let s = &src[p..self.buf.pos()];
                             return MacroToken::None(s);
                         } else {
                             self.buf.dec();
-                            return MacroToken::Id(self.get_preproc_identifier());
+                            return MacroToken::Id(self.get_preproc_identifier(src));
                         }
                     }
                     Kind::IDU => {
                         let p = self.buf.pos();
                         self.buf.inc();
                         if self.get_special_string_char(StringType::UU).is_some() {
-                            let s = self.buf.slice(p);
+                            let s = &src[p..self.buf.pos()];
                             return MacroToken::None(s);
                         } else {
                             self.buf.dec();
-                            return MacroToken::Id(self.get_preproc_identifier());
+                            return MacroToken::Id(self.get_preproc_identifier(src));
                         }
                     }
                     Kind::IDu => {
                         let p = self.buf.pos();
                         self.buf.inc();
                         if self.get_special_string_char(StringType::U).is_some() {
-                            let s = self.buf.slice(p);
+                            let s = &src[p..self.buf.pos()];
                             return MacroToken::None(s);
                         } else {
                             self.buf.dec();
-                            return MacroToken::Id(self.get_preproc_identifier());
+                            return MacroToken::Id(self.get_preproc_identifier(src));
                         }
                     }
                     Kind::NUM => {
                         let p = self.buf.pos();
                         self.buf.inc();
-                        self.skip_number(c);
-                        let s = self.buf.slice(p);
+                        self.skip_pp_number();
+                        let s = &src[p..self.buf.pos()];
                         return MacroToken::None(s);
                     }
                     Kind::SPA => {
@@ -354,14 +433,8 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
                     }
                     Kind::HAS => {
                         self.buf.inc();
-                        if self.buf.has_char() {
-                            let c = self.buf.next_char();
-                            if c == b'#' {
-                                self.buf.inc();
-                                skip_whites!(self);
-                                return MacroToken::Concat;
-                            }
-                            return MacroToken::Stringify;
+                        if let Some(tok) = self.get_stringify_or_concat() {
+                            return tok;
                         }
                     }
                     Kind::QUO => {
@@ -369,12 +442,12 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
                         let p = self.buf.pos();
                         self.buf.inc();
                         self.skip_by_delim(c);
-                        let s = self.buf.slice(p);
+                        let s = &src[p..self.buf.pos()];
                         return MacroToken::None(s);
                     }
                     Kind::RET => {
                         self.buf.inc();
-                        self.buf.add_new_line();
+                        self.buf.add_new_line_after();
                         break;
                     }
                     Kind::SLA => {
@@ -398,9 +471,16 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
                         }
                     }
                     Kind::NON => {
+                        if self.get_hash_len() == 2 {
+                            self.buf.inc_n(2);
+                            if let Some(tok) = self.get_stringify_or_concat() {
+                                return tok;
+                            }
+                            continue;
+                        }
                         let p = self.buf.pos();
                         self.skip_none();
-                        let s = self.buf.slice(p);
+                        let s = &src[p..self.buf.pos()];
                         return MacroToken::None(s);
                     }
                 }
@@ -411,20 +491,52 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
         MacroToken::Eom
     }
 
+    /// Get the token after a # (or %:) in a macro definition
+    #[inline(always)]
+    fn get_stringify_or_concat(&mut self) -> Option<MacroToken<'static>> {
+        if self.buf.has_char() {
+            let n = self.get_hash_len();
+            if n != 0 {
+                self.buf.inc_n(n);
+                skip_whites!(self);
+                return Some(MacroToken::Concat);
+            }
+            Some(MacroToken::Stringify)
+        } else {
+            None
+        }
+    }
+
     #[inline(always)]
     pub(crate) fn get_function_definition(
         &mut self,
+        src: &[u8],
         args: HashMap<&str, usize>,
         va_args: Option<usize>,
         info: FileInfo,
     ) -> MacroFunction {
+        let mut va_opts = Vec::new();
+        let (out, actions) =
+            self.get_replacement_list(src, &args, va_args, va_args.map(|_| &mut va_opts));
+        MacroFunction::new(out, actions, va_opts, args.len(), va_args, info)
+    }
+
+    /// Get the replacement list of a function-like macro: the __VA_OPT__(...) are
+    /// only handled in the one of a variadic macro (and not in a __VA_OPT__ itself)
+    fn get_replacement_list(
+        &mut self,
+        src: &[u8],
+        args: &HashMap<&str, usize>,
+        va_args: Option<usize>,
+        mut va_opts: Option<&mut Vec<VaOpt>>,
+    ) -> (Vec<u8>, Vec<Action>) {
         let mut out = Vec::with_capacity(1024);
         let mut actions = Vec::with_capacity(args.len());
         let mut last_kind = LastKind::None;
         let mut last_chunk_end = 0;
 
         loop {
-            let tok = self.next_macro_token();
+            let tok = self.next_macro_token(src);
             match tok {
                 MacroToken::None(s) => {
                     out.extend_from_slice(s);
@@ -433,11 +545,19 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
                 MacroToken::Id(id) => {
                     if let Some(arg_pos) = args.get(id) {
                         let n = *arg_pos;
-                        if last_chunk_end != out.len() {
-                            actions.push(Action::Chunk(out.len()));
-                            last_chunk_end = out.len();
+                        // , ## __VA_ARGS__: the comma is removed with the arguments (GNU)
+                        let comma = last_kind == LastKind::Concat
+                            && va_args == Some(n)
+                            && out.last() == Some(&b',');
+                        let end = if comma { out.len() - 1 } else { out.len() };
+                        if last_chunk_end != end {
+                            actions.push(Action::Chunk(end));
+                            last_chunk_end = end;
                         }
                         match last_kind {
+                            LastKind::Concat if comma => {
+                                actions.push(Action::CommaVaArgs(n));
+                            }
                             LastKind::Concat => {
                                 actions.push(Action::Concat(n));
                             }
@@ -446,6 +566,20 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
                             }
                         }
                         last_kind = LastKind::Arg(n);
+                    } else if let Some(va_opts) = va_opts.as_mut().filter(|_| id == "__VA_OPT__") {
+                        let va_opt = self.get_va_opt(src, args, va_args);
+                        if last_chunk_end != out.len() {
+                            actions.push(Action::Chunk(out.len()));
+                            last_chunk_end = out.len();
+                        }
+                        let n = va_opts.len();
+                        va_opts.push(va_opt);
+                        if last_kind == LastKind::Concat {
+                            actions.push(Action::ConcatVaOpt(n));
+                        } else {
+                            actions.push(Action::VaOpt(n));
+                        }
+                        last_kind = LastKind::VaOpt(n);
                     } else {
                         out.extend_from_slice(id.as_bytes());
                         last_kind = LastKind::None;
@@ -461,7 +595,7 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
                     if tok == MacroToken::WhiteStringify && last_kind != LastKind::Space {
                         out.push(b' ');
                     }
-                    let id = self.get_preproc_identifier();
+                    let id = self.get_preproc_identifier(src);
                     if let Some(arg_pos) = args.get(id) {
                         out.extend_from_slice(b"\"\"");
                         if last_chunk_end != out.len() - 1 {
@@ -469,6 +603,15 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
                             last_chunk_end = out.len() - 1;
                         }
                         actions.push(Action::Stringify(*arg_pos));
+                    } else if let Some(va_opts) = va_opts.as_mut().filter(|_| id == "__VA_OPT__") {
+                        let va_opt = self.get_va_opt(src, args, va_args);
+                        out.extend_from_slice(b"\"\"");
+                        if last_chunk_end != out.len() - 1 {
+                            actions.push(Action::Chunk(out.len() - 1));
+                            last_chunk_end = out.len() - 1;
+                        }
+                        actions.push(Action::StringifyVaOpt(va_opts.len()));
+                        va_opts.push(va_opt);
                     } else {
                         out.push(b'#');
                         out.extend_from_slice(id.as_bytes());
@@ -476,9 +619,18 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
                     last_kind = LastKind::None;
                 }
                 MacroToken::Concat => {
-                    if let LastKind::Arg(n) = last_kind {
-                        actions.pop();
-                        actions.push(Action::Concat(n));
+                    match last_kind {
+                        LastKind::Arg(n) => {
+                            if let Some(Action::Arg(_)) = actions.last() {
+                                actions.pop();
+                                actions.push(Action::Concat(n));
+                            }
+                        }
+                        LastKind::VaOpt(n) => {
+                            actions.pop();
+                            actions.push(Action::ConcatVaOpt(n));
+                        }
+                        _ => {}
                     }
                     last_kind = LastKind::Concat;
                 }
@@ -488,11 +640,89 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
             }
         }
 
-        MacroFunction::new(out, actions, args.len(), va_args, info)
+        (out, actions)
+    }
+
+    /// Get the replacement list in __VA_OPT__(...)
+    fn get_va_opt(
+        &mut self,
+        src: &[u8],
+        args: &HashMap<&str, usize>,
+        va_args: Option<usize>,
+    ) -> VaOpt {
+        let content = match self.get_va_opt_content(src) {
+            Ok(content) => content,
+            Err(paren) => {
+                self.errors.push(LexerError::MissingParenAfterOperator {
+                    sp: self.span(),
+                    name: "__VA_OPT__".to_string(),
+                    paren,
+                });
+                b""
+            }
+        };
+
+        let content = content.trim_ascii();
+        let mut lexer = Lexer::<EmptyContext>::new(content);
+        let (out, actions) = lexer.get_replacement_list(content, args, va_args, None);
+        VaOpt { out, actions }
+    }
+
+    /// Get the tokens between the parenthesis after __VA_OPT__
+    /// (or the missing parenthesis)
+    fn get_va_opt_content<'s>(&mut self, src: &'s [u8]) -> Result<&'s [u8], char> {
+        skip_whites!(self);
+        if !self.buf.has_char() || self.buf.next_char() != b'(' {
+            return Err('(');
+        }
+        self.buf.inc();
+
+        let spos = self.buf.pos();
+        let mut level = 0;
+        while self.buf.has_char() {
+            let c = self.buf.next_char();
+            match c {
+                b'(' => {
+                    level += 1;
+                }
+                b')' => {
+                    if level == 0 {
+                        let content = &src[spos..self.buf.pos()];
+                        self.buf.inc();
+                        return Ok(content);
+                    }
+                    level -= 1;
+                }
+                b'"' | b'\'' => {
+                    self.buf.inc();
+                    self.skip_by_delim(c);
+                    continue;
+                }
+                b'/' => {
+                    self.buf.inc();
+                    self.skip_slash_or_not();
+                    continue;
+                }
+                b'\\' => {
+                    self.buf.inc();
+                    if self.buf.has_char() && self.buf.next_char() == b'\n' {
+                        self.buf.add_new_line();
+                        self.buf.inc();
+                    }
+                    continue;
+                }
+                b'\n' => {
+                    break;
+                }
+                _ => {}
+            }
+            self.buf.inc();
+        }
+        Err(')')
     }
 
     #[inline(always)]
-    pub(crate) fn get_object_definition(&mut self, info: FileInfo) -> MacroObject {
+    pub(crate) fn get_object_definition(&mut self, src: &[u8], info: FileInfo) -> MacroObject {
         let mut out = Vec::with_capacity(64);
         let mut last_kind = LastKind::None;
         let mut has_id = false;
@@ -500,7 +730,7 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
         skip_whites!(self);
 
         loop {
-            let tok = self.next_macro_token();
+            let tok = self.next_macro_token(src);
             match tok {
                 MacroToken::None(s) => {
                     out.extend_from_slice(s);
@@ -534,11 +764,12 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
         context: &P,
         info: &FileInfo,
     ) {
+        let src = self.buf.source();
         let mut fake: Option<String> = None;
         loop {
             let tok = fake
                 .as_ref()
-                .map_or_else(|| self.next_macro_token(), |x| MacroToken::Id(x));
+                .map_or_else(|| self.next_macro_token(&src), |x| MacroToken::Id(x));
             match tok {
                 MacroToken::None(s) => {
                     out.invalidate();
@@ -554,7 +785,7 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
                             }
                             Macro::Function(mac) => {
                                 if let Some(args) =
-                                    self.get_arguments(mac.len(), mac.va_args.as_ref())
+                                    self.get_arguments(&src, mac.len(), mac.va_args.as_ref())
                                 {
                                     mac.eval_parsed_args(&args, context, info, out);
                                     fake = out.last.take();
@@ -608,6 +839,16 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
         // So need to figure out a solution to avoid double lookup
         match self.context.get_type(name) {
             MacroType::None => {
+                if self
+                    .context
+                    .get_pragmas()
+                    .is_some_and(|state| state.is_poisoned(name))
+                {
+                    self.errors.push(LexerError::PoisonedIdentifier {
+                        sp: self.span(),
+                        name: name.to_string(),
+                    });
+                }
                 return false;
             }
             MacroType::Object(mac) => {
@@ -615,7 +856,8 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
                 mac.eval(self.buf.get_preproc_buf(), &self.context, &info);
             }
             MacroType::Function((n, va_args)) => {
-                if let Some(args) = self.get_arguments(n, va_args.as_ref()) {
+                let src = self.buf.source();
+                if let Some(args) = self.get_arguments(&src, n, va_args.as_ref()) {
                     let info = self.buf.get_line_file();
                     if let Macro::Function(mac) = self.context.get(name).unwrap() {
                         mac.eval_parsed_args(
@@ -662,6 +904,10 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
         }
 
         loop {
+            // Only the quotes, the new lines and the comments are interesting here
+            let rem = self.buf.remainder();
+            self.buf
+                .inc_n(scan::find_byte4(rem, b'\n', b'"', b'\'', b'/').unwrap_or(rem.len()));
             if self.buf.has_char() {
                 let c = self.buf.next_char();
                 self.buf.inc();
@@ -705,11 +951,11 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
         // we must be after a newline and skipped whites
         // the goal is to avoid to catch #define foo(else) #else
         Ok(if self.buf.has_char() {
-            let c = self.buf.next_char();
-            if c == b'#' {
+            let n = self.get_hash_len();
+            if n != 0 {
                 let raw_pos = self.buf.raw_pos();
                 // we've a hash at the beginning of a line
-                self.buf.inc();
+                self.buf.inc_n(n);
                 skip_whites!(self);
                 // we're looking only for an id starting with a 'i' or a 'e'
                 let c = self.buf.next_char();
@@ -752,16 +998,35 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
             skip_whites!(self);
             let condition = match kind {
                 IfKind::If => {
+                    // #if !defined(NAME) may be an include guard too
+                    let guard = if scan::is_blank_or_comment(self.buf.slice_p(0, pos)) {
+                        let rest = self.buf.remainder();
+                        let line = &rest[..scan::find_byte(rest, b'\n').unwrap_or(rest.len())];
+                        scan::get_negated_defined(line)
+                            .map(|name| String::from_utf8_lossy(name).into_owned())
+                    } else {
+                        None
+                    };
+
                     let mut condition = Condition::new(self);
-                    condition.eval_as_bool()
+                    let condition = condition.eval_as_bool();
+                    if let Some(name) = guard.filter(|_| condition) {
+                        self.buf.set_guard_candidate(name, pos);
+                    }
+                    condition
                 }
                 IfKind::Ifdef => {
-                    let id = self.get_preproc_identifier();
-                    self.context.defined(id)
+                    let id = self.get_name().into_owned();
+                    self.is_defined(&id)
                 }
                 IfKind::Ifndef => {
-                    let id = self.get_preproc_identifier();
-                    !self.context.defined(id)
+                    let id = self.get_name().into_owned();
+                    let defined = self.is_defined(&id);
+                    if !defined && scan::is_blank_or_comment(self.buf.slice_p(0, pos)) {
+                        // Maybe an include guard: check it on the matching #endif
+                        self.buf.set_guard_candidate(id, pos);
+                    }
+                    !defined
                 }
             };
 
@@ -857,6 +1122,11 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
 
             self.context.save_switch(file_id, prev, pos);
             self.context.rm_if();
+            if let Some(name) = self.buf.take_guard_candidate(prev) {
+                if scan::is_blank_or_comment(self.buf.remainder()) {
+                    self.context.set_guard(file_id, name);
+                }
+            }
             Ok(if let Some(state) = self.context.if_state() {
                 std::mem::discriminant(state) == std::mem::discriminant(&IfState::Eval(0))
             } else {
@@ -872,20 +1142,21 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
         let info = self.buf.get_line_file();
 
         skip_whites!(self);
-        let name = self.get_preproc_identifier();
+        let name = self.get_name().into_owned();
         //self.debug(&format!("DEFINE {}", name));
         if self.buf.has_char() {
+            let src = self.buf.source();
             let c = self.buf.next_char();
             if c == b'(' {
                 self.buf.inc();
-                let (args, va_args) = self.get_macro_arguments();
+                let (args, va_args) = self.get_macro_arguments(&src);
                 skip_whites!(self);
-                let mac = self.get_function_definition(args, va_args, info);
-                self.context.add_function(name.to_string(), mac);
+                let mac = self.get_function_definition(&src, args, va_args, info);
+                self.context.add_function(name, mac);
             } else {
                 skip_whites!(self);
-                let obj = self.get_object_definition(info);
-                self.context.add_object(name.to_string(), obj);
+                let obj = self.get_object_definition(&src, info);
+                self.context.add_object(name, obj);
             }
         }
     }
@@ -898,7 +1169,7 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
             let name = if c == b'(' {
                 self.buf.inc();
                 skip_whites!(self);
-                let name = self.get_preproc_identifier();
+                let name = self.get_name().into_owned();
                 skip_whites!(self);
                 if self.buf.has_char() {
                     let c = self.buf.next_char();
@@ -908,22 +1179,88 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
                 }
                 name
             } else {
-                self.get_preproc_identifier()
+                self.get_name().into_owned()
             };
             if !skip {
-                return self.context.defined(name) as u64;
+                return self.is_defined(&name) as u64;
             }
         }
 
         0
     }
 
+    /// The operators usable in a condition are defined like macros
+    /// (e.g. #if defined(__has_include) && __has_include(<optional>))
+    fn is_defined(&mut self, name: &str) -> bool {
+        self.context.defined(name)
+            || name == "__has_include"
+            || name == "__has_include_next"
+            || Capability::from_operator(name).is_some()
+    }
+
+    /// Evaluate __has_attribute(x), __has_cpp_attribute(x), ... from the capability table
+    /// (the operand can be a scoped attribute: gnu::always_inline)
+    pub(crate) fn get_has_capability(
+        &mut self,
+        kind: Capability,
+        skip: bool,
+    ) -> Result<u64, LexerError> {
+        skip_whites!(self);
+        if !self.buf.has_char() || self.buf.next_char() != b'(' {
+            return Err(LexerError::MissingParenAfterOperator {
+                sp: self.span(),
+                name: kind.operator().to_string(),
+                paren: '(',
+            });
+        }
+        self.buf.inc();
+        skip_whites!(self);
+
+        let mut name = self.get_name().into_owned();
+        skip_whites!(self);
+        if self.buf.has_char() && self.buf.next_char() == b':' {
+            self.buf.inc();
+            if self.buf.has_char() && self.buf.next_char() == b':' {
+                self.buf.inc();
+                skip_whites!(self);
+                name.push_str("::");
+                name.push_str(&self.get_name());
+                skip_whites!(self);
+            }
+        }
+        if name.is_empty() || name.ends_with(':') {
+            return Err(LexerError::ExpectedIdentifierInOperator {
+                sp: self.span(),
+                name: kind.operator().to_string(),
+            });
+        }
+
+        if self.buf.has_char() && self.buf.next_char() == b')' {
+            self.buf.inc();
+        } else {
+            return Err(LexerError::MissingParenAfterOperator {
+                sp: self.span(),
+                name: kind.operator().to_string(),
+                paren: ')',
+            });
+        }
+
+        if skip {
+            Ok(0)
+        } else {
+            Ok(self
+                .context
+                .get_capabilities()
+                .map_or(0, |caps| caps.get(kind, &name)))
+        }
+    }
+
     #[inline(always)]
     pub(crate) fn get_undef(&mut self) {
         skip_whites!(self);
-        let name = self.get_preproc_identifier();
+        let name = self.get_name().into_owned();
         //self.debug(&format!("UNDEF {}", name));
-        self.context.undef(name);
+        self.context.undef(&name);
     }
 }
 
@@ -933,7 +1270,8 @@ mod tests {
     use super::*;
     use crate::lexer::preprocessor::cache::IfCache;
     use crate::lexer::preprocessor::context::DefaultContext;
-    use crate::lexer::source::FileId;
+    use crate::lexer::preprocessor::include::IncludeLocator;
+    use crate::lexer::source::{self, FileId};
     use pretty_assertions::assert_eq;
     use std::sync::Arc;
 
@@ -941,7 +1279,8 @@ mod tests {
     fn test_parse_args() {
         let mut p = Lexer::<DefaultContext>::new(b"(abcd,efgh    \t , \t \t _ijkl , mno_123)");
         p.buf.inc();
-        let (map, _) = p.get_macro_arguments();
+        let src = p.buf.source();
+        let (map, _) = p.get_macro_arguments(&src);
         let mut expected = HashMap::default();
         for (i, name) in vec!["abcd", "efgh", "_ijkl", "mno_123"].iter().enumerate() {
             expected.insert(*name, i);
@@ -951,14 +1290,16 @@ mod tests {
 
         let mut p = Lexer::<DefaultContext>::new(b"()");
         p.buf.inc();
-        let (map, _) = p.get_macro_arguments();
+        let src = p.buf.source();
+        let (map, _) = p.get_macro_arguments(&src);
         let expected = HashMap::default();
 
         assert_eq!(map, expected);
 
         let mut p = Lexer::<DefaultContext>::new(b"(    )");
         p.buf.inc();
-        let (map, _) = p.get_macro_arguments();
+        let src = p.buf.source();
+        let (map, _) = p.get_macro_arguments(&src);
         let expected = HashMap::default();
 
         assert_eq!(map, expected);
@@ -982,7 +1323,7 @@ mod tests {
         assert_eq!(p.next_token(), Token::PreprocDefine);
         assert_eq!(p.next_token(), Token::PreprocEndif);
         assert_eq!(p.next_token(), Token::Eol);
-        assert_eq!(p.next_token(), Token::LiteralInt(56));
+        assert_eq!(p.next_token(), Token::from_number("56").unwrap());
 
         let mut p = Lexer::<DefaultContext>::new(
             concat!(
@@ -998,7 +1339,7 @@ mod tests {
         assert_eq!(p.next_token(), Token::PreprocDefine);
         assert_eq!(p.next_token(), Token::PreprocIf);
         assert_eq!(p.next_token(), Token::Eol);
-        assert_eq!(p.next_token(), Token::LiteralInt(37));
+        assert_eq!(p.next_token(), Token::from_number("37").unwrap());
 
         let mut p = Lexer::<DefaultContext>::new(
             concat!(
@@ -1019,7 +1360,7 @@ mod tests {
         assert_eq!(p.next_token(), Token::PreprocDefine);
         assert_eq!(p.next_token(), Token::PreprocEndif);
         assert_eq!(p.next_token(), Token::Eol);
-        assert_eq!(p.next_token(), Token::LiteralInt(78));
+        assert_eq!(p.next_token(), Token::from_number("78").unwrap());
 
         let mut p = Lexer::<DefaultContext>::new(
             concat!(
@@ -1039,7 +1380,7 @@ mod tests {
         assert_eq!(p.next_token(), Token::PreprocDefine);
         assert_eq!(p.next_token(), Token::PreprocElse);
         assert_eq!(p.next_token(), Token::Eol);
-        assert_eq!(p.next_token(), Token::LiteralInt(56));
+        assert_eq!(p.next_token(), Token::from_number("56").unwrap());
     }
 
     #[test]
@@ -1083,8 +1424,8 @@ mod tests {
         assert_eq!(p.next_token(), Token::Eol);
         assert_eq!(p.next_token(), Token::PreprocEndif);
         assert_eq!(p.next_token(), Token::Eol);
-        assert_eq!(p.next_token(), Token::LiteralInt(56));
-        assert_eq!(p.next_token(), Token::LiteralInt(910));
+        assert_eq!(p.next_token(), Token::from_number("56").unwrap());
+        assert_eq!(p.next_token(), Token::from_number("910").unwrap());
 
         let mut p = Lexer::<DefaultContext>::new(
             concat!(
@@ -1121,8 +1462,8 @@ mod tests {
         assert_eq!(p.next_token(), Token::Eol);
         assert_eq!(p.next_token(), Token::PreprocEndif);
         assert_eq!(p.next_token(), Token::Eol);
-        assert_eq!(p.next_token(), Token::LiteralInt(56));
-        assert_eq!(p.next_token(), Token::LiteralInt(78));
+        assert_eq!(p.next_token(), Token::from_number("56").unwrap());
+        assert_eq!(p.next_token(), Token::from_number("78").unwrap());
 
         let mut p = Lexer::<DefaultContext>::new(
             concat!(
@@ -1160,8 +1501,8 @@ mod tests {
         assert_eq!(p.next_token(), Token::Eol);
         assert_eq!(p.next_token(), Token::PreprocEndif);
         assert_eq!(p.next_token(), Token::Eol);
-        assert_eq!(p.next_token(), Token::LiteralInt(56));
-        assert_eq!(p.next_token(), Token::LiteralInt(1112));
+        assert_eq!(p.next_token(), Token::from_number("56").unwrap());
+        assert_eq!(p.next_token(), Token::from_number("1112").unwrap());
     }
 
     #[test]
@@ -1188,7 +1529,7 @@ mod tests {
         assert_eq!(p.next_token(), Token::PreprocEndif);
         assert_eq!(p.next_token(), Token::Eol);
 
-        assert_eq!(p.next_token(), Token::LiteralInt(56));
+        assert_eq!(p.next_token(), Token::from_number("56").unwrap());
     }
 
     #[test]
@@ -1211,7 +1552,7 @@ mod tests {
         assert_eq!(p.next_token(), Token::PreprocDefine);
         assert_eq!(p.next_token(), Token::PreprocEndif);
         assert_eq!(p.next_token(), Token::Eol);
-        assert_eq!(p.next_token(), Token::LiteralInt(56));
+        assert_eq!(p.next_token(), Token::from_number("56").unwrap());
     }
 
     #[test]
@@ -1233,7 +1574,7 @@ mod tests {
         assert_eq!(p.next_token(), Token::PreprocDefine);
         assert_eq!(p.next_token(), Token::PreprocIf);
         assert_eq!(p.next_token(), Token::Eol);
-        assert_eq!(p.next_token(), Token::LiteralInt(123));
+        assert_eq!(p.next_token(), Token::from_number("123").unwrap());
     }
 
     #[test]
@@ -1256,7 +1597,7 @@ mod tests {
         assert_eq!(p.next_token(), Token::PreprocDefine);
         assert_eq!(p.next_token(), Token::PreprocIf);
         assert_eq!(p.next_token(), Token::Eol);
-        assert_eq!(p.next_token(), Token::LiteralInt(123));
+        assert_eq!(p.next_token(), Token::from_number("123").unwrap());
     }
 
     #[test]
@@ -1284,7 +1625,7 @@ mod tests {
         assert_eq!(p.next_token(), Token::PreprocDefine);
         assert_eq!(p.next_token(), Token::PreprocEndif);
         assert_eq!(p.next_token(), Token::Eol);
-        assert_eq!(p.next_token(), Token::LiteralInt(789));
+        assert_eq!(p.next_token(), Token::from_number("789").unwrap());
     }
 
     #[test]
@@ -1305,15 +1646,97 @@ mod tests {
         );
 
         assert_eq!(p.next_token(), Token::PreprocDefine);
-        assert_eq!(p.next_token(), Token::LiteralInt(2));
+        assert_eq!(p.next_token(), Token::from_number("2").unwrap());
         assert_eq!(p.next_token(), Token::Eol);
-        assert_eq!(p.next_token(), Token::LiteralInt(3));
+        assert_eq!(p.next_token(), Token::from_number("3").unwrap());
         assert_eq!(p.next_token(), Token::Eol);
-        assert_eq!(p.next_token(), Token::LiteralInt(4));
+        assert_eq!(p.next_token(), Token::from_number("4").unwrap());
         assert_eq!(p.next_token(), Token::Eol);
         assert_eq!(p.next_token(), Token::Comment);
         assert_eq!(p.next_token(), Token::Eol);
-        assert_eq!(p.next_token(), Token::LiteralInt(9));
+        assert_eq!(p.next_token(), Token::from_number("9").unwrap());
+    }
+
+    #[test]
+    fn test_line_directive() {
+        let mut p = Lexer::<DefaultContext>::new(
+            concat!(
+                "#line 10 \"foo.y\"\n",  // 1
+                "__LINE__ __FILE__\n",   // 2
+                "#line 20 // comment\n", // 3
+                "__LINE__ __FILE__\n",   // 4
+                "#define L 100\n",       // 5
+                "#line L\n",             // 6
+                "__LINE__\n",            // 7
+                "a\n",                   // 8
+            )
+            .as_bytes(),
+        );
+        p.context.set_source(source::get_source_mutex());
+
+        assert_eq!(p.next_token(), Token::PreprocLine);
+        assert_eq!(p.next_token(), Token::from_number("10").unwrap());
+        assert_eq!(p.next_token(), Token::LiteralString("foo.y".into()));
+        assert_eq!(p.next_token(), Token::Eol);
+        assert_eq!(p.next_token(), Token::PreprocLine);
+        assert_eq!(p.next_token(), Token::from_number("20").unwrap());
+        assert_eq!(p.next_token(), Token::LiteralString("foo.y".into()));
+        assert_eq!(p.next_token(), Token::Eol);
+        assert_eq!(p.next_token(), Token::PreprocDefine);
+        assert_eq!(p.next_token(), Token::PreprocLine);
+        assert_eq!(p.next_token(), Token::from_number("100").unwrap());
+        assert_eq!(p.next_token(), Token::Eol);
+        assert_eq!(p.next_token(), p.id("a"));
+
+        let sp = p.span();
+        assert_eq!(sp.file, Some(FileId(0)));
+        assert_eq!(sp.start.line, 8);
+        assert_eq!(sp.presumed.start_line, 101);
+        assert_eq!(
+            p.context.get_path(sp.presumed.file.unwrap()),
+            PathBuf::from("foo.y")
+        );
+        assert!(p.errors.is_empty());
+    }
+
+    #[test]
+    fn test_linemarker() {
+        let mut p = Lexer::<DefaultContext>::new(
+            concat!(
+                "# 42 \"bar.h\" 1 3\n", // 1
+                "x\n",                  // 2
+                "#line 0x10\n",         // 3
+                "#line 7 foo\n",        // 4
+                "y\n",                  // 5
+            )
+            .as_bytes(),
+        );
+        p.context.set_source(source::get_source_mutex());
+
+        assert_eq!(p.next_token(), Token::PreprocLine);
+        assert_eq!(p.next_token(), p.id("x"));
+        let sp = p.span();
+        assert_eq!((sp.start.line, sp.presumed.start_line), (2, 42));
+        assert_eq!(
+            p.context.get_path(sp.presumed.file.unwrap()),
+            PathBuf::from("bar.h")
+        );
+
+        assert_eq!(p.next_token(), Token::Eol);
+        assert_eq!(p.next_token(), Token::PreprocLine);
+        assert_eq!(p.next_token(), Token::PreprocLine);
+        assert_eq!(p.next_token(), p.id("y"));
+        let sp = p.span();
+        assert_eq!((sp.start.line, sp.presumed.start_line), (5, 45));
+
+        let errors: Vec<_> = p.errors.iter().map(|e| e.stringly().message).collect();
+        assert_eq!(
+            errors,
+            vec![
+                "#line directive requires a simple digit sequence",
+                "invalid filename for #line directive"
+            ]
+        );
     }
 
     #[test]
@@ -1330,13 +1753,13 @@ mod tests {
         );
 
         assert_eq!(p.next_token(), Token::PreprocDefine);
-        assert_eq!(p.next_token(), Token::LiteralInt(0));
+        assert_eq!(p.next_token(), Token::from_number("0").unwrap());
         assert_eq!(p.next_token(), Token::Eol);
-        assert_eq!(p.next_token(), Token::LiteralInt(1));
+        assert_eq!(p.next_token(), Token::from_number("1").unwrap());
         assert_eq!(p.next_token(), Token::Eol);
-        assert_eq!(p.next_token(), Token::LiteralInt(2));
+        assert_eq!(p.next_token(), Token::from_number("2").unwrap());
         assert_eq!(p.next_token(), Token::Eol);
-        assert_eq!(p.next_token(), Token::LiteralInt(3));
+        assert_eq!(p.next_token(), Token::from_number("3").unwrap());
     }
 
     #[test]
@@ -1392,9 +1815,9 @@ mod tests {
             assert_eq!(p.next_token(), Token::Eol);
             assert_eq!(p.next_token(), Token::PreprocIf);
             assert_eq!(p.next_token(), Token::Eol);
-            assert_eq!(p.next_token(), Token::LiteralInt(3));
+            assert_eq!(p.next_token(), Token::from_number("3").unwrap());
             assert_eq!(p.next_token(), Token::Eol);
-            assert_eq!(p.next_token(), Token::LiteralInt(4));
+            assert_eq!(p.next_token(), Token::from_number("4").unwrap());
             assert_eq!(p.next_token(), Token::Eol);
 
             assert_eq!(cache.get_next(FileId(0), 1).map(|p| p.pos), Some(16));
@@ -1422,7 +1845,7 @@ mod tests {
             assert_eq!(p.next_token(), Token::Eol);
             assert_eq!(p.next_token(), Token::PreprocIf);
             assert_eq!(p.next_token(), Token::Eol);
-            assert_eq!(p.next_token(), Token::LiteralInt(5));
+            assert_eq!(p.next_token(), Token::from_number("5").unwrap());
 
             assert_eq!(cache.get_next(FileId(0), 1).map(|p| p.pos), Some(17));
         }
@@ -1449,11 +1872,11 @@ mod tests {
 
             assert_eq!(p.next_token(), Token::Eol);
             assert_eq!(p.next_token(), Token::PreprocIf);
-            assert_eq!(p.next_token(), Token::LiteralInt(0));
+            assert_eq!(p.next_token(), Token::from_number("0").unwrap());
             assert_eq!(p.next_token(), Token::Eol);
-            assert_eq!(p.next_token(), Token::LiteralInt(1));
+            assert_eq!(p.next_token(), Token::from_number("1").unwrap());
             assert_eq!(p.next_token(), Token::Eol);
-            assert_eq!(p.next_token(), Token::LiteralInt(2));
+            assert_eq!(p.next_token(), Token::from_number("2").unwrap());
             assert_eq!(p.next_token(), Token::Eol);
             assert_eq!(p.next_token(), Token::PreprocElse);
             assert_eq!(p.next_token(), Token::Eol);
@@ -0,0 +1,539 @@
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Fast paths for the loops over the bytes of a buffer.
+//! On x86_64, the chunks of 16 bytes are checked with SSE2 (always available) and
+//! the long scans use chunks of 32 bytes when AVX2 is there.
+//! Elsewhere (and for the tail of a buffer) the bytes are checked one by one.
+//! Each arch function returns Ok(position) if it found something or Err(n)
+//! where n is the number of bytes it has checked.
+
+#[cfg(target_arch = "x86_64")]
+mod x86 {
+    /// Get the bitmask of the bytes of the chunk at position i for which the expression (using v) is true
+    macro_rules! chunk_mask {
+        ($load: ident, $vec: ty, $movemask: ident, $full: expr, $hay: expr, $i: expr, |$v: ident| $e: expr) => {{
+            let $v = $load($hay.as_ptr().add($i) as *const $vec);
+            ($movemask($e) as u32) & $full
+        }};
+    }
+
+    /// Return the position of the first byte for which the mask is set (or the number of checked bytes)
+    macro_rules! find {
+        ($width: expr, $hay: expr, |$i: ident| $mask: expr) => {{
+            let mut $i = 0;
+            while $i + $width <= $hay.len() {
+                let mask = $mask;
+                if mask != 0 {
+                    return Ok($i + mask.trailing_zeros() as usize);
+                }
+                $i += $width;
+            }
+            Err($i)
+        }};
+    }
+
+    /// The bytes in [lo, hi]: the comparisons are signed so the ones >= 0x80 are never in
+    macro_rules! range {
+        ($set1: ident, $gt: ident, $and: ident, $v: expr, $lo: expr, $hi: expr) => {
+            $and(
+                $gt($v, $set1(($lo - 1) as i8)),
+                $gt($set1(($hi + 1) as i8), $v),
+            )
+        };
+    }
+
+    macro_rules! kernels {
+        ($name: ident, $feature: literal, $vec: ty, $width: expr, $full: expr,
+         $load: ident, $set1: ident, $eq: ident, $gt: ident, $or: ident, $and: ident, $movemask: ident) => {
+            pub(super) mod $name {
+                use std::arch::x86_64::*;
+
+                pub(crate) const WIDTH: usize = $width;
+
+                #[target_feature(enable = $feature)]
+                pub(crate) unsafe fn find_byte(hay: &[u8], a: u8) -> Result<usize, usize> {
+                    let a = $set1(a as i8);
+                    find!(WIDTH, hay, |i| chunk_mask!(
+                        $load,
+                        $vec,
+                        $movemask,
+                        $full,
+                        hay,
+                        i,
+                        |v| $eq(v, a)
+                    ))
+                }
+
+                #[target_feature(enable = $feature)]
+                pub(crate) unsafe fn find_byte2(hay: &[u8], a: u8, b: u8) -> Result<usize, usize> {
+                    let a = $set1(a as i8);
+                    let b = $set1(b as i8);
+                    find!(WIDTH, hay, |i| chunk_mask!(
+                        $load,
+                        $vec,
+                        $movemask,
+                        $full,
+                        hay,
+                        i,
+                        |v| { $or($eq(v, a), $eq(v, b)) }
+                    ))
+                }
+
+                #[target_feature(enable = $feature)]
+                pub(crate) unsafe fn find_byte4(
+                    hay: &[u8],
+                    a: u8,
+                    b: u8,
+                    c: u8,
+                    d: u8,
+                ) -> Result<usize, usize> {
+                    let a = $set1(a as i8);
+                    let b = $set1(b as i8);
+                    let c = $set1(c as i8);
+                    let d = $set1(d as i8);
+                    find!(WIDTH, hay, |i| chunk_mask!(
+                        $load,
+                        $vec,
+                        $movemask,
+                        $full,
+                        hay,
+                        i,
+                        |v| { $or($or($eq(v, a), $eq(v, b)), $or($eq(v, c), $eq(v, d))) }
+                    ))
+                }
+
+                #[target_feature(enable = $feature)]
+                pub(crate) unsafe fn skip_blanks(hay: &[u8]) -> Result<usize, usize> {
+                    let space = $set1(b' ' as i8);
+                    let tab = $set1(b'\t' as i8);
+                    find!(WIDTH, hay, |i| {
+                        !chunk_mask!($load, $vec, $movemask, $full, hay, i, |v| {
+                            $or($eq(v, space), $eq(v, tab))
+                        }) & $full
+                    })
+                }
+
+                #[target_feature(enable = $feature)]
+                pub(crate) unsafe fn identifier_len(hay: &[u8]) -> Result<usize, usize> {
+                    let underscore = $set1(b'_' as i8);
+                    find!(WIDTH, hay, |i| {
+                        !chunk_mask!($load, $vec, $movemask, $full, hay, i, |v| {
+                            $or(
+                                $or(
+                                    range!($set1, $gt, $and, v, b'a', b'z'),
+                                    range!($set1, $gt, $and, v, b'A', b'Z'),
+                                ),
+                                $or(range!($set1, $gt, $and, v, b'0', b'9'), $eq(v, underscore)),
+                            )
+                        }) & $full
+                    })
+                }
+
+                /// Get the number of \n, the position of the last one and the number of checked bytes
+                #[target_feature(enable = $feature)]
+                pub(crate) unsafe fn count_lines(hay: &[u8]) -> (usize, Option<usize>, usize) {
+                    let nl = $set1(b'\n' as i8);
+                    let mut count = 0;
+                    let mut last = None;
+                    let mut i = 0;
+                    while i + WIDTH <= hay.len() {
+                        let mask =
+                            chunk_mask!($load, $vec, $movemask, $full, hay, i, |v| $eq(v, nl));
+                        if mask != 0 {
+                            count += mask.count_ones() as usize;
+                            last = Some(i + 31 - mask.leading_zeros() as usize);
+                        }
+                        i += WIDTH;
+                    }
+                    (count, last, i)
+                }
+            }
+        };
+    }
+
+    kernels!(
+        sse2,
+        "sse2",
+        __m128i,
+        16,
+        0xFFFF,
+        _mm_loadu_si128,
+        _mm_set1_epi8,
+        _mm_cmpeq_epi8,
+        _mm_cmpgt_epi8,
+        _mm_or_si128,
+        _mm_and_si128,
+        _mm_movemask_epi8
+    );
+
+    kernels!(
+        avx2,
+        "avx2",
+        __m256i,
+        32,
+        0xFFFF_FFFF,
+        _mm256_loadu_si256,
+        _mm256_set1_epi8,
+        _mm256_cmpeq_epi8,
+        _mm256_cmpgt_epi8,
+        _mm256_or_si256,
+        _mm256_and_si256,
+        _mm256_movemask_epi8
+    );
+
+    #[inline(always)]
+    fn has_avx2() -> bool {
+        is_x86_feature_detected!("avx2")
+    }
+
+    /// For the long scans (comments, skipped blocks, ...)
+    macro_rules! long_scan {
+        ($func: ident, $hay: expr, $( $arg: expr ),*) => {{
+            let hay = $hay;
+            if hay.len() >= avx2::WIDTH && has_avx2() {
+                unsafe { avx2::$func(hay, $( $arg ),*) }
+            } else if hay.len() >= sse2::WIDTH {
+                unsafe { sse2::$func(hay, $( $arg ),*) }
+            } else {
+                Err(0)
+            }
+        }};
+    }
+
+    /// For the short ones (blanks, identifiers) where 16 bytes are generally enough
+    macro_rules! short_scan {
+        ($func: ident, $hay: expr) => {{
+            let hay = $hay;
+            if hay.len() >= sse2::WIDTH {
+                unsafe { sse2::$func(hay) }
+            } else {
+                Err(0)
+            }
+        }};
+    }
+
+    #[inline(always)]
+    pub(super) fn find_byte(hay: &[u8], a: u8) -> Result<usize, usize> {
+        long_scan!(find_byte, hay, a)
+    }
+
+    #[inline(always)]
+    pub(super) fn find_byte2(hay: &[u8], a: u8, b: u8) -> Result<usize, usize> {
+        long_scan!(find_byte2, hay, a, b)
+    }
+
+    #[inline(always)]
+    pub(super) fn find_byte4(hay: &[u8], a: u8, b: u8, c: u8, d: u8) -> Result<usize, usize> {
+        long_scan!(find_byte4, hay, a, b, c, d)
+    }
+
+    #[inline(always)]
+    pub(super) fn skip_blanks(hay: &[u8]) -> Result<usize, usize> {
+        short_scan!(skip_blanks, hay)
+    }
+
+    #[inline(always)]
+    pub(super) fn identifier_len(hay: &[u8]) -> Result<usize, usize> {
+        short_scan!(identifier_len, hay)
+    }
+
+    #[inline(always)]
+    pub(super) fn count_lines(hay: &[u8]) -> (usize, Option<usize>, usize) {
+        if hay.len() >= avx2::WIDTH && has_avx2() {
+            unsafe { avx2::count_lines(hay) }
+        } else if hay.len() >= sse2::WIDTH {
+            unsafe { sse2::count_lines(hay) }
+        } else {
+            (0, None, 0)
+        }
+    }
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+mod portable {
+    #[inline(always)]
+    pub(super) fn find_byte(_hay: &[u8], _a: u8) -> Result<usize, usize> {
+        Err(0)
+    }
+
+    #[inline(always)]
+    pub(super) fn find_byte2(_hay: &[u8], _a: u8, _b: u8) -> Result<usize, usize> {
+        Err(0)
+    }
+
+    #[inline(always)]
+    pub(super) fn find_byte4(_hay: &[u8], _a: u8, _b: u8, _c: u8, _d: u8) -> Result<usize, usize> {
+        Err(0)
+    }
+
+    #[inline(always)]
+    pub(super) fn skip_blanks(_hay: &[u8]) -> Result<usize, usize> {
+        Err(0)
+    }
+
+    #[inline(always)]
+    pub(super) fn identifier_len(_hay: &[u8]) -> Result<usize, usize> {
+        Err(0)
+    }
+
+    #[inline(always)]
+    pub(super) fn count_lines(_hay: &[u8]) -> (usize, Option<usize>, usize) {
+        (0, None, 0)
+    }
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+use portable as arch;
+#[cfg(target_arch = "x86_64")]
+use x86 as arch;
+
+/// Finish the scan with the bytes which haven't been checked by the arch function
+#[inline(always)]
+fn finish(hay: &[u8], res: Result<usize, usize>, pred: impl Fn(u8) -> bool) -> Option<usize> {
+    match res {
+        Ok(pos) => Some(pos),
+        Err(start) => hay[start..]
+            .iter()
+            .position(|c| pred(*c))
+            .map(|pos| pos + start),
+    }
+}
+
+/// Get the position of the first a
+#[inline(always)]
+pub(crate) fn find_byte(hay: &[u8], a: u8) -> Option<usize> {
+    finish(hay, arch::find_byte(hay, a), |c| c == a)
+}
+
+/// Get the position of the first a or b
+#[inline(always)]
+pub(crate) fn find_byte2(hay: &[u8], a: u8, b: u8) -> Option<usize> {
+    finish(hay, arch::find_byte2(hay, a, b), |c| c == a || c == b)
+}
+
+/// Get the position of the first a, b, c or d
+#[inline(always)]
+pub(crate) fn find_byte4(hay: &[u8], a: u8, b: u8, c: u8, d: u8) -> Option<usize> {
+    finish(hay, arch::find_byte4(hay, a, b, c, d), |x| {
+        x == a || x == b || x == c || x == d
+    })
+}
+
+/// Get the number of spaces and tabs at the beginning of hay
+#[inline(always)]
+pub(crate) fn skip_blanks(hay: &[u8]) -> usize {
+    finish(hay, arch::skip_blanks(hay), |c| c != b' ' && c != b'\t').unwrap_or(hay.len())
+}
+
+/// Get the number of ASCII identifier chars ([a-zA-Z0-9_]) at the beginning of hay
+#[inline(always)]
+pub(crate) fn identifier_len(hay: &[u8]) -> usize {
+    finish(hay, arch::identifier_len(hay), |c| {
+        !c.is_ascii_alphanumeric() && c != b'_'
+    })
+    .unwrap_or(hay.len())
+}
+
+/// Get the number of \n in hay and the position of the last one
+#[inline(always)]
+pub(crate) fn count_lines(hay: &[u8]) -> (usize, Option<usize>) {
+    let (mut count, mut last, start) = arch::count_lines(hay);
+    for (i, c) in hay[start..].iter().enumerate() {
+        if *c == b'\n' {
+            count += 1;
+            last = Some(start + i);
+        }
+    }
+    (count, last)
+}
+
+/// Get the position of the * in the */ ending a comment
+/// (hay starts just after the /* so a / at the beginning doesn't end it)
+#[inline(always)]
+pub(crate) fn find_comment_end(hay: &[u8]) -> Option<usize> {
+    let mut from = 1;
+    while from < hay.len() {
+        let pos = from + find_byte(&hay[from..], b'/')?;
+        if hay[pos - 1] == b'*' {
+            return Some(pos - 1);
+        }
+        from = pos + 1;
+    }
+    None
+}
+
+/// Check that hay only contains whitespaces, line continuations and comments
+/// (a line comment continued on the next line isn't considered as a comment)
+pub(crate) fn is_blank_or_comment(mut hay: &[u8]) -> bool {
+    loop {
+        hay = match hay {
+            [] => return true,
+            [b' ', rest @ ..]
+            | [b'\t', rest @ ..]
+            | [b'\n', rest @ ..]
+            | [b'\r', rest @ ..]
+            | [b'\x0B', rest @ ..]
+            | [b'\x0C', rest @ ..]
+            | [b'\\', b'\n', rest @ ..]
+            | [b'\\', b'\r', b'\n', rest @ ..] => rest,
+            [b'/', b'/', rest @ ..] => match find_byte(rest, b'\n') {
+                Some(pos) => &rest[pos..],
+                None => return true,
+            },
+            [b'/', b'*', rest @ ..] => match find_comment_end(rest) {
+                Some(pos) => &rest[pos + 2..],
+                None => return false,
+            },
+            _ => return false,
+        };
+    }
+}
+
+/// Get the macro name in the condition of a directive like #if !defined(NAME)
+/// or #if ! defined NAME which may be an include guard (line is the rest of the directive)
+pub(crate) fn get_negated_defined(line: &[u8]) -> Option<&[u8]> {
+    let line = line.strip_prefix(b"!")?;
+    let line = &line[skip_blanks(line)..];
+    let line = line.strip_prefix(b"defined")?;
+    let n = skip_blanks(line);
+    let (paren, line) = match line[n..].strip_prefix(b"(") {
+        Some(line) => (true, line),
+        None if n != 0 => (false, &line[n..]),
+        None => return None,
+    };
+
+    let line = &line[skip_blanks(line)..];
+    let len = identifier_len(line);
+    if len == 0 || line[0].is_ascii_digit() {
+        return None;
+    }
+
+    let (name, mut rest) = line.split_at(len);
+    if paren {
+        rest = &rest[skip_blanks(rest)..];
+        rest = rest.strip_prefix(b")")?;
+    }
+
+    if is_blank_or_comment(rest) {
+        Some(name)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    /// Some buffers with the interesting bytes at every position in and around the chunks
+    fn get_buffers() -> Vec<Vec<u8>> {
+        let mut bufs = Vec::new();
+        for len in 0..80 {
+            for pos in 0..len {
+                for c in b"\n/* #\xE9" {
+                    let mut buf = vec![b'a'; len];
+                    buf[pos] = *c;
+                    bufs.push(buf);
+                }
+            }
+        }
+        bufs.push(b"  \t  \t    \t\t   int main() { return 0; } // comment \n".to_vec());
+        bufs.push(b"/*\n * A comment with some lines\n *\n * and a star / * / */ int a;".to_vec());
+        bufs.push(b"identifier_with_many_chars_0123456789_and_more_chars + b".to_vec());
+        bufs.push(
+            b"   \n\n\n  \"a string\" 'c' / \\ \n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n".to_vec(),
+        );
+        bufs
+    }
+
+    fn slow_count_lines(hay: &[u8]) -> (usize, Option<usize>) {
+        (
+            hay.iter().filter(|c| **c == b'\n').count(),
+            hay.iter().rposition(|c| *c == b'\n'),
+        )
+    }
+
+    #[test]
+    fn test_scan() {
+        for buf in get_buffers() {
+            let buf = buf.as_slice();
+            assert_eq!(find_byte(buf, b'\n'), buf.iter().position(|c| *c == b'\n'));
+            assert_eq!(
+                find_byte2(buf, b'/', b'\n'),
+                buf.iter().position(|c| *c == b'/' || *c == b'\n')
+            );
+            assert_eq!(
+                find_byte4(buf, b'\n', b'"', b'\'', b'/'),
+                buf.iter()
+                    .position(|c| *c == b'\n' || *c == b'"' || *c == b'\'' || *c == b'/')
+            );
+            assert_eq!(
+                skip_blanks(buf),
+                buf.iter()
+                    .take_while(|c| **c == b' ' || **c == b'\t')
+                    .count()
+            );
+            assert_eq!(
+                identifier_len(buf),
+                buf.iter()
+                    .take_while(|c| c.is_ascii_alphanumeric() || **c == b'_')
+                    .count()
+            );
+            assert_eq!(count_lines(buf), slow_count_lines(buf));
+            assert_eq!(
+                find_comment_end(buf),
+                (1..buf.len())
+                    .find(|i| buf[*i] == b'/' && buf[i - 1] == b'*')
+                    .map(|i| i - 1)
+            );
+        }
+    }
+
+    #[test]
+    fn test_is_blank_or_comment() {
+        assert!(is_blank_or_comment(b""));
+        assert!(is_blank_or_comment(b"  \t\n\r\n"));
+        assert!(is_blank_or_comment(
+            b"// License\n/* A comment\n * on several lines */\n  \\\n// end"
+        ));
+        assert!(!is_blank_or_comment(b"/* unterminated"));
+        assert!(!is_blank_or_comment(b"// comment\nint a;"));
+        assert!(!is_blank_or_comment(b"/**/#define A"));
+    }
+
+    #[test]
+    fn test_get_negated_defined() {
+        assert_eq!(get_negated_defined(b"!defined(FOO_H)"), Some(&b"FOO_H"[..]));
+        assert_eq!(
+            get_negated_defined(b"! defined ( FOO_H ) // guard\r"),
+            Some(&b"FOO_H"[..])
+        );
+        assert_eq!(get_negated_defined(b"!defined FOO_H"), Some(&b"FOO_H"[..]));
+        assert_eq!(get_negated_defined(b"!definedFOO_H"), None);
+        assert_eq!(get_negated_defined(b"!defined(FOO_H) && BAR"), None);
+        assert_eq!(get_negated_defined(b"defined(FOO_H)"), None);
+        assert_eq!(get_negated_defined(b"!defined(FOO_H"), None);
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn test_scan_sse2() {
+        // Check the SSE2 kernels even when AVX2 is available
+        for buf in get_buffers() {
+            let buf = buf.as_slice();
+            let res = unsafe { x86::sse2::find_byte(buf, b'\n') };
+            assert_eq!(finish(buf, res, |c| c == b'\n'), find_byte(buf, b'\n'));
+            let (count, last, start) = unsafe { x86::sse2::count_lines(buf) };
+            let (c, l) = slow_count_lines(&buf[start..]);
+            assert_eq!(
+                (count + c, l.map(|l| l + start).or(last)),
+                slow_count_lines(buf)
+            );
+        }
+    }
+}
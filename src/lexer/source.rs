@@ -4,16 +4,115 @@
 // copied, modified, or distributed except according to those terms.
 
 use hashbrown::{hash_map, HashMap};
+use same_file::Handle;
+use std::fs::File;
+use std::io::{self, Read};
+use std::ops::Deref;
 use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
 
-#[derive(Debug, Clone, Copy, Default, Hash, PartialEq)]
+#[cfg(unix)]
+use super::mmap::Mmap;
+
+/// The files smaller than that are just read: mapping them costs more
+#[cfg(unix)]
+const MMAP_THRESHOLD: usize = 16 * 1024;
+
+#[derive(Debug)]
+enum Storage {
+    #[cfg(unix)]
+    Mapped(Mmap),
+    Owned(Vec<u8>),
+}
+
+/// The content of a file: it's loaded once (and mapped in memory when it has been
+/// explicitly allowed) and then shared by all the buffers and the threads using it
+#[derive(Clone, Debug)]
+pub struct FileContent(Arc<Storage>);
+
+impl FileContent {
+    pub fn read(path: &PathBuf) -> io::Result<Self> {
+        Self::read_file(&mut File::open(path)?)
+    }
+
+    /// Map the file in memory (it's just read when it's small or on non-unix systems)
+    ///
+    /// # Safety
+    ///
+    /// The file mustn't be modified or truncated as long as the content is alive.
+    pub unsafe fn map(path: &PathBuf) -> io::Result<Self> {
+        Self::map_file(&mut File::open(path)?)
+    }
+
+    fn read_file(file: &mut File) -> io::Result<Self> {
+        let len = file.metadata()?.len() as usize;
+        let mut data = Vec::with_capacity(len + 1);
+        file.read_to_end(&mut data)?;
+        Ok(Self::from(data))
+    }
+
+    unsafe fn map_file(file: &mut File) -> io::Result<Self> {
+        #[cfg(unix)]
+        {
+            let len = file.metadata()?.len() as usize;
+            if len >= MMAP_THRESHOLD {
+                if let Ok(mmap) = Mmap::new(file, len) {
+                    return Ok(Self(Arc::new(Storage::Mapped(mmap))));
+                }
+            }
+        }
+
+        Self::read_file(file)
+    }
+}
+
+impl From<Vec<u8>> for FileContent {
+    fn from(data: Vec<u8>) -> Self {
+        Self(Arc::new(Storage::Owned(data)))
+    }
+}
+
+impl Deref for FileContent {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self.0.as_ref() {
+            #[cfg(unix)]
+            Storage::Mapped(mmap) => mmap.as_slice(),
+            Storage::Owned(data) => data,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Hash, PartialEq, Eq)]
 pub struct FileId(pub u32);
 
+/// What makes a file unique whatever the path used to reach it (links, `..`, ...)
+#[cfg(unix)]
+type Identity = (u64, u64);
+#[cfg(not(unix))]
+type Identity = Handle;
+
+#[cfg(unix)]
+fn get_identity(handle: Handle) -> Identity {
+    (handle.dev(), handle.ino())
+}
+
+#[cfg(not(unix))]
+fn get_identity(handle: Handle) -> Identity {
+    handle
+}
+
 #[derive(Debug)]
 pub struct SourceLocator {
     file2id: HashMap<PathBuf, FileId>,
     id2file: Vec<PathBuf>,
+    contents: Vec<Option<FileContent>>,
+    identities: HashMap<Identity, FileId>,
+    /// The id of the first path used to load the same file
+    unique: Vec<FileId>,
+    /// Map the files in memory instead of reading them
+    mmap: bool,
 }
 
 impl Default for SourceLocator {
@@ -25,6 +124,10 @@ impl Default for SourceLocator {
                 map
             },
             id2file: vec![PathBuf::from("")],
+            contents: vec![None],
+            identities: HashMap::default(),
+            unique: vec![FileId(0)],
+            mmap: false,
         }
     }
 }
@@ -35,7 +138,47 @@ pub fn get_source_mutex() -> SourceMutex {
     Arc::new(Mutex::new(SourceLocator::default()))
 }
 
+/// Get the id and the content of a file: it's read only the first time
+/// (and not under the lock so the other threads aren't blocked)
+pub fn load_file(source: &SourceMutex, path: &PathBuf) -> io::Result<(FileId, FileContent)> {
+    let mmap = {
+        let source = source.lock().unwrap();
+        if let Some(res) = source.get_loaded(path) {
+            return Ok(res);
+        }
+        source.mmap
+    };
+
+    let mut handle = Handle::from_path(path)?;
+    let content = if mmap {
+        // Safety: the caller of enable_mmap guarantees that the files aren't modified
+        unsafe { FileContent::map_file(handle.as_file_mut())? }
+    } else {
+        FileContent::read_file(handle.as_file_mut())?
+    };
+    let identity = get_identity(handle);
+    let mut source = source.lock().unwrap();
+    let id = source.get_id(path);
+    let unique = *source.identities.entry(identity).or_insert(id);
+    source.unique[id.0 as usize] = unique;
+    // Another thread may have loaded it in the meantime
+    let content = source.contents[id.0 as usize]
+        .get_or_insert(content)
+        .clone();
+    Ok((id, content))
+}
+
 impl SourceLocator {
+    /// Map the files in memory instead of reading them: it avoids a copy of the big files
+    ///
+    /// # Safety
+    ///
+    /// The loaded files mustn't be modified or truncated as long as their contents are alive:
+    /// the tokens could change under our feet or accessing them could raise a SIGBUS.
+    pub unsafe fn enable_mmap(&mut self) {
+        self.mmap = true;
+    }
+
     pub fn get_id(&mut self, path: &PathBuf) -> FileId {
         match self.file2id.entry(path.clone()) {
             hash_map::Entry::Occupied(e) => *e.get(),
@@ -43,12 +186,76 @@ impl SourceLocator {
                 let id = FileId(self.id2file.len() as u32);
                 p.insert(id);
                 self.id2file.push(path.clone());
+                self.contents.push(None);
+                self.unique.push(id);
                 id
             }
         }
     }
 
+    fn get_loaded(&self, path: &PathBuf) -> Option<(FileId, FileContent)> {
+        let id = *self.file2id.get(path)?;
+        let content = self.contents[id.0 as usize].as_ref()?;
+        Some((id, content.clone()))
+    }
+
     pub fn get_path(&self, id: FileId) -> PathBuf {
         unsafe { self.id2file.get_unchecked(id.0 as usize).clone() }
     }
+
+    /// Get the same id for all the paths leading to the same file
+    /// (once the file has been loaded)
+    pub fn get_unique_id(&self, id: FileId) -> FileId {
+        self.unique.get(id.0 as usize).copied().unwrap_or(id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_load_file() {
+        let tmp = TempDir::new("source").unwrap();
+        let small = tmp.path().join("small.h");
+        let large = tmp.path().join("large.h");
+        let code = "int a;\n".repeat(10000);
+        std::fs::write(&small, "int a;\n").unwrap();
+        std::fs::write(&large, &code).unwrap();
+
+        let source = get_source_mutex();
+        let (small_id, small_content) = load_file(&source, &small).unwrap();
+        let (large_id, large_content) = load_file(&source, &large).unwrap();
+
+        assert_eq!(&*small_content, b"int a;\n");
+        assert_eq!(&*large_content, code.as_bytes());
+        assert_ne!(small_id, large_id);
+
+        // The second time the content is shared
+        let (id, content) = load_file(&source, &large).unwrap();
+        assert_eq!(id, large_id);
+        assert!(std::ptr::eq(content.as_ptr(), large_content.as_ptr()));
+        assert_eq!(source.lock().unwrap().get_path(id), large);
+
+        assert!(load_file(&source, &tmp.path().join("missing.h")).is_err());
+
+        let source = get_source_mutex();
+        // Safety: the files aren't touched until the end of the test
+        unsafe { source.lock().unwrap().enable_mmap() };
+        let (_, small_content) = load_file(&source, &small).unwrap();
+        let (_, large_content) = load_file(&source, &large).unwrap();
+
+        assert_eq!(&*small_content, b"int a;\n");
+        assert_eq!(&*large_content, code.as_bytes());
+        #[cfg(unix)]
+        {
+            if let Storage::Mapped(_) = large_content.0.as_ref() {
+            } else {
+                panic!("The large file should be mapped");
+            }
+        }
+    }
 }
@@ -3,7 +3,8 @@
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
-use super::lexer::{Lexer, Token};
+use super::errors::LexerError;
+use super::lexer::{Lexer, TLexer, Token};
 use super::preprocessor::context::PreprocContext;
 
 #[derive(PartialEq)]
@@ -106,7 +107,7 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
                     let s = Box::new((s, suf));
                     Token::LiteralLStringUD(s)
                 } else {
-                    Token::LiteralLString(s)
+                    Token::LiteralLString(s.into())
                 }
             }
             StringType::UU => {
@@ -115,7 +116,7 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
                     let s = Box::new((s, suf));
                     Token::LiteralUUStringUD(s)
                 } else {
-                    Token::LiteralUUString(s)
+                    Token::LiteralUUString(s.into())
                 }
             }
             StringType::R => {
@@ -124,7 +125,7 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
                     let s = Box::new((s, suf));
                     Token::LiteralRStringUD(s)
                 } else {
-                    Token::LiteralRString(s)
+                    Token::LiteralRString(s.into())
                 }
             }
             StringType::U => {
@@ -133,7 +134,7 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
                     let s = Box::new((s, suf));
                     Token::LiteralUStringUD(s)
                 } else {
-                    Token::LiteralUString(s)
+                    Token::LiteralUString(s.into())
                 }
             }
             StringType::U8 => {
@@ -142,7 +143,7 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
                     let s = Box::new((s, suf));
                     Token::LiteralU8StringUD(s)
                 } else {
-                    Token::LiteralU8String(s)
+                    Token::LiteralU8String(s.into())
                 }
             }
             StringType::LR => {
@@ -151,7 +152,7 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
                     let s = Box::new((s, suf));
                     Token::LiteralLRStringUD(s)
                 } else {
-                    Token::LiteralLRString(s)
+                    Token::LiteralLRString(s.into())
                 }
             }
             StringType::UUR => {
@@ -160,7 +161,7 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
                     let s = Box::new((s, suf));
                     Token::LiteralUURStringUD(s)
                 } else {
-                    Token::LiteralUURString(s)
+                    Token::LiteralUURString(s.into())
                 }
             }
             StringType::UR => {
@@ -169,7 +170,7 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
                     let s = Box::new((s, suf));
                     Token::LiteralURStringUD(s)
                 } else {
-                    Token::LiteralURString(s)
+                    Token::LiteralURString(s.into())
                 }
             }
             StringType::U8R => {
@@ -178,7 +179,7 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
                     let s = Box::new((s, suf));
                     Token::LiteralU8RStringUD(s)
                 } else {
-                    Token::LiteralU8RString(s)
+                    Token::LiteralU8RString(s.into())
                 }
             }
             StringType::None => {
@@ -187,12 +188,24 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
                     let s = Box::new((s, suf));
                     Token::LiteralStringUD(s)
                 } else {
-                    Token::LiteralString(s)
+                    Token::LiteralString(s.into())
                 }
             }
         }
     }
 
+    /// Convert the bytes of a literal into a String, invalid UTF-8 sequences are replaced
+    fn literal_to_string(&mut self, bytes: Vec<u8>) -> String {
+        match String::from_utf8(bytes) {
+            Ok(s) => s,
+            Err(e) => {
+                self.errors
+                    .push(LexerError::InvalidUtf8 { sp: self.span() });
+                String::from_utf8_lossy(e.as_bytes()).into_owned()
+            }
+        }
+    }
+
     #[inline(always)]
     pub(crate) fn get_string_content(&mut self) -> String {
         let mut buf = Vec::new();
@@ -207,52 +220,101 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
                     self.buf.inc();
                     if let Some(code) = self.get_escape() {
                         buf.extend_from_slice(self.buf.slice_p(spos, pos));
-                        ch.push(std::char::from_u32(code).unwrap());
+                        ch.push(
+                            std::char::from_u32(code).unwrap_or(std::char::REPLACEMENT_CHARACTER),
+                        );
                         buf.extend_from_slice(ch.as_bytes());
                         ch.clear();
                         spos = self.buf.pos();
                     }
                 } else if c == b'\"' {
-                    let s = if buf.is_empty() {
-                        String::from_utf8(self.buf.slice(spos).to_vec()).unwrap()
-                    } else {
-                        buf.extend_from_slice(self.buf.slice(spos));
-                        String::from_utf8(buf).unwrap()
-                    };
+                    buf.extend_from_slice(self.buf.slice(spos));
                     self.buf.inc();
-                    return s;
+                    return self.literal_to_string(buf);
+                } else if c == b'\n' {
+                    // The new line isn't consumed to have an Eol
+                    self.errors
+                        .push(LexerError::UnterminatedString { sp: self.span() });
+                    buf.extend_from_slice(self.buf.slice(spos));
+                    return self.literal_to_string(buf);
                 } else {
                     self.buf.inc();
                 }
             } else {
-                return if buf.is_empty() {
-                    String::from_utf8(self.buf.slice(spos).to_vec()).unwrap()
-                } else {
-                    buf.extend_from_slice(self.buf.slice(spos));
-                    String::from_utf8(buf).unwrap()
-                };
+                self.errors
+                    .push(LexerError::UnterminatedString { sp: self.span() });
+                buf.extend_from_slice(self.buf.slice(spos));
+                return self.literal_to_string(buf);
             }
         }
     }
 
+    /// Report an invalid char in the delimiter of a raw string and skip the rest of the literal
+    fn skip_invalid_r_string(&mut self, c: u8) -> String {
+        if c == b'\n' {
+            self.errors.push(LexerError::InvalidRawStringDelimiter {
+                sp: self.span(),
+                c: '\n',
+            });
+            return String::new();
+        }
+
+        self.buf.inc();
+        self.errors.push(LexerError::InvalidRawStringDelimiter {
+            sp: self.span_n(1),
+            c: char::from(c),
+        });
+
+        // Skip until the next quote on the line
+        while self.buf.has_char() {
+            let c = self.buf.next_char();
+            if c == b'\n' {
+                break;
+            }
+            self.buf.inc();
+            if c == b'\"' {
+                break;
+            }
+        }
+        String::new()
+    }
+
     #[inline(always)]
     pub(crate) fn get_r_string_content(&mut self) -> String {
         let spos = self.buf.pos();
         loop {
             if self.buf.has_char() {
                 let c = self.buf.next_char();
-                self.buf.inc();
-                if c == b'(' {
-                    break;
+                match c {
+                    b'(' => {
+                        self.buf.inc();
+                        break;
+                    }
+                    b' ' | b')' | b'\\' | b'\t' | b'\x0B' | b'\x0C' | b'\n' => {
+                        return self.skip_invalid_r_string(c);
+                    }
+                    _ => self.buf.inc(),
                 }
             } else {
-                return String::from_utf8(self.buf.slice(spos).to_vec()).unwrap();
+                let delim = String::from_utf8_lossy(self.buf.slice(spos)).into_owned();
+                self.errors.push(LexerError::UnterminatedRawString {
+                    sp: self.span(),
+                    delim,
+                });
+                return String::new();
             }
         }
 
         // delimiter doesn't contain parenthesis, spaces or backslashes
-        let delimiter = self.buf.slice_m_n(spos, 1);
-        let delim_len = delimiter.len();
+        let dspos = spos;
+        let delim_len = self.buf.pos() - 1 - spos;
+        if delim_len > 16 {
+            let mut sp = self.span_n(delim_len + 1);
+            sp.end.pos -= 1;
+            sp.end.column -= 1;
+            self.errors
+                .push(LexerError::RawStringDelimiterTooLong { sp });
+        }
 
         let spos = self.buf.pos();
         loop {
@@ -262,11 +324,16 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
                     let rspos = self.buf.pos();
                     let mut delim_pos = 0;
                     self.buf.inc();
-                    loop {
+                    while self.buf.has_char() {
                         let c = self.buf.next_char();
 
                         if delim_pos < delim_len {
-                            let d = unsafe { *delimiter.get_unchecked(delim_pos) };
+                            let d = unsafe {
+                                *self
+                                    .buf
+                                    .slice_p(dspos, dspos + delim_len)
+                                    .get_unchecked(delim_pos)
+                            };
 
                             if c != d {
                                 break;
@@ -276,12 +343,9 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
                             delim_pos += 1;
                         } else if c == b'\"' {
                             self.buf.inc();
-                            return String::from_utf8(self.buf.slice_p(spos, rspos).to_vec())
-                                .unwrap();
-                        } else if c == b'\n' {
-                            self.buf.add_new_line();
-                            self.buf.inc();
-                            break;
+                            // The trigraphs are reverted in a raw string
+                            let content = self.buf.original_slice(spos, rspos).into_owned();
+                            return self.literal_to_string(content);
                         } else {
                             break;
                         }
@@ -293,7 +357,13 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
                     self.buf.inc();
                 }
             } else {
-                return String::from_utf8(self.buf.slice(spos).to_vec()).unwrap();
+                let delim = String::from_utf8_lossy(self.buf.slice_p(dspos, dspos + delim_len))
+                    .into_owned();
+                self.errors.push(LexerError::UnterminatedRawString {
+                    sp: self.span(),
+                    delim,
+                });
+                return self.literal_to_string(self.buf.slice(spos).to_vec());
             }
         }
     }
@@ -306,6 +376,9 @@ impl<'a, PC: PreprocContext> Lexer<'a, PC> {
                 if c == delim {
                     self.buf.inc();
                     break;
+                } else if c == b'\n' {
+                    // Unterminated: the new line is let to the caller
+                    break;
                 } else if c == b'\\' {
                     self.buf.inc();
                     if self.buf.has_char() {
@@ -337,77 +410,56 @@ mod tests {
     #[test]
     fn test_string() {
         let mut p = Lexer::<DefaultContext>::new(b"\"foo\" \"foo\\\"bar\"");
-        assert_eq!(p.next_token(), Token::LiteralString("foo".to_string()));
-        assert_eq!(p.next_token(), Token::LiteralString("foo\"bar".to_string()));
+        assert_eq!(p.next_token(), Token::LiteralString("foo".into()));
+        assert_eq!(p.next_token(), Token::LiteralString("foo\"bar".into()));
 
         let mut p = Lexer::<DefaultContext>::new(b"u\"foo\" u\"foo\\\"bar\"");
-        assert_eq!(p.next_token(), Token::LiteralUString("foo".to_string()));
-        assert_eq!(
-            p.next_token(),
-            Token::LiteralUString("foo\"bar".to_string())
-        );
+        assert_eq!(p.next_token(), Token::LiteralUString("foo".into()));
+        assert_eq!(p.next_token(), Token::LiteralUString("foo\"bar".into()));
 
         let mut p = Lexer::<DefaultContext>::new(b"U\"foo\" U\"foo\\\"bar\"");
-        assert_eq!(p.next_token(), Token::LiteralUUString("foo".to_string()));
-        assert_eq!(
-            p.next_token(),
-            Token::LiteralUUString("foo\"bar".to_string())
-        );
+        assert_eq!(p.next_token(), Token::LiteralUUString("foo".into()));
+        assert_eq!(p.next_token(), Token::LiteralUUString("foo\"bar".into()));
 
         let mut p = Lexer::<DefaultContext>::new(b"u8\"foo\" u8\"foo\\\"bar\"");
-        assert_eq!(p.next_token(), Token::LiteralU8String("foo".to_string()));
-        assert_eq!(
-            p.next_token(),
-            Token::LiteralU8String("foo\"bar".to_string())
-        );
+        assert_eq!(p.next_token(), Token::LiteralU8String("foo".into()));
+        assert_eq!(p.next_token(), Token::LiteralU8String("foo\"bar".into()));
 
         let mut p = Lexer::<DefaultContext>::new(b"L\"foo\" L\"foo\\\"bar\"");
-        assert_eq!(p.next_token(), Token::LiteralLString("foo".to_string()));
-        assert_eq!(
-            p.next_token(),
-            Token::LiteralLString("foo\"bar".to_string())
-        );
+        assert_eq!(p.next_token(), Token::LiteralLString("foo".into()));
+        assert_eq!(p.next_token(), Token::LiteralLString("foo\"bar".into()));
 
         let mut p = Lexer::<DefaultContext>::new(
             b"R\"hello(foo)hello\" R\"world(foo\n\\\"bar)world\" R\"world(foo)world  )world\"",
         );
-        assert_eq!(p.next_token(), Token::LiteralRString("foo".to_string()));
-        assert_eq!(
-            p.next_token(),
-            Token::LiteralRString("foo\n\\\"bar".to_string())
-        );
-        assert_eq!(
-            p.next_token(),
-            Token::LiteralRString("foo)world  ".to_string())
-        );
+        assert_eq!(p.next_token(), Token::LiteralRString("foo".into()));
+        assert_eq!(p.next_token(), Token::LiteralRString("foo\n\\\"bar".into()));
+        assert_eq!(p.next_token(), Token::LiteralRString("foo)world  ".into()));
 
         let mut p =
             Lexer::<DefaultContext>::new(b"LR\"hello(foo)hello\" UR\"world(foo\n\\\"bar)world\"");
-        assert_eq!(p.next_token(), Token::LiteralLRString("foo".to_string()));
+        assert_eq!(p.next_token(), Token::LiteralLRString("foo".into()));
         assert_eq!(
             p.next_token(),
-            Token::LiteralUURString("foo\n\\\"bar".to_string())
+            Token::LiteralUURString("foo\n\\\"bar".into())
         );
 
         let mut p =
             Lexer::<DefaultContext>::new(b"uR\"hello(foo)hello\" u8R\"world(foo\n\\\"bar)world\"");
-        assert_eq!(p.next_token(), Token::LiteralURString("foo".to_string()));
+        assert_eq!(p.next_token(), Token::LiteralURString("foo".into()));
         assert_eq!(
             p.next_token(),
-            Token::LiteralU8RString("foo\n\\\"bar".to_string())
+            Token::LiteralU8RString("foo\n\\\"bar".into())
         );
 
         let mut p = Lexer::<DefaultContext>::new(b"R\"(abc)\ndef)\n)\"");
-        assert_eq!(
-            p.next_token(),
-            Token::LiteralRString("abc)\ndef)\n".to_string())
-        );
+        assert_eq!(p.next_token(), Token::LiteralRString("abc)\ndef)\n".into()));
 
         let mut p =
             Lexer::<DefaultContext>::new(b"\"test\\0\\\\\\\"\\t\\a\\b\\234\\u1234\\U0010ffff\"");
         assert_eq!(
             p.next_token(),
-            Token::LiteralString("test\u{0}\\\"\t\u{7}\u{8}\u{9c}\u{1234}\u{10ffff}".to_string())
+            Token::LiteralString("test\u{0}\\\"\t\u{7}\u{8}\u{9c}\u{1234}\u{10ffff}".into())
         );
     }
 
@@ -419,4 +471,39 @@ mod tests {
             Token::LiteralStringUD(Box::new(("foo".to_string(), "_abcde".to_string())))
         );
     }
+
+    #[test]
+    fn test_string_errors() {
+        let mut p = Lexer::<DefaultContext>::new(
+            b"\"abc\n\"\\x\" R\"abcdefghijklmnopq(x)abcdefghijklmnopq\" R\"a b(x)a b\" int R\"x(abc",
+        );
+        assert_eq!(p.next_token(), Token::LiteralString("abc".into()));
+        assert_eq!(p.next_token(), Token::Eol);
+        assert_eq!(p.next_token(), Token::LiteralString("\u{0}".into()));
+        assert_eq!(p.next_token(), Token::LiteralRString("x".into()));
+        assert_eq!(p.next_token(), Token::LiteralRString("".into()));
+        assert_eq!(p.next_token(), Token::Int);
+        assert_eq!(p.next_token(), Token::LiteralRString("abc".into()));
+        assert_eq!(p.next_token(), Token::Eof);
+
+        let errors = p.get_errors();
+        assert_eq!(errors.len(), 5);
+        assert!(matches!(errors[0], LexerError::UnterminatedString { .. }));
+        assert!(matches!(errors[1], LexerError::MissingHexDigits { .. }));
+        assert!(matches!(
+            errors[2],
+            LexerError::RawStringDelimiterTooLong { .. }
+        ));
+        assert!(matches!(
+            errors[3],
+            LexerError::InvalidRawStringDelimiter { c: ' ', .. }
+        ));
+        assert_eq!(
+            errors[4].stringly().message,
+            "raw string missing terminating delimiter )x\""
+        );
+
+        let sp = errors[2].stringly().sp;
+        assert_eq!((sp.start.column, sp.end.column), (8, 25));
+    }
 }
@@ -0,0 +1,75 @@
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use hashbrown::HashMap;
+use std::sync::Arc;
+
+/// An interned string: an index in the SymbolTable of the lexer which produced it
+/// (see TLexer::resolve to get the string back).
+/// Two symbols from the same table are equal if and only if their strings are equal
+/// so comparing or hashing them never touches the strings.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Symbol(u32);
+
+impl Symbol {
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+/// The table used by a lexer to share the identifiers it produces
+#[derive(Debug, Default)]
+pub struct SymbolTable {
+    symbols: HashMap<Arc<str>, Symbol>,
+    strings: Vec<Arc<str>>,
+}
+
+impl SymbolTable {
+    /// Get the symbol for a string (the same string always gives the same symbol)
+    pub fn intern(&mut self, s: &str) -> Symbol {
+        if let Some(sym) = self.symbols.get(s) {
+            return *sym;
+        }
+
+        let sym = Symbol(self.strings.len() as u32);
+        let s: Arc<str> = Arc::from(s);
+        self.strings.push(Arc::clone(&s));
+        self.symbols.insert(s, sym);
+        sym
+    }
+
+    /// Get the string of a symbol coming from this table
+    pub fn resolve(&self, sym: Symbol) -> &str {
+        &self.strings[sym.index()]
+    }
+
+    pub fn len(&self) -> usize {
+        self.strings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.strings.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_symbol() {
+        let mut table = SymbolTable::default();
+        let a = table.intern("foo");
+        let b = table.intern("foo");
+        let c = table.intern("bar");
+        assert_eq!(a, b);
+        assert!(a != c);
+        assert_eq!(table.len(), 2);
+        assert_eq!(table.resolve(a), "foo");
+        assert_eq!(table.resolve(c), "bar");
+    }
+}
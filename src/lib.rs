@@ -19,3 +19,4 @@ pub mod defaults;
 pub mod errors;
 pub mod lexer;
 pub mod parser;
+pub mod toolchain;
@@ -14,26 +14,27 @@ macro_rules! node {
 macro_rules! skip_whites {
     ( $lexer: expr) => {{
         loop {
-            if $lexer.buf.has_char() {
-                let c = $lexer.buf.next_char();
-                if c == b'\\' {
-                    if $lexer.buf.has_char_n(1) {
-                        let c = $lexer.buf.next_char_n(1);
-                        if c == b'\n' {
-                            $lexer.buf.add_new_line();
-                            $lexer.buf.inc_n(2);
-                            continue;
-                        }
-                    }
-                    break;
-                }
-                if c != b' ' && c != b'\t' {
-                    break;
-                }
+            $lexer.buf.skip_blanks();
+            if $lexer.buf.has_char_n(1)
+                && $lexer.buf.next_char() == b'\\'
+                && $lexer.buf.next_char_n(1) == b'\n'
+            {
                 $lexer.buf.inc();
-            } else {
-                break;
+                $lexer.buf.add_new_line();
+                $lexer.buf.inc();
+                continue;
+            }
+            if $lexer.buf.has_char_n(2)
+                && $lexer.buf.next_char() == b'\\'
+                && $lexer.buf.next_char_n(1) == b'\r'
+                && $lexer.buf.next_char_n(2) == b'\n'
+            {
+                $lexer.buf.inc_n(2);
+                $lexer.buf.add_new_line();
+                $lexer.buf.inc();
+                continue;
             }
+            break;
         }
     }};
 }
@@ -71,7 +71,7 @@ impl<'a, L: TLexer> UsingParser<'a, L> {
         if tok == Token::Using {
             let tok = self.lexer.next_useful();
             if let Token::Identifier(ns) = tok {
-                let ns = Some(ns);
+                let ns = Some(self.lexer.resolve(ns).to_string());
                 let tok = self.lexer.next_useful();
                 match tok {
                     Token::Colon => {
@@ -196,10 +196,10 @@ impl<'a, L: TLexer> NameParser<'a, L> {
             Token::Identifier(id) => {
                 let tk = self.lexer.next_useful();
                 if tk == Token::ColonColon {
-                    let ns = Some(id);
+                    let ns = Some(self.lexer.resolve(id).to_string());
                     let tk = self.lexer.next_useful();
                     if let Token::Identifier(id) = tk {
-                        Ok((None, (ns, id)))
+                        Ok((None, (ns, self.lexer.resolve(id).to_string())))
                     } else {
                         Err(ParserError::InvalidTokenInAttrs {
                             sp: self.lexer.span(),
@@ -207,7 +207,7 @@ impl<'a, L: TLexer> NameParser<'a, L> {
                         })
                     }
                 } else {
-                    Ok((Some(tk), (None, id)))
+                    Ok((Some(tk), (None, self.lexer.resolve(id).to_string())))
                 }
             }
             _ => Err(ParserError::InvalidTokenInAttrs {
@@ -379,7 +379,7 @@ mod tests {
                 namespace: None,
                 name: "deprecated".to_string(),
                 arg: Some(AttributeArg {
-                    tokens: vec![Token::LiteralString("because".to_string()),],
+                    tokens: vec![Token::LiteralString("because".into()),],
                 }),
                 has_using: false
             },]
@@ -400,7 +400,7 @@ mod tests {
                     namespace: Some("CC".to_string()),
                     name: "opt".to_string(),
                     arg: Some(AttributeArg {
-                        tokens: vec![Token::LiteralInt(1),],
+                        tokens: vec![Token::from_number("1").unwrap(),],
                     }),
                     has_using: true,
                 },
@@ -9,6 +9,7 @@ use hashbrown::HashMap;
 use std::cell::RefCell;
 use std::rc::Rc;
 
+use crate::args::{LangStandard, Language};
 use crate::parser::declarations::namespace::NsNames;
 use crate::parser::declarations::TypeDeclarator;
 use crate::parser::dump::Dump;
@@ -115,6 +116,7 @@ impl Default for Scope {
 #[derive(Clone, Debug)]
 pub struct Context {
     stack: Vec<Rc<RefCell<Scope>>>,
+    std: LangStandard,
 }
 
 #[derive(Clone, Debug)]
@@ -138,6 +140,7 @@ impl Default for Context {
     fn default() -> Self {
         Self {
             stack: vec![Rc::new(RefCell::new(Scope::default()))],
+            std: LangStandard::default_for(Language::CPP),
         }
     }
 }
@@ -234,6 +237,14 @@ impl Search for Rc<RefCell<Scope>> {
 }
 
 impl Context {
+    pub fn get_standard(&self) -> LangStandard {
+        self.std
+    }
+
+    pub fn set_standard(&mut self, std: LangStandard) {
+        self.std = std;
+    }
+
     pub fn search(&self, name: Option<&Qualified>) -> Option<SearchResult> {
         if let Some(name) = name {
             for scope in self.stack.iter().rev() {
@@ -344,9 +355,7 @@ impl Context {
 
     pub fn add_alias(&mut self, name: &str, typ: Rc<TypeDeclarator>) {
         let scope = Rc::clone(self.stack.last().unwrap());
-        let name = Name::Identifier(Identifier {
-            val: name.to_string(),
-        });
+        let name = Name::Identifier(Identifier { val: name.into() });
         scope.borrow_mut().decls.insert(name, Kind::Type(typ));
     }
 }
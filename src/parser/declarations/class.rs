@@ -272,7 +272,8 @@ impl<'a, L: TLexer> ClassParser<'a, L> {
 
         // optional: final
         let tok = tok.unwrap_or_else(|| self.lexer.next_useful());
-        let (tok, r#final) = if tok == Token::Final {
+        let (tok, r#final) = if matches!(&tok, Token::Identifier(id) if self.lexer.resolve(*id) == "final")
+        {
             (None, true)
         } else {
             (Some(tok), false)
@@ -437,6 +438,7 @@ public:
             },
             init: None,
             bitfield_size: None,
+            doc: None,
         });
 
         let expected = Class {
@@ -471,6 +473,7 @@ public:
                         },
                         init: None,
                         bitfield_size: None,
+                        doc: None,
                     })),
                     Member::Type(Rc::new(TypeDeclarator {
                         typ: Type {
@@ -515,6 +518,7 @@ public:
                         },
                         init: None,
                         bitfield_size: None,
+                        doc: None,
                     })),
                 ],
                 protected: vec![Member::Type(Rc::new(TypeDeclarator {
@@ -535,6 +539,7 @@ public:
                     },
                     init: Some(Initializer::Equal(ExprNode::Nullptr(Box::new(Nullptr {})))),
                     bitfield_size: None,
+                    doc: None,
                 }))],
                 private: vec![
                     Member::Type(Rc::clone(&x)),
@@ -568,6 +573,7 @@ public:
                         },
                         init: None,
                         bitfield_size: None,
+                        doc: None,
                     })),
                 ],
             }),
@@ -575,4 +581,44 @@ public:
 
         assert_eq!(c, expected);
     }
+
+    #[test]
+    fn test_class_final_override() {
+        let mut l = Lexer::<DefaultContext>::new(
+            br#"
+struct A final : B {
+    void f() override;
+    void g() final;
+    int override;
+}
+"#,
+        );
+        let p = ClassParser::new(&mut l);
+        let mut context = Context::default();
+        let (_, c, _) = p.parse(None, &mut context).unwrap();
+        let c = c.unwrap();
+
+        assert!(c.r#final);
+        assert_eq!(c.bases.map(|b| b.len()), Some(1));
+
+        let members = c.body.unwrap().public;
+        let virt: Vec<_> = members
+            .iter()
+            .map(|m| match m {
+                Member::Type(t) => match t.typ.base() {
+                    BaseType::Function(f) => Some(f.virt_specifier),
+                    _ => None,
+                },
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            virt,
+            vec![
+                Some(VirtSpecifier::OVERRIDE),
+                Some(VirtSpecifier::FINAL),
+                None
+            ]
+        );
+    }
 }
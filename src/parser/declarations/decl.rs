@@ -15,6 +15,7 @@ use crate::check_semicolon;
 
 use crate::lexer::{TLexer, Token};
 use crate::parser::attributes::{Attributes, AttributesParser};
+use crate::parser::doc::Doc;
 use crate::parser::dump::Dump;
 use crate::parser::errors::ParserError;
 use crate::parser::Context;
@@ -69,6 +70,14 @@ impl Dump for Declarations {
 }
 
 impl Declaration {
+    /// The documentation comment of the declaration
+    pub fn doc(&self) -> Option<&Doc> {
+        match self {
+            Self::Type(d) => d.doc.as_ref(),
+            _ => None,
+        }
+    }
+
     pub(crate) fn has_semicolon(&self) -> bool {
         match self {
             Self::Type(d) => d.has_semicolon(),
@@ -98,6 +107,7 @@ impl<'a, L: TLexer> DeclarationParser<'a, L> {
         if tok == Token::SemiColon {
             return Ok((None, Some(Declaration::Empty)));
         }
+        let doc = self.lexer.take_doc().map(|doc| Doc::parse(&doc));
         let tok = Some(tok);
 
         let ep = ExternParser::new(self.lexer);
@@ -151,7 +161,13 @@ impl<'a, L: TLexer> DeclarationParser<'a, L> {
         let tdp = TypeDeclaratorParser::new(self.lexer);
         let (tok, decl) = tdp.parse(tok, hint, true, context)?;
 
-        let decl = if let Some(decl) = decl {
+        let decl = if let Some(mut decl) = decl {
+            let mut doc = doc;
+            if tok.is_some() {
+                // int a; ///< doc
+                Doc::add(&mut doc, self.lexer.take_trailing_doc());
+            }
+            TypeDeclarator::set_doc(&mut decl, doc);
             context.add_type_decl(Rc::clone(&decl));
             Some(Declaration::Type(decl))
         } else {
@@ -9,6 +9,7 @@ use crate::lexer::{TLexer, Token};
 use crate::parser::attributes::{Attributes, AttributesParser};
 use crate::parser::context::{Context, ScopeKind, TypeToFix};
 use crate::parser::declarations::DeclSpecifierParser;
+use crate::parser::doc::Doc;
 use crate::parser::dump::Dump;
 use crate::parser::errors::ParserError;
 use crate::parser::expressions::{ExprNode, ExpressionParser};
@@ -37,11 +38,12 @@ pub struct Entry {
     pub(crate) name: String,
     pub(crate) attributes: Option<Attributes>,
     pub(crate) init: Option<ExprNode>,
+    pub(crate) doc: Option<Doc>,
 }
 
 impl Dump for Entry {
     fn dump(&self, name: &str, prefix: &str, last: bool, stdout: &mut StandardStreamLock) {
-        dump_obj!(self, name, "", prefix, last, stdout, name, attributes, init);
+        dump_obj!(self, name, "", prefix, last, stdout, name, attributes, init, doc);
     }
 }
 
@@ -117,6 +119,7 @@ impl<'a, L: TLexer> EntryParser<'a, L> {
         } else {
             return Ok((Some(tok), None));
         };
+        let doc = self.lexer.take_doc().map(|doc| Doc::parse(&doc));
 
         // optional: attributes
         let ap = AttributesParser::new(self.lexer);
@@ -133,9 +136,10 @@ impl<'a, L: TLexer> EntryParser<'a, L> {
         Ok((
             tok,
             Some(Entry {
-                name: id,
+                name: self.lexer.resolve(id).to_string(),
                 attributes,
                 init,
+                doc,
             }),
         ))
     }
@@ -157,11 +161,14 @@ impl<'a, L: TLexer> EntriesParser<'a, L> {
             let ep = EntryParser::new(self.lexer);
             let (tok, entry) = ep.parse(None, context)?;
 
-            if let Some(entry) = entry {
+            let tok = tok.unwrap_or_else(|| self.lexer.next_useful());
+
+            if let Some(mut entry) = entry {
+                // A, ///< doc
+                Doc::add(&mut entry.doc, self.lexer.take_trailing_doc());
                 entries.push(entry);
             }
 
-            let tok = tok.unwrap_or_else(|| self.lexer.next_useful());
             match tok {
                 Token::Comma => continue,
                 Token::RightBrace => {
@@ -274,17 +281,20 @@ mod tests {
                     Entry {
                         name: "red".to_string(),
                         attributes: None,
-                        init: None
+                        init: None,
+                        doc: None,
                     },
                     Entry {
                         name: "green".to_string(),
                         attributes: None,
-                        init: None
+                        init: None,
+                        doc: None,
                     },
                     Entry {
                         name: "blue".to_string(),
                         attributes: None,
-                        init: None
+                        init: None,
+                        doc: None,
                     },
                 ]),
             }
@@ -309,17 +319,20 @@ mod tests {
                     Entry {
                         name: "red".to_string(),
                         attributes: None,
-                        init: None
+                        init: None,
+                        doc: None,
                     },
                     Entry {
                         name: "green".to_string(),
                         attributes: None,
-                        init: None
+                        init: None,
+                        doc: None,
                     },
                     Entry {
                         name: "blue".to_string(),
                         attributes: None,
-                        init: None
+                        init: None,
+                        doc: None,
                     },
                 ]),
             }
@@ -349,17 +362,20 @@ mod tests {
                     Entry {
                         name: "red".to_string(),
                         attributes: None,
-                        init: None
+                        init: None,
+                        doc: None,
                     },
                     Entry {
                         name: "green".to_string(),
                         attributes: None,
-                        init: None
+                        init: None,
+                        doc: None,
                     },
                     Entry {
                         name: "blue".to_string(),
                         attributes: None,
-                        init: None
+                        init: None,
+                        doc: None,
                     },
                 ]),
             }
@@ -415,7 +431,8 @@ mod tests {
                         attributes: None,
                         init: Some(ExprNode::Integer(Box::new(Integer {
                             value: IntLiteral::Int(0)
-                        })))
+                        }))),
+                        doc: None,
                     },
                     Entry {
                         name: "green".to_string(),
@@ -427,14 +444,16 @@ mod tests {
                         }]),
                         init: Some(ExprNode::Integer(Box::new(Integer {
                             value: IntLiteral::Int(2)
-                        })))
+                        }))),
+                        doc: None,
                     },
                     Entry {
                         name: "blue".to_string(),
                         attributes: None,
                         init: Some(ExprNode::Integer(Box::new(Integer {
                             value: IntLiteral::Int(0xFF00)
-                        })))
+                        }))),
+                        doc: None,
                     },
                 ],),
             }
@@ -459,17 +478,20 @@ mod tests {
                     Entry {
                         name: "red".to_string(),
                         attributes: None,
-                        init: None
+                        init: None,
+                        doc: None,
                     },
                     Entry {
                         name: "green".to_string(),
                         attributes: None,
-                        init: None
+                        init: None,
+                        doc: None,
                     },
                     Entry {
                         name: "blue".to_string(),
                         attributes: None,
-                        init: None
+                        init: None,
+                        doc: None,
                     },
                 ]),
             }
@@ -504,17 +526,20 @@ mod tests {
                     Entry {
                         name: "red".to_string(),
                         attributes: None,
-                        init: None
+                        init: None,
+                        doc: None,
                     },
                     Entry {
                         name: "green".to_string(),
                         attributes: None,
-                        init: None
+                        init: None,
+                        doc: None,
                     },
                     Entry {
                         name: "blue".to_string(),
                         attributes: None,
-                        init: None
+                        init: None,
+                        doc: None,
                     },
                 ]),
             }
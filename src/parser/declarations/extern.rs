@@ -61,7 +61,7 @@ impl<'a, L: TLexer> ExternParser<'a, L> {
                     Ok((
                         None,
                         Some(Declaration::Extern(Extern {
-                            language,
+                            language: language.to_string(),
                             decls: list.unwrap(),
                             multiple: true,
                         })),
@@ -76,7 +76,7 @@ impl<'a, L: TLexer> ExternParser<'a, L> {
                 Ok((
                     tok,
                     Some(Declaration::Extern(Extern {
-                        language,
+                        language: language.to_string(),
                         decls: list.unwrap(),
                         multiple: false,
                     })),
@@ -148,7 +148,9 @@ extern "C" {
                                     },
                                     init: None,
                                     bitfield_size: None,
+                                    doc: None,
                                 }),
+                                doc: None,
                             }],
                             cv: CVQualifier::empty(),
                             refq: RefQualifier::None,
@@ -171,6 +173,7 @@ extern "C" {
                     },
                     init: None,
                     bitfield_size: None,
+                    doc: None,
                 }))],
                 multiple: true,
             })
@@ -215,7 +218,9 @@ extern double sqrt(double);
                                 },
                                 init: None,
                                 bitfield_size: None,
+                                doc: None,
                             }),
+                            doc: None,
                         }],
                         cv: CVQualifier::empty(),
                         refq: RefQualifier::None,
@@ -238,6 +243,7 @@ extern double sqrt(double);
                 },
                 init: None,
                 bitfield_size: None,
+                doc: None,
             }))
         );
     }
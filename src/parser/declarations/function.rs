@@ -12,6 +12,7 @@ use super::specifier::Specifier;
 use super::types::{Identifier, TypeDeclarator, TypeDeclaratorParser};
 use crate::lexer::{TLexer, Token};
 use crate::parser::attributes::{Attributes, AttributesParser};
+use crate::parser::doc::Doc;
 use crate::parser::dump::Dump;
 use crate::parser::errors::ParserError;
 use crate::parser::expressions::{ExprNode, ExpressionParser, Parameters, ParametersParser};
@@ -24,11 +25,12 @@ use crate::parser::{Context, ScopeKind, TypeToFix};
 pub struct Parameter {
     pub(crate) attributes: Option<Attributes>,
     pub(crate) decl: Rc<TypeDeclarator>,
+    pub(crate) doc: Option<Doc>,
 }
 
 impl Dump for Parameter {
     fn dump(&self, name: &str, prefix: &str, last: bool, stdout: &mut StandardStreamLock) {
-        dump_obj!(self, name, "", prefix, last, stdout, attributes, decl);
+        dump_obj!(self, name, "", prefix, last, stdout, attributes, decl, doc);
     }
 }
 
@@ -127,13 +129,13 @@ impl Dump for FunStatus {
 }
 
 impl VirtSpecifier {
-    pub(crate) fn from_tok(&mut self, tok: &Token) -> bool {
+    pub(crate) fn from_tok<L: TLexer>(&mut self, tok: &Token, lexer: &L) -> bool {
         match tok {
-            Token::Final => {
+            Token::Identifier(id) if lexer.resolve(*id) == "final" => {
                 *self |= VirtSpecifier::FINAL;
                 true
             }
-            Token::Override => {
+            Token::Identifier(id) if lexer.resolve(*id) == "override" => {
                 *self |= VirtSpecifier::OVERRIDE;
                 true
             }
@@ -286,8 +288,11 @@ impl<'a, L: TLexer> ParameterListParser<'a, L> {
         let mut params = Vec::new();
 
         loop {
+            let tk = tok.unwrap_or_else(|| self.lexer.next_useful());
+            let mut doc = self.lexer.take_doc().map(|doc| Doc::parse(&doc));
+
             let ap = AttributesParser::new(self.lexer);
-            let (tk, attributes) = ap.parse(tok, context)?;
+            let (tk, attributes) = ap.parse(Some(tk), context)?;
 
             let dp = TypeDeclaratorParser::new(self.lexer);
             let (tk, decl) = dp.parse(tk, None, true, context)?;
@@ -298,12 +303,23 @@ impl<'a, L: TLexer> ParameterListParser<'a, L> {
             };
 
             let tk = tk.unwrap_or_else(|| self.lexer.next_useful());
+            // int a, ///< doc
+            Doc::add(&mut doc, self.lexer.take_trailing_doc());
+
             match tk {
                 Token::Comma => {
-                    params.push(Parameter { attributes, decl });
+                    params.push(Parameter {
+                        attributes,
+                        decl,
+                        doc,
+                    });
                 }
                 Token::RightParen => {
-                    params.push(Parameter { attributes, decl });
+                    params.push(Parameter {
+                        attributes,
+                        decl,
+                        doc,
+                    });
                     return Ok((None, Some(params)));
                 }
                 _ => {
@@ -376,7 +392,7 @@ impl<'a, L: TLexer> FunctionParser<'a, L> {
 
         let mut virt_specifier = VirtSpecifier::empty();
         let mut tok = tok.unwrap_or_else(|| self.lexer.next_useful());
-        while virt_specifier.from_tok(&tok) {
+        while virt_specifier.from_tok(&tok, self.lexer) {
             tok = self.lexer.next_useful();
         }
 
@@ -394,7 +410,9 @@ impl<'a, L: TLexer> FunctionParser<'a, L> {
             match tok {
                 Token::Default => (self.lexer.next_useful(), FunStatus::Default),
                 Token::Delete => (self.lexer.next_useful(), FunStatus::Delete),
-                Token::LiteralInt(0) => (self.lexer.next_useful(), FunStatus::Pure),
+                Token::LiteralInteger(ref n) if n.spelling == "0" => {
+                    (self.lexer.next_useful(), FunStatus::Pure)
+                }
                 _ => {
                     return Err(ParserError::InvalidTokenInFuncDecl {
                         sp: self.lexer.span(),
@@ -550,6 +568,7 @@ impl<'a, L: TLexer> ConvOperatorDeclaratorParser<'a, L> {
                     },
                     init: None,
                     bitfield_size: None,
+                    doc: None,
                 }),
                 to_fix,
             ))
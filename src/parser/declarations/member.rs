@@ -10,6 +10,7 @@ use super::bitfield::BitFieldDeclaratorParser;
 use super::{StaticAssert, StaticAssertParser, UsingAlias, UsingDecl, UsingEnum, UsingParser};
 use crate::lexer::{TLexer, Token};
 use crate::parser::declarations::{Declaration, TypeDeclarator, TypeDeclaratorParser};
+use crate::parser::doc::Doc;
 use crate::parser::dump::Dump;
 use crate::parser::errors::ParserError;
 use crate::parser::Context;
@@ -25,6 +26,14 @@ pub enum Member {
 }
 
 impl Member {
+    /// The documentation comment of the member
+    pub fn doc(&self) -> Option<&Doc> {
+        match self {
+            Self::Type(d) => d.doc.as_ref(),
+            _ => None,
+        }
+    }
+
     pub(crate) fn has_semicolon(&self) -> bool {
         match self {
             Self::Type(d) => d.has_semicolon(),
@@ -92,6 +101,7 @@ impl<'a, L: TLexer> MemberParser<'a, L> {
         if tok == Token::SemiColon {
             return Ok((None, Some(MemberRes::Decl(Member::Empty))));
         }
+        let mut doc = self.lexer.take_doc().map(|doc| Doc::parse(&doc));
         let tok = Some(tok);
 
         let pppp = PPPParser::new(self.lexer);
@@ -146,6 +156,12 @@ impl<'a, L: TLexer> MemberParser<'a, L> {
             Some(tok)
         };
 
+        if tok.is_some() {
+            // int a; ///< doc
+            Doc::add(&mut doc, self.lexer.take_trailing_doc());
+        }
+        TypeDeclarator::set_doc(&mut typ, doc);
+
         context.add_type_decl(Rc::clone(&typ));
 
         Ok((tok, Some(MemberRes::Decl(Member::Type(typ)))))
@@ -247,6 +263,7 @@ mod tests {
                 bitfield_size: Some(ExprNode::Integer(Box::new(Integer {
                     value: IntLiteral::Int(4)
                 }))),
+                doc: None,
             }))
         );
     }
@@ -283,6 +300,7 @@ mod tests {
                 bitfield_size: Some(ExprNode::Integer(Box::new(Integer {
                     value: IntLiteral::Int(4)
                 }))),
+                doc: None,
             }))
         );
     }
@@ -321,6 +339,7 @@ mod tests {
                 bitfield_size: Some(ExprNode::Integer(Box::new(Integer {
                     value: IntLiteral::Int(4)
                 }))),
+                doc: None,
             }))
         );
     }
@@ -24,6 +24,9 @@ pub use self::array::*;
 pub mod r#enum;
 pub use self::r#enum::*;
 
+pub mod r#typeof;
+pub use self::r#typeof::*;
+
 pub mod member;
 pub use self::member::*;
 
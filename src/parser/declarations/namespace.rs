@@ -94,7 +94,10 @@ impl<'a, L: TLexer> NsNamesParser<'a, L> {
                     inline = true;
                 }
                 Token::Identifier(id) => {
-                    names.push(NsName { inline, name: id });
+                    names.push(NsName {
+                        inline,
+                        name: self.lexer.resolve(id).to_string(),
+                    });
                 }
                 Token::ColonColon => {
                     inline = false;
@@ -323,6 +326,7 @@ namespace A {
                             },
                             init: None,
                             bitfield_size: None,
+                            doc: None,
                         }))],
                     },),
                     Declaration::Type(Rc::new(TypeDeclarator {
@@ -355,6 +359,7 @@ namespace A {
                         },
                         init: None,
                         bitfield_size: None,
+                        doc: None,
                     }))
                 ],
             })
@@ -30,6 +30,7 @@ bitflags! {
         const STDCALL = 0b1_0000_0000_0000_0000;
         const THISCALL = 0b10_0000_0000_0000_0000;
         const VECTORCALL = 0b100_0000_0000_0000_0000;
+        const NORETURN = 0b1000_0000_0000_0000_0000;
     }
 }
 
@@ -75,7 +76,9 @@ impl ToString for Specifier {
             THISCALL,
             "__thiscall",
             VECTORCALL,
-            "__vectorcall"
+            "__vectorcall",
+            NORETURN,
+            "_Noreturn"
         )
     }
 }
@@ -165,6 +168,10 @@ impl Specifier {
                 *self |= Specifier::VECTORCALL;
                 true
             }
+            Token::Noreturn => {
+                *self |= Specifier::NORETURN;
+                true
+            }
             _ => false,
         }
     }
@@ -189,7 +196,8 @@ impl Specifier {
             | Token::Fastcall
             | Token::Stdcall
             | Token::Thiscall
-            | Token::Vectorcall => true,
+            | Token::Vectorcall
+            | Token::Noreturn => true,
             _ => false,
         }
     }
@@ -0,0 +1,163 @@
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use termcolor::StandardStreamLock;
+
+use super::types::{DeclHint, TypeDeclarator, TypeDeclaratorParser};
+use crate::lexer::{TLexer, Token};
+use crate::parser::context::{Context, SearchResult};
+use crate::parser::dump::Dump;
+use crate::parser::errors::ParserError;
+use crate::parser::expressions::{ExprNode, ExpressionParser};
+use crate::parser::names::QualifiedParser;
+use crate::parser::types::{BaseType, CVQualifier, Modifier, UDType, UserDefined};
+
+/// typeof(...) and typeof_unqual(...): the argument is either an expression
+/// or an ExprNode::Type when the operand is a type-id
+#[derive(Clone, Debug, PartialEq)]
+pub struct Typeof {
+    pub arg: ExprNode,
+    pub unqual: bool,
+}
+
+impl Dump for Typeof {
+    fn dump(&self, name: &str, prefix: &str, last: bool, stdout: &mut StandardStreamLock) {
+        dump_obj!(self, name, "typeof", prefix, last, stdout, arg, unqual);
+    }
+}
+
+pub(crate) struct TypeofParser<'a, L: TLexer> {
+    lexer: &'a mut L,
+}
+
+impl<'a, L: TLexer> TypeofParser<'a, L> {
+    pub(crate) fn new(lexer: &'a mut L) -> Self {
+        Self { lexer }
+    }
+
+    pub(crate) fn parse(
+        self,
+        tok: Option<Token>,
+        context: &mut Context,
+    ) -> Result<(Option<Token>, Option<Typeof>), ParserError> {
+        let tok = tok.unwrap_or_else(|| self.lexer.next_useful());
+        if tok != Token::Typeof && tok != Token::TypeofUnqual {
+            return Ok((Some(tok), None));
+        }
+
+        let unqual = tok == Token::TypeofUnqual;
+
+        let tok = self.lexer.next_useful();
+        if tok != Token::LeftParen {
+            return Err(ParserError::InvalidTokenInTypeof {
+                sp: self.lexer.span(),
+                tok,
+            });
+        }
+
+        let tok = self.lexer.next_useful();
+        let (tok, arg) = if CVQualifier::is_cv(&tok)
+            || TypeDeclarator::is_type_part(&tok)
+            || Modifier::is_primitive_part(&tok)
+            || tok == Token::Typeof
+            || tok == Token::TypeofUnqual
+        {
+            let tdp = TypeDeclaratorParser::new(self.lexer);
+            let (tok, typ) = tdp.parse_type_id(Some(tok), None, context)?;
+            (tok, typ.map(|typ| ExprNode::Type(Box::new(typ))))
+        } else if let Token::Identifier(id) = tok {
+            let id = self.lexer.resolve(id).to_string();
+            let qp = QualifiedParser::new(self.lexer);
+            let (tok, qual) = qp.parse(None, Some(id), context)?;
+            let qual = qual.unwrap();
+
+            let typ = match context.search(Some(&qual)) {
+                Some(SearchResult::Type(typ)) => Some(UDType::Direct(typ)),
+                Some(SearchResult::IncompleteType(typ)) => Some(UDType::Indirect(typ)),
+                _ => None,
+            };
+
+            if let Some(typ) = typ {
+                let hint = DeclHint::Type(BaseType::UD(Box::new(UserDefined { name: qual, typ })));
+                let tdp = TypeDeclaratorParser::new(self.lexer);
+                let (tok, typ) = tdp.parse_type_id(tok, Some(hint), context)?;
+                (tok, typ.map(|typ| ExprNode::Type(Box::new(typ))))
+            } else {
+                let mut ep = ExpressionParser::new(self.lexer, Token::RightParen);
+                ep.parse_with_id(tok, qual, context)?
+            }
+        } else {
+            let mut ep = ExpressionParser::new(self.lexer, Token::RightParen);
+            ep.parse(Some(tok), context)?
+        };
+
+        let tok = tok.unwrap_or_else(|| self.lexer.next_useful());
+        match arg {
+            Some(arg) if tok == Token::RightParen => Ok((None, Some(Typeof { arg, unqual }))),
+            _ => Err(ParserError::InvalidTokenInTypeof {
+                sp: self.lexer.span(),
+                tok,
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::lexer::{preprocessor::context::DefaultContext, Lexer};
+    use crate::mk_var;
+    use crate::parser::declarations::{MSModifier, Pointer, PtrKind};
+    use crate::parser::expressions::{BinaryOp, Operator};
+    use crate::parser::names::Qualified;
+    use crate::parser::types::{Primitive, Type};
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_typeof_expr() {
+        let mut l = Lexer::<DefaultContext>::new(b"__typeof__(a + b)");
+        let p = TypeofParser::new(&mut l);
+        let mut context = Context::default();
+        let (_, t) = p.parse(None, &mut context).unwrap();
+
+        assert_eq!(
+            t.unwrap(),
+            Typeof {
+                arg: node!(BinaryOp {
+                    op: Operator::Add,
+                    arg1: ExprNode::Variable(Box::new(mk_var!("a"))),
+                    arg2: ExprNode::Variable(Box::new(mk_var!("b"))),
+                }),
+                unqual: false,
+            }
+        );
+    }
+
+    #[test]
+    fn test_typeof_type() {
+        let mut l = Lexer::<DefaultContext>::new(b"__typeof_unqual__(const int *)");
+        let p = TypeofParser::new(&mut l);
+        let mut context = Context::default();
+        let (_, t) = p.parse(None, &mut context).unwrap();
+
+        assert_eq!(
+            t.unwrap(),
+            Typeof {
+                arg: ExprNode::Type(Box::new(Type {
+                    base: BaseType::Primitive(Primitive::Int),
+                    cv: CVQualifier::CONST,
+                    pointers: Some(vec![Pointer {
+                        kind: PtrKind::Pointer,
+                        attributes: None,
+                        cv: CVQualifier::empty(),
+                        ms: MSModifier::empty(),
+                    }]),
+                })),
+                unqual: true,
+            }
+        );
+    }
+}
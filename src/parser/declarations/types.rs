@@ -11,6 +11,7 @@ use super::class::ClassParser;
 use super::function::{ConvOperatorDeclaratorParser, FunctionParser};
 use super::pointer::{ParenPointerDeclaratorParser, PointerDeclaratorParser};
 use super::r#enum::EnumParser;
+use super::r#typeof::TypeofParser;
 use super::specifier::Specifier;
 use crate::lexer::{
     extra::{CombinedLexers, SavedLexer},
@@ -18,6 +19,7 @@ use crate::lexer::{
 };
 use crate::parser::attributes::{Attributes, AttributesParser};
 use crate::parser::context::{Context, SearchResult, TypeToFix};
+use crate::parser::doc::Doc;
 use crate::parser::dump::Dump;
 use crate::parser::errors::ParserError;
 use crate::parser::expressions::{ExprNode, ExpressionParser, VarDecl, Variable};
@@ -45,6 +47,7 @@ pub struct TypeDeclarator {
     pub identifier: Identifier,
     pub init: Option<Initializer>,
     pub bitfield_size: Option<ExprNode>,
+    pub doc: Option<Doc>,
 }
 
 impl Dump for TypeDeclarator {
@@ -60,7 +63,8 @@ impl Dump for TypeDeclarator {
             specifier,
             identifier,
             init,
-            bitfield_size
+            bitfield_size,
+            doc
         );
     }
 }
@@ -89,6 +93,14 @@ impl TypeDeclarator {
         }
     }
 
+    /// The declarator is only shared when it names an incomplete type (typedef struct { } A;)
+    /// and in this case the type keeps the declarator without its documentation
+    pub(crate) fn set_doc(decl: &mut Rc<TypeDeclarator>, doc: Option<Doc>) {
+        if doc.is_some() {
+            Rc::make_mut(decl).doc = doc;
+        }
+    }
+
     pub(crate) fn is_type_part(tok: &Token) -> bool {
         *tok == Token::Class || *tok == Token::Enum || *tok == Token::Struct
     }
@@ -192,6 +204,32 @@ impl<'a, L: TLexer> DeclSpecifierParser<'a, L> {
 
         let mut tok = tok.unwrap_or_else(|| self.lexer.next_useful());
         loop {
+            // _Atomic(T) is a type specifier, _Atomic alone is a qualifier
+            if tok == Token::Atomic {
+                tok = self.lexer.next_useful();
+                if tok == Token::LeftParen && typ.is_none() && ty_modif.is_empty() {
+                    let tdp = TypeDeclaratorParser::new(self.lexer);
+                    let (tk, atomic) = tdp.parse_type_id(None, None, context)?;
+                    let tk = tk.unwrap_or_else(|| self.lexer.next_useful());
+
+                    match atomic {
+                        Some(atomic) if tk == Token::RightParen => {
+                            typ = Some(BaseType::Atomic(Box::new(atomic)));
+                        }
+                        _ => {
+                            return Err(ParserError::InvalidTokenInAtomic {
+                                sp: self.lexer.span(),
+                                tok: tk,
+                            });
+                        }
+                    }
+                    tok = self.lexer.next_useful();
+                } else {
+                    cv |= CVQualifier::ATOMIC;
+                }
+                continue;
+            }
+
             // const, volatile
             if cv.from_tok(&tok) {
                 tok = self.lexer.next_useful();
@@ -236,6 +274,7 @@ impl<'a, L: TLexer> DeclSpecifierParser<'a, L> {
                 // identifier
                 let tk = tk.unwrap_or_else(|| self.lexer.next_useful());
                 if let Token::Identifier(id) = tk {
+                    let id = self.lexer.resolve(id).to_string();
                     let qp = QualifiedParser::new(self.lexer);
                     let (tk, name) = qp.parse(None, Some(id), context)?;
                     let name = name.unwrap();
@@ -269,6 +308,17 @@ impl<'a, L: TLexer> DeclSpecifierParser<'a, L> {
                     continue;
                 }
 
+                // typeof, typeof_unqual
+                let tp = TypeofParser::new(self.lexer);
+                let (tk, tof) = tp.parse(Some(tk), context)?;
+
+                if let Some(tof) = tof {
+                    typ = Some(BaseType::Typeof(Box::new(tof)));
+                    tok = tk.unwrap_or_else(|| self.lexer.next_useful());
+                    continue;
+                }
+                let tk = tk.unwrap_or_else(|| self.lexer.next_useful());
+
                 tok = tk;
             }
 
@@ -368,6 +418,7 @@ impl<'a, L: TLexer> NoPtrDeclaratorParser<'a, L> {
                     identifier,
                     init: None,
                     bitfield_size: None,
+                    doc: None,
                 }),
                 to_fix,
             ));
@@ -404,6 +455,7 @@ impl<'a, L: TLexer> NoPtrDeclaratorParser<'a, L> {
                 identifier,
                 init,
                 bitfield_size: None,
+                doc: None,
             }),
             None,
         ))
@@ -480,6 +532,7 @@ impl<'a, L: TLexer> TypeDeclaratorParser<'a, L> {
                 identifier,
                 init: _,
                 bitfield_size: _,
+                doc: _,
             } = paren_decl;
             let Type {
                 base,
@@ -516,6 +569,19 @@ impl<'a, L: TLexer> TypeDeclaratorParser<'a, L> {
         Ok((tok, Some(decl)))
     }
 
+    /// Parse a type-id, i.e. a declaration without declarator-id as in _Atomic(T) or typeof(T)
+    pub(crate) fn parse_type_id(
+        self,
+        tok: Option<Token>,
+        hint: Option<DeclHint>,
+        context: &mut Context,
+    ) -> Result<(Option<Token>, Option<Type>), ParserError> {
+        let (tok, decl) = self.parse(tok, hint, false, context)?;
+        let typ = decl.map(|decl| Rc::try_unwrap(decl).map_or_else(|d| d.typ.clone(), |d| d.typ));
+
+        Ok((tok, typ))
+    }
+
     pub(crate) fn is_decl_part(tok: &Token) -> bool {
         match tok {
             Token::Star | Token::And | Token::AndAnd => true,
@@ -562,6 +628,7 @@ impl<'a, L: TLexer> DeclOrExprParser<'a, L> {
         let tok = tok.unwrap_or_else(|| self.lexer.next_useful());
         let (typ, var, tok) = match tok {
             Token::Identifier(id) => {
+                let id = self.lexer.resolve(id).to_string();
                 let qp = QualifiedParser::new(self.lexer);
                 let (tok, name) = qp.parse(None, Some(id), context)?;
 
@@ -639,7 +706,7 @@ impl<'a, L: TLexer> DeclOrExprParser<'a, L> {
                 // the closing parenthesis, so perform a lookahead.
                 let (tok, mut saved) = self.lexer.save_until(Token::RightParen);
                 let tok = self.lexer.next_useful();
-                saved.push(tok.clone());
+                saved.push(tok.clone(), self.lexer.span());
 
                 let mut combined = CombinedLexers::new(&mut saved, self.lexer);
 
@@ -709,6 +776,7 @@ mod tests {
 
     use super::super::function::*;
     use super::*;
+    use crate::args::{LangStandard, Standard};
     use crate::lexer::{preprocessor::context::DefaultContext, Lexer};
     use crate::mk_var;
     use crate::parser::array::*;
@@ -737,6 +805,7 @@ mod tests {
             },
             init: None,
             bitfield_size: None,
+            doc: None,
         });
 
         context.add_type_decl(Rc::clone(&t));
@@ -777,6 +846,8 @@ mod tests {
             ("long double _Imaginary", Primitive::LongDoubleImaginary),
         ] {
             let mut l = Lexer::<DefaultContext>::new(buf.as_bytes());
+            // char8_t is a keyword since C++20
+            l.set_standard(LangStandard::new(Standard::CPP20, false));
             let p = DeclSpecifierParser::new(&mut l);
             let mut context = Context::default();
             let (_, (_, ty, _, _)) = p.parse(None, None, &mut context).unwrap();
@@ -870,6 +941,7 @@ mod tests {
                 },
                 init: Some(Initializer::Equal(ExprNode::Nullptr(Box::new(Nullptr {})))),
                 bitfield_size: None,
+                doc: None,
             })
         );
     }
@@ -902,6 +974,7 @@ mod tests {
                 },
                 init: Some(Initializer::Equal(ExprNode::Nullptr(Box::new(Nullptr {})))),
                 bitfield_size: None,
+                doc: None,
             })
         );
     }
@@ -937,6 +1010,7 @@ mod tests {
                 },
                 init: Some(Initializer::Equal(ExprNode::Nullptr(Box::new(Nullptr {})))),
                 bitfield_size: None,
+                doc: None,
             })
         );
     }
@@ -968,6 +1042,7 @@ mod tests {
                     }
                 )),])),
                 bitfield_size: None,
+                doc: None,
             })
         );
     }
@@ -1002,6 +1077,7 @@ mod tests {
                     "NULL"
                 ))))),
                 bitfield_size: None,
+                doc: None,
             })
         );
     }
@@ -1042,6 +1118,7 @@ mod tests {
                 },
                 init: None,
                 bitfield_size: None,
+                doc: None,
             })
         );
     }
@@ -1088,6 +1165,7 @@ mod tests {
                 },
                 init: None,
                 bitfield_size: None,
+                doc: None,
             })
         );
     }
@@ -1137,6 +1215,7 @@ mod tests {
                 },
                 init: None,
                 bitfield_size: None,
+                doc: None,
             })
         );
     }
@@ -1169,6 +1248,7 @@ mod tests {
                 },
                 init: None,
                 bitfield_size: None,
+                doc: None,
             })
         );
     }
@@ -1201,6 +1281,7 @@ mod tests {
                 },
                 init: None,
                 bitfield_size: None,
+                doc: None,
             })
         );
     }
@@ -1233,6 +1314,7 @@ mod tests {
                 },
                 init: None,
                 bitfield_size: None,
+                doc: None,
             })
         );
     }
@@ -1273,6 +1355,7 @@ mod tests {
                 },
                 init: None,
                 bitfield_size: None,
+                doc: None,
             })
         );
     }
@@ -1329,6 +1412,7 @@ mod tests {
                 },
                 init: None,
                 bitfield_size: None,
+                doc: None,
             })
         );
     }
@@ -1378,6 +1462,7 @@ mod tests {
                 },
                 init: None,
                 bitfield_size: None,
+                doc: None,
             })
         );
     }
@@ -1415,7 +1500,9 @@ mod tests {
                                 },
                                 init: None,
                                 bitfield_size: None,
+                                doc: None,
                             }),
+                            doc: None,
                         }],
                         cv: CVQualifier::empty(),
                         refq: RefQualifier::None,
@@ -1438,6 +1525,7 @@ mod tests {
                 },
                 init: None,
                 bitfield_size: None,
+                doc: None,
             })
         );
     }
@@ -1475,7 +1563,9 @@ mod tests {
                                 },
                                 init: None,
                                 bitfield_size: None,
+                                doc: None,
                             }),
+                            doc: None,
                         }],
                         cv: CVQualifier::empty(),
                         refq: RefQualifier::None,
@@ -1511,6 +1601,7 @@ mod tests {
                 },
                 init: None,
                 bitfield_size: None,
+                doc: None,
             })
         );
     }
@@ -1561,7 +1652,9 @@ mod tests {
                                 },
                                 init: None,
                                 bitfield_size: None,
+                                doc: None,
                             }),
+                            doc: None,
                         }],
                         cv: CVQualifier::empty(),
                         refq: RefQualifier::None,
@@ -1584,6 +1677,7 @@ mod tests {
                 },
                 init: None,
                 bitfield_size: None,
+                doc: None,
             })
         );
     }
@@ -1622,7 +1716,9 @@ mod tests {
                                     },
                                     init: None,
                                     bitfield_size: None,
+                                    doc: None,
                                 }),
+                                doc: None,
                             },
                             Parameter {
                                 attributes: None,
@@ -1644,7 +1740,9 @@ mod tests {
                                     },
                                     init: None,
                                     bitfield_size: None,
+                                    doc: None,
                                 }),
+                                doc: None,
                             }
                         ],
                         cv: CVQualifier::empty(),
@@ -1668,6 +1766,7 @@ mod tests {
                 },
                 init: None,
                 bitfield_size: None,
+                doc: None,
             })
         );
     }
@@ -1709,7 +1808,9 @@ mod tests {
                                     }
                                 )))),
                                 bitfield_size: None,
+                                doc: None,
                             }),
+                            doc: None,
                         }],
                         cv: CVQualifier::empty(),
                         refq: RefQualifier::None,
@@ -1732,6 +1833,7 @@ mod tests {
                 },
                 init: None,
                 bitfield_size: None,
+                doc: None,
             })
         );
     }
@@ -1780,7 +1882,9 @@ mod tests {
                                     }
                                 )))),
                                 bitfield_size: None,
+                                doc: None,
                             }),
+                            doc: None,
                         }],
                         cv: CVQualifier::CONST,
                         refq: RefQualifier::RValue,
@@ -1818,6 +1922,7 @@ mod tests {
                 },
                 init: None,
                 bitfield_size: None,
+                doc: None,
             })
         );
     }
@@ -1857,6 +1962,7 @@ mod tests {
                 },
                 init: None,
                 bitfield_size: None,
+                doc: None,
             })
         );
     }
@@ -1881,7 +1987,8 @@ mod tests {
                         entries: Some(vec![Entry {
                             name: "a".to_string(),
                             attributes: None,
-                            init: None
+                            init: None,
+                            doc: None,
                         },]),
                     })),
                     cv: CVQualifier::empty(),
@@ -1894,6 +2001,7 @@ mod tests {
                 },
                 init: None,
                 bitfield_size: None,
+                doc: None,
             })
         );
     }
@@ -1930,6 +2038,7 @@ mod tests {
                                 },
                                 init: None,
                                 bitfield_size: None,
+                                doc: None,
                             })),],
                             protected: vec![],
                             private: vec![],
@@ -1945,6 +2054,7 @@ mod tests {
                 },
                 init: None,
                 bitfield_size: None,
+                doc: None,
             })
         );
     }
@@ -1989,9 +2099,7 @@ mod tests {
                 identifier: Identifier {
                     identifier: Some(Qualified {
                         names: vec![
-                            Name::Identifier(names::Identifier {
-                                val: "B".to_string()
-                            }),
+                            Name::Identifier(names::Identifier { val: "B".into() }),
                             Name::Operator(Box::new(operator::Operator::Op(
                                 expressions::Operator::Plus
                             ))),
@@ -2001,6 +2109,7 @@ mod tests {
                 },
                 init: None,
                 bitfield_size: None,
+                doc: None,
             })
         );
     }
@@ -2052,6 +2161,7 @@ mod tests {
                 },
                 init: None,
                 bitfield_size: None,
+                doc: None,
             })
         );
     }
@@ -2089,9 +2199,7 @@ mod tests {
                 identifier: Identifier {
                     identifier: Some(Qualified {
                         names: vec![
-                            Name::Identifier(names::Identifier {
-                                val: "A".to_string()
-                            }),
+                            Name::Identifier(names::Identifier { val: "A".into() }),
                             Name::Operator(Box::new(operator::Operator::Conv(ConvType {
                                 base: ConvBaseType::UD(Box::new(UserDefined {
                                     name: mk_id!("B"),
@@ -2106,6 +2214,7 @@ mod tests {
                 },
                 init: None,
                 bitfield_size: None,
+                doc: None,
             })
         );
     }
@@ -2222,7 +2331,9 @@ mod tests {
                                 },
                                 init: None,
                                 bitfield_size: None,
+                                doc: None,
                             }),
+                            doc: None,
                         }],
                         cv: CVQualifier::empty(),
                         refq: RefQualifier::None,
@@ -2250,6 +2361,7 @@ mod tests {
                 },
                 init: None,
                 bitfield_size: None,
+                doc: None,
             }))
         );
     }
@@ -0,0 +1,432 @@
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use termcolor::StandardStreamLock;
+
+use crate::parser::dump::Dump;
+
+/// A documented parameter (@param or @tparam)
+#[derive(Clone, Debug, PartialEq)]
+pub struct DocParam {
+    pub name: String,
+    /// in, out or in,out
+    pub direction: Option<String>,
+    pub description: String,
+}
+
+impl Dump for DocParam {
+    fn dump(&self, name: &str, prefix: &str, last: bool, stdout: &mut StandardStreamLock) {
+        dump_obj!(
+            self,
+            name,
+            "",
+            prefix,
+            last,
+            stdout,
+            name,
+            direction,
+            description
+        );
+    }
+}
+
+pub type DocParams = Vec<DocParam>;
+
+impl Dump for DocParams {
+    fn dump(&self, name: &str, prefix: &str, last: bool, stdout: &mut StandardStreamLock) {
+        dump_vec!(name, self, "param", prefix, last, stdout);
+    }
+}
+
+/// A block command which isn't handled specifically (e.g. @note, @see, @throws)
+#[derive(Clone, Debug, PartialEq)]
+pub struct DocCommand {
+    pub name: String,
+    pub text: String,
+}
+
+impl Dump for DocCommand {
+    fn dump(&self, name: &str, prefix: &str, last: bool, stdout: &mut StandardStreamLock) {
+        dump_obj!(self, name, "", prefix, last, stdout, name, text);
+    }
+}
+
+pub type DocCommands = Vec<DocCommand>;
+
+impl Dump for DocCommands {
+    fn dump(&self, name: &str, prefix: &str, last: bool, stdout: &mut StandardStreamLock) {
+        dump_vec!(name, self, "cmd", prefix, last, stdout);
+    }
+}
+
+/// A documentation comment with its Doxygen/Javadoc commands
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Doc {
+    /// The text of the comment without the comment markers
+    pub text: String,
+    /// The text of @brief or else the first paragraph
+    pub brief: Option<String>,
+    /// The paragraphs which aren't in a command
+    pub details: Vec<String>,
+    pub params: DocParams,
+    pub tparams: DocParams,
+    pub returns: Option<String>,
+    pub deprecated: Option<String>,
+    pub commands: DocCommands,
+}
+
+impl Dump for Doc {
+    fn dump(&self, name: &str, prefix: &str, last: bool, stdout: &mut StandardStreamLock) {
+        dump_obj!(
+            self, name, "doc", prefix, last, stdout, brief, details, params, tparams, returns,
+            deprecated, commands
+        );
+    }
+}
+
+impl Dump for Vec<String> {
+    fn dump(&self, name: &str, prefix: &str, last: bool, stdout: &mut StandardStreamLock) {
+        dump_vec!(name, self, "par", prefix, last, stdout);
+    }
+}
+
+/// Where the text of the current paragraph goes
+enum Target {
+    Details,
+    Brief,
+    Param,
+    TParam,
+    Returns,
+    Deprecated,
+    Command,
+}
+
+impl Doc {
+    pub fn parse(text: &str) -> Self {
+        let mut doc = Doc {
+            text: text.to_string(),
+            ..Default::default()
+        };
+        let mut target = Target::Details;
+        let mut par = String::new();
+
+        for line in text.lines().map(|l| l.trim()) {
+            if line.is_empty() {
+                doc.flush(&mut target, &mut par);
+                continue;
+            }
+
+            if let Some((cmd, rem)) = Self::get_command(line) {
+                doc.flush(&mut target, &mut par);
+                target = doc.start_command(cmd, rem, &mut par);
+                continue;
+            }
+
+            if !par.is_empty() {
+                par.push(' ');
+            }
+            par.push_str(line);
+        }
+        doc.flush(&mut target, &mut par);
+
+        if doc.brief.is_none() && !doc.details.is_empty() {
+            doc.brief = Some(doc.details.remove(0));
+        }
+
+        doc
+    }
+
+    /// Get a block command (@name or \name) at the beginning of a line
+    fn get_command(line: &str) -> Option<(&str, &str)> {
+        let rem = line.strip_prefix('@').or_else(|| line.strip_prefix('\\'))?;
+        let n = rem
+            .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+            .unwrap_or(rem.len());
+        if n == 0 {
+            return None;
+        }
+        Some((&rem[..n], &rem[n..]))
+    }
+
+    fn start_command(&mut self, cmd: &str, rem: &str, par: &mut String) -> Target {
+        let target = match cmd {
+            "brief" | "short" => Target::Brief,
+            "param" | "tparam" => {
+                let (direction, rem) = if let Some(rem) = rem.strip_prefix('[') {
+                    let end = rem.find(']').unwrap_or(rem.len());
+                    let direction = rem[..end].split_whitespace().collect::<String>();
+                    (Some(direction), rem.get(end + 1..).unwrap_or(""))
+                } else {
+                    (None, rem)
+                };
+                let rem = rem.trim_start();
+                let n = rem.find(char::is_whitespace).unwrap_or(rem.len());
+                let param = DocParam {
+                    name: rem[..n].to_string(),
+                    direction,
+                    description: String::new(),
+                };
+                par.push_str(rem[n..].trim());
+
+                if cmd == "param" {
+                    self.params.push(param);
+                    return Target::Param;
+                } else {
+                    self.tparams.push(param);
+                    return Target::TParam;
+                }
+            }
+            "return" | "returns" | "result" => Target::Returns,
+            "deprecated" => {
+                self.deprecated = Some(String::new());
+                Target::Deprecated
+            }
+            _ => {
+                self.commands.push(DocCommand {
+                    name: cmd.to_string(),
+                    text: String::new(),
+                });
+                Target::Command
+            }
+        };
+        par.push_str(rem.trim());
+        target
+    }
+
+    fn flush(&mut self, target: &mut Target, par: &mut String) {
+        let text = std::mem::take(par);
+        match target {
+            Target::Details => {
+                if !text.is_empty() {
+                    self.details.push(text);
+                }
+            }
+            Target::Brief => self.brief = Some(text),
+            Target::Param => self.params.last_mut().unwrap().description = text,
+            Target::TParam => self.tparams.last_mut().unwrap().description = text,
+            Target::Returns => self.returns = Some(text),
+            Target::Deprecated => self.deprecated = Some(text),
+            Target::Command => self.commands.last_mut().unwrap().text = text,
+        }
+        // A command ends with its paragraph
+        *target = Target::Details;
+    }
+
+    /// Add the text of a trailing comment (///<) to the documentation
+    pub(crate) fn add(doc: &mut Option<Doc>, text: Option<String>) {
+        if let Some(text) = text {
+            *doc = Some(match doc.take() {
+                Some(prev) => Doc::parse(&format!("{}\n{}", prev.text, text)),
+                None => Doc::parse(&text),
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::lexer::preprocessor::context::DefaultContext;
+    use crate::lexer::{Lexer, TLexer, Token};
+    use crate::parser::declarations::{Declaration, DeclarationListParser};
+    use crate::parser::types::BaseType;
+    use crate::parser::Context;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_doc_commands() {
+        let doc = Doc::parse(concat!(
+            "\\brief Compute the sum.\n",
+            "\n",
+            "It's a long description\n",
+            "on two lines.\n",
+            "\n",
+            "@param[in] a the first\n",
+            "  operand\n",
+            "@param b the second one\n",
+            "@tparam T the type\n",
+            "@return the sum\n",
+            "@deprecated Use add instead\n",
+            "@note Nothing special",
+        ));
+
+        assert_eq!(doc.brief, Some("Compute the sum.".to_string()));
+        assert_eq!(
+            doc.details,
+            vec!["It's a long description on two lines.".to_string()]
+        );
+        assert_eq!(
+            doc.params,
+            vec![
+                DocParam {
+                    name: "a".to_string(),
+                    direction: Some("in".to_string()),
+                    description: "the first operand".to_string(),
+                },
+                DocParam {
+                    name: "b".to_string(),
+                    direction: None,
+                    description: "the second one".to_string(),
+                },
+            ]
+        );
+        assert_eq!(
+            doc.tparams,
+            vec![DocParam {
+                name: "T".to_string(),
+                direction: None,
+                description: "the type".to_string(),
+            }]
+        );
+        assert_eq!(doc.returns, Some("the sum".to_string()));
+        assert_eq!(doc.deprecated, Some("Use add instead".to_string()));
+        assert_eq!(
+            doc.commands,
+            vec![DocCommand {
+                name: "note".to_string(),
+                text: "Nothing special".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_doc_autobrief() {
+        let doc = Doc::parse("First paragraph\ncontinued.\n\nSecond one.\n@deprecated");
+        assert_eq!(doc.brief, Some("First paragraph continued.".to_string()));
+        assert_eq!(doc.details, vec!["Second one.".to_string()]);
+        assert_eq!(doc.deprecated, Some("".to_string()));
+    }
+
+    #[test]
+    fn test_doc_comments_lexer() {
+        let mut l = Lexer::<DefaultContext>::new(
+            concat!(
+                "/// Line 1\n",
+                "/// Line 2\n",
+                "int a; ///< After a\n",
+                "/**\n",
+                " * Block\n",
+                " *   indented\n",
+                " */\n",
+                "/* not a doc */\n",
+                "//// not a doc\n",
+                "int b /**< after b */, c;\n",
+                "//!< after c\n",
+                "}\n",
+            )
+            .as_bytes(),
+        );
+
+        assert_eq!(l.next_useful(), Token::Int);
+        assert_eq!(l.take_doc(), Some("Line 1\nLine 2".to_string()));
+        assert_eq!(l.take_trailing_doc(), None);
+
+        assert_eq!(l.next_useful(), l.id("a"));
+        assert_eq!(l.take_doc(), None);
+        assert_eq!(l.next_useful(), Token::SemiColon);
+        assert_eq!(l.take_trailing_doc(), Some("After a".to_string()));
+
+        assert_eq!(l.next_useful(), Token::Int);
+        assert_eq!(l.take_doc(), Some("Block\n  indented".to_string()));
+
+        assert_eq!(l.next_useful(), l.id("b"));
+        assert_eq!(l.take_trailing_doc(), None);
+        assert_eq!(l.next_useful(), Token::Comma);
+        assert_eq!(l.take_trailing_doc(), Some("after b".to_string()));
+        assert_eq!(l.next_useful(), l.id("c"));
+        assert_eq!(l.next_useful(), Token::SemiColon);
+        assert_eq!(l.take_trailing_doc(), None);
+        assert_eq!(l.next_useful(), Token::RightBrace);
+        assert_eq!(l.take_trailing_doc(), Some("after c".to_string()));
+    }
+
+    fn brief(doc: Option<&Doc>) -> Option<&str> {
+        doc.and_then(|d| d.brief.as_deref())
+    }
+
+    #[test]
+    fn test_doc_declarations() {
+        let mut l = Lexer::<DefaultContext>::new(
+            br#"
+/// A point
+struct Point {
+    int x; ///< The abscissa
+    /** The ordinate */
+    int y;
+};
+
+/**
+ * @brief The colors
+ */
+enum Color {
+    Red, ///< red
+    /// green
+    Green,
+    Blue ///< blue
+};
+
+/// Add two numbers
+/// @return the sum
+int add(int a, ///< first
+        /// second
+        int b);
+
+int undocumented;
+"#,
+        );
+        let p = DeclarationListParser::new(&mut l);
+        let mut context = Context::default();
+        let (_, decls) = p.parse(None, &mut context).unwrap();
+        let decls = decls.unwrap();
+
+        assert_eq!(brief(decls[0].doc()), Some("A point"));
+        let d = if let Declaration::Type(d) = &decls[0] {
+            d
+        } else {
+            panic!("Not a type declarator");
+        };
+        let body = if let BaseType::Class(c) = &d.typ.base {
+            c.body.as_ref().unwrap()
+        } else {
+            panic!("Not a class");
+        };
+        let members: Vec<_> = body.public.iter().chain(body.private.iter()).collect();
+        assert_eq!(brief(members[0].doc()), Some("The abscissa"));
+        assert_eq!(brief(members[1].doc()), Some("The ordinate"));
+
+        assert_eq!(brief(decls[1].doc()), Some("The colors"));
+        let d = if let Declaration::Type(d) = &decls[1] {
+            d
+        } else {
+            panic!("Not a type declarator");
+        };
+        let entries = if let BaseType::Enum(e) = &d.typ.base {
+            e.entries.as_ref().unwrap()
+        } else {
+            panic!("Not an enum");
+        };
+        let docs: Vec<_> = entries.iter().map(|e| brief(e.doc.as_ref())).collect();
+        assert_eq!(docs, vec![Some("red"), Some("green"), Some("blue")]);
+
+        let doc = decls[2].doc().unwrap();
+        assert_eq!(doc.brief, Some("Add two numbers".to_string()));
+        assert_eq!(doc.returns, Some("the sum".to_string()));
+        let d = if let Declaration::Type(d) = &decls[2] {
+            d
+        } else {
+            panic!("Not a type declarator");
+        };
+        let params = if let BaseType::Function(f) = &d.typ.base {
+            &f.params
+        } else {
+            panic!("Not a function");
+        };
+        let docs: Vec<_> = params.iter().map(|p| brief(p.doc.as_ref())).collect();
+        assert_eq!(docs, vec![Some("first"), Some("second")]);
+
+        assert_eq!(decls[3].doc(), None);
+    }
+}
@@ -31,6 +31,9 @@ pub enum ParserError {
     InvalidTokenInUsing { sp: Span, tok: Token },
     InvalidTokenInAlias { sp: Span, tok: Token },
     InvalidTokenInConditional { sp: Span, tok: Token },
+    InvalidTokenInGeneric { sp: Span, tok: Token },
+    InvalidTokenInTypeof { sp: Span, tok: Token },
+    InvalidTokenInAtomic { sp: Span, tok: Token },
     InvalidTokenInDo { sp: Span, tok: Token },
     InvalidTokenInStmt { sp: Span, tok: Token },
     InvalidTokenInFor { sp: Span, tok: Token },
@@ -46,6 +49,7 @@ pub enum ParserError {
     InvalidBitfieldSize { sp: Span },
     InvalidCtorInit { sp: Span },
     InvalidCast { sp: Span },
+    IntegerTooLarge { sp: Span, spelling: String },
 }
 
 impl ParserError {
@@ -126,6 +130,15 @@ impl ParserError {
                 *sp,
                 format!("Invalid token {:?} in conditional expression", tok),
             ),
+            InvalidTokenInGeneric { sp, tok } => {
+                (*sp, format!("Invalid token {:?} in generic selection", tok))
+            }
+            InvalidTokenInTypeof { sp, tok } => {
+                (*sp, format!("Invalid token {:?} in typeof specifier", tok))
+            }
+            InvalidTokenInAtomic { sp, tok } => {
+                (*sp, format!("Invalid token {:?} in _Atomic specifier", tok))
+            }
             InvalidTokenInDo { sp, tok } => {
                 (*sp, format!("Invalid token {:?} in do statement", tok))
             }
@@ -151,6 +164,9 @@ impl ParserError {
             InvalidCtorInit { sp } => (*sp, format!("Invalid ctor initializer")),
             InvalidArgInStaticAssert { sp } => (*sp, format!("Invalid argument in static_assert")),
             InvalidCast { sp } => (*sp, format!("Invalid cast")),
+            IntegerTooLarge { sp, spelling } => {
+                (*sp, format!("Integer literal {} is too large", spelling))
+            }
         };
         StringlyError { message, sp }
     }
@@ -3,9 +3,11 @@
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
+use std::convert::TryFrom;
 use std::rc::Rc;
 use termcolor::StandardStreamLock;
 
+use super::generic::Generic;
 use super::list::{ListInitialization, ListInitializationParser};
 use super::operator::{BinaryOp, Conditional, Operator, UnaryOp};
 use super::params::{Parameters, ParametersParser};
@@ -95,6 +97,7 @@ pub enum ExprNode {
     BinaryOp(Box<BinaryOp>),
     Conditional(Box<Conditional>),
     CallExpr(Box<CallExpr>),
+    Generic(Box<Generic>),
     Variable(Box<Variable>),
     ListInit(Box<ListInitialization>),
     InitExpr(Box<InitExpr>),
@@ -122,6 +125,7 @@ impl Dump for ExprNode {
             Self::BinaryOp(x) => dump!(x),
             Self::Conditional(x) => dump!(x),
             Self::CallExpr(x) => dump!(x),
+            Self::Generic(x) => dump!(x),
             Self::Variable(x) => dump!(x),
             Self::Integer(x) => dump!(x),
             Self::Float(x) => dump!(x),
@@ -175,7 +179,7 @@ fn precedence(op: Operator) -> (u32, Associativity) {
         ScopeResolution => (1, Associativity::LR),
         PostInc | PostDec | Call | Dot | Arrow | Subscript => (2, Associativity::LR),
         PreInc | PreDec | Plus | Minus | Indirection | AddressOf | AddressOfLabel | Not
-        | BitNeg | Sizeof | New | NewArray | Delete | DeleteArray | CoAwait | Cast => {
+        | BitNeg | Sizeof | Alignof | New | NewArray | Delete | DeleteArray | CoAwait | Cast => {
             (3, Associativity::RL)
         }
         DotIndirection | ArrowIndirection => (4, Associativity::LR),
@@ -417,20 +421,30 @@ impl<'a, L: TLexer> ExpressionParser<'a, L> {
                         self.push_operator(Operator::PreDec);
                     }
                 }
-                Token::Sizeof => {
+                Token::Sizeof | Token::Alignof => {
+                    let op = if tok == Token::Sizeof {
+                        Operator::Sizeof
+                    } else {
+                        Operator::Alignof
+                    };
                     let tk = self.lexer.next_useful();
                     if tk == Token::LeftParen {
                         let pp = ParametersParser::new(self.lexer, Token::RightParen);
                         let (_, params) = pp.parse(None, None, context)?;
                         self.operands.push(ExprNode::UnaryOp(Box::new(UnaryOp {
-                            op: Operator::Sizeof,
+                            op,
                             arg: params.unwrap().pop().unwrap(),
                         })));
                         self.last = LastKind::Operand;
                     } else {
-                        self.push_operator(Operator::Sizeof);
+                        self.push_operator(op);
+                        tok = tk;
+                        continue;
                     }
                 }
+                Token::Generic => {
+                    self.parse_generic(context)?;
+                }
                 Token::Arrow => {
                     self.push_operator(Operator::Arrow);
                 }
@@ -737,24 +751,8 @@ impl<'a, L: TLexer> ExpressionParser<'a, L> {
                         continue;
                     }
                 }
-                Token::Final => {
-                    tok = self.handle_id("final".to_string(), context)?;
-                    continue;
-                }
-                Token::Import => {
-                    tok = self.handle_id("import".to_string(), context)?;
-                    continue;
-                }
-                Token::Module => {
-                    tok = self.handle_id("module".to_string(), context)?;
-                    continue;
-                }
-                Token::Override => {
-                    tok = self.handle_id("override".to_string(), context)?;
-                    continue;
-                }
                 Token::Identifier(id) => {
-                    tok = self.handle_id(id, context)?;
+                    tok = self.handle_id(self.lexer.resolve(id).to_string(), context)?;
                     continue;
                 }
                 Token::LiteralChar(x) => {
@@ -817,64 +815,20 @@ impl<'a, L: TLexer> ExpressionParser<'a, L> {
                     })));
                     self.last = LastKind::Operand;
                 }
-                Token::LiteralDouble(x) => {
-                    self.operands.push(ExprNode::Float(Box::new(Float {
-                        value: FloatLiteral::Double(x),
-                    })));
-                    self.last = LastKind::Operand;
-                }
-                Token::LiteralFloat(x) => {
+                Token::LiteralFloating(x) => {
                     self.operands.push(ExprNode::Float(Box::new(Float {
-                        value: FloatLiteral::Float(x),
+                        value: FloatLiteral::from(*x),
                     })));
                     self.last = LastKind::Operand;
                 }
-                Token::LiteralFloatUD(x) => {
-                    self.operands.push(ExprNode::Float(Box::new(Float {
-                        value: FloatLiteral::FloatUD(x),
-                    })));
-                    self.last = LastKind::Operand;
-                }
-                Token::LiteralLongDouble(x) => {
-                    self.operands.push(ExprNode::Float(Box::new(Float {
-                        value: FloatLiteral::LongDouble(x),
-                    })));
-                    self.last = LastKind::Operand;
-                }
-                Token::LiteralInt(x) => {
-                    self.operands.push(ExprNode::Integer(Box::new(Integer {
-                        value: IntLiteral::Int(x),
-                    })));
-                    self.last = LastKind::Operand;
-                }
-                Token::LiteralUInt(x) => {
-                    self.operands.push(ExprNode::Integer(Box::new(Integer {
-                        value: IntLiteral::UInt(x),
-                    })));
-                    self.last = LastKind::Operand;
-                }
-                Token::LiteralLong(x) => {
-                    self.operands.push(ExprNode::Integer(Box::new(Integer {
-                        value: IntLiteral::Long(x),
-                    })));
-                    self.last = LastKind::Operand;
-                }
-                Token::LiteralULong(x) => {
-                    self.operands.push(ExprNode::Integer(Box::new(Integer {
-                        value: IntLiteral::ULong(x),
-                    })));
-                    self.last = LastKind::Operand;
-                }
-                Token::LiteralLongLong(x) => {
-                    self.operands.push(ExprNode::Integer(Box::new(Integer {
-                        value: IntLiteral::LongLong(x),
-                    })));
-                    self.last = LastKind::Operand;
-                }
-                Token::LiteralULongLong(x) => {
-                    self.operands.push(ExprNode::Integer(Box::new(Integer {
-                        value: IntLiteral::ULongLong(x),
-                    })));
+                Token::LiteralInteger(x) => {
+                    let value =
+                        IntLiteral::try_from(*x).map_err(|n| ParserError::IntegerTooLarge {
+                            sp: self.lexer.span(),
+                            spelling: n.spelling,
+                        })?;
+                    self.operands
+                        .push(ExprNode::Integer(Box::new(Integer { value })));
                     self.last = LastKind::Operand;
                 }
                 Token::LiteralString(x) => {
@@ -1116,6 +1070,77 @@ mod tests {
         assert_eq!(node, expected);
     }
 
+    #[test]
+    fn test_literal_too_large() {
+        let mut lexer = Lexer::<DefaultContext>::new(b"a + 100000000000000000000000");
+        let mut parser = ExpressionParser::new(&mut lexer, Token::Eof);
+        let mut context = Context::default();
+        match parser.parse(None, &mut context) {
+            Err(ParserError::IntegerTooLarge { spelling, .. }) => {
+                assert_eq!(spelling, "100000000000000000000000")
+            }
+            res => panic!("Unexpected result: {:?}", res),
+        }
+
+        let mut lexer =
+            Lexer::<DefaultContext>::new(b"340282366920938463463374607431768211455ui128");
+        let mut parser = ExpressionParser::new(&mut lexer, Token::Eof);
+        let node = parser.parse(None, &mut context).unwrap().1.unwrap();
+        assert_eq!(
+            node,
+            ExprNode::Integer(Box::new(Integer {
+                value: IntLiteral::UInt128(u128::MAX),
+            }))
+        );
+    }
+
+    #[test]
+    fn test_long_double_literal() {
+        let mut lexer = Lexer::<DefaultContext>::new(b"0.1000000000000000000001L");
+        let mut parser = ExpressionParser::new(&mut lexer, Token::Eof);
+        let mut context = Context::default();
+        let node = parser.parse(None, &mut context).unwrap().1.unwrap();
+        match node {
+            ExprNode::Float(f) => {
+                assert_eq!(f.to_string(), "0.1000000000000000000001");
+                match f.value {
+                    FloatLiteral::LongDouble(n) => {
+                        assert_eq!(n.mantissa.to_string(), "1000000000000000000001");
+                        assert_eq!(n.exponent, -22);
+                    }
+                    x => panic!("Not a long double: {:?}", x),
+                }
+            }
+            node => panic!("Not a float: {:?}", node),
+        }
+    }
+
+    #[test]
+    fn test_alignof() {
+        let mut lexer = Lexer::<DefaultContext>::new(b"_Alignof(int) + __alignof__ a");
+        let mut parser = ExpressionParser::new(&mut lexer, Token::Eof);
+        let mut context = Context::default();
+        let node = parser.parse(None, &mut context).unwrap().1.unwrap();
+
+        let expected = node!(BinaryOp {
+            op: Operator::Add,
+            arg1: node!(UnaryOp {
+                op: Operator::Alignof,
+                arg: ExprNode::Type(Box::new(Type {
+                    base: BaseType::Primitive(Primitive::Int),
+                    cv: CVQualifier::empty(),
+                    pointers: None,
+                })),
+            }),
+            arg2: node!(UnaryOp {
+                op: Operator::Alignof,
+                arg: ExprNode::Variable(Box::new(mk_var!("a"))),
+            }),
+        });
+
+        assert_eq!(node, expected);
+    }
+
     #[test]
     fn test_array() {
         let mut lexer = Lexer::<DefaultContext>::new(b"abc[x]");
@@ -0,0 +1,210 @@
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use termcolor::StandardStreamLock;
+
+use super::expr::{ExprNode, ExpressionParser, LastKind};
+use crate::lexer::{TLexer, Token};
+use crate::parser::declarations::TypeDeclaratorParser;
+use crate::parser::dump::Dump;
+use crate::parser::errors::ParserError;
+use crate::parser::types::Type;
+use crate::parser::Context;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct GenericAssociation {
+    /// None for the default association
+    pub typ: Option<Type>,
+    pub expr: ExprNode,
+}
+
+impl Dump for GenericAssociation {
+    fn dump(&self, name: &str, prefix: &str, last: bool, stdout: &mut StandardStreamLock) {
+        dump_obj!(self, name, "association", prefix, last, stdout, typ, expr);
+    }
+}
+
+pub type GenericAssociations = Vec<GenericAssociation>;
+
+impl Dump for GenericAssociations {
+    fn dump(&self, name: &str, prefix: &str, last: bool, stdout: &mut StandardStreamLock) {
+        dump_vec!(name, self, "assoc", prefix, last, stdout);
+    }
+}
+
+/// C11 generic selection: _Generic(controlling, type: expr, ..., default: expr)
+#[derive(Clone, Debug, PartialEq)]
+pub struct Generic {
+    pub controlling: ExprNode,
+    pub associations: GenericAssociations,
+}
+
+impl Dump for Generic {
+    fn dump(&self, name: &str, prefix: &str, last: bool, stdout: &mut StandardStreamLock) {
+        dump_obj!(
+            self,
+            name,
+            "_Generic",
+            prefix,
+            last,
+            stdout,
+            controlling,
+            associations
+        );
+    }
+}
+
+impl<'a, L: TLexer> ExpressionParser<'a, L> {
+    pub(super) fn parse_generic(&mut self, context: &mut Context) -> Result<(), ParserError> {
+        let tok = self.lexer.next_useful();
+        if tok != Token::LeftParen {
+            return Err(ParserError::InvalidTokenInGeneric {
+                sp: self.lexer.span(),
+                tok,
+            });
+        }
+
+        let mut ep = ExpressionParser::new(self.lexer, Token::Comma);
+        let (tok, controlling) = ep.parse(None, context)?;
+        let tok = tok.unwrap_or_else(|| self.lexer.next_useful());
+
+        let controlling = match controlling {
+            Some(controlling) if tok == Token::Comma => controlling,
+            _ => {
+                return Err(ParserError::InvalidTokenInGeneric {
+                    sp: self.lexer.span(),
+                    tok,
+                });
+            }
+        };
+
+        let mut associations = Vec::new();
+        loop {
+            let tok = self.lexer.next_useful();
+            let (tok, typ) = if tok == Token::Default {
+                (None, None)
+            } else {
+                let tdp = TypeDeclaratorParser::new(self.lexer);
+                let (tk, typ) = tdp.parse_type_id(Some(tok), None, context)?;
+                if typ.is_none() {
+                    return Err(ParserError::InvalidTokenInGeneric {
+                        sp: self.lexer.span(),
+                        tok: tk.unwrap_or_else(|| self.lexer.next_useful()),
+                    });
+                }
+                (tk, typ)
+            };
+
+            let tok = tok.unwrap_or_else(|| self.lexer.next_useful());
+            if tok != Token::Colon {
+                return Err(ParserError::InvalidTokenInGeneric {
+                    sp: self.lexer.span(),
+                    tok,
+                });
+            }
+
+            let mut ep = ExpressionParser::new(self.lexer, Token::Comma);
+            let (tok, expr) = ep.parse(None, context)?;
+            let tok = tok.unwrap_or_else(|| self.lexer.next_useful());
+
+            let expr = if let Some(expr) = expr {
+                expr
+            } else {
+                return Err(ParserError::InvalidTokenInGeneric {
+                    sp: self.lexer.span(),
+                    tok,
+                });
+            };
+
+            associations.push(GenericAssociation { typ, expr });
+
+            match tok {
+                Token::Comma => {}
+                Token::RightParen => break,
+                _ => {
+                    return Err(ParserError::InvalidTokenInGeneric {
+                        sp: self.lexer.span(),
+                        tok,
+                    });
+                }
+            }
+        }
+
+        self.operands.push(ExprNode::Generic(Box::new(Generic {
+            controlling,
+            associations,
+        })));
+        self.last = LastKind::Operand;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::lexer::{preprocessor::context::DefaultContext, Lexer};
+    use crate::parser::declarations::{MSModifier, Pointer, PtrKind};
+    use crate::parser::literals::{IntLiteral, Integer};
+    use crate::parser::types::{BaseType, CVQualifier, Primitive};
+    use pretty_assertions::assert_eq;
+
+    fn int_node(value: u64) -> ExprNode {
+        ExprNode::Integer(Box::new(Integer {
+            value: IntLiteral::Int(value),
+        }))
+    }
+
+    #[test]
+    fn test_generic() {
+        let mut l = Lexer::<DefaultContext>::new(b"_Generic(1, int: 2, char *: 3, default: 4)");
+        let mut p = ExpressionParser::new(&mut l, Token::Eof);
+        let mut context = Context::default();
+        let node = p.parse(None, &mut context).unwrap().1.unwrap();
+
+        let expected = ExprNode::Generic(Box::new(Generic {
+            controlling: int_node(1),
+            associations: vec![
+                GenericAssociation {
+                    typ: Some(Type {
+                        base: BaseType::Primitive(Primitive::Int),
+                        cv: CVQualifier::empty(),
+                        pointers: None,
+                    }),
+                    expr: int_node(2),
+                },
+                GenericAssociation {
+                    typ: Some(Type {
+                        base: BaseType::Primitive(Primitive::Char),
+                        cv: CVQualifier::empty(),
+                        pointers: Some(vec![Pointer {
+                            kind: PtrKind::Pointer,
+                            attributes: None,
+                            cv: CVQualifier::empty(),
+                            ms: MSModifier::empty(),
+                        }]),
+                    }),
+                    expr: int_node(3),
+                },
+                GenericAssociation {
+                    typ: None,
+                    expr: int_node(4),
+                },
+            ],
+        }));
+
+        assert_eq!(node, expected);
+    }
+
+    #[test]
+    fn test_generic_error() {
+        let mut l = Lexer::<DefaultContext>::new(b"_Generic(1, int 2)");
+        let mut p = ExpressionParser::new(&mut l, Token::Eof);
+        let mut context = Context::default();
+
+        assert!(p.parse(None, &mut context).is_err());
+    }
+}
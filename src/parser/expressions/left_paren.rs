@@ -178,6 +178,7 @@ impl<'a, L: TLexer> ExpressionParser<'a, L> {
         // TODO: handle case where id is final, override, ...
         match tok {
             Token::Identifier(id) => {
+                let id = self.lexer.resolve(id).to_string();
                 let qp = QualifiedParser::new(self.lexer);
                 let (tok, qual) = qp.parse(None, Some(id), context)?;
                 let qual = qual.unwrap();
@@ -367,6 +368,7 @@ mod tests {
             },
             init: None,
             bitfield_size: None,
+            doc: None,
         });
         context.add_type_decl(Rc::clone(&t));
 
@@ -424,7 +426,9 @@ mod tests {
                             },
                             init: None,
                             bitfield_size: None,
+                            doc: None,
                         }),
+                        doc: None,
                     }],
                     cv: CVQualifier::empty(),
                     refq: RefQualifier::None,
@@ -469,6 +473,7 @@ mod tests {
             },
             init: None,
             bitfield_size: None,
+            doc: None,
         });
         context.add_type_decl(Rc::clone(&t));
 
@@ -501,7 +506,9 @@ mod tests {
                             },
                             init: None,
                             bitfield_size: None,
+                            doc: None,
                         }),
+                        doc: None,
                     }],
                     cv: CVQualifier::empty(),
                     refq: RefQualifier::None,
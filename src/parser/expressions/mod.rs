@@ -18,3 +18,6 @@ pub use self::list::*;
 
 pub mod left_paren;
 pub use self::left_paren::*;
+
+pub mod generic;
+pub use self::generic::*;
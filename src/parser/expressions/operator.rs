@@ -26,6 +26,7 @@ pub enum Operator {
     AddressOf,
     AddressOfLabel,
     Sizeof,
+    Alignof,
     New,
     NewArray,
     Delete,
@@ -77,7 +78,8 @@ impl Operator {
         use Operator::*;
 
         match self {
-            Plus | Minus | Not | BitNeg | Sizeof | PreInc | PreDec | Indirection | AddressOf => {
+            Plus | Minus | Not | BitNeg | Sizeof | Alignof | PreInc | PreDec | Indirection
+            | AddressOf => {
                 let arg = stack.pop().unwrap();
                 stack.push(ExprNode::UnaryOp(Box::new(UnaryOp { op: self, arg })));
             }
@@ -123,6 +125,7 @@ impl Operator {
             AddressOf => "address-of",
             AddressOfLabel => "&&",
             Sizeof => "sizeof",
+            Alignof => "alignof",
             New => "new",
             NewArray => "new []",
             Delete => "delete",
@@ -3,8 +3,10 @@
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
+use std::convert::TryFrom;
 use termcolor::StandardStreamLock;
 
+use crate::lexer::numeric::{FloatNumber, FloatType, IntNumber, IntType};
 use crate::lexer::{TLexer, Token};
 use crate::parser::dump::Dump;
 use crate::parser::errors::ParserError;
@@ -18,6 +20,10 @@ pub enum IntLiteral {
     ULong(u64),
     LongLong(u64),
     ULongLong(u64),
+    Int128(u128),
+    UInt128(u128),
+    BitInt(Box<IntNumber>),
+    IntUD(Box<(u64, String)>),
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -25,18 +31,60 @@ pub struct Integer {
     pub value: IntLiteral,
 }
 
+/// The number is given back when its value doesn't fit in its type
+impl TryFrom<IntNumber> for IntLiteral {
+    type Error = IntNumber;
+
+    fn try_from(n: IntNumber) -> Result<Self, IntNumber> {
+        let x = match n.typ {
+            IntType::Int128 | IntType::UInt128 => {
+                return match n.value.to_u128() {
+                    Some(x) if n.typ == IntType::Int128 => Ok(IntLiteral::Int128(x)),
+                    Some(x) => Ok(IntLiteral::UInt128(x)),
+                    None => Err(n),
+                };
+            }
+            IntType::BitInt(_) | IntType::UBitInt(_) => return Ok(IntLiteral::BitInt(Box::new(n))),
+            _ => match n.value.to_u64() {
+                Some(x) => x,
+                None => return Err(n),
+            },
+        };
+
+        Ok(match n.typ {
+            IntType::Int => IntLiteral::Int(x),
+            IntType::UInt => IntLiteral::UInt(x),
+            IntType::Long => IntLiteral::Long(x),
+            IntType::ULong => IntLiteral::ULong(x),
+            IntType::LongLong => IntLiteral::LongLong(x),
+            IntType::ULongLong => IntLiteral::ULongLong(x),
+            IntType::UserDefined(suffix) => IntLiteral::IntUD(Box::new((x, suffix))),
+            _ => unreachable!(),
+        })
+    }
+}
+
 impl Into<u64> for &IntLiteral {
     fn into(self) -> u64 {
         use IntLiteral::*;
-        match *self {
-            Int(n) | UInt(n) | Long(n) | ULong(n) | LongLong(n) | ULongLong(n) => n,
+        match self {
+            Int(n) | UInt(n) | Long(n) | ULong(n) | LongLong(n) | ULongLong(n) => *n,
+            Int128(n) | UInt128(n) => *n as u64,
+            BitInt(n) => n.to_u64(),
+            IntUD(x) => x.0,
         }
     }
 }
 
 impl ToString for Integer {
     fn to_string(&self) -> String {
-        format!("{}", Into::<u64>::into(&self.value))
+        use IntLiteral::*;
+        match &self.value {
+            Int128(n) | UInt128(n) => format!("{}", n),
+            BitInt(n) => format!("{}", n.value),
+            IntUD(x) => format!("{}{}", x.0, x.1),
+            x => format!("{}", Into::<u64>::into(x)),
+        }
     }
 }
 
@@ -46,11 +94,17 @@ impl Dump for Integer {
     }
 }
 
+/// The types wider than a double keep the exact value of the literal
 #[derive(Clone, Debug, PartialEq)]
 pub enum FloatLiteral {
     Float(f64),
     Double(f64),
-    LongDouble(f64),
+    LongDouble(Box<FloatNumber>),
+    Float16(f64),
+    BFloat16(f64),
+    Float32(f64),
+    Float64(f64),
+    Float128(Box<FloatNumber>),
     FloatUD(Box<(f64, String)>),
 }
 
@@ -59,11 +113,29 @@ pub struct Float {
     pub value: FloatLiteral,
 }
 
+impl From<FloatNumber> for FloatLiteral {
+    fn from(n: FloatNumber) -> Self {
+        let x = n.to_f64();
+        match n.typ {
+            FloatType::Float => FloatLiteral::Float(x),
+            FloatType::Double => FloatLiteral::Double(x),
+            FloatType::LongDouble => FloatLiteral::LongDouble(Box::new(n)),
+            FloatType::Float16 => FloatLiteral::Float16(x),
+            FloatType::BFloat16 => FloatLiteral::BFloat16(x),
+            FloatType::Float32 => FloatLiteral::Float32(x),
+            FloatType::Float64 => FloatLiteral::Float64(x),
+            FloatType::Float128 => FloatLiteral::Float128(Box::new(n)),
+            FloatType::UserDefined(suffix) => FloatLiteral::FloatUD(Box::new((x, suffix))),
+        }
+    }
+}
+
 impl Into<f64> for &FloatLiteral {
     fn into(self) -> f64 {
         use FloatLiteral::*;
         match &*self {
-            Float(x) | Double(x) | LongDouble(x) => *x,
+            Float(x) | Double(x) | Float16(x) | BFloat16(x) | Float32(x) | Float64(x) => *x,
+            LongDouble(n) | Float128(n) => n.to_f64(),
             FloatUD(ref x) => x.0,
         }
     }
@@ -73,6 +145,9 @@ impl ToString for Float {
     fn to_string(&self) -> String {
         match &self.value {
             FloatLiteral::FloatUD(x) => format!("{}{}", x.0, x.1),
+            FloatLiteral::LongDouble(n) | FloatLiteral::Float128(n) => {
+                n.spelling[..n.spelling.len() - n.suffix().len()].to_string()
+            }
             x => format!("{}", Into::<f64>::into(x)),
         }
     }
@@ -254,7 +329,7 @@ impl<'a, L: TLexer> StringLiteralParser<'a, L> {
                 | Token::LiteralUURString(s)
                 | Token::LiteralU8RString(s) => {
                     lens += s.len();
-                    strings.push(s);
+                    strings.push(s.to_string());
                 }
                 Token::LiteralStringUD(s_suf)
                 | Token::LiteralLStringUD(s_suf)
@@ -35,6 +35,9 @@ pub use self::literals::*;
 pub mod context;
 pub use self::context::*;
 
+pub mod doc;
+pub use self::doc::*;
+
 pub mod unit;
 pub use self::unit::*;
 
@@ -79,7 +79,7 @@ impl<'a, L: TLexer> OperatorParser<'a, L> {
             Token::LiteralString(_) => {
                 let tok = self.lexer.next_useful();
                 if let Token::Identifier(id) = tok {
-                    Ok((None, Some(Operator::UD(id))))
+                    Ok((None, Some(Operator::UD(self.lexer.resolve(id).to_string()))))
                 } else {
                     Err(ParserError::InvalidTokenInOp {
                         sp: self.lexer.span(),
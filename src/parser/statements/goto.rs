@@ -60,7 +60,7 @@ impl<'a, L: TLexer> GotoStmtParser<'a, L> {
                 None,
                 Some(Goto {
                     attributes,
-                    label: Label::Id(id),
+                    label: Label::Id(self.lexer.resolve(id).to_string()),
                 }),
             )),
             Token::Star => {
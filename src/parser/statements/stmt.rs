@@ -222,6 +222,7 @@ impl<'a, L: TLexer> StatementParser<'a, L> {
             }
             Token::SemiColon => Ok((None, Some(Statement::Empty))),
             Token::Identifier(id) => {
+                let id = self.lexer.resolve(id).to_string();
                 let qp = QualifiedParser::new(self.lexer);
                 let (tok, name) = qp.parse(None, Some(id), context)?;
 
@@ -353,6 +354,7 @@ mod tests {
                             }
                         )))),
                         bitfield_size: None,
+                        doc: None,
                     })))),
                     Statement::Declaration(Box::new(Declaration::Type(Rc::new(TypeDeclarator {
                         typ: Type {
@@ -375,6 +377,7 @@ mod tests {
                         },
                         init: Some(Initializer::Equal(ExprNode::Nullptr(Box::new(Nullptr {})))),
                         bitfield_size: None,
+                        doc: None,
                     })))),
                     Statement::Empty,
                 ]
@@ -498,6 +501,7 @@ mod tests {
                 },
                 init: None,
                 bitfield_size: None,
+                doc: None,
             })),
             handler: Box::new(Statement::Compound(Box::new(Compound {
                 attributes: None,
@@ -550,6 +554,7 @@ mod tests {
                 },
                 init: None,
                 bitfield_size: None,
+                doc: None,
             })),
             handler: Box::new(Statement::Compound(Box::new(Compound {
                 attributes: None,
@@ -643,6 +648,7 @@ mod tests {
                 },
             )))),
             bitfield_size: None,
+            doc: None,
         });
 
         let expected = Statement::For(Box::new(For {
@@ -704,6 +710,7 @@ mod tests {
                 },
                 init: None,
                 bitfield_size: None,
+                doc: None,
             }),
             expr: node!(CallExpr {
                 callee: node!(BinaryOp {
@@ -744,6 +751,7 @@ mod tests {
             },
             init: None,
             bitfield_size: None,
+            doc: None,
         });
         let thing = Rc::new(TypeDeclarator {
             typ: Type {
@@ -764,6 +772,7 @@ mod tests {
                 params: vec![]
             }))),
             bitfield_size: None,
+            doc: None,
         });
 
         context.add_type_decl(Rc::clone(&t));
@@ -791,6 +800,7 @@ mod tests {
                 },
                 init: None,
                 bitfield_size: None,
+                doc: None,
             }),
             expr: node!(CallExpr {
                 callee: node!(BinaryOp {
@@ -843,6 +853,7 @@ mod tests {
             },
             init: None,
             bitfield_size: None,
+            doc: None,
         });
         context.add_type_decl(Rc::clone(&x));
 
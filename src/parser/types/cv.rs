@@ -14,12 +14,16 @@ bitflags! {
         const CONST = 0b1;
         const VOLATILE = 0b10;
         const RESTRICT = 0b100;
+        const ATOMIC = 0b1000;
     }
 }
 
 impl ToString for CVQualifier {
     fn to_string(&self) -> String {
-        bitflags_to_str!(self, Self, CONST, "const", VOLATILE, "volatile", RESTRICT, "restrict")
+        bitflags_to_str!(
+            self, Self, CONST, "const", VOLATILE, "volatile", RESTRICT, "restrict", ATOMIC,
+            "_Atomic"
+        )
     }
 }
 
@@ -44,13 +48,17 @@ impl CVQualifier {
                 *self |= Self::RESTRICT;
                 true
             }
+            Token::Atomic => {
+                *self |= Self::ATOMIC;
+                true
+            }
             _ => false,
         }
     }
 
     pub(crate) fn is_cv(tok: &Token) -> bool {
         match tok {
-            Token::Const | Token::Volatile | Token::Restrict => true,
+            Token::Const | Token::Volatile | Token::Restrict | Token::Atomic => true,
             _ => false,
         }
     }
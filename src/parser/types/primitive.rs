@@ -246,6 +246,22 @@ impl Modifier {
                 *self |= Modifier::VOID;
                 true
             }
+            Token::MSInt8 => {
+                *self |= Modifier::CHAR;
+                true
+            }
+            Token::MSInt16 => {
+                *self |= Modifier::SHORT;
+                true
+            }
+            Token::MSInt32 => {
+                *self |= Modifier::INT;
+                true
+            }
+            Token::MSInt64 => {
+                *self |= Modifier::LONGLONG;
+                true
+            }
             _ => false,
         }
     }
@@ -267,7 +283,11 @@ impl Modifier {
             | Token::Char8T
             | Token::Char16T
             | Token::Char32T
-            | Token::Void => true,
+            | Token::Void
+            | Token::MSInt8
+            | Token::MSInt16
+            | Token::MSInt32
+            | Token::MSInt64 => true,
             _ => false,
         }
     }
@@ -11,7 +11,7 @@ use super::cv::CVQualifier;
 use super::primitive::Primitive;
 use crate::parser::context::TypeToFix;
 use crate::parser::declarations::types::TypeDeclarator;
-use crate::parser::declarations::{Array, Class, Enum, Function, Pointers};
+use crate::parser::declarations::{Array, Class, Enum, Function, Pointers, Typeof};
 use crate::parser::dump::Dump;
 use crate::parser::names::Qualified;
 
@@ -61,6 +61,8 @@ pub enum BaseType {
     Class(Box<Class>),
     Function(Box<Function>),
     Array(Box<Array>),
+    Typeof(Box<Typeof>),
+    Atomic(Box<Type>),
 }
 
 impl ToString for BaseType {
@@ -76,6 +78,8 @@ impl ToString for BaseType {
             Class(_) => "class".to_string(),
             Function(_) => "function".to_string(),
             Array(_) => "array".to_string(),
+            Typeof(_) => "typeof".to_string(),
+            Atomic(_) => "_Atomic".to_string(),
         }
     }
 }
@@ -96,6 +100,8 @@ impl Dump for BaseType {
             Self::Enum(x) => dump!(x),
             Self::Class(x) => dump!(x),
             Self::Function(x) => dump!(x),
+            Self::Typeof(x) => dump!(x),
+            Self::Atomic(x) => dump!(x),
             //Self::Array(x) => dump!(x),
             _ => {}
         }
@@ -14,6 +14,13 @@ pub struct Unit {
     decls: Declarations,
 }
 
+impl Unit {
+    /// The top level declarations of the translation unit
+    pub fn decls(&self) -> &Declarations {
+        &self.decls
+    }
+}
+
 pub struct UnitParser<'a, PC: PreprocContext> {
     buf: &'a [u8],
     lexer: Lexer<'a, PC>,
@@ -22,10 +29,16 @@ pub struct UnitParser<'a, PC: PreprocContext> {
 
 impl<'a, PC: PreprocContext> UnitParser<'a, PC> {
     pub fn new(buf: &'a [u8]) -> Self {
+        Self::new_with_lexer(buf, Lexer::new(buf))
+    }
+
+    pub fn new_with_lexer(buf: &'a [u8], lexer: Lexer<'a, PC>) -> Self {
+        let mut context = Context::default();
+        context.set_standard(lexer.get_standard());
         Self {
             buf,
-            lexer: Lexer::new(buf),
-            context: Context::default(),
+            lexer,
+            context,
         }
     }
 
@@ -46,3 +59,63 @@ impl<'a, PC: PreprocContext> UnitParser<'a, PC> {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::args::{LangStandard, Language};
+    use crate::lexer::preprocessor::context::DefaultContext;
+    use crate::parser::Declaration;
+
+    fn parse(buf: &[u8], lang: Language) -> Result<Unit, ParserError> {
+        let mut lexer = Lexer::<DefaultContext>::new(buf);
+        lexer.set_standard(LangStandard::default_for(lang));
+        let mut parser = UnitParser::new_with_lexer(buf, lexer);
+        parser.parse()
+    }
+
+    #[test]
+    fn test_c11_keywords() {
+        let units: [&[u8]; 7] = [
+            b"int x = _Alignof(int);",
+            b"int x = __alignof__(x);",
+            b"int x = _Generic(1, int: 2, default: 3);",
+            b"int x; __typeof__(x) y;",
+            b"__typeof_unqual__(const int) x;",
+            b"_Atomic(int) x;",
+            b"_Atomic int x;",
+        ];
+
+        for lang in [Language::C, Language::CPP].iter() {
+            for buf in units.iter() {
+                assert!(
+                    parse(buf, *lang).is_ok(),
+                    "{} ({:?})",
+                    String::from_utf8_lossy(buf),
+                    lang
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_c11_keywords_errors() {
+        assert!(parse(b"int x = _Generic(1, int 2);", Language::C).is_err());
+        assert!(parse(b"__typeof__ x;", Language::C).is_err());
+        assert!(parse(b"_Atomic(int x;", Language::C).is_err());
+    }
+
+    #[test]
+    fn test_unit_decls() {
+        let buf = b"int x;\nnamespace n {}\nusing namespace n;";
+        let mut parser = UnitParser::<DefaultContext>::new(buf);
+        let unit = parser.parse().unwrap();
+
+        let decls = unit.decls();
+        assert_eq!(decls.len(), 3);
+        assert!(matches!(decls[0], Declaration::Type(_)));
+        assert!(matches!(decls[1], Declaration::Namespace(_)));
+        assert!(matches!(decls[2], Declaration::UsingNS(_)));
+    }
+}
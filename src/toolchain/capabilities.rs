@@ -0,0 +1,197 @@
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use hashbrown::HashMap;
+
+/// The feature-test operators answered from a capability table
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Capability {
+    /// __has_attribute
+    Attribute,
+    /// __has_cpp_attribute
+    CppAttribute,
+    /// __has_builtin
+    Builtin,
+    /// __has_feature
+    Feature,
+    /// __has_extension
+    Extension,
+}
+
+impl Capability {
+    pub fn from_operator(name: &str) -> Option<Self> {
+        match name {
+            "__has_attribute" => Some(Capability::Attribute),
+            "__has_cpp_attribute" => Some(Capability::CppAttribute),
+            "__has_builtin" => Some(Capability::Builtin),
+            "__has_feature" => Some(Capability::Feature),
+            "__has_extension" => Some(Capability::Extension),
+            _ => None,
+        }
+    }
+
+    pub fn operator(self) -> &'static str {
+        match self {
+            Capability::Attribute => "__has_attribute",
+            Capability::CppAttribute => "__has_cpp_attribute",
+            Capability::Builtin => "__has_builtin",
+            Capability::Feature => "__has_feature",
+            Capability::Extension => "__has_extension",
+        }
+    }
+
+    fn from_keyword(kw: &str) -> Option<Self> {
+        match kw {
+            "attribute" => Some(Capability::Attribute),
+            "cpp_attribute" => Some(Capability::CppAttribute),
+            "builtin" => Some(Capability::Builtin),
+            "feature" => Some(Capability::Feature),
+            "extension" => Some(Capability::Extension),
+            _ => None,
+        }
+    }
+}
+
+/// What a compiler supports: the values of __has_attribute(x), __has_cpp_attribute(x), ...
+/// The table is made of lines `kind name [value]` where kind is one of attribute,
+/// cpp_attribute, builtin, feature or extension (the value is 1 by default)
+/// and the lines starting with # are comments.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Capabilities {
+    attributes: HashMap<String, u64>,
+    cpp_attributes: HashMap<String, u64>,
+    builtins: HashMap<String, u64>,
+    features: HashMap<String, u64>,
+    extensions: HashMap<String, u64>,
+}
+
+/// Remove the underscores around a name: __packed__ is packed
+fn normalize(name: &str) -> &str {
+    name.strip_prefix("__")
+        .and_then(|n| n.strip_suffix("__"))
+        .filter(|n| !n.is_empty())
+        .unwrap_or(name)
+}
+
+impl Capabilities {
+    pub fn parse(table: &str) -> Self {
+        let mut caps = Self::default();
+        for line in table.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut parts = line.split_whitespace();
+            let kind = parts.next().and_then(Capability::from_keyword);
+            let name = parts.next();
+            let value = parts.next().map_or(Some(1), |v| v.parse::<u64>().ok());
+            if let (Some(kind), Some(name), Some(value)) = (kind, name, value) {
+                caps.set(kind, name, value);
+            }
+        }
+        caps
+    }
+
+    fn get_map(&self, kind: Capability) -> &HashMap<String, u64> {
+        match kind {
+            Capability::Attribute => &self.attributes,
+            Capability::CppAttribute => &self.cpp_attributes,
+            Capability::Builtin => &self.builtins,
+            Capability::Feature => &self.features,
+            Capability::Extension => &self.extensions,
+        }
+    }
+
+    /// Add or change an entry (a value of 0 means unsupported)
+    pub fn set(&mut self, kind: Capability, name: &str, value: u64) {
+        let map = match kind {
+            Capability::Attribute => &mut self.attributes,
+            Capability::CppAttribute => &mut self.cpp_attributes,
+            Capability::Builtin => &mut self.builtins,
+            Capability::Feature => &mut self.features,
+            Capability::Extension => &mut self.extensions,
+        };
+        map.insert(name.to_string(), value);
+    }
+
+    /// Get the value of the operator for a name (0 when it isn't supported)
+    pub fn get(&self, kind: Capability, name: &str) -> u64 {
+        match kind {
+            Capability::Attribute | Capability::Feature => self
+                .get_map(kind)
+                .get(normalize(name))
+                .copied()
+                .unwrap_or(0),
+            Capability::CppAttribute => {
+                // The scoped attributes are gnu::foo, __gnu__::__foo__, clang::foo...
+                let key = match name.split_once("::") {
+                    Some((scope, name)) => {
+                        format!("{}::{}", normalize(scope.trim()), normalize(name.trim()))
+                    }
+                    None => normalize(name).to_string(),
+                };
+                match self.cpp_attributes.get(&key) {
+                    Some(value) => *value,
+                    None => match key.split_once("::") {
+                        // The GNU attributes can be used with the gnu scope
+                        Some(("gnu", name)) => self.attributes.get(name).copied().unwrap_or(0),
+                        _ => 0,
+                    },
+                }
+            }
+            Capability::Builtin => self.builtins.get(name).copied().unwrap_or(0),
+            Capability::Extension => {
+                // A feature is an extension too
+                let name = normalize(name);
+                self.extensions
+                    .get(name)
+                    .or_else(|| self.features.get(name))
+                    .copied()
+                    .unwrap_or(0)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_capabilities() {
+        let caps = Capabilities::parse(concat!(
+            "# A comment\n",
+            "attribute packed\n",
+            "attribute unused\n",
+            "cpp_attribute nodiscard 201907\n",
+            "cpp_attribute clang::fallthrough\n",
+            "builtin __builtin_expect\n",
+            "feature cxx_rtti\n",
+            "extension c_static_assert\n",
+            "attribute invalid value\n",
+            "unknown foo\n",
+        ));
+
+        assert_eq!(caps.get(Capability::Attribute, "packed"), 1);
+        assert_eq!(caps.get(Capability::Attribute, "__packed__"), 1);
+        assert_eq!(caps.get(Capability::Attribute, "invalid"), 0);
+        assert_eq!(caps.get(Capability::Attribute, "nodiscard"), 0);
+        assert_eq!(caps.get(Capability::CppAttribute, "nodiscard"), 201907);
+        assert_eq!(caps.get(Capability::CppAttribute, "__nodiscard__"), 201907);
+        assert_eq!(caps.get(Capability::CppAttribute, "clang::fallthrough"), 1);
+        assert_eq!(caps.get(Capability::CppAttribute, "gnu::unused"), 1);
+        assert_eq!(caps.get(Capability::CppAttribute, "__gnu__::__unused__"), 1);
+        assert_eq!(caps.get(Capability::CppAttribute, "gnu::nodiscard"), 0);
+        assert_eq!(caps.get(Capability::Builtin, "__builtin_expect"), 1);
+        assert_eq!(caps.get(Capability::Builtin, "__builtin_trap"), 0);
+        assert_eq!(caps.get(Capability::Feature, "cxx_rtti"), 1);
+        assert_eq!(caps.get(Capability::Feature, "c_static_assert"), 0);
+        assert_eq!(caps.get(Capability::Extension, "c_static_assert"), 1);
+        assert_eq!(caps.get(Capability::Extension, "cxx_rtti"), 1);
+    }
+}
@@ -0,0 +1,522 @@
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use hashbrown::HashMap;
+use lazy_static::lazy_static;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use crate::args::{Language, Macro, PreprocOptions};
+
+mod capabilities;
+pub use self::capabilities::*;
+
+/// The target used when nothing in the command line allows to guess it
+pub const DEFAULT_TARGET: &str = "x86_64-linux-gnu";
+
+/// The capability tables shared by the bundled profiles
+const GNU_CAPS: &str = include_str!("profiles/gnu.caps");
+const CLANG_CL_CAPS: &str = include_str!("profiles/clang-cl.caps");
+
+/// The profiles shipped with the crate: (target, `-dM -E` dump, `-E -v` transcript, capabilities)
+/// They've been made with the C++ frontend (e.g. `echo | g++ -x c++ -dM -E -`).
+const BUNDLED: &[(&str, &str, &str, &str)] = &[
+    (
+        "x86_64-linux-gnu",
+        include_str!("profiles/x86_64-linux-gnu.macros"),
+        include_str!("profiles/x86_64-linux-gnu.search"),
+        GNU_CAPS,
+    ),
+    (
+        "aarch64-linux-gnu",
+        include_str!("profiles/aarch64-linux-gnu.macros"),
+        include_str!("profiles/aarch64-linux-gnu.search"),
+        GNU_CAPS,
+    ),
+    (
+        "i686-linux-gnu",
+        include_str!("profiles/i686-linux-gnu.macros"),
+        include_str!("profiles/i686-linux-gnu.search"),
+        GNU_CAPS,
+    ),
+    (
+        "x86_64-windows-msvc",
+        include_str!("profiles/x86_64-windows-msvc.macros"),
+        include_str!("profiles/x86_64-windows-msvc.search"),
+        CLANG_CL_CAPS,
+    ),
+];
+
+lazy_static! {
+    static ref DEFAULT_CAPABILITIES: Capabilities = Capabilities::parse(GNU_CAPS);
+}
+
+/// The capabilities used when none have been given: the ones of the default target
+pub fn default_capabilities() -> &'static Capabilities {
+    &DEFAULT_CAPABILITIES
+}
+
+/// These macros depend on the -std used when the dump has been made:
+/// they're defined from args::LangStandard
+const STD_MACROS: &[&str] = &[
+    "__cplusplus",
+    "__STDC_VERSION__",
+    "__STRICT_ANSI__",
+    "__GNUC_GNU_INLINE__",
+    "__GNUC_STDC_INLINE__",
+    "_GNU_SOURCE",
+    "_MSVC_LANG",
+];
+
+/// The macros only defined by the C++ frontend
+const CPP_MACROS: &[&str] = &[
+    "__GNUG__",
+    "__DEPRECATED",
+    "__EXCEPTIONS",
+    "__BOOL_DEFINED",
+    "_CPPRTTI",
+    "_CPPUNWIND",
+    "_NATIVE_WCHAR_T_DEFINED",
+    "_WCHAR_T_DEFINED",
+];
+
+fn is_cpp_macro(name: &str) -> bool {
+    name.starts_with("__cpp_") || name.starts_with("__GXX_") || CPP_MACROS.contains(&name)
+}
+
+/// Parse the output of `cc -dM -E - </dev/null`
+pub fn parse_macros(dump: &str) -> Vec<Macro> {
+    let mut defs = Vec::new();
+    for line in dump.lines() {
+        let line = if let Some(line) = line.trim_start().strip_prefix("#define ") {
+            line
+        } else {
+            continue;
+        };
+
+        // The name can be a function-like one: FOO(a,b)
+        let end = line.find([' ', '(']).unwrap_or(line.len());
+        let end = if line[end..].starts_with('(') {
+            line[end..].find(')').map_or(line.len(), |p| end + p + 1)
+        } else {
+            end
+        };
+        let name = &line[..end];
+        let value = line[end..].trim();
+        if !name.is_empty() && !STD_MACROS.contains(&name) {
+            defs.push(Macro::Defined((name.to_string(), value.to_string())));
+        }
+    }
+    defs
+}
+
+/// Remove the `..` and `.` from a path without touching the filesystem
+/// (the paths in a transcript can come from another machine)
+fn normalize(path: &str) -> String {
+    let mut res = PathBuf::new();
+    for c in Path::new(path).components() {
+        match c {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !res.pop() {
+                    res.push("..");
+                }
+            }
+            c => res.push(c),
+        }
+    }
+    res.to_str().unwrap().to_string()
+}
+
+/// Parse the search list from the output of `cc -E -v - </dev/null`
+/// (or `cpp -Wp,-v`): only the <...> directories are kept, the frameworks are skipped
+pub fn parse_search_paths(transcript: &str) -> Vec<String> {
+    let mut paths = Vec::new();
+    let mut in_list = false;
+    for line in transcript.lines() {
+        if line.starts_with("#include <...> search starts here:") {
+            in_list = true;
+        } else if line.starts_with("End of search list.") {
+            break;
+        } else if in_list {
+            let path = line.trim();
+            if !path.is_empty() && !path.ends_with("(framework directory)") {
+                paths.push(normalize(path));
+            }
+        }
+    }
+    paths
+}
+
+/// The predefined macros, the default include paths and the capabilities
+/// of a compiler for a target
+#[derive(Clone, Debug, Default)]
+pub struct Profile {
+    pub target: String,
+    pub defined: Vec<Macro>,
+    pub sys_paths: Vec<String>,
+    /// None when the profile has no capability table: default_capabilities() is used
+    pub capabilities: Option<Capabilities>,
+}
+
+impl Profile {
+    pub fn new(target: &str, macros: &str, transcript: &str) -> Self {
+        Self {
+            target: target.to_string(),
+            defined: parse_macros(macros),
+            sys_paths: parse_search_paths(transcript),
+            capabilities: None,
+        }
+    }
+
+    pub fn from_files<P: AsRef<Path>>(target: &str, macros: P, transcript: P) -> io::Result<Self> {
+        let macros = fs::read_to_string(macros)?;
+        let transcript = fs::read_to_string(transcript)?;
+        Ok(Self::new(target, &macros, &transcript))
+    }
+
+    /// Set the capability table (see Capabilities::parse)
+    pub fn with_capabilities(mut self, caps: Capabilities) -> Self {
+        self.capabilities = Some(caps);
+        self
+    }
+
+    /// Get the capabilities of the profile or the default ones
+    pub fn get_capabilities(&self) -> &Capabilities {
+        self.capabilities
+            .as_ref()
+            .unwrap_or_else(|| default_capabilities())
+    }
+
+    /// Get the predefined macros for the given language
+    pub fn get_defined(&self, lang: Language) -> Vec<Macro> {
+        self.defined
+            .iter()
+            .filter(|m| match m {
+                Macro::Defined((name, _)) => lang == Language::CPP || !is_cpp_macro(name),
+                _ => true,
+            })
+            .cloned()
+            .collect()
+    }
+}
+
+/// Guess a normalized target (arch-os-env) from the command line options:
+/// --target=..., -m32/-m64 and -march=...
+pub fn get_target(opt: &PreprocOptions) -> String {
+    let triple = if let Some(target) = opt.target.as_ref() {
+        target.as_str()
+    } else if opt.driver.is_msvc() {
+        "x86_64-windows-msvc"
+    } else {
+        DEFAULT_TARGET
+    };
+
+    let mut parts = triple.splitn(2, '-');
+    let arch = parts.next().unwrap();
+    let rest = parts.next().unwrap_or("");
+
+    let mut arch = match arch {
+        "amd64" | "x64" => "x86_64",
+        "i386" | "i486" | "i586" | "x86" => "i686",
+        "arm64" => "aarch64",
+        arch => arch,
+    };
+
+    if let Some(march) = opt.march.as_ref() {
+        if march.starts_with("armv8") || march.starts_with("armv9") {
+            arch = "aarch64";
+        } else if march.starts_with('i') && march[1..].ends_with("86")
+            || march.starts_with("pentium")
+            || march.starts_with("athlon")
+        {
+            // A 32 bits only cpu
+            arch = "i686";
+        }
+    }
+
+    match (arch, opt.machine_bits) {
+        ("x86_64", Some(32)) => arch = "i686",
+        ("i686", Some(64)) => arch = "x86_64",
+        _ => {}
+    }
+
+    let os = if rest.contains("windows") || rest.contains("msvc") || rest.contains("win32") {
+        "windows-msvc"
+    } else if rest.contains("linux") {
+        "linux-gnu"
+    } else {
+        rest
+    };
+
+    if os.is_empty() {
+        arch.to_string()
+    } else {
+        format!("{}-{}", arch, os)
+    }
+}
+
+/// A set of profiles indexed by target
+#[derive(Clone, Debug, Default)]
+pub struct Profiles {
+    profiles: HashMap<String, Profile>,
+}
+
+impl Profiles {
+    /// Get the profiles shipped with the crate
+    pub fn bundled() -> Self {
+        let mut profiles = Self::default();
+        for (target, macros, transcript, caps) in BUNDLED {
+            profiles.add(
+                Profile::new(target, macros, transcript)
+                    .with_capabilities(Capabilities::parse(caps)),
+            );
+        }
+        profiles
+    }
+
+    pub fn add(&mut self, profile: Profile) {
+        self.profiles.insert(profile.target.clone(), profile);
+    }
+
+    /// Load the profiles saved in a directory: for a target foo,
+    /// foo.macros contains the `-dM -E` dump, foo.search the `-E -v` transcript
+    /// and foo.caps the capability table (if any)
+    pub fn load_dir<P: AsRef<Path>>(&mut self, dir: P) -> io::Result<()> {
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.extension().is_none_or(|e| e != "macros") {
+                continue;
+            }
+            let target = path.file_stem().unwrap().to_str().unwrap().to_string();
+            let macros = fs::read_to_string(&path)?;
+            let transcript = fs::read_to_string(path.with_extension("search")).unwrap_or_default();
+            let mut profile = Profile::new(&target, &macros, &transcript);
+            if let Ok(caps) = fs::read_to_string(path.with_extension("caps")) {
+                profile = profile.with_capabilities(Capabilities::parse(&caps));
+            }
+            self.add(profile);
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, target: &str) -> Option<&Profile> {
+        self.profiles.get(target)
+    }
+
+    /// Get the profile to use with the given options: an exact match on --target
+    /// is preferred to the normalized target and then to the default one
+    pub fn get_for(&self, opt: &PreprocOptions) -> Option<&Profile> {
+        opt.target
+            .as_ref()
+            .and_then(|t| self.get(t))
+            .or_else(|| self.get(&get_target(opt)))
+            .or_else(|| self.get(DEFAULT_TARGET))
+    }
+
+    pub fn len(&self) -> usize {
+        self.profiles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.profiles.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::args::{Driver, Macro};
+    use pretty_assertions::assert_eq;
+    use tempdir::TempDir;
+
+    fn has(defs: &[Macro], name: &str, value: &str) -> bool {
+        defs.contains(&Macro::Defined((name.to_string(), value.to_string())))
+    }
+
+    fn has_name(defs: &[Macro], name: &str) -> bool {
+        defs.iter().any(|m| match m {
+            Macro::Defined((n, _)) => n == name,
+            _ => false,
+        })
+    }
+
+    #[test]
+    fn test_parse_macros() {
+        let defs = parse_macros(concat!(
+            "#define __x86_64__ 1\n",
+            "#define __INT64_C(c) c ## L\n",
+            "#define __VERSION__ \"12.2.0\"\n",
+            "#define __REGISTER_PREFIX__ \n",
+            "#define __cplusplus 201703L\n",
+            "#define __USER_LABEL_PREFIX__\n",
+        ));
+
+        assert_eq!(
+            defs,
+            vec![
+                Macro::Defined(("__x86_64__".to_string(), "1".to_string())),
+                Macro::Defined(("__INT64_C(c)".to_string(), "c ## L".to_string())),
+                Macro::Defined(("__VERSION__".to_string(), "\"12.2.0\"".to_string())),
+                Macro::Defined(("__REGISTER_PREFIX__".to_string(), "".to_string())),
+                Macro::Defined(("__USER_LABEL_PREFIX__".to_string(), "".to_string())),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_search_paths() {
+        let paths = parse_search_paths(concat!(
+            "ignoring nonexistent directory \"/usr/local/include/x86_64-linux-gnu\"\n",
+            "#include \"...\" search starts here:\n",
+            " /quote/only\n",
+            "#include <...> search starts here:\n",
+            " /usr/lib/gcc/x86_64-linux-gnu/9/../../../../include/c++/9\n",
+            " /usr/include\n",
+            " /System/Library/Frameworks (framework directory)\n",
+            "End of search list.\n",
+            " /not/a/path\n",
+        ));
+
+        assert_eq!(paths, vec!["/usr/include/c++/9", "/usr/include"]);
+    }
+
+    #[test]
+    fn test_bundled() {
+        let profiles = Profiles::bundled();
+        assert_eq!(profiles.len(), 4);
+
+        let x86_64 = profiles.get("x86_64-linux-gnu").unwrap();
+        let defs = x86_64.get_defined(Language::CPP);
+        assert!(has(&defs, "__x86_64__", "1"));
+        assert!(has(&defs, "__SIZEOF_POINTER__", "8"));
+        assert!(has_name(&defs, "__GNUG__"));
+        assert!(!has_name(&defs, "__cplusplus"));
+        assert!(x86_64.sys_paths.contains(&"/usr/include".to_string()));
+        let caps = x86_64.get_capabilities();
+        assert_eq!(caps.get(Capability::Attribute, "always_inline"), 1);
+        assert_eq!(caps.get(Capability::CppAttribute, "nodiscard"), 201907);
+        assert_eq!(caps.get(Capability::Builtin, "__builtin_expect"), 1);
+        assert_eq!(caps.get(Capability::Feature, "cxx_rtti"), 0);
+
+        let defs = x86_64.get_defined(Language::C);
+        assert!(!has_name(&defs, "__GNUG__"));
+        assert!(!has_name(&defs, "__cpp_rtti"));
+
+        let aarch64 = profiles.get("aarch64-linux-gnu").unwrap();
+        let defs = aarch64.get_defined(Language::C);
+        assert!(has(&defs, "__aarch64__", "1"));
+        assert!(has(&defs, "__CHAR_UNSIGNED__", "1"));
+        assert!(!has_name(&defs, "__x86_64__"));
+
+        let i686 = profiles.get("i686-linux-gnu").unwrap();
+        let defs = i686.get_defined(Language::C);
+        assert!(has(&defs, "__i386__", "1"));
+        assert!(has(&defs, "__SIZEOF_POINTER__", "4"));
+
+        let msvc = profiles.get("x86_64-windows-msvc").unwrap();
+        let defs = msvc.get_defined(Language::CPP);
+        assert!(has_name(&defs, "_MSC_VER"));
+        assert!(has(&defs, "_WIN64", "1"));
+        assert!(has(&defs, "__SIZEOF_LONG__", "4"));
+        assert!(msvc.sys_paths[0].ends_with(r"LLVM\lib\clang\16\include"));
+        let caps = msvc.get_capabilities();
+        assert_eq!(caps.get(Capability::Attribute, "dllimport"), 1);
+        assert_eq!(caps.get(Capability::CppAttribute, "clang::fallthrough"), 1);
+        assert_eq!(caps.get(Capability::Feature, "cxx_rtti"), 1);
+    }
+
+    #[test]
+    fn test_get_target() {
+        let target = |target: Option<&str>, march: Option<&str>, bits: Option<u32>| {
+            let opt = PreprocOptions {
+                target: target.map(|t| t.to_string()),
+                march: march.map(|t| t.to_string()),
+                machine_bits: bits,
+                ..Default::default()
+            };
+            get_target(&opt)
+        };
+
+        assert_eq!(target(None, None, None), "x86_64-linux-gnu");
+        assert_eq!(target(None, None, Some(32)), "i686-linux-gnu");
+        assert_eq!(target(None, Some("i686"), None), "i686-linux-gnu");
+        assert_eq!(target(None, Some("armv8.2-a"), None), "aarch64-linux-gnu");
+        assert_eq!(target(None, Some("skylake"), None), "x86_64-linux-gnu");
+        assert_eq!(
+            target(Some("aarch64-unknown-linux-gnu"), None, None),
+            "aarch64-linux-gnu"
+        );
+        assert_eq!(
+            target(Some("arm64-linux-gnu"), None, None),
+            "aarch64-linux-gnu"
+        );
+        assert_eq!(
+            target(Some("i386-pc-linux-gnu"), None, Some(64)),
+            "x86_64-linux-gnu"
+        );
+        assert_eq!(
+            target(Some("x86_64-pc-windows-msvc"), None, None),
+            "x86_64-windows-msvc"
+        );
+        assert_eq!(
+            target(Some("riscv64-unknown-elf"), None, None),
+            "riscv64-unknown-elf"
+        );
+
+        let opt = PreprocOptions {
+            driver: Driver::ClangCL,
+            ..Default::default()
+        };
+        assert_eq!(get_target(&opt), "x86_64-windows-msvc");
+    }
+
+    #[test]
+    fn test_profiles_get_for() {
+        let tmp = TempDir::new("test").unwrap();
+        std::fs::write(
+            tmp.path().join("riscv64-unknown-elf.macros"),
+            "#define __riscv 1\n",
+        )
+        .unwrap();
+        std::fs::write(
+            tmp.path().join("riscv64-unknown-elf.search"),
+            "#include <...> search starts here:\n /opt/riscv/include\nEnd of search list.\n",
+        )
+        .unwrap();
+        std::fs::write(
+            tmp.path().join("riscv64-unknown-elf.caps"),
+            "attribute packed\nbuiltin __builtin_riscv_pause\n",
+        )
+        .unwrap();
+
+        let mut profiles = Profiles::bundled();
+        profiles.load_dir(tmp.path()).unwrap();
+        assert_eq!(profiles.len(), 5);
+
+        let mut opt = PreprocOptions {
+            target: Some("riscv64-unknown-elf".to_string()),
+            ..Default::default()
+        };
+        let profile = profiles.get_for(&opt).unwrap();
+        assert_eq!(profile.target, "riscv64-unknown-elf");
+        assert_eq!(profile.sys_paths, vec!["/opt/riscv/include"]);
+        assert_eq!(
+            profile
+                .get_capabilities()
+                .get(Capability::Builtin, "__builtin_riscv_pause"),
+            1
+        );
+
+        opt.target = Some("mips-unknown-linux-gnu".to_string());
+        assert_eq!(profiles.get_for(&opt).unwrap().target, DEFAULT_TARGET);
+
+        opt.target = Some("x86_64-pc-linux-gnu".to_string());
+        opt.machine_bits = Some(32);
+        assert_eq!(profiles.get_for(&opt).unwrap().target, "i686-linux-gnu");
+    }
+}